@@ -0,0 +1,216 @@
+//! #OVH-rs derive
+//!
+//! `#[derive(OvhEndpoint)]` implements `ovh::endpoint::ApiEndpoint` from attributes:
+//!
+//! ```ignore
+//! #[derive(OvhEndpoint)]
+//! #[ovh(method = "GET", path = "/dedicated/server/{name}/task", response = "Vec<u64>")]
+//! struct ServerTasks {
+//!     name: String,
+//!     #[ovh(query)]
+//!     status: Option<String>,
+//! }
+//! ```
+//!
+//! Placeholders of the path are filled with the fields of the same name,
+//! or the field marked `#[ovh(path = "placeholder")]`. Fields marked
+//! `#[ovh(query)]` go to the query string (skipped when `None`) and fields
+//! marked `#[ovh(body)]` to the JSON body; both accept `= "name"` to rename
+//! the parameter. The response defaults to `ovh::Value`.
+//!
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, Meta, NestedMeta, Type};
+
+#[proc_macro_derive(OvhEndpoint, attributes(ovh))]
+pub fn derive_endpoint(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Where a field goes in the request.
+enum Role {
+    Path(String),
+    Query(String),
+    Body(String),
+}
+
+/// `name = "value"` and bare `name` items of the `#[ovh(...)]` attributes.
+fn ovh_items(attrs: &[Attribute]) -> syn::Result<Vec<(String, Option<String>)>> {
+    let mut items = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("ovh")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[ovh(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(ref path)) if path.get_ident().is_some() => {
+                    items.push((path.get_ident().unwrap().to_string(), None));
+                }
+                NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.path.get_ident().is_some() => {
+                    let value = match pair.lit {
+                        Lit::Str(ref value) => value.value(),
+                        ref lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                    };
+                    items.push((pair.path.get_ident().unwrap().to_string(), Some(value)));
+                }
+                other => return Err(syn::Error::new_spanned(other, "unexpected ovh attribute")),
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Names of the `{name}` placeholders of a path template.
+fn placeholders(path: &str) -> Vec<String> {
+    path.split('{')
+        .skip(1)
+        .filter_map(|part| part.find('}').map(|end| part[..end].to_string()))
+        .collect()
+}
+
+fn is_option(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) => path.path.segments.last().is_some_and(|s| s.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut method = None;
+    let mut path = None;
+    let mut response = None;
+    for (key, value) in ovh_items(&input.attrs)? {
+        match (key.as_str(), value) {
+            ("method", Some(value)) => method = Some(value.to_uppercase()),
+            ("path", Some(value)) => path = Some(value),
+            ("response", Some(value)) => response = Some(syn::parse_str::<Type>(&value)?),
+            (key, _) => {
+                return Err(syn::Error::new_spanned(&input.ident, format!("unknown ovh attribute {}", key)))
+            }
+        }
+    }
+    let method = method.ok_or_else(|| syn::Error::new_spanned(&input.ident, "missing #[ovh(method = \"...\")]"))?;
+    let path = path.ok_or_else(|| syn::Error::new_spanned(&input.ident, "missing #[ovh(path = \"...\")]"))?;
+    let response = response.unwrap_or_else(|| syn::parse_str::<Type>("::ovh::Value").unwrap());
+
+    let fields = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref fields) => fields.named.iter().collect(),
+                Fields::Unit => Vec::new(),
+                Fields::Unnamed(_) => {
+                    return Err(syn::Error::new_spanned(&input.ident, "OvhEndpoint needs named fields"))
+                }
+            }
+        }
+        _ => return Err(syn::Error::new_spanned(&input.ident, "OvhEndpoint only supports structs")),
+    };
+
+    let mut path_params = Vec::new();
+    let mut query_params = Vec::new();
+    let mut body_fields = Vec::new();
+    let mut bound = Vec::new();
+    for field in fields {
+        let ident = field.ident.clone().unwrap();
+        let mut role = None;
+        for (key, value) in ovh_items(&field.attrs)? {
+            let name = value.unwrap_or_else(|| ident.to_string());
+            role = Some(match key.as_str() {
+                "path" => Role::Path(name),
+                "query" => Role::Query(name),
+                "body" => Role::Body(name),
+                other => return Err(syn::Error::new_spanned(&ident, format!("unknown ovh attribute {}", other))),
+            });
+        }
+        let role = match role {
+            Some(role) => role,
+            None if placeholders(&path).contains(&ident.to_string()) => Role::Path(ident.to_string()),
+            None => continue,
+        };
+        match role {
+            Role::Path(name) => {
+                bound.push(name.clone());
+                path_params.push(quote! { (#name, self.#ident.to_string()) });
+            }
+            Role::Query(ref name) if is_option(&field.ty) => {
+                query_params.push(quote! {
+                    if let Some(ref value) = self.#ident {
+                        params.push((#name, value.to_string()));
+                    }
+                });
+            }
+            Role::Query(name) => {
+                query_params.push(quote! { params.push((#name, self.#ident.to_string())); });
+            }
+            Role::Body(name) => {
+                body_fields.push(quote! { (#name, ::ovh::endpoint::to_json(&self.#ident)?) });
+            }
+        }
+    }
+    for placeholder in placeholders(&path) {
+        if !bound.contains(&placeholder) {
+            return Err(syn::Error::new(Span::call_site(),
+                                       format!("no field for the {{{}}} placeholder", placeholder)));
+        }
+    }
+
+    let (request, body) = if body_fields.is_empty() {
+        (quote! { () }, quote! { None })
+    } else {
+        let object = quote! { ::ovh::endpoint::json_object(vec![#(#body_fields),*]) };
+        (quote! { ::ovh::Value }, quote! { Some(#object) })
+    };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ovh::endpoint::ApiEndpoint for #name #ty_generics #where_clause {
+            type Request = #request;
+            type Response = #response;
+
+            const METHOD: &'static str = #method;
+            const PATH: &'static str = #path;
+
+            fn path_params(&self) -> Vec<(&'static str, String)> {
+                vec![#(#path_params),*]
+            }
+
+            #[allow(unused_mut)]
+            fn query_params(&self) -> Vec<(&'static str, String)> {
+                let mut params = Vec::new();
+                #(#query_params)*
+                params
+            }
+
+            fn request(&self) -> ::std::io::Result<Option<#request>> {
+                Ok(#body)
+            }
+
+            fn parse_response(value: &::ovh::Value) -> ::std::io::Result<#response> {
+                <#response as ::ovh::endpoint::FromResponse>::from_response(value)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::placeholders;
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(vec!["serviceName".to_string(), "id".to_string()],
+                   placeholders("/dedicated/server/{serviceName}/task/{id}"));
+        assert!(placeholders("/me").is_empty());
+    }
+}
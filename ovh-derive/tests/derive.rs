@@ -0,0 +1,54 @@
+#[macro_use]
+extern crate ovh;
+
+use ovh::endpoint::ApiEndpoint;
+
+#[derive(OvhEndpoint)]
+#[ovh(method = "GET", path = "/dedicated/server/{name}/task", response = "Vec<u64>")]
+struct ServerTasks {
+    name: String,
+    #[ovh(query)]
+    status: Option<String>,
+    #[ovh(query = "function")]
+    kind: String,
+}
+
+#[derive(OvhEndpoint)]
+#[ovh(method = "POST", path = "/vrack/{serviceName}/ip")]
+struct AddVrackBlock {
+    #[ovh(path = "serviceName")]
+    vrack: String,
+    #[ovh(body)]
+    block: String,
+    #[ovh(body = "comment")]
+    note: Option<String>,
+}
+
+#[test]
+fn test_derive_path() {
+    let mut tasks = ServerTasks {
+        name: "ns1.example.com".to_string(),
+        status: None,
+        kind: "reboot".to_string(),
+    };
+    assert_eq!(("GET", "/dedicated/server/ns1.example.com/task?function=reboot".to_string()),
+               (ServerTasks::METHOD, tasks.path().unwrap()));
+    tasks.status = Some("todo".to_string());
+    assert_eq!("/dedicated/server/ns1.example.com/task?status=todo&function=reboot",
+               tasks.path().unwrap());
+    assert!(tasks.request().unwrap().is_none());
+    assert_eq!(vec![4, 2], ServerTasks::parse_response(&ovh::Value::from(vec![4, 2])).unwrap());
+}
+
+#[test]
+fn test_derive_body() {
+    let block = AddVrackBlock {
+        vrack: "pn-123".to_string(),
+        block: "10.0.0.0/24".to_string(),
+        note: None,
+    };
+    assert_eq!("/vrack/pn-123/ip", block.path().unwrap());
+    let body = block.request().unwrap().unwrap();
+    assert_eq!(Some("10.0.0.0/24"), body.get("block").and_then(ovh::Value::as_str));
+    assert_eq!(Some(&ovh::Value::Null), body.get("comment"));
+}
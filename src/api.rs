@@ -0,0 +1,8 @@
+//! # Api
+//!
+//! The curated modules: typed models and helpers for the routes the crate
+//! covers. They are also available at the root of the crate (`ovh::dns`
+//! is `ovh::api::dns`); see `raw` for the untyped layer underneath.
+//!
+
+pub use {auth, cloud, dedicated, dns, domain, email, me, services, sms, stock, task, telephony};
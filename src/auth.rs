@@ -0,0 +1,347 @@
+//! # Auth
+//!
+//! Consumer keys: request one for an application (`/auth/credential`), and keep
+//! those of the account tidy (`/me/api/credential`): find keys which are old,
+//! unused or allowed everything, and revoke them.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::auth::AccessRules;
+//! use ovh::client::OVHClient;
+//!
+//! let mut client = OVHClient::try_new()?;
+//! let rules = AccessRules::new().get("/me").all("/domain/zone/example.com/*");
+//! let pending = client.request_consumer_key(&rules, "https://example.com/welcome")?;
+//! println!("Allow the application at {}", pending.validation_url);
+//! // once allowed, keep it in Config.toml
+//! client.credential.consumer_key = pending.consumer_key;
+//! client.credential.save()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+use services::parse_date;
+
+use std::io;
+
+use chrono::{Local, NaiveDate};
+
+use serde_json::Value;
+
+/// An access rule of a consumer key.
+#[derive(Debug,Clone,PartialEq)]
+pub struct AccessRule {
+    pub method: String,
+    pub path: String,
+}
+
+impl AccessRule {
+    pub fn new(method: &str, path: &str) -> AccessRule {
+        AccessRule {
+            method: method.to_string(),
+            path: path.to_string(),
+        }
+    }
+}
+
+/// Builder of the access rules of a consumer key request.
+///
+/// Paths are API routes, `*` matching any suffix: `/domain/zone/example.com/*`.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct AccessRules {
+    rules: Vec<AccessRule>,
+}
+
+impl AccessRules {
+    pub fn new() -> AccessRules {
+        AccessRules::default()
+    }
+
+    /// Allow `method` on `path`; duplicates are ignored.
+    pub fn rule(mut self, method: &str, path: &str) -> AccessRules {
+        let rule = AccessRule::new(&method.to_uppercase(), path);
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+        }
+        self
+    }
+
+    pub fn get(self, path: &str) -> AccessRules {
+        self.rule("GET", path)
+    }
+
+    pub fn post(self, path: &str) -> AccessRules {
+        self.rule("POST", path)
+    }
+
+    pub fn put(self, path: &str) -> AccessRules {
+        self.rule("PUT", path)
+    }
+
+    pub fn delete(self, path: &str) -> AccessRules {
+        self.rule("DELETE", path)
+    }
+
+    /// Allow every method on `path`.
+    pub fn all(self, path: &str) -> AccessRules {
+        self.get(path).post(path).put(path).delete(path)
+    }
+
+    pub fn rules(&self) -> &[AccessRule] {
+        &self.rules
+    }
+
+    /// The `accessRules` of the request.
+    pub fn to_json(&self) -> Value {
+        Value::Array(self.rules
+            .iter()
+            .map(|rule| json!({ "method": rule.method, "path": rule.path }))
+            .collect())
+    }
+}
+
+/// A consumer key waiting for the user to allow it at `validation_url`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PendingConsumerKey {
+    pub consumer_key: String,
+    pub validation_url: String,
+    /// `pendingValidation` until the user logs in and allows the key.
+    pub state: String,
+}
+
+impl OVHClient {
+    /// Request a consumer key for the application of the client.
+    ///
+    /// The key is usable once the user allowed it at `validation_url`;
+    /// they are then redirected to `redirect_url` (none when empty).
+    pub fn request_consumer_key(&self,
+                                rules: &AccessRules,
+                                redirect_url: &str)
+                                -> io::Result<PendingConsumerKey> {
+        if rules.rules().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no access rule requested"));
+        }
+        let mut body = json!({ "accessRules": rules.to_json() });
+        if !redirect_url.is_empty() {
+            body["redirection"] = json!(redirect_url);
+        }
+        // the route is not signed, only the application is sent
+        let mut credential = self.credential.clone();
+        credential.consumer_key = String::new();
+        let unsigned = OVHClient::with_credential(credential);
+        let value = rest::post(&unsigned, "/auth/credential", &body)?;
+        Ok(PendingConsumerKey {
+            consumer_key: rest::str_field(&value, "consumerKey")?,
+            validation_url: rest::str_field(&value, "validationUrl")?,
+            state: rest::opt_str_field(&value, "state").unwrap_or_default(),
+        })
+    }
+}
+
+/// A consumer key of the account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ApiCredential {
+    pub id: u64,
+    pub application_id: u64,
+    /// `validated`, `pendingValidation`, `expired` or `refused`.
+    pub status: String,
+    pub creation: Option<NaiveDate>,
+    pub last_use: Option<NaiveDate>,
+    pub expiration: Option<NaiveDate>,
+    pub rules: Vec<AccessRule>,
+}
+
+impl Model for ApiCredential {
+    const NAME: &'static str = "ApiCredential";
+    const FIELDS: &'static [&'static str] = &["credentialId", "applicationId", "status", "creation",
+                                              "lastUse", "expiration", "rules", "ovhSupport",
+                                              "allowedIPs"];
+
+    fn from_json(value: &Value) -> io::Result<ApiCredential> {
+        let date = |key: &str| value.get(key).and_then(Value::as_str).and_then(parse_date);
+        let mut rules = Vec::new();
+        if let Some(items) = value.get("rules").and_then(Value::as_array) {
+            for item in items {
+                rules.push(AccessRule::new(&rest::str_field(item, "method")?,
+                                           &rest::str_field(item, "path")?));
+            }
+        }
+        Ok(ApiCredential {
+            id: rest::u64_field(value, "credentialId")?,
+            application_id: rest::opt_u64_field(value, "applicationId").unwrap_or(0),
+            status: rest::str_field(value, "status")?,
+            creation: date("creation"),
+            last_use: date("lastUse"),
+            expiration: date("expiration"),
+            rules,
+        })
+    }
+}
+
+impl ApiCredential {
+    /// Whether a rule gives access to every route, for at least one method.
+    pub fn is_over_privileged(&self) -> bool {
+        self.rules.iter().any(|rule| rule.path == "/*")
+    }
+}
+
+pub fn credential_ids(client: &OVHClient) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, "/me/api/credential")?)
+}
+
+pub fn credential(client: &OVHClient, id: u64) -> io::Result<ApiCredential> {
+    rest::decode(client, &rest::get(client, &format!("/me/api/credential/{}", id))?)
+}
+
+/// Id of the consumer key used by the client.
+pub fn current_credential_id(client: &OVHClient) -> io::Result<u64> {
+    rest::u64_field(&rest::get(client, "/auth/currentCredential")?, "credentialId")
+}
+
+/// The consumer key the client signs with.
+pub fn current_credential(client: &OVHClient) -> io::Result<ApiCredential> {
+    rest::decode(client, &rest::get(client, "/auth/currentCredential")?)
+}
+
+pub fn revoke(client: &OVHClient, id: u64) -> io::Result<()> {
+    rest::delete(client, &format!("/me/api/credential/{}", id))?;
+    Ok(())
+}
+
+/// Why a key is pruned.
+#[derive(Debug,Clone,PartialEq)]
+pub enum PruneReason {
+    /// Expired or refused keys.
+    Inactive,
+    /// Created more than `older_than` days ago.
+    Old(i64),
+    /// Not used for more than `unused_for` days, or never used.
+    Unused(Option<i64>),
+    OverPrivileged,
+}
+
+/// Which keys `prune_credentials` revokes. Keys matching any criterion are revoked.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PrunePolicy {
+    pub older_than: Option<i64>,
+    pub unused_for: Option<i64>,
+    pub over_privileged: bool,
+    /// Only report what would be revoked.
+    pub dry_run: bool,
+}
+
+impl PrunePolicy {
+    /// Dry run pruning keys unused for `unused_for` days or older than `older_than` days.
+    pub fn new(older_than: Option<i64>, unused_for: Option<i64>) -> PrunePolicy {
+        PrunePolicy {
+            older_than,
+            unused_for,
+            over_privileged: false,
+            dry_run: true,
+        }
+    }
+
+    /// Why the key should be pruned, if it should be.
+    pub fn reason(&self, credential: &ApiCredential, today: NaiveDate) -> Option<PruneReason> {
+        if credential.status == "expired" || credential.status == "refused" {
+            return Some(PruneReason::Inactive);
+        }
+        if let (Some(days), Some(creation)) = (self.older_than, credential.creation) {
+            let age = (today - creation).num_days();
+            if age > days {
+                return Some(PruneReason::Old(age));
+            }
+        }
+        if let Some(days) = self.unused_for {
+            match credential.last_use {
+                Some(last_use) if (today - last_use).num_days() <= days => {}
+                Some(last_use) => return Some(PruneReason::Unused(Some((today - last_use).num_days()))),
+                None => return Some(PruneReason::Unused(None)),
+            }
+        }
+        if self.over_privileged && credential.is_over_privileged() {
+            return Some(PruneReason::OverPrivileged);
+        }
+        None
+    }
+}
+
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct PruneReport {
+    /// Keys revoked, or to revoke in a dry run.
+    pub revoked: Vec<(ApiCredential, PruneReason)>,
+    pub kept: Vec<ApiCredential>,
+    pub dry_run: bool,
+}
+
+/// Revoke the consumer keys matching the policy.
+/// The key used by the client is always kept.
+pub fn prune_credentials(client: &OVHClient, policy: &PrunePolicy) -> io::Result<PruneReport> {
+    let current = current_credential_id(client)?;
+    let today = Local::today().naive_local();
+    let mut report = PruneReport {
+        dry_run: policy.dry_run,
+        ..PruneReport::default()
+    };
+    for id in credential_ids(client)? {
+        let credential = credential(client, id)?;
+        let reason = if id == current {
+            None
+        } else {
+            policy.reason(&credential, today)
+        };
+        match reason {
+            Some(reason) => {
+                if !policy.dry_run {
+                    revoke(client, id)?;
+                    info!("Revoked consumer key {}: {:?}", id, reason);
+                }
+                report.revoked.push((credential, reason));
+            }
+            None => report.kept.push(credential),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessRule, AccessRules, ApiCredential, PrunePolicy, PruneReason};
+    use rest::Model;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_access_rules() {
+        let rules = AccessRules::new().get("/me").all("/domain/*").rule("get", "/me");
+        assert_eq!(5, rules.rules().len());
+        assert_eq!(AccessRule::new("DELETE", "/domain/*"), rules.rules()[4]);
+        assert_eq!(json!({ "method": "GET", "path": "/me" }), rules.to_json()[0]);
+    }
+
+    #[test]
+    fn test_prune_reason() {
+        let mut credential = ApiCredential::from_json(&json!({
+            "credentialId": 1,
+            "applicationId": 2,
+            "status": "validated",
+            "creation": "2016-01-01T00:00:00+01:00",
+            "lastUse": "2017-04-01T00:00:00+02:00",
+            "rules": [{ "method": "GET", "path": "/*" }],
+        }))
+            .unwrap();
+        let today = NaiveDate::from_ymd(2017, 5, 1);
+        let mut policy = PrunePolicy::new(None, Some(90));
+        assert_eq!(None, policy.reason(&credential, today));
+        policy.over_privileged = true;
+        assert_eq!(Some(PruneReason::OverPrivileged), policy.reason(&credential, today));
+        policy.unused_for = Some(7);
+        assert_eq!(Some(PruneReason::Unused(Some(30))), policy.reason(&credential, today));
+        policy.older_than = Some(365);
+        assert_eq!(Some(PruneReason::Old(486)), policy.reason(&credential, today));
+        credential.status = "expired".to_string();
+        assert_eq!(Some(PruneReason::Inactive), policy.reason(&credential, today));
+    }
+}
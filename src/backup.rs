@@ -0,0 +1,1204 @@
+//! # Backup
+//!
+//! Dump the configuration of an account into YAML files meant to be
+//! committed, so `git diff` shows how it drifted between two dumps:
+//!
+//! - `zones/<zone>.yaml`: the records of each DNS zone,
+//! - `firewall/<ip block>.yaml`: the IP firewalls and their rules,
+//! - `iplb/<service>.yaml`: the frontends, farms and servers of each IP load balancer,
+//! - `cloud/<project>.yaml`: each Public Cloud project with its instances,
+//!   volumes, SSH keys and private networks.
+//!
+//! Files are deterministic: keys are sorted, lists of resources too, and
+//! timestamps which change without a change of configuration are left out.
+//! A file is only rewritten when its content changed, and files of resources
+//! which no longer exist are removed.
+//!
+//! ```no_run
+//! # use ovh::OVHClient;
+//! let client = OVHClient::new();
+//! let report = ovh::backup::dump(&client, "ovh-config").unwrap();
+//! println!("{} files changed", report.written.len() + report.removed.len());
+//! ```
+//!
+//! `restore` replays a dump: DNS zones through the planning of `dns::sync`,
+//! firewalls and load balancers by comparing the dump with their live state.
+//! Its plan can be previewed first:
+//!
+//! ```no_run
+//! # use ovh::OVHClient;
+//! use ovh::backup::{self, Scope, Section};
+//!
+//! let client = OVHClient::new();
+//! let scope = Scope::section(Section::Zones).name("example.com");
+//! let plan = backup::restore_plan(&client, "ovh-config", &scope).unwrap();
+//! print!("{}", plan);
+//! plan.apply(&client).unwrap();
+//! ```
+//!
+//! Public Cloud projects are dumped for reference only and cannot be restored.
+//!
+
+use client::OVHClient;
+use dns::{self, Record, RecordSpec};
+use dns::sync::{self, spec_hash, Journal, JournalEntry};
+use error;
+use rest;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Map, Value};
+
+/// Fields left out of the dumps as they change without a change of configuration.
+pub const VOLATILE_FIELDS: &[&str] = &["lastUpdate",
+                                                        "lastUpdateDate",
+                                                        "lastModification"];
+
+/// A part of the configuration, dumped in its own directory.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Section {
+    Zones,
+    Firewalls,
+    LoadBalancers,
+    Cloud,
+}
+
+impl Section {
+    pub const ALL: &'static [Section] = &[Section::Zones,
+                                          Section::Firewalls,
+                                          Section::LoadBalancers,
+                                          Section::Cloud];
+
+    /// Sections which `restore` can bring back.
+    pub const RESTORABLE: &'static [Section] = &[Section::Zones,
+                                                 Section::Firewalls,
+                                                 Section::LoadBalancers];
+
+    /// Directory of the section in the dump.
+    pub fn dir_name(&self) -> &'static str {
+        match *self {
+            Section::Zones => "zones",
+            Section::Firewalls => "firewall",
+            Section::LoadBalancers => "iplb",
+            Section::Cloud => "cloud",
+        }
+    }
+
+    /// The documents of the section, by name.
+    fn documents(&self, client: &OVHClient) -> io::Result<Vec<(String, Value)>> {
+        match *self {
+            Section::Zones => each(client, "/domain/zone", zone),
+            Section::Firewalls => each(client, "/ip", firewall),
+            Section::LoadBalancers => each(client, "/ipLoadbalancing", load_balancer),
+            Section::Cloud => each(client, "/cloud/project", cloud_project),
+        }
+    }
+}
+
+/// Outcome of a `dump`.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct DumpReport {
+    /// Files created or changed.
+    pub written: Vec<PathBuf>,
+    /// Number of files already up to date.
+    pub unchanged: usize,
+    /// Files of resources which no longer exist.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Dump every section into `dir`.
+pub fn dump<P: AsRef<Path>>(client: &OVHClient, dir: P) -> io::Result<DumpReport> {
+    dump_sections(client, dir, Section::ALL)
+}
+
+/// Dump some sections into `dir`, leaving the others as they are.
+pub fn dump_sections<P: AsRef<Path>>(client: &OVHClient,
+                                     dir: P,
+                                     sections: &[Section])
+                                     -> io::Result<DumpReport> {
+    let mut report = DumpReport::default();
+    for section in sections {
+        let documents = section.documents(client)?;
+        write_section(&dir.as_ref().join(section.dir_name()), &documents, &mut report)?;
+    }
+    Ok(report)
+}
+
+/// Write the documents of a section and remove the files of the missing ones.
+fn write_section(dir: &Path,
+                 documents: &[(String, Value)],
+                 report: &mut DumpReport)
+                 -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut kept = BTreeSet::new();
+    for (name, document) in documents {
+        let path = dir.join(format!("{}.yaml", file_name(name)));
+        if write_if_changed(&path, &to_yaml(document))? {
+            report.written.push(path.clone());
+        } else {
+            report.unchanged += 1;
+        }
+        kept.insert(path);
+    }
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "yaml") && !kept.contains(&path) {
+            stale.push(path);
+        }
+    }
+    stale.sort();
+    for path in stale {
+        fs::remove_file(&path)?;
+        report.removed.push(path);
+    }
+    Ok(())
+}
+
+/// Write `content` atomically unless the file already holds it; `true` if written.
+fn write_if_changed(path: &Path, content: &str) -> io::Result<bool> {
+    let mut current = String::new();
+    if let Ok(mut fd) = File::open(path) {
+        if fd.read_to_string(&mut current).is_ok() && current == content {
+            return Ok(false);
+        }
+    }
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    File::create(&tmp)?.write_all(content.as_bytes())?;
+    fs::rename(&tmp, path)?;
+    Ok(true)
+}
+
+/// A resource name usable as a file name: `192.0.2.0/24` gives `192.0.2.0_24`.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+        .collect()
+}
+
+fn is_not_found(err: &io::Error) -> bool {
+    error::api_error(err).is_some_and(|api| api.http_code == 404)
+}
+
+/// Path segment of an id listed by the API, a string or a number.
+fn segment(id: &Value) -> String {
+    id.as_str().map_or_else(|| id.to_string(), rest::escape)
+}
+
+/// The ids listed at `route`, sorted, none if the route does not exist.
+fn ids(client: &OVHClient, route: &str) -> io::Result<Vec<Value>> {
+    let mut ids = match rest::get(client, route) {
+        Ok(value) => rest::array(&value)?.clone(),
+        Err(ref err) if is_not_found(err) => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    ids.sort_by(|a, b| match (a.as_u64(), b.as_u64()) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.to_string().cmp(&b.to_string()),
+    });
+    Ok(ids)
+}
+
+/// Build a document for each of the resources listed at `route`.
+fn each<F>(client: &OVHClient, route: &str, document: F) -> io::Result<Vec<(String, Value)>>
+    where F: Fn(&OVHClient, &str) -> io::Result<Value>
+{
+    let mut documents = Vec::new();
+    for id in ids(client, route)? {
+        let name = id.as_str().map_or_else(|| id.to_string(), String::from);
+        documents.push((name.clone(), document(client, &name)?));
+    }
+    Ok(documents)
+}
+
+/// The resources listed at `route`, in the order of their ids.
+fn fetch_all(client: &OVHClient, route: &str) -> io::Result<Vec<Value>> {
+    ids(client, route)?
+        .iter()
+        .map(|id| rest::get(client, &format!("{}/{}", route, segment(id))).map(|item| clean(&item)))
+        .collect()
+}
+
+/// Copy of `value` without the `VOLATILE_FIELDS`, at any depth.
+pub fn clean(value: &Value) -> Value {
+    match *value {
+        Value::Object(ref map) => {
+            let mut cleaned = Map::new();
+            for (key, item) in map {
+                if !VOLATILE_FIELDS.contains(&key.as_str()) {
+                    cleaned.insert(key.clone(), clean(item));
+                }
+            }
+            Value::Object(cleaned)
+        }
+        Value::Array(ref items) => Value::Array(items.iter().map(clean).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Sort resources by content, for those whose ids change when they are recreated.
+fn sorted(mut items: Vec<Value>) -> Vec<Value> {
+    items.sort_by_key(|item| item.to_string());
+    items
+}
+
+fn without(mut value: Value, keys: &[&str]) -> Value {
+    if let Value::Object(ref mut map) = value {
+        for key in keys {
+            map.remove(*key);
+        }
+    }
+    value
+}
+
+fn zone(client: &OVHClient, zone: &str) -> io::Result<Value> {
+    let route = format!("/domain/zone/{}/record", rest::escape(zone));
+    let records = fetch_all(client, &route)?
+        .into_iter()
+        .map(|record| without(record, &["id", "zone"]))
+        .collect();
+    Ok(json!({ "zone": zone, "records": sorted(records) }))
+}
+
+fn firewall(client: &OVHClient, block: &str) -> io::Result<Value> {
+    let route = format!("/ip/{}/firewall", rest::escape(block));
+    let mut firewalls = Map::new();
+    for ip in ids(client, &route)? {
+        let ip_route = format!("{}/{}", route, segment(&ip));
+        let mut state = clean(&rest::get(client, &ip_route)?);
+        if let Value::Object(ref mut map) = state {
+            map.insert("rules".to_string(),
+                       Value::Array(fetch_all(client, &format!("{}/rule", ip_route))?));
+        }
+        firewalls.insert(ip.as_str().map_or_else(|| ip.to_string(), String::from), state);
+    }
+    Ok(json!({ "block": block, "firewalls": firewalls }))
+}
+
+fn load_balancer(client: &OVHClient, service: &str) -> io::Result<Value> {
+    let route = format!("/ipLoadbalancing/{}", rest::escape(service));
+    let mut document = Map::new();
+    document.insert("service".to_string(), clean(&rest::get(client, &route)?));
+    for protocol in &["http", "tcp", "udp"] {
+        let mut farms = Vec::new();
+        let farm_route = format!("{}/{}/farm", route, protocol);
+        for farm in ids(client, &farm_route)? {
+            let farm_route = format!("{}/{}", farm_route, segment(&farm));
+            let mut farm = clean(&rest::get(client, &farm_route)?);
+            if let Value::Object(ref mut map) = farm {
+                map.insert("servers".to_string(),
+                           Value::Array(fetch_all(client, &format!("{}/server", farm_route))?));
+            }
+            farms.push(farm);
+        }
+        let frontends = fetch_all(client, &format!("{}/{}/frontend", route, protocol))?;
+        if !farms.is_empty() || !frontends.is_empty() {
+            document.insert(protocol.to_string(),
+                            json!({ "frontends": frontends, "farms": farms }));
+        }
+    }
+    Ok(Value::Object(document))
+}
+
+fn cloud_project(client: &OVHClient, project_id: &str) -> io::Result<Value> {
+    let route = format!("/cloud/project/{}", rest::escape(project_id));
+    let list = |resource: &str| -> io::Result<Value> {
+        match rest::get(client, &format!("{}/{}", route, resource)) {
+            Ok(value) => Ok(Value::Array(sorted(rest::array(&clean(&value))?.clone()))),
+            Err(ref err) if is_not_found(err) => Ok(Value::Array(Vec::new())),
+            Err(err) => Err(err),
+        }
+    };
+    Ok(json!({
+        "project": clean(&rest::get(client, &route)?),
+        "instances": list("instance")?,
+        "volumes": list("volume")?,
+        "sshKeys": list("sshkey")?,
+        "privateNetworks": list("network/private")?,
+    }))
+}
+
+/// A call of a restore.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Change {
+    pub section: Section,
+    /// Zone, IP block or load balancer changed.
+    pub resource: String,
+    pub method: &'static str,
+    /// Route called; `{farm}` stands for a farm created earlier by the same plan.
+    pub route: String,
+    pub body: Option<Value>,
+    /// Id in the dump of the farm this change creates.
+    creates_farm: Option<u64>,
+    /// Id in the dump of the farm, created by the plan, which the route or body refers to.
+    farm: Option<u64>,
+}
+
+impl Change {
+    fn new(section: Section,
+           resource: &str,
+           method: &'static str,
+           route: String,
+           body: Option<Value>)
+           -> Change {
+        Change {
+            section,
+            resource: resource.to_string(),
+            method,
+            route,
+            body,
+            creates_farm: None,
+            farm: None,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.route)?;
+        match self.body {
+            Some(ref body) => write!(f, " {}", body),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What a restore replays: some sections, optionally restricted to some resources.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Scope {
+    pub sections: Vec<Section>,
+    /// Zones, IP blocks or load balancers to restore, all of them when empty.
+    pub names: Vec<String>,
+}
+
+impl Scope {
+    /// Every section which can be restored.
+    pub fn all() -> Scope {
+        Scope {
+            sections: Section::RESTORABLE.to_vec(),
+            names: Vec::new(),
+        }
+    }
+
+    pub fn section(section: Section) -> Scope {
+        Scope {
+            sections: vec![section],
+            names: Vec::new(),
+        }
+    }
+
+    /// Restrict the restore to a resource, by name.
+    pub fn name(mut self, name: &str) -> Self {
+        self.names.push(name.to_string());
+        self
+    }
+
+    fn includes(&self, name: &str) -> bool {
+        self.names.is_empty() || self.names.iter().any(|wanted| wanted == name)
+    }
+}
+
+/// The calls bringing the account back to the state of a dump.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct RestorePlan {
+    pub changes: Vec<Change>,
+}
+
+impl RestorePlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Send the changes in order, stopping at the first failure.
+    /// Returns the number of changes sent.
+    pub fn apply(&self, client: &OVHClient) -> io::Result<usize> {
+        let mut farms: HashMap<u64, u64> = HashMap::new();
+        for (done, change) in self.changes.iter().enumerate() {
+            let mut route = change.route.clone();
+            let mut body = change.body.clone();
+            if let Some(old) = change.farm {
+                let id = farms.get(&old).cloned().unwrap_or(old);
+                route = route.replace("{farm}", &id.to_string());
+                if let Some(Value::Object(ref mut map)) = body {
+                    if map.contains_key("defaultFarmId") {
+                        map.insert("defaultFarmId".to_string(), json!(id));
+                    }
+                }
+            }
+            let answer = rest::call(client, change.method, &route, body.as_ref()).map_err(|err| {
+                io::Error::new(err.kind(),
+                               format!("{} {} failed after {} of {} changes: {}",
+                                       change.method,
+                                       route,
+                                       done,
+                                       self.changes.len(),
+                                       err))
+            })?;
+            if let Some(old) = change.creates_farm {
+                farms.insert(old, rest::u64_field(&answer, "farmId")?);
+            }
+        }
+        Ok(self.changes.len())
+    }
+}
+
+impl fmt::Display for RestorePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}/{}: {}", change.section.dir_name(), change.resource, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The changes which `restore` would send to bring back the configuration dumped in `dir`.
+pub fn restore_plan<P: AsRef<Path>>(client: &OVHClient,
+                                    dir: P,
+                                    scope: &Scope)
+                                    -> io::Result<RestorePlan> {
+    if scope.sections.contains(&Section::Cloud) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "Public Cloud projects cannot be restored"));
+    }
+    let mut plan = RestorePlan::default();
+    for section in &scope.sections {
+        for (name, document) in read_section(&dir.as_ref().join(section.dir_name()), *section)? {
+            if !scope.includes(&name) {
+                continue;
+            }
+            let changes = match *section {
+                Section::Zones => {
+                    dns_changes(&name, &dns::records(client, &name)?, &record_specs(&document)?)
+                }
+                Section::Firewalls => firewall_changes(&name, &document, &firewall(client, &name)?),
+                Section::LoadBalancers => {
+                    load_balancer_changes(&name, &document, &load_balancer(client, &name)?)
+                }
+                Section::Cloud => unreachable!(),
+            };
+            plan.changes.extend(changes);
+        }
+    }
+    Ok(plan)
+}
+
+/// Bring back the configuration dumped in `dir` and return the changes sent.
+pub fn restore<P: AsRef<Path>>(client: &OVHClient,
+                               dir: P,
+                               scope: &Scope)
+                               -> io::Result<RestorePlan> {
+    let plan = restore_plan(client, dir, scope)?;
+    plan.apply(client)?;
+    Ok(plan)
+}
+
+/// The documents of a section of a dump, by resource name.
+fn read_section(dir: &Path, section: Section) -> io::Result<Vec<(String, Value)>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "yaml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    let mut documents = Vec::new();
+    for path in paths {
+        let mut text = String::new();
+        File::open(&path)?.read_to_string(&mut text)?;
+        let invalid = |message: String| rest::invalid_data(&format!("{:?}: {}", path, message));
+        let document = from_yaml(&text).map_err(|err| invalid(err.to_string()))?;
+        let name = match section {
+            Section::Zones => document.get("zone"),
+            Section::Firewalls => document.get("block"),
+            Section::LoadBalancers => document.get("service").and_then(|s| s.get("serviceName")),
+            Section::Cloud => document.get("project").and_then(|p| p.get("project_id")),
+        };
+        match name.and_then(Value::as_str) {
+            Some(name) => documents.push((name.to_string(), document.clone())),
+            None => return Err(invalid("resource name missing".to_string())),
+        }
+    }
+    Ok(documents)
+}
+
+fn record_specs(document: &Value) -> io::Result<Vec<RecordSpec>> {
+    let records = match document.get("records") {
+        Some(records) => rest::array(records)?,
+        None => return Ok(Vec::new()),
+    };
+    let mut specs = Vec::new();
+    for record in records {
+        specs.push(RecordSpec::new(&rest::str_field(record, "fieldType")?,
+                                   &rest::opt_str_field(record, "subDomain").unwrap_or_default(),
+                                   &rest::str_field(record, "target")?,
+                                   rest::opt_u64_field(record, "ttl").unwrap_or(0) as u32));
+    }
+    Ok(specs)
+}
+
+/// Changes of a zone, planned by the DNS sync as if every live record was its own.
+fn dns_changes(zone: &str, live: &[Record], desired: &[RecordSpec]) -> Vec<Change> {
+    let mut journal = Journal::new(zone);
+    let mut delete = Vec::new();
+    for record in live {
+        let entry = JournalEntry {
+            id: record.id,
+            field_type: record.field_type.clone(),
+            sub_domain: record.sub_domain.clone(),
+        };
+        if let Some(duplicate) = journal.records.insert(spec_hash(&record.spec()), entry) {
+            delete.push(duplicate.id);
+        }
+    }
+    let plan = sync::plan(&journal, desired);
+    delete.extend(plan.delete);
+
+    let route = format!("/domain/zone/{}/record", rest::escape(zone));
+    let change = |method, route, body| Change::new(Section::Zones, zone, method, route, body);
+    let mut changes = Vec::new();
+    for id in delete {
+        changes.push(change("DELETE", format!("{}/{}", route, id), None));
+    }
+    for (id, spec) in plan.update {
+        let body = json!({ "subDomain": spec.sub_domain, "target": spec.target, "ttl": spec.ttl });
+        changes.push(change("PUT", format!("{}/{}", route, id), Some(body)));
+    }
+    for spec in plan.create {
+        changes.push(change("POST", route.clone(), Some(spec.to_json())));
+    }
+    if !changes.is_empty() {
+        let refresh = format!("/domain/zone/{}/refresh", rest::escape(zone));
+        changes.push(change("POST", refresh, None));
+    }
+    changes
+}
+
+/// Fields of a firewall rule compared by a restore.
+const RULE_FIELDS: &[&str] = &["action", "protocol", "source", "destinationPort",
+                                               "sourcePort", "fragments", "tcpOption"];
+
+fn rule_key(rule: &Value) -> Vec<Value> {
+    RULE_FIELDS.iter().map(|field| rule.get(*field).cloned().unwrap_or(Value::Null)).collect()
+}
+
+/// Port of a rule, `eq 443` as listed by the API.
+fn port(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str().and_then(|text| text.split(' ').next_back()?.parse().ok()))
+}
+
+/// Body creating a rule as dumped.
+fn rule_body(rule: &Value) -> Value {
+    let mut body = Map::new();
+    for key in &["sequence", "action", "protocol", "source"] {
+        match rule.get(*key) {
+            Some(&Value::Null) | None => {}
+            Some(value) => {
+                body.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+    for key in &["destinationPort", "sourcePort"] {
+        if let Some(port) = rule.get(*key).and_then(port) {
+            body.insert(key.to_string(), json!(port));
+        }
+    }
+    let mut tcp_option = Map::new();
+    if let Some(option) = rule.get("tcpOption").filter(|option| option.is_string()) {
+        tcp_option.insert("option".to_string(), option.clone());
+    }
+    if let Some(&Value::Bool(true)) = rule.get("fragments") {
+        tcp_option.insert("fragments".to_string(), Value::Bool(true));
+    }
+    if !tcp_option.is_empty() {
+        body.insert("tcpOption".to_string(), Value::Object(tcp_option));
+    }
+    Value::Object(body)
+}
+
+fn firewalls(document: &Value) -> Option<&Map<String, Value>> {
+    document.get("firewalls").and_then(Value::as_object)
+}
+
+fn rules_by_sequence(state: Option<&Value>) -> BTreeMap<u64, &Value> {
+    let rules = state.and_then(|state| state.get("rules")).and_then(Value::as_array);
+    rules.into_iter()
+        .flat_map(|rules| rules.iter())
+        .filter_map(|rule| rest::opt_u64_field(rule, "sequence").map(|sequence| (sequence, rule)))
+        .collect()
+}
+
+/// Changes of the firewalls of an IP block, between a dumped and a live document.
+/// Rules cannot be updated: a changed rule is removed and created again.
+fn firewall_changes(block: &str, wanted: &Value, live: &Value) -> Vec<Change> {
+    let route = format!("/ip/{}/firewall", rest::escape(block));
+    let change = |method, route, body| Change::new(Section::Firewalls, block, method, route, body);
+    let empty = Map::new();
+    let mut changes = Vec::new();
+    for (ip, state) in firewalls(wanted).unwrap_or(&empty) {
+        let current = firewalls(live).and_then(|live| live.get(ip));
+        let ip_route = format!("{}/{}", route, rest::escape(ip));
+        if current.is_none() {
+            changes.push(change("POST", route.clone(), Some(json!({ "ipOnFirewall": ip }))));
+        }
+        let wanted_rules = rules_by_sequence(Some(state));
+        let current_rules = rules_by_sequence(current);
+        for (sequence, rule) in &current_rules {
+            let same = |wanted: &&Value| rule_key(wanted) == rule_key(rule);
+            if !wanted_rules.get(sequence).is_some_and(same) {
+                changes.push(change("DELETE", format!("{}/rule/{}", ip_route, sequence), None));
+            }
+        }
+        for (sequence, rule) in &wanted_rules {
+            let same = |current: &&Value| rule_key(current) == rule_key(rule);
+            if !current_rules.get(sequence).is_some_and(same) {
+                changes.push(change("POST", format!("{}/rule", ip_route), Some(rule_body(rule))));
+            }
+        }
+        let enabled = |state: Option<&Value>| {
+            state.and_then(|state| state.get("enabled")).and_then(Value::as_bool).unwrap_or(false)
+        };
+        if enabled(Some(state)) != enabled(current) {
+            changes.push(change("PUT", ip_route, Some(json!({ "enabled": enabled(Some(state)) }))));
+        }
+    }
+    changes
+}
+
+/// Fields of load balancer resources which cannot be written.
+const READ_ONLY_FIELDS: &[&str] = &["farmId", "frontendId", "serverId", "servers",
+                                                    "status"];
+
+/// Name matching a dumped load balancer resource with a live one, its display name if any.
+fn iplb_key(item: &Value, fallbacks: &[&str]) -> Option<String> {
+    let mut fields = vec!["displayName"];
+    fields.extend_from_slice(fallbacks);
+    fields.iter()
+        .filter_map(|field| item.get(*field))
+        .filter(|value| !value.is_null() && value.as_str() != Some(""))
+        .map(|value| value.as_str().map_or_else(|| value.to_string(), String::from))
+        .next()
+}
+
+fn matching<'a>(items: &'a [Value], item: &Value, fallbacks: &[&str]) -> Option<&'a Value> {
+    let key = iplb_key(item, fallbacks)?;
+    items.iter().find(|other| iplb_key(other, fallbacks).as_ref() == Some(&key))
+}
+
+fn list<'a>(document: &'a Value, protocol: &str, name: &str) -> &'a [Value] {
+    document.get(protocol)
+        .and_then(|part| part.get(name))
+        .and_then(Value::as_array)
+        .map_or(&[], |items| &items[..])
+}
+
+/// Changes of the servers of a farm; `route` is the route of its servers.
+fn server_changes(service: &str,
+                  route: &str,
+                  farm: Option<u64>,
+                  wanted: &[Value],
+                  live: &[Value])
+                  -> Vec<Change> {
+    let fallbacks = &["address"];
+    let change = |method, route, body| {
+        Change::new(Section::LoadBalancers, service, method, route, body)
+    };
+    let mut changes = Vec::new();
+    for server in wanted {
+        let body = without(server.clone(), READ_ONLY_FIELDS);
+        let mut change = match matching(live, server, fallbacks) {
+            Some(current) if without(current.clone(), READ_ONLY_FIELDS) == body => continue,
+            Some(current) => {
+                let id = rest::opt_u64_field(current, "serverId").unwrap_or(0);
+                change("PUT", format!("{}/{}", route, id), Some(body))
+            }
+            None => change("POST", route.to_string(), Some(body)),
+        };
+        change.farm = farm;
+        changes.push(change);
+    }
+    for current in live {
+        if matching(wanted, current, fallbacks).is_none() {
+            let id = rest::opt_u64_field(current, "serverId").unwrap_or(0);
+            changes.push(change("DELETE", format!("{}/{}", route, id), None));
+        }
+    }
+    changes
+}
+
+/// Changes of a load balancer, between a dumped and a live document. Farms and
+/// frontends are matched by display name, servers by display name or address.
+/// Frontends are removed before farms, which they may use.
+fn load_balancer_changes(service: &str, wanted: &Value, live: &Value) -> Vec<Change> {
+    let route = format!("/ipLoadbalancing/{}", rest::escape(service));
+    let change = |method, route, body| {
+        Change::new(Section::LoadBalancers, service, method, route, body)
+    };
+    let mut changes = Vec::new();
+    let mut removed_frontends = Vec::new();
+    let mut removed_farms = Vec::new();
+    for protocol in &["http", "tcp", "udp"] {
+        let farm_route = format!("{}/{}/farm", route, protocol);
+        // Live id of each dumped farm, none for those created by the plan.
+        let mut farms: HashMap<u64, Option<u64>> = HashMap::new();
+        let live_farms = list(live, protocol, "farms");
+        for farm in list(wanted, protocol, "farms") {
+            let old_id = rest::opt_u64_field(farm, "farmId").unwrap_or(0);
+            let body = without(farm.clone(), READ_ONLY_FIELDS);
+            let servers = |farm: &Value| {
+                farm.get("servers").and_then(Value::as_array).cloned().unwrap_or_default()
+            };
+            match matching(live_farms, farm, &["farmId"]) {
+                Some(current) => {
+                    let id = rest::opt_u64_field(current, "farmId").unwrap_or(0);
+                    farms.insert(old_id, Some(id));
+                    let id_route = format!("{}/{}", farm_route, id);
+                    if without(current.clone(), READ_ONLY_FIELDS) != body {
+                        changes.push(change("PUT", id_route.clone(), Some(body)));
+                    }
+                    changes.extend(server_changes(service,
+                                                  &format!("{}/server", id_route),
+                                                  None,
+                                                  &servers(farm),
+                                                  &servers(current)));
+                }
+                None => {
+                    farms.insert(old_id, None);
+                    let mut create = change("POST", farm_route.clone(), Some(body));
+                    create.creates_farm = Some(old_id);
+                    changes.push(create);
+                    changes.extend(server_changes(service,
+                                                  &format!("{}/{{farm}}/server", farm_route),
+                                                  Some(old_id),
+                                                  &servers(farm),
+                                                  &[]));
+                }
+            }
+        }
+        for current in live_farms {
+            if matching(list(wanted, protocol, "farms"), current, &["farmId"]).is_none() {
+                let id = rest::opt_u64_field(current, "farmId").unwrap_or(0);
+                removed_farms.push(change("DELETE", format!("{}/{}", farm_route, id), None));
+            }
+        }
+
+        let frontend_route = format!("{}/{}/frontend", route, protocol);
+        let live_frontends = list(live, protocol, "frontends");
+        for frontend in list(wanted, protocol, "frontends") {
+            let mut body = without(frontend.clone(), READ_ONLY_FIELDS);
+            let mut farm = None;
+            if let Some(old) = rest::opt_u64_field(frontend, "defaultFarmId") {
+                match farms.get(&old) {
+                    Some(&Some(id)) => {
+                        body["defaultFarmId"] = json!(id);
+                    }
+                    Some(&None) => farm = Some(old),
+                    None => {}
+                }
+            }
+            let mut change = match matching(live_frontends, frontend, &["frontendId"]) {
+                Some(current) if without(current.clone(), READ_ONLY_FIELDS) == body => continue,
+                Some(current) => {
+                    let id = rest::opt_u64_field(current, "frontendId").unwrap_or(0);
+                    change("PUT", format!("{}/{}", frontend_route, id), Some(body))
+                }
+                None => change("POST", frontend_route.clone(), Some(body)),
+            };
+            change.farm = farm;
+            changes.push(change);
+        }
+        for current in live_frontends {
+            if matching(list(wanted, protocol, "frontends"), current, &["frontendId"]).is_none() {
+                let id = rest::opt_u64_field(current, "frontendId").unwrap_or(0);
+                let id_route = format!("{}/{}", frontend_route, id);
+                removed_frontends.push(change("DELETE", id_route, None));
+            }
+        }
+    }
+    changes.extend(removed_frontends);
+    changes.extend(removed_farms);
+    if !changes.is_empty() {
+        changes.push(change("POST", format!("{}/refresh", route), None));
+    }
+    changes
+}
+
+/// Whether a string can be written without quotes and still read back as the same string.
+fn is_plain(text: &str) -> bool {
+    let reserved = ["true", "false", "yes", "no", "on", "off", "null", "~", "y", "n", ".inf",
+                    ".nan"];
+    let first = match text.chars().next() {
+        Some(first) => first,
+        None => return false,
+    };
+    // Numbers, dates and times of YAML 1.1, IP addresses with them.
+    let numeric = first.is_ascii_digit() &&
+                  text.chars().all(|c| c.is_ascii_hexdigit() || ".:-_+xXoOtTzZ ".contains(c));
+    !reserved.contains(&text.to_lowercase().as_str()) && !numeric &&
+    !"-?:,[]{}#&*!|>'\"%@`".contains(first) && !first.is_whitespace() &&
+    !text.ends_with(char::is_whitespace) && !text.ends_with(':') &&
+    !text.contains(": ") && !text.contains(" #") && !text.chars().any(char::is_control)
+}
+
+fn scalar(value: &Value) -> String {
+    match *value {
+        Value::String(ref text) if is_plain(text) => text.clone(),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        // A JSON string is a valid YAML double-quoted one.
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    match *value {
+        Value::Array(ref items) => !items.is_empty(),
+        Value::Object(ref map) => !map.is_empty(),
+        _ => false,
+    }
+}
+
+fn write_block(value: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match *value {
+        Value::Object(ref map) => {
+            for (key, item) in map {
+                let key = scalar(&Value::String(key.clone()));
+                if is_nested(item) {
+                    out.push_str(&format!("{}{}:\n", pad, key));
+                    write_block(item, indent + 2, out);
+                } else {
+                    out.push_str(&format!("{}{}: {}\n", pad, key, scalar(item)));
+                }
+            }
+        }
+        Value::Array(ref items) => {
+            for item in items {
+                match *item {
+                    Value::Object(_) if is_nested(item) => {
+                        let mut nested = String::new();
+                        write_block(item, indent + 2, &mut nested);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    Value::Array(_) if is_nested(item) => {
+                        out.push_str(&format!("{}-\n", pad));
+                        write_block(item, indent + 2, out);
+                    }
+                    _ => out.push_str(&format!("{}- {}\n", pad, scalar(item))),
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", pad, scalar(value))),
+    }
+}
+
+/// Write a value as block-style YAML, keys in their (sorted) order.
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    write_block(value, 0, &mut out);
+    out
+}
+
+/// Lines of a YAML document: number, indentation and content, blank lines left out.
+type Lines = Vec<(usize, usize, String)>;
+
+fn yaml_error(lines: &Lines, pos: usize, message: &str) -> io::Error {
+    match lines.get(pos) {
+        Some(&(number, _, _)) => rest::invalid_data(&format!("line {}: {}", number, message)),
+        None => rest::invalid_data(&format!("end of document: {}", message)),
+    }
+}
+
+fn is_item(line: &str) -> bool {
+    line == "-" || line.starts_with("- ")
+}
+
+/// End of the double-quoted string at the start of `text`.
+fn quoted_end(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `key: value` and `key:` lines; `None` for a scalar.
+fn split_key(text: &str) -> Option<(String, Option<String>)> {
+    let (key, rest) = if text.starts_with('"') {
+        let end = quoted_end(text)?;
+        (serde_json::from_str(&text[..end]).ok()?, &text[end..])
+    } else {
+        match text.find(": ") {
+            Some(at) => (text[..at].to_string(), &text[at..]),
+            None if text.ends_with(':') => (text[..text.len() - 1].to_string(), ":"),
+            None => return None,
+        }
+    };
+    if rest == ":" {
+        return Some((key, None));
+    }
+    rest.strip_prefix(": ").map(|value| (key, Some(value.to_string())))
+}
+
+fn read_scalar(text: &str) -> Option<Value> {
+    match text {
+        "[]" => Some(Value::Array(Vec::new())),
+        "{}" => Some(Value::Object(Map::new())),
+        _ if text.starts_with('"') => serde_json::from_str(text).ok(),
+        _ => {
+            match serde_json::from_str(text) {
+                Ok(value @ Value::Null) |
+                Ok(value @ Value::Bool(_)) |
+                Ok(value @ Value::Number(_)) => Some(value),
+                _ => Some(Value::String(text.to_string())),
+            }
+        }
+    }
+}
+
+/// The block under a `key:` or `-` line, more indented than it.
+fn read_nested(lines: &mut Lines, pos: &mut usize, indent: usize) -> io::Result<Value> {
+    match lines.get(*pos).map(|line| line.1) {
+        Some(next) if next > indent => read_block(lines, pos, next),
+        _ => Err(yaml_error(lines, *pos, "nested block expected")),
+    }
+}
+
+fn read_block(lines: &mut Lines, pos: &mut usize, indent: usize) -> io::Result<Value> {
+    if is_item(&lines[*pos].2) {
+        let mut items = Vec::new();
+        while *pos < lines.len() && lines[*pos].1 == indent && is_item(&lines[*pos].2) {
+            let rest = lines[*pos].2[1..].trim_start().to_string();
+            if rest.is_empty() {
+                *pos += 1;
+                items.push(read_nested(lines, pos, indent)?);
+            } else if split_key(&rest).is_some() {
+                // The first entry of a mapping, on the line of its dash.
+                lines[*pos].1 = indent + 2;
+                lines[*pos].2 = rest;
+                items.push(read_block(lines, pos, indent + 2)?);
+            } else {
+                let item = read_scalar(&rest)
+                    .ok_or_else(|| yaml_error(lines, *pos, "invalid scalar"))?;
+                items.push(item);
+                *pos += 1;
+            }
+        }
+        Ok(Value::Array(items))
+    } else {
+        let mut map = Map::new();
+        while *pos < lines.len() && lines[*pos].1 == indent && !is_item(&lines[*pos].2) {
+            let (key, rest) = split_key(&lines[*pos].2)
+                .ok_or_else(|| yaml_error(lines, *pos, "key expected"))?;
+            if map.contains_key(&key) {
+                return Err(yaml_error(lines, *pos, "duplicate key"));
+            }
+            let value = match rest {
+                Some(rest) => {
+                    let value = read_scalar(&rest)
+                        .ok_or_else(|| yaml_error(lines, *pos, "invalid scalar"))?;
+                    *pos += 1;
+                    value
+                }
+                None => {
+                    *pos += 1;
+                    read_nested(lines, pos, indent)?
+                }
+            };
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+/// Read back a value written by `to_yaml`; other YAML constructs are not supported.
+pub fn from_yaml(text: &str) -> io::Result<Value> {
+    let mut lines: Lines = text.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let content = line.trim_start_matches(' ');
+            (index + 1, line.len() - content.len(), content.trim_end().to_string())
+        })
+        .collect();
+    if lines.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    let mut pos = 0;
+    let indent = lines[0].1;
+    let value = read_block(&mut lines, &mut pos, indent)?;
+    if pos < lines.len() {
+        return Err(yaml_error(&lines, pos, "unexpected indentation"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clean, dns_changes, file_name, firewall_changes, from_yaml, load_balancer_changes,
+                to_yaml, write_section, DumpReport};
+    use dns::{Record, RecordSpec};
+    use testutil;
+    use std::fs;
+    use std::slice;
+
+    #[test]
+    fn test_to_yaml() {
+        let value = json!({
+            "zone": "example.com",
+            "records": [
+                { "fieldType": "A", "subDomain": "", "target": "192.0.2.1", "ttl": 0 },
+                { "fieldType": "TXT", "subDomain": "www", "target": "v=spf1 -all: x" },
+            ],
+            "empty": [],
+            "flags": ["true", "42", "plain", null],
+            "nested": [[1, 2]],
+        });
+        let expected = "empty: []\n\
+                        flags:\n  - \"true\"\n  - \"42\"\n  - plain\n  - null\n\
+                        nested:\n  -\n    - 1\n    - 2\n\
+                        records:\n  \
+                        - fieldType: A\n    subDomain: \"\"\n    \
+                          target: \"192.0.2.1\"\n    ttl: 0\n  \
+                        - fieldType: TXT\n    subDomain: www\n    target: \"v=spf1 -all: x\"\n\
+                        zone: example.com\n";
+        assert_eq!(expected, to_yaml(&value));
+        assert_eq!(value, from_yaml(expected).unwrap());
+        assert!(from_yaml("zone: a\n  records: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_dns_changes() {
+        let record = |id, target: &str| {
+            Record {
+                id,
+                zone: "example.com".to_string(),
+                field_type: "A".to_string(),
+                sub_domain: "www".to_string(),
+                target: target.to_string(),
+                ttl: 0,
+            }
+        };
+        let www = RecordSpec::new("A", "www", "192.0.2.1", 0);
+        let live = [record(1, "192.0.2.1"), record(2, "192.0.2.1")];
+        let routes: Vec<String> = dns_changes("example.com", &live, slice::from_ref(&www))
+            .iter()
+            .map(|change| format!("{} {}", change.method, change.route))
+            .collect();
+        assert_eq!(vec!["DELETE /domain/zone/example.com/record/1",
+                        "POST /domain/zone/example.com/refresh"],
+                   routes);
+        assert!(dns_changes("example.com", &live[..1], &[www]).is_empty());
+
+        let moved = RecordSpec::new("A", "www", "192.0.2.2", 0);
+        let changes = dns_changes("example.com", &live[..1], &[moved]);
+        assert_eq!("PUT /domain/zone/example.com/record/1 \
+                    {\"subDomain\":\"www\",\"target\":\"192.0.2.2\",\"ttl\":0}",
+                   changes[0].to_string());
+    }
+
+    #[test]
+    fn test_firewall_changes() {
+        let rule = |sequence, port: &str| {
+            json!({ "sequence": sequence, "action": "permit", "protocol": "tcp",
+                    "destinationPort": port, "tcpOption": null, "state": "ok" })
+        };
+        let wanted = json!({ "block": "192.0.2.0/28", "firewalls": { "192.0.2.1": {
+            "enabled": true, "rules": [rule(0, "eq 22"), rule(1, "eq 443")],
+        } } });
+        let live = json!({ "block": "192.0.2.0/28", "firewalls": { "192.0.2.1": {
+            "enabled": true, "rules": [rule(0, "eq 22"), rule(1, "eq 80"), rule(2, "eq 25")],
+        } } });
+        let changes: Vec<String> = firewall_changes("192.0.2.0/28", &wanted, &live)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(vec!["DELETE /ip/192.0.2.0%2F28/firewall/192.0.2.1/rule/1",
+                        "DELETE /ip/192.0.2.0%2F28/firewall/192.0.2.1/rule/2",
+                        "POST /ip/192.0.2.0%2F28/firewall/192.0.2.1/rule \
+                         {\"action\":\"permit\",\"destinationPort\":443,\
+                         \"protocol\":\"tcp\",\"sequence\":1}"],
+                   changes);
+
+        let changes = firewall_changes("192.0.2.0/28", &wanted, &json!({}));
+        assert_eq!(4, changes.len());
+        assert_eq!("POST /ip/192.0.2.0%2F28/firewall {\"ipOnFirewall\":\"192.0.2.1\"}",
+                   changes[0].to_string());
+        assert_eq!("PUT /ip/192.0.2.0%2F28/firewall/192.0.2.1 {\"enabled\":true}",
+                   changes[3].to_string());
+    }
+
+    #[test]
+    fn test_load_balancer_changes() {
+        let wanted = json!({ "http": {
+            "farms": [{ "farmId": 7, "displayName": "web", "port": 80,
+                        "servers": [{ "serverId": 1, "address": "10.0.0.1", "port": 80 }] }],
+            "frontends": [{ "frontendId": 3, "displayName": "www", "defaultFarmId": 7 }],
+        } });
+        let changes = load_balancer_changes("lb-1", &wanted, &json!({}));
+        let routes: Vec<String> = changes.iter()
+            .map(|change| format!("{} {}", change.method, change.route))
+            .collect();
+        assert_eq!(vec!["POST /ipLoadbalancing/lb-1/http/farm",
+                        "POST /ipLoadbalancing/lb-1/http/farm/{farm}/server",
+                        "POST /ipLoadbalancing/lb-1/http/frontend",
+                        "POST /ipLoadbalancing/lb-1/refresh"],
+                   routes);
+        assert_eq!(Some(7), changes[0].creates_farm);
+        assert_eq!(Some(7), changes[2].farm);
+
+        let live = json!({ "http": {
+            "farms": [{ "farmId": 12, "displayName": "web", "port": 80, "status": "ok",
+                        "servers": [{ "serverId": 5, "address": "10.0.0.1", "port": 80 }] }],
+            "frontends": [{ "frontendId": 4, "displayName": "www", "defaultFarmId": 12 },
+                          { "frontendId": 9, "displayName": "old", "defaultFarmId": 12 }],
+        } });
+        let changes: Vec<String> = load_balancer_changes("lb-1", &wanted, &live)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(vec!["DELETE /ipLoadbalancing/lb-1/http/frontend/9",
+                        "POST /ipLoadbalancing/lb-1/refresh"],
+                   changes);
+    }
+
+    #[test]
+    fn test_clean() {
+        let value = json!({
+            "name": "web",
+            "lastUpdate": "2017-06-01",
+            "farm": { "lastUpdate": 1 },
+        });
+        assert_eq!(json!({ "name": "web", "farm": {} }), clean(&value));
+        assert_eq!("192.0.2.0_24", file_name("192.0.2.0/24"));
+    }
+
+    #[test]
+    fn test_write_section() {
+        let dir = testutil::temp_path("ovh-rs-test-backup");
+        let _ = fs::remove_dir_all(&dir);
+        let documents = vec![("a.com".to_string(), json!({ "zone": "a.com" })),
+                             ("b.com".to_string(), json!({ "zone": "b.com" }))];
+        let mut report = DumpReport::default();
+        write_section(&dir, &documents, &mut report).unwrap();
+        assert_eq!(2, report.written.len());
+
+        let mut report = DumpReport::default();
+        write_section(&dir, &documents[..1], &mut report).unwrap();
+        assert!(report.written.is_empty());
+        assert_eq!(1, report.unchanged);
+        assert_eq!(vec![dir.join("b.com.yaml")], report.removed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! # ovh
+//!
+//! Command line companion of the crate.
+//!
+//! `ovh doctor [--json]` checks that the endpoint is reachable, the clock
+//! in sync and the credential valid, and exits with 1 when a check fails.
+//!
+//! `ovh seal-config [path]` encrypts a credential file (`Config.toml` by default)
+//! in place with a passphrase, see `ovh::seal`.
+//!
+//! `ovh select <product> <pattern>...` prints the services of a product
+//! (`vps`, `dedicated/server`, ...) matching any of the patterns, e.g. `web-*`,
+//! see `ovh::select`.
+//!
+
+extern crate ovh;
+
+use ovh::OVHClient;
+use ovh::seal::{self, SealKey};
+use ovh::select;
+
+use std::env;
+use std::io;
+use std::process;
+
+const USAGE: &str = "usage: ovh doctor [--json] | ovh seal-config [path] \
+                              | ovh select <product> <pattern>...";
+
+fn doctor(json: bool) -> i32 {
+    let client = match OVHClient::try_new() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("create Config.toml from Config.toml.dist \
+                       or set OVH_ENDPOINT, OVH_APPLICATION_KEY, OVH_APPLICATION_SECRET \
+                       and OVH_CONSUMER_KEY");
+            return 1;
+        }
+    };
+    let report = client.healthcheck();
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        println!("{}", report);
+    }
+    if report.is_healthy() { 0 } else { 1 }
+}
+
+fn seal_config(path: &str) -> i32 {
+    let result = seal::passphrase("New passphrase: ").and_then(|passphrase| {
+        let confirmation = seal::passphrase("Confirm passphrase: ")?;
+        if passphrase.is_empty() || passphrase != confirmation {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "empty or mismatching passphrases"));
+        }
+        seal::seal_file(path, SealKey::Passphrase(&passphrase))
+    });
+    match result {
+        Ok(()) => {
+            println!("{} sealed", path);
+            0
+        }
+        Err(err) => {
+            eprintln!("cannot seal {}: {}", path, err);
+            1
+        }
+    }
+}
+
+fn select(product: &str, patterns: &[&str]) -> i32 {
+    let result = OVHClient::try_new()
+        .map_err(io::Error::from)
+        .and_then(|client| select::select(&client, patterns, product));
+    match result {
+        Ok(names) => {
+            for name in names {
+                println!("{}", name);
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("cannot select {}: {}", product, err);
+            1
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(String::as_str) {
+        Some("doctor") => doctor(args.iter().any(|arg| arg == "--json")),
+        Some("seal-config") => {
+            seal_config(args.get(1).map(String::as_str).unwrap_or("Config.toml"))
+        }
+        Some("select") if args.len() > 2 => {
+            let patterns: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+            select(&args[1], &patterns)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    };
+    process::exit(code);
+}
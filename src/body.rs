@@ -0,0 +1,107 @@
+//! # Body
+//!
+//! Request bodies and their exact bytes.
+//!
+//! The signature of a request covers the body byte for byte. `serde_json::Value`
+//! sorts object keys, so a body built from it cannot follow the key order of
+//! another tool or of a captured request. `OrderedBody` keeps the insertion
+//! order and a compact formatting, so signatures can be compared and requests
+//! replayed byte for byte.
+//!
+
+use serde_json::Value;
+
+/// A JSON object serialized in insertion order.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct OrderedBody {
+    fields: Vec<(String, Field)>,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Field {
+    Value(Value),
+    Object(OrderedBody),
+}
+
+impl OrderedBody {
+    pub fn new() -> OrderedBody {
+        OrderedBody { fields: Vec::new() }
+    }
+
+    /// Set a field; a field set again keeps its first position.
+    pub fn field(mut self, key: &str, value: Value) -> OrderedBody {
+        self.set(key, Field::Value(value));
+        self
+    }
+
+    /// Set a nested object, itself in insertion order.
+    pub fn object(mut self, key: &str, value: OrderedBody) -> OrderedBody {
+        self.set(key, Field::Object(value));
+        self
+    }
+
+    fn set(&mut self, key: &str, field: Field) {
+        match self.fields.iter().position(|(k, _)| k == key) {
+            Some(index) => self.fields[index].1 = field,
+            None => self.fields.push((key.to_string(), field)),
+        }
+    }
+
+    /// Compact serialization: no whitespace, keys in insertion order.
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::from("{");
+        for (index, (key, field)) in self.fields.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            out.push_str(&Value::String(key.clone()).to_string());
+            out.push(':');
+            match *field {
+                Field::Value(ref value) => out.push_str(&value.to_string()),
+                Field::Object(ref object) => out.push_str(&object.to_json_string()),
+            }
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Body of a request.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Body {
+    Empty,
+    /// Serialized compactly, object keys sorted.
+    Json(Value),
+    Ordered(OrderedBody),
+    /// Sent as is, e.g. to replay a captured request.
+    Raw(String),
+}
+
+impl Body {
+    /// The exact bytes sent and signed.
+    pub fn to_bytes(&self) -> String {
+        match *self {
+            Body::Empty => String::new(),
+            Body::Json(ref value) => value.to_string(),
+            Body::Ordered(ref body) => body.to_json_string(),
+            Body::Raw(ref raw) => raw.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Body, OrderedBody};
+
+    #[test]
+    fn test_ordered_body() {
+        let body = OrderedBody::new()
+            .field("subDomain", json!("www"))
+            .field("fieldType", json!("A"))
+            .object("options", OrderedBody::new().field("z", json!(1)).field("a", json!(2)))
+            .field("subDomain", json!("api"));
+        assert_eq!(r#"{"subDomain":"api","fieldType":"A","options":{"z":1,"a":2}}"#,
+                   Body::Ordered(body).to_bytes());
+        assert_eq!(r#"{"a":1,"b":2}"#, Body::Json(json!({ "b": 2, "a": 1 })).to_bytes());
+    }
+}
@@ -0,0 +1,266 @@
+//! # Bootstrap
+//!
+//! Idempotent setup of a Public Cloud project from a declarative spec:
+//! private networks, SSH keys and users are created when missing,
+//! and quotas are checked. Running it again on a bootstrapped project
+//! changes nothing, so it can run on every deployment.
+//!
+//! Quotas are raised by OVH, not through the API: a quota below the spec
+//! is reported instead of failing the bootstrap.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use cloud::sshkey;
+use rest;
+
+use std::io;
+
+use serde_json::Value;
+
+/// A private network (vRack VLAN) the project must have.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NetworkSpec {
+    pub name: String,
+    pub vlan_id: u64,
+    pub regions: Vec<String>,
+}
+
+/// An OpenStack user, identified by its description (usernames are generated).
+#[derive(Debug,Clone,PartialEq)]
+pub struct UserSpec {
+    pub description: String,
+    /// `administrator`, `compute_operator`, `network_operator`, ...
+    pub role: String,
+}
+
+/// Minimal quotas of a region.
+#[derive(Debug,Clone,PartialEq)]
+pub struct QuotaSpec {
+    pub region: String,
+    pub max_instances: u64,
+    pub max_cores: u64,
+    /// RAM in MB.
+    pub max_ram: u64,
+}
+
+/// Desired state of a project.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ProjectSpec {
+    pub networks: Vec<NetworkSpec>,
+    /// `(name, public key)`; a key is matched on its material, not its name.
+    pub ssh_keys: Vec<(String, String)>,
+    pub users: Vec<UserSpec>,
+    pub quotas: Vec<QuotaSpec>,
+}
+
+/// Current state of a project, as far as the spec is concerned.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ProjectState {
+    /// `(name, vlan id)` of the private networks.
+    pub networks: Vec<(String, u64)>,
+    pub public_keys: Vec<String>,
+    pub user_descriptions: Vec<String>,
+    /// Quotas by region, with the same fields as `QuotaSpec`.
+    pub quotas: Vec<QuotaSpec>,
+}
+
+/// What a bootstrap has to do.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct BootstrapPlan {
+    pub networks: Vec<NetworkSpec>,
+    pub ssh_keys: Vec<(String, String)>,
+    pub users: Vec<UserSpec>,
+    /// Quotas below the spec, with the reason.
+    pub quota_shortfalls: Vec<String>,
+}
+
+impl BootstrapPlan {
+    /// Whether the project already matches the spec, quotas aside.
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty() && self.ssh_keys.is_empty() && self.users.is_empty()
+    }
+}
+
+/// Key type and material of an OpenSSH public key, without its comment.
+fn key_material(public_key: &str) -> String {
+    public_key.split_whitespace().take(2).collect::<Vec<&str>>().join(" ")
+}
+
+/// Compare a project with a spec.
+pub fn plan(spec: &ProjectSpec, state: &ProjectState) -> BootstrapPlan {
+    let mut plan = BootstrapPlan::default();
+    for network in &spec.networks {
+        if !state.networks.iter().any(|&(_, vlan_id)| vlan_id == network.vlan_id) {
+            plan.networks.push(network.clone());
+        }
+    }
+    let existing_keys: Vec<String> = state.public_keys
+        .iter()
+        .map(|key| key_material(key))
+        .collect();
+    for (name, public_key) in &spec.ssh_keys {
+        if !existing_keys.contains(&key_material(public_key)) {
+            plan.ssh_keys.push((name.clone(), public_key.clone()));
+        }
+    }
+    for user in &spec.users {
+        if !state.user_descriptions.contains(&user.description) {
+            plan.users.push(user.clone());
+        }
+    }
+    for wanted in &spec.quotas {
+        match state.quotas.iter().find(|quota| quota.region == wanted.region) {
+            None => plan.quota_shortfalls.push(format!("no quota in region {}", wanted.region)),
+            Some(quota) => {
+                let checks = [("instances", quota.max_instances, wanted.max_instances),
+                              ("cores", quota.max_cores, wanted.max_cores),
+                              ("RAM", quota.max_ram, wanted.max_ram)];
+                for &(name, current, minimum) in &checks {
+                    if current < minimum {
+                        plan.quota_shortfalls.push(format!("{} {}: {} < {}",
+                                                           wanted.region,
+                                                           name,
+                                                           current,
+                                                           minimum));
+                    }
+                }
+            }
+        }
+    }
+    plan
+}
+
+/// Read the parts of a project covered by the spec.
+pub fn project_state(client: &OVHClient, project_id: &str) -> io::Result<ProjectState> {
+    let base = project_path(project_id);
+    let mut state = ProjectState::default();
+    for network in rest::array(&rest::get(client, &format!("{}/network/private", base))?)? {
+        state.networks.push((rest::opt_str_field(network, "name").unwrap_or_default(),
+                             rest::opt_u64_field(network, "vlanId").unwrap_or(0)));
+    }
+    state.public_keys = sshkey::ssh_keys(client, project_id)?
+        .into_iter()
+        .map(|key| key.public_key)
+        .collect();
+    for user in rest::array(&rest::get(client, &format!("{}/user", base))?)? {
+        if let Some(description) = rest::opt_str_field(user, "description") {
+            state.user_descriptions.push(description);
+        }
+    }
+    for quota in rest::array(&rest::get(client, &format!("{}/quota", base))?)? {
+        let instance = quota.get("instance").unwrap_or(&Value::Null);
+        state.quotas.push(QuotaSpec {
+            region: rest::str_field(quota, "region")?,
+            max_instances: rest::opt_u64_field(instance, "maxInstances").unwrap_or(0),
+            max_cores: rest::opt_u64_field(instance, "maxCores").unwrap_or(0),
+            max_ram: rest::opt_u64_field(instance, "maxRam").unwrap_or(0),
+        });
+    }
+    Ok(state)
+}
+
+/// A user created by a bootstrap; its password is only shown once.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CreatedUser {
+    pub description: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Outcome of a bootstrap.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct BootstrapReport {
+    /// What was created, e.g. `network prod (vlan 10)`.
+    pub created: Vec<String>,
+    pub users: Vec<CreatedUser>,
+    pub quota_shortfalls: Vec<String>,
+}
+
+/// Create what the project is missing from `spec`.
+pub fn bootstrap_project(client: &OVHClient,
+                         project_id: &str,
+                         spec: &ProjectSpec)
+                         -> io::Result<BootstrapReport> {
+    let plan = plan(spec, &project_state(client, project_id)?);
+    let base = project_path(project_id);
+    let mut report = BootstrapReport::default();
+    for network in &plan.networks {
+        rest::post(client,
+                   &format!("{}/network/private", base),
+                   &json!({
+                       "name": network.name,
+                       "vlanId": network.vlan_id,
+                       "regions": network.regions,
+                   }))?;
+        report.created.push(format!("network {} (vlan {})", network.name, network.vlan_id));
+    }
+    for (name, public_key) in &plan.ssh_keys {
+        sshkey::create_ssh_key(client, project_id, name, public_key)?;
+        report.created.push(format!("ssh key {}", name));
+    }
+    for user in &plan.users {
+        let value = rest::post(client,
+                               &format!("{}/user", base),
+                               &json!({ "description": user.description, "role": user.role }))?;
+        report.created.push(format!("user {}", user.description));
+        report.users.push(CreatedUser {
+            description: user.description.clone(),
+            username: rest::opt_str_field(&value, "username").unwrap_or_default(),
+            password: rest::opt_str_field(&value, "password").unwrap_or_default(),
+        });
+    }
+    for shortfall in &plan.quota_shortfalls {
+        warn!("Quota of project {} below spec: {}", project_id, shortfall);
+    }
+    report.quota_shortfalls = plan.quota_shortfalls;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan, NetworkSpec, ProjectSpec, ProjectState, QuotaSpec, UserSpec};
+
+    #[test]
+    fn test_plan() {
+        let spec = ProjectSpec {
+            networks: vec![NetworkSpec {
+                               name: "prod".to_string(),
+                               vlan_id: 10,
+                               regions: vec!["GRA7".to_string()],
+                           },
+                           NetworkSpec {
+                               name: "admin".to_string(),
+                               vlan_id: 20,
+                               regions: vec!["GRA7".to_string()],
+                           }],
+            ssh_keys: vec![("ops".to_string(), "ssh-ed25519 AAAAC3 ops@laptop".to_string())],
+            users: vec![UserSpec {
+                            description: "terraform".to_string(),
+                            role: "administrator".to_string(),
+                        }],
+            quotas: vec![QuotaSpec {
+                             region: "GRA7".to_string(),
+                             max_instances: 20,
+                             max_cores: 40,
+                             max_ram: 81920,
+                         }],
+        };
+        let state = ProjectState {
+            networks: vec![("production".to_string(), 10)],
+            public_keys: vec!["ssh-ed25519 AAAAC3 someone@else".to_string()],
+            user_descriptions: vec![],
+            quotas: vec![QuotaSpec {
+                             region: "GRA7".to_string(),
+                             max_instances: 20,
+                             max_cores: 20,
+                             max_ram: 81920,
+                         }],
+        };
+        let plan = plan(&spec, &state);
+        assert_eq!(vec![20], plan.networks.iter().map(|n| n.vlan_id).collect::<Vec<u64>>());
+        assert!(plan.ssh_keys.is_empty());
+        assert_eq!(1, plan.users.len());
+        assert_eq!(vec!["GRA7 cores: 20 < 40".to_string()], plan.quota_shortfalls);
+    }
+}
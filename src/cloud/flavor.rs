@@ -0,0 +1,59 @@
+//! # Flavors
+//!
+//! Instance flavors of a project (`/cloud/project/{id}/flavor`)
+//! with their availability per region.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// An instance flavor in a region.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Flavor {
+    pub id: String,
+    pub name: String,
+    pub region: String,
+    /// Whether instances of this flavor can currently be created in the region.
+    pub available: bool,
+    pub vcpus: u64,
+    /// Memory in GB.
+    pub ram: u64,
+    /// Disk in GB.
+    pub disk: u64,
+    pub kind: String,
+}
+
+impl Model for Flavor {
+    const NAME: &'static str = "Flavor";
+    const FIELDS: &'static [&'static str] = &["id", "name", "region", "ram", "disk", "vcpus",
+                                              "type", "osType", "inboundBandwidth",
+                                              "outboundBandwidth", "available", "planCodes",
+                                              "quota"];
+
+    fn from_json(value: &Value) -> io::Result<Flavor> {
+        Ok(Flavor {
+            id: rest::str_field(value, "id")?,
+            name: rest::str_field(value, "name")?,
+            region: rest::str_field(value, "region")?,
+            available: value.get("available").and_then(Value::as_bool).unwrap_or(false),
+            vcpus: rest::opt_u64_field(value, "vcpus").unwrap_or(0),
+            ram: rest::opt_u64_field(value, "ram").unwrap_or(0),
+            disk: rest::opt_u64_field(value, "disk").unwrap_or(0),
+            kind: rest::opt_str_field(value, "type").unwrap_or_default(),
+        })
+    }
+}
+
+/// Flavors of a project, in every region or in a single one.
+pub fn flavors(client: &OVHClient, project_id: &str, region: Option<&str>) -> io::Result<Vec<Flavor>> {
+    let mut query = format!("{}/flavor", project_path(project_id));
+    if let Some(region) = region {
+        query = query + "?region=" + &rest::escape(region);
+    }
+    rest::decode_list(client, &rest::get(client, &query)?)
+}
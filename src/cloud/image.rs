@@ -0,0 +1,277 @@
+//! # Images
+//!
+//! Images of a project (`/cloud/project/{id}/image`), and the bring your own
+//! image workflow: `bring_your_own_image` imports a disk image published in
+//! object storage (or any HTTPS URL) in each target region, then waits until
+//! every copy is `active` and can boot instances.
+//!
+//! Images are regional: a copy is imported per region, from the same URL.
+//! Regions which already have an active private image of that name keep it,
+//! so running the workflow again only imports the missing copies.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use progress::{Progress, ProgressEvent};
+use rest::{self, Model};
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// An image of a project, public or private.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Image {
+    pub id: String,
+    pub name: String,
+    pub region: String,
+    /// `queued`, `saving`, `importing`, `active`, `killed`, `deleted`, ...
+    pub status: String,
+    /// `public` for the images of OVH, `private` for the imported ones.
+    pub visibility: String,
+    pub os_type: Option<String>,
+    /// Minimal disk of the flavors able to boot the image, in GB.
+    pub min_disk: u64,
+}
+
+impl Model for Image {
+    const NAME: &'static str = "Image";
+    const FIELDS: &'static [&'static str] = &["id", "name", "region", "status", "visibility",
+                                              "type", "minDisk", "minRam", "size", "creationDate",
+                                              "user", "flavorType", "tags", "planCode"];
+
+    fn from_json(value: &Value) -> io::Result<Image> {
+        Ok(Image {
+            id: rest::str_field(value, "id")?,
+            name: rest::str_field(value, "name")?,
+            region: rest::str_field(value, "region")?,
+            status: rest::str_field(value, "status")?,
+            visibility: rest::opt_str_field(value, "visibility").unwrap_or_default(),
+            os_type: rest::opt_str_field(value, "type"),
+            min_disk: rest::opt_u64_field(value, "minDisk").unwrap_or(0),
+        })
+    }
+}
+
+impl Image {
+    pub fn is_active(&self) -> bool {
+        self.status == "active"
+    }
+
+    /// Whether the import failed or the image is gone.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status.as_str(), "killed" | "deleted" | "pending_delete" | "error")
+    }
+}
+
+/// An image to import.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ImageImport {
+    pub name: String,
+    /// URL the image is downloaded from, e.g. a temporary URL of an object storage container.
+    pub url: String,
+    /// `linux`, `windows`, `bsd`.
+    pub os_type: String,
+    /// `qcow2` or `raw`.
+    pub disk_format: String,
+}
+
+impl ImageImport {
+    /// A Linux qcow2 image.
+    pub fn new(name: &str, url: &str) -> io::Result<ImageImport> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("{} is not an HTTP(S) URL", url)));
+        }
+        Ok(ImageImport {
+            name: name.to_string(),
+            url: url.to_string(),
+            os_type: "linux".to_string(),
+            disk_format: "qcow2".to_string(),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "url": self.url,
+            "osType": self.os_type,
+            "diskFormat": self.disk_format,
+        })
+    }
+}
+
+fn image_path(project_id: &str) -> String {
+    format!("{}/image", project_path(project_id))
+}
+
+/// Images of a project, in every region or in a single one.
+pub fn images(client: &OVHClient,
+              project_id: &str,
+              region: Option<&str>)
+              -> io::Result<Vec<Image>> {
+    let mut query = image_path(project_id);
+    if let Some(region) = region {
+        query = query + "?region=" + &rest::escape(region);
+    }
+    rest::decode_list(client, &rest::get(client, &query)?)
+}
+
+pub fn image(client: &OVHClient, project_id: &str, image_id: &str) -> io::Result<Image> {
+    rest::decode(client,
+                 &rest::get(client,
+                            &format!("{}/{}", image_path(project_id), rest::escape(image_id)))?)
+}
+
+/// Start the import of an image in `region`; it is `active` once downloaded.
+pub fn import_image(client: &OVHClient,
+                    project_id: &str,
+                    region: &str,
+                    import: &ImageImport)
+                    -> io::Result<Image> {
+    let query = format!("{}/region/{}/image", project_path(project_id), rest::escape(region));
+    rest::decode(client, &rest::post(client, &query, &import.to_json())?)
+}
+
+/// Poll an image every `interval` until it is `active`.
+/// Fails if the import fails or after `timeout`.
+pub fn wait_image(client: &OVHClient,
+                  project_id: &str,
+                  image_id: &str,
+                  interval: Duration,
+                  timeout: Duration,
+                  progress: &Progress)
+                  -> io::Result<Image> {
+    let operation = format!("image {} to active", image_id);
+    progress.emit(ProgressEvent::Started {
+        operation: operation.clone(),
+        total: None,
+    });
+    let started = Instant::now();
+    let mut last = String::new();
+    loop {
+        let current = image(client, project_id, image_id)?;
+        if current.status != last {
+            progress.emit(ProgressEvent::Status {
+                operation: operation.clone(),
+                status: current.status.clone(),
+            });
+        }
+        if current.is_active() {
+            progress.emit(ProgressEvent::Finished { operation });
+            return Ok(current);
+        }
+        let err = if current.is_failed() {
+            Some(io::Error::other(format!("import of image {} is {}", image_id, current.status)))
+        } else if started.elapsed() >= timeout {
+            Some(io::Error::new(io::ErrorKind::TimedOut,
+                                format!("image {} still {}", image_id, current.status)))
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            progress.emit(ProgressEvent::Failed {
+                operation,
+                error: err.to_string(),
+            });
+            return Err(err);
+        }
+        last = current.status;
+        progress.emit(ProgressEvent::Waiting {
+            operation: operation.clone(),
+            seconds: interval.as_secs(),
+        });
+        thread::sleep(interval);
+    }
+}
+
+/// Interval between two polls of an import.
+const POLL_INTERVAL: u64 = 10;
+
+/// Private image named `name` in `region` among `images` which is usable or on its way.
+fn existing<'a>(images: &'a [Image], name: &str, region: &str) -> Option<&'a Image> {
+    images.iter().find(|image| {
+        image.visibility == "private" && image.name == name && image.region == region &&
+        !image.is_failed()
+    })
+}
+
+/// Import an image in every region of `regions` which doesn't have it yet,
+/// and wait until all its copies are `active`. Returns them in the order of `regions`.
+pub fn bring_your_own_image(client: &OVHClient,
+                            project_id: &str,
+                            import: &ImageImport,
+                            regions: &[&str],
+                            timeout: Duration,
+                            progress: &Progress)
+                            -> io::Result<Vec<Image>> {
+    let operation = format!("import of image {}", import.name);
+    progress.emit(ProgressEvent::Started {
+        operation: operation.clone(),
+        total: Some(regions.len()),
+    });
+    let known = images(client, project_id, None)?;
+    let mut started = Vec::new();
+    for (done, region) in regions.iter().enumerate() {
+        let (image, message) = match existing(&known, &import.name, region) {
+            Some(image) => (image.clone(), format!("{} already in {}", image.id, region)),
+            None => {
+                let image = import_image(client, project_id, region, import)?;
+                let message = format!("{} importing in {}", image.id, region);
+                (image, message)
+            }
+        };
+        progress.emit(ProgressEvent::Step {
+            operation: operation.clone(),
+            done: done + 1,
+            total: Some(regions.len()),
+            message,
+        });
+        started.push(image);
+    }
+    let deadline = Instant::now() + timeout;
+    let images = started.into_iter()
+        .map(|image| if image.is_active() {
+            Ok(image)
+        } else {
+            let left = deadline.saturating_duration_since(Instant::now());
+            wait_image(client,
+                       project_id,
+                       &image.id,
+                       Duration::from_secs(POLL_INTERVAL),
+                       left,
+                       progress)
+        })
+        .collect::<io::Result<Vec<Image>>>()?;
+    progress.emit(ProgressEvent::Finished { operation });
+    Ok(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{existing, Image, ImageImport};
+    use rest::Model;
+
+    #[test]
+    fn test_existing() {
+        let image = |id: &str, region: &str, visibility: &str, status: &str| {
+            Image::from_json(&json!({
+                "id": id,
+                "name": "web-2024",
+                "region": region,
+                "status": status,
+                "visibility": visibility,
+            }))
+                .unwrap()
+        };
+        let images = vec![image("a", "GRA7", "public", "active"),
+                          image("b", "GRA7", "private", "killed"),
+                          image("c", "SBG5", "private", "queued")];
+        assert_eq!(None, existing(&images, "web-2024", "GRA7"));
+        assert_eq!("c", existing(&images, "web-2024", "SBG5").unwrap().id);
+        assert_eq!(None, existing(&images, "web-2025", "SBG5"));
+        assert!(ImageImport::new("web", "s3://bucket/web.qcow2").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! # Instances
+//!
+//! Public Cloud instances of a project (`/cloud/project/{id}/instance`).
+//!
+//! For incident response, `instance_rescue` reboots an instance on a rescue
+//! image and waits until it can be reached, `instance_unrescue` brings it back,
+//! and `vnc_console` gives a console URL when the network is down.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use cloud::userdata::UserData;
+use progress::{Progress, ProgressEvent};
+use rest::{self, Model};
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// A Public Cloud instance.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Instance {
+    pub id: String,
+    pub name: String,
+    /// OpenStack status: `BUILD`, `ACTIVE`, `RESCUE`, `ERROR`, ...
+    pub status: String,
+    pub region: String,
+    pub flavor_id: String,
+    pub image_id: Option<String>,
+    pub ip_addresses: Vec<String>,
+}
+
+impl Model for Instance {
+    const NAME: &'static str = "Instance";
+    const FIELDS: &'static [&'static str] = &["id", "name", "ipAddresses", "flavorId", "imageId",
+                                              "sshKeyId", "created", "region", "monthlyBilling",
+                                              "status", "planCode", "operationIds",
+                                              "currentMonthOutgoingTraffic"];
+
+    fn from_json(value: &Value) -> io::Result<Instance> {
+        let ip_addresses = match value.get("ipAddresses").and_then(Value::as_array) {
+            Some(ips) => ips.iter().filter_map(|ip| rest::opt_str_field(ip, "ip")).collect(),
+            None => Vec::new(),
+        };
+        Ok(Instance {
+            id: rest::str_field(value, "id")?,
+            name: rest::str_field(value, "name")?,
+            status: rest::opt_str_field(value, "status").unwrap_or_default(),
+            region: rest::opt_str_field(value, "region").unwrap_or_default(),
+            flavor_id: rest::opt_str_field(value, "flavorId").unwrap_or_default(),
+            image_id: rest::opt_str_field(value, "imageId"),
+            ip_addresses,
+        })
+    }
+}
+
+/// Parameters of a new instance.
+#[derive(Debug,Clone,PartialEq)]
+pub struct InstanceSpec {
+    pub name: String,
+    pub region: String,
+    pub flavor_id: String,
+    pub image_id: String,
+    /// Id of a project-level SSH key.
+    pub ssh_key_id: Option<String>,
+    pub user_data: Option<UserData>,
+    pub monthly_billing: bool,
+}
+
+impl InstanceSpec {
+    pub fn new(name: &str, region: &str, flavor_id: &str, image_id: &str) -> InstanceSpec {
+        InstanceSpec {
+            name: name.to_string(),
+            region: region.to_string(),
+            flavor_id: flavor_id.to_string(),
+            image_id: image_id.to_string(),
+            ssh_key_id: None,
+            user_data: None,
+            monthly_billing: false,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut body = json!({
+            "name": self.name,
+            "region": self.region,
+            "flavorId": self.flavor_id,
+            "imageId": self.image_id,
+            "monthlyBilling": self.monthly_billing,
+        });
+        if let Some(map) = body.as_object_mut() {
+            if let Some(ref key) = self.ssh_key_id {
+                map.insert("sshKeyId".to_string(), Value::String(key.clone()));
+            }
+            if let Some(ref user_data) = self.user_data {
+                map.insert("userData".to_string(),
+                           Value::String(user_data.as_str().to_string()));
+            }
+        }
+        body
+    }
+}
+
+fn instance_path(project_id: &str) -> String {
+    format!("{}/instance", project_path(project_id))
+}
+
+pub fn instances(client: &OVHClient, project_id: &str) -> io::Result<Vec<Instance>> {
+    rest::decode_list(client, &rest::get(client, &instance_path(project_id))?)
+}
+
+pub fn instance(client: &OVHClient, project_id: &str, instance_id: &str) -> io::Result<Instance> {
+    let path = format!("{}/{}", instance_path(project_id), rest::escape(instance_id));
+    rest::decode(client, &rest::get(client, &path)?)
+}
+
+/// Create an instance; its user data is validated before anything is sent.
+pub fn create_instance(client: &OVHClient, project_id: &str, spec: &InstanceSpec) -> io::Result<Instance> {
+    if let Some(ref user_data) = spec.user_data {
+        user_data.validate()?;
+    }
+    rest::decode(client, &rest::post(client, &instance_path(project_id), &spec.to_json())?)
+}
+
+pub fn delete_instance(client: &OVHClient, project_id: &str, instance_id: &str) -> io::Result<()> {
+    rest::delete(client,
+                 &format!("{}/{}", instance_path(project_id), rest::escape(instance_id)))?;
+    Ok(())
+}
+
+/// Poll an instance every `interval` until its status is `status`.
+/// Fails if the instance goes to `ERROR` or after `timeout`.
+pub fn wait_status(client: &OVHClient,
+                   project_id: &str,
+                   instance_id: &str,
+                   status: &str,
+                   interval: Duration,
+                   timeout: Duration,
+                   progress: &Progress)
+                   -> io::Result<Instance> {
+    let operation = format!("instance {} to {}", instance_id, status);
+    progress.emit(ProgressEvent::Started {
+        operation: operation.clone(),
+        total: None,
+    });
+    let started = Instant::now();
+    let mut last = String::new();
+    loop {
+        let current = instance(client, project_id, instance_id)?;
+        if current.status != last {
+            progress.emit(ProgressEvent::Status {
+                operation: operation.clone(),
+                status: current.status.clone(),
+            });
+        }
+        if current.status == status {
+            progress.emit(ProgressEvent::Finished { operation });
+            return Ok(current);
+        }
+        let err = if current.status == "ERROR" {
+            Some(io::Error::other(format!("instance {} is in error", instance_id)))
+        } else if started.elapsed() >= timeout {
+            Some(io::Error::new(io::ErrorKind::TimedOut,
+                                format!("instance {} still {}", instance_id, current.status)))
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            progress.emit(ProgressEvent::Failed {
+                operation,
+                error: err.to_string(),
+            });
+            return Err(err);
+        }
+        last = current.status;
+        progress.emit(ProgressEvent::Waiting {
+            operation: operation.clone(),
+            seconds: interval.as_secs(),
+        });
+        thread::sleep(interval);
+    }
+}
+
+/// Interval between two polls of the rescue helpers.
+const POLL_INTERVAL: u64 = 5;
+
+/// An instance booted in rescue mode.
+#[derive(Debug,Clone,PartialEq)]
+pub struct RescueAccess {
+    pub instance: Instance,
+    /// Root password of the rescue system, when the image provides one.
+    pub admin_password: Option<String>,
+}
+
+fn set_rescue_mode(client: &OVHClient,
+                   project_id: &str,
+                   instance_id: &str,
+                   body: &Value)
+                   -> io::Result<Value> {
+    rest::post(client,
+               &format!("{}/{}/rescueMode", instance_path(project_id), rest::escape(instance_id)),
+               body)
+}
+
+/// Reboot an instance on a rescue image (the default one when `image_id` is `None`)
+/// and wait until it is in `RESCUE` status.
+pub fn instance_rescue(client: &OVHClient,
+                       project_id: &str,
+                       instance_id: &str,
+                       image_id: Option<&str>,
+                       timeout: Duration,
+                       progress: &Progress)
+                       -> io::Result<RescueAccess> {
+    let body = match image_id {
+        Some(image_id) => json!({ "rescue": true, "imageId": image_id }),
+        None => json!({ "rescue": true }),
+    };
+    let value = set_rescue_mode(client, project_id, instance_id, &body)?;
+    let instance = wait_status(client,
+                               project_id,
+                               instance_id,
+                               "RESCUE",
+                               Duration::from_secs(POLL_INTERVAL),
+                               timeout,
+                               progress)?;
+    Ok(RescueAccess {
+        instance,
+        admin_password: rest::opt_str_field(&value, "adminPassword"),
+    })
+}
+
+/// Reboot an instance out of rescue mode and wait until it is `ACTIVE`.
+pub fn instance_unrescue(client: &OVHClient,
+                         project_id: &str,
+                         instance_id: &str,
+                         timeout: Duration,
+                         progress: &Progress)
+                         -> io::Result<Instance> {
+    set_rescue_mode(client, project_id, instance_id, &json!({ "rescue": false }))?;
+    wait_status(client,
+                project_id,
+                instance_id,
+                "ACTIVE",
+                Duration::from_secs(POLL_INTERVAL),
+                timeout,
+                progress)
+}
+
+/// A remote console of an instance.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Console {
+    /// Short-lived URL of the console.
+    pub url: String,
+    pub protocol: String,
+    pub console_type: String,
+}
+
+/// Open a VNC console on an instance.
+pub fn vnc_console(client: &OVHClient, project_id: &str, instance_id: &str) -> io::Result<Console> {
+    let path = format!("{}/{}/vnc", instance_path(project_id), rest::escape(instance_id));
+    let value = rest::call(client, "POST", &path, None)?;
+    Ok(Console {
+        url: rest::str_field(&value, "url")?,
+        protocol: rest::opt_str_field(&value, "protocol").unwrap_or_default(),
+        console_type: rest::opt_str_field(&value, "type").unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instance_rescue, vnc_console, wait_status, InstanceSpec};
+    use cloud::userdata::UserData;
+    use progress::{Progress, ProgressEvent};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use testutil;
+
+    #[test]
+    fn test_spec_to_json() {
+        let mut spec = InstanceSpec::new("web-1", "GRA7", "b2-7", "debian-9");
+        assert_eq!(None, spec.to_json().get("userData"));
+
+        spec.user_data = Some(UserData::new("#cloud-config\n").unwrap());
+        assert_eq!("#cloud-config\n", spec.to_json()["userData"]);
+    }
+
+    #[test]
+    fn test_instance_rescue() {
+        let (host, server) = testutil::serve(vec![(200, "{\"adminPassword\":\"pw\"}"),
+                                                  (200, "{\"id\":\"i1\",\"name\":\"web-1\",\
+                                                         \"status\":\"RESCUE\"}")]);
+        let client = testutil::local_client(&host);
+        let access = instance_rescue(&client,
+                                     "p1",
+                                     "i1",
+                                     Some("rescue-img"),
+                                     Duration::from_secs(5),
+                                     &Progress::none())
+            .unwrap();
+        assert_eq!((Some("pw"), "RESCUE"),
+                   (access.admin_password.as_deref(), &access.instance.status[..]));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /1.0/cloud/project/p1/instance/i1/rescueMode "));
+        assert!(requests[0].contains("\"imageId\":\"rescue-img\""));
+        assert!(requests[1].starts_with("GET /1.0/cloud/project/p1/instance/i1 "));
+    }
+
+    #[test]
+    fn test_wait_status_error() {
+        let (host, server) = testutil::serve(vec![(200, "{\"id\":\"i1\",\"name\":\"web-1\",\
+                                                         \"status\":\"HARD_REBOOT\"}"),
+                                                  (200, "{\"id\":\"i1\",\"name\":\"web-1\",\
+                                                         \"status\":\"ERROR\"}")]);
+        let client = testutil::local_client(&host);
+        let (sender, events) = mpsc::channel();
+        let err = wait_status(&client,
+                              "p1",
+                              "i1",
+                              "RESCUE",
+                              Duration::from_millis(1),
+                              Duration::from_secs(5),
+                              &Progress::channel(sender))
+            .unwrap_err();
+        assert_eq!("instance i1 is in error", err.to_string());
+        let statuses: Vec<String> = events.try_iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Status { status, .. } => Some(status),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["HARD_REBOOT", "ERROR"], statuses);
+        assert_eq!(2, server.join().unwrap().len());
+    }
+
+    #[test]
+    fn test_vnc_console() {
+        let (host, server) = testutil::serve(vec![(200, "{\"url\":\"https://vnc.example.test/\
+                                                         ?token=t\",\"protocol\":\"vnc\",\
+                                                         \"type\":\"novnc\"}")]);
+        let client = testutil::local_client(&host);
+        let console = vnc_console(&client, "p1", "i1").unwrap();
+        assert_eq!(("https://vnc.example.test/?token=t", "vnc", "novnc"),
+                   (&console.url[..], &console.protocol[..], &console.console_type[..]));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /1.0/cloud/project/p1/instance/i1/vnc "));
+    }
+}
@@ -0,0 +1,329 @@
+//! # Kubernetes
+//!
+//! Security settings of the Managed Kubernetes clusters of a project
+//! (`/cloud/project/{id}/kube`): the OpenID Connect provider of the API server,
+//! the networks allowed to reach it and the private network configuration.
+//!
+//! `enforce` applies a `SecuritySpec` to a cluster and only sends the settings
+//! which differ, so it can run on every deployment to keep the posture as code.
+//! `kubeconfig::merge_cluster` adds a cluster to a local kubeconfig file.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use error;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A Managed Kubernetes cluster.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Cluster {
+    pub id: String,
+    pub name: String,
+    pub region: String,
+    pub version: String,
+    /// `INSTALLING`, `READY`, `REDEPLOYING`, `RESETTING`, `ERROR`, ...
+    pub status: String,
+    /// URL of the API server.
+    pub url: Option<String>,
+    pub private_network_id: Option<String>,
+}
+
+impl Model for Cluster {
+    const NAME: &'static str = "Cluster";
+    const FIELDS: &'static [&'static str] = &["id", "name", "region", "version", "status", "url",
+                                              "privateNetworkId", "privateNetworkConfiguration",
+                                              "nodesUrl", "controlPlaneIsUpToDate", "isUpToDate",
+                                              "nextUpgradeVersions", "nodesSubnetId",
+                                              "loadBalancersSubnetId", "kubeProxyMode",
+                                              "customization", "updatePolicy", "createdAt",
+                                              "updatedAt", "auditLogsSubscribed", "plan"];
+
+    fn from_json(value: &Value) -> io::Result<Cluster> {
+        Ok(Cluster {
+            id: rest::str_field(value, "id")?,
+            name: rest::opt_str_field(value, "name").unwrap_or_default(),
+            region: rest::str_field(value, "region")?,
+            version: rest::opt_str_field(value, "version").unwrap_or_default(),
+            status: rest::str_field(value, "status")?,
+            url: rest::opt_str_field(value, "url"),
+            private_network_id: rest::opt_str_field(value, "privateNetworkId"),
+        })
+    }
+}
+
+/// OpenID Connect provider authenticating the users of the API server.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Claim used as user name, `sub` by default.
+    pub username_claim: Option<String>,
+    pub username_prefix: Option<String>,
+    pub groups_claim: Vec<String>,
+    pub groups_prefix: Option<String>,
+    /// `claim=value` pairs the ID tokens must have.
+    pub required_claims: Vec<String>,
+    /// PEM encoded CA of the issuer, when not publicly trusted.
+    pub ca_content: Option<String>,
+}
+
+impl Model for OidcConfig {
+    const NAME: &'static str = "OidcConfig";
+    const FIELDS: &'static [&'static str] = &["issuerUrl", "clientId", "usernameClaim",
+                                              "usernamePrefix", "groupsClaim", "groupsPrefix",
+                                              "requiredClaim", "signingAlgorithms", "caContent"];
+
+    fn from_json(value: &Value) -> io::Result<OidcConfig> {
+        let list = |key: &str| match value.get(key) {
+            Some(items) if !items.is_null() => rest::string_list(items),
+            _ => Ok(Vec::new()),
+        };
+        Ok(OidcConfig {
+            issuer_url: rest::str_field(value, "issuerUrl")?,
+            client_id: rest::str_field(value, "clientId")?,
+            username_claim: rest::opt_str_field(value, "usernameClaim"),
+            username_prefix: rest::opt_str_field(value, "usernamePrefix"),
+            groups_claim: list("groupsClaim")?,
+            groups_prefix: rest::opt_str_field(value, "groupsPrefix"),
+            required_claims: list("requiredClaim")?,
+            ca_content: rest::opt_str_field(value, "caContent"),
+        })
+    }
+}
+
+impl OidcConfig {
+    pub fn new(issuer_url: &str, client_id: &str) -> OidcConfig {
+        OidcConfig {
+            issuer_url: issuer_url.to_string(),
+            client_id: client_id.to_string(),
+            ..OidcConfig::default()
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "issuerUrl": self.issuer_url,
+            "clientId": self.client_id,
+            "usernameClaim": self.username_claim,
+            "usernamePrefix": self.username_prefix,
+            "groupsClaim": self.groups_claim,
+            "groupsPrefix": self.groups_prefix,
+            "requiredClaim": self.required_claims,
+            "caContent": self.ca_content,
+        })
+    }
+}
+
+/// Routing of the nodes attached to a private network.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PrivateNetworkConfiguration {
+    /// Gateway of the private network used as default route, empty for the DHCP one.
+    pub default_vrack_gateway: String,
+    /// Route the public traffic through the private network rather than the public one.
+    pub private_network_routing_as_default: bool,
+}
+
+impl Model for PrivateNetworkConfiguration {
+    const NAME: &'static str = "PrivateNetworkConfiguration";
+    const FIELDS: &'static [&'static str] = &["defaultVrackGateway",
+                                              "privateNetworkRoutingAsDefault"];
+
+    fn from_json(value: &Value) -> io::Result<PrivateNetworkConfiguration> {
+        Ok(PrivateNetworkConfiguration {
+            default_vrack_gateway: rest::opt_str_field(value, "defaultVrackGateway")
+                .unwrap_or_default(),
+            private_network_routing_as_default: value.get("privateNetworkRoutingAsDefault")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        })
+    }
+}
+
+fn kube_path(project_id: &str) -> String {
+    format!("{}/kube", project_path(project_id))
+}
+
+fn cluster_path(project_id: &str, kube_id: &str) -> String {
+    format!("{}/{}", kube_path(project_id), rest::escape(kube_id))
+}
+
+pub fn cluster_ids(client: &OVHClient, project_id: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &kube_path(project_id))?)
+}
+
+pub fn cluster(client: &OVHClient, project_id: &str, kube_id: &str) -> io::Result<Cluster> {
+    rest::decode(client, &rest::get(client, &cluster_path(project_id, kube_id))?)
+}
+
+/// Kubeconfig of a cluster, with admin credentials; see `kubeconfig` to merge it.
+pub fn kubeconfig(client: &OVHClient, project_id: &str, kube_id: &str) -> io::Result<String> {
+    let path = format!("{}/kubeconfig", cluster_path(project_id, kube_id));
+    rest::str_field(&rest::post(client, &path, &json!({}))?, "content")
+}
+
+/// The OpenID Connect provider of a cluster, if one is configured.
+pub fn oidc(client: &OVHClient, project_id: &str, kube_id: &str) -> io::Result<Option<OidcConfig>> {
+    let path = format!("{}/openIdConnect", cluster_path(project_id, kube_id));
+    match rest::get(client, &path) {
+        Ok(value) => rest::decode(client, &value).map(Some),
+        Err(ref err) if error::api_error(err).is_some_and(|api| api.http_code == 404) => {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Configure the OpenID Connect provider of a cluster, replacing the current one.
+/// The API server restarts, which takes a few minutes.
+pub fn set_oidc(client: &OVHClient,
+                project_id: &str,
+                kube_id: &str,
+                config: &OidcConfig)
+                -> io::Result<()> {
+    let path = format!("{}/openIdConnect", cluster_path(project_id, kube_id));
+    match oidc(client, project_id, kube_id)? {
+        Some(_) => rest::put(client, &path, &config.to_json())?,
+        None => rest::post(client, &path, &config.to_json())?,
+    };
+    Ok(())
+}
+
+pub fn remove_oidc(client: &OVHClient, project_id: &str, kube_id: &str) -> io::Result<()> {
+    rest::delete(client,
+                 &format!("{}/openIdConnect", cluster_path(project_id, kube_id)))?;
+    Ok(())
+}
+
+/// Networks (CIDR) allowed to reach the API server; empty when it is open to all.
+pub fn ip_restrictions(client: &OVHClient,
+                       project_id: &str,
+                       kube_id: &str)
+                       -> io::Result<Vec<String>> {
+    let path = format!("{}/ipRestrictions", cluster_path(project_id, kube_id));
+    rest::string_list(&rest::get(client, &path)?)
+}
+
+/// Replace the networks allowed to reach the API server; none opens it to all.
+pub fn set_ip_restrictions(client: &OVHClient,
+                           project_id: &str,
+                           kube_id: &str,
+                           ips: &[String])
+                           -> io::Result<()> {
+    rest::put(client,
+              &format!("{}/ipRestrictions", cluster_path(project_id, kube_id)),
+              &json!({ "ips": ips }))?;
+    Ok(())
+}
+
+pub fn private_network_configuration(client: &OVHClient,
+                                     project_id: &str,
+                                     kube_id: &str)
+                                     -> io::Result<PrivateNetworkConfiguration> {
+    let value = rest::get(client, &cluster_path(project_id, kube_id))?;
+    match value.get("privateNetworkConfiguration") {
+        Some(config) if !config.is_null() => rest::decode(client, config),
+        _ => Err(rest::invalid_data("the cluster is not attached to a private network")),
+    }
+}
+
+pub fn set_private_network_configuration(client: &OVHClient,
+                                         project_id: &str,
+                                         kube_id: &str,
+                                         config: &PrivateNetworkConfiguration)
+                                         -> io::Result<()> {
+    let body = json!({
+        "defaultVrackGateway": config.default_vrack_gateway,
+        "privateNetworkRoutingAsDefault": config.private_network_routing_as_default,
+    });
+    rest::put(client,
+              &format!("{}/privateNetworkConfiguration", cluster_path(project_id, kube_id)),
+              &body)?;
+    Ok(())
+}
+
+/// Desired security settings of a cluster; `None` leaves a setting as it is.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SecuritySpec {
+    /// `Some(None)` removes the provider.
+    pub oidc: Option<Option<OidcConfig>>,
+    pub ip_restrictions: Option<Vec<String>>,
+    pub private_network: Option<PrivateNetworkConfiguration>,
+}
+
+/// Same networks, whatever their order and duplicates.
+fn same_networks(current: &[String], wanted: &[String]) -> bool {
+    let normalize = |ips: &[String]| {
+        let mut ips = ips.iter().map(|ip| ip.trim().to_lowercase()).collect::<Vec<_>>();
+        ips.sort();
+        ips.dedup();
+        ips
+    };
+    normalize(current) == normalize(wanted)
+}
+
+/// Apply `spec` to a cluster, sending only the settings which differ.
+/// Returns the settings changed.
+pub fn enforce(client: &OVHClient,
+               project_id: &str,
+               kube_id: &str,
+               spec: &SecuritySpec)
+               -> io::Result<Vec<&'static str>> {
+    let mut changed = Vec::new();
+    if let Some(ref wanted) = spec.ip_restrictions {
+        if !same_networks(&ip_restrictions(client, project_id, kube_id)?, wanted) {
+            set_ip_restrictions(client, project_id, kube_id, wanted)?;
+            changed.push("ipRestrictions");
+        }
+    }
+    if let Some(ref wanted) = spec.private_network {
+        if private_network_configuration(client, project_id, kube_id)? != *wanted {
+            set_private_network_configuration(client, project_id, kube_id, wanted)?;
+            changed.push("privateNetworkConfiguration");
+        }
+    }
+    if let Some(ref wanted) = spec.oidc {
+        let current = oidc(client, project_id, kube_id)?;
+        if current != *wanted {
+            match *wanted {
+                Some(ref config) => set_oidc(client, project_id, kube_id, config)?,
+                None => remove_oidc(client, project_id, kube_id)?,
+            }
+            changed.push("openIdConnect");
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{same_networks, OidcConfig};
+    use rest::Model;
+
+    #[test]
+    fn test_oidc_config() {
+        let mut config = OidcConfig::new("https://sso.example.com/realms/ops", "kubernetes");
+        config.groups_claim = vec!["groups".to_string()];
+        config.required_claims = vec!["aud=kubernetes".to_string()];
+        assert_eq!(config, OidcConfig::from_json(&config.to_json()).unwrap());
+        let answer = json!({
+            "issuerUrl": "https://sso.example.com/realms/ops",
+            "clientId": "kubernetes",
+            "groupsClaim": null,
+        });
+        assert_eq!(Vec::<String>::new(), OidcConfig::from_json(&answer).unwrap().groups_claim);
+    }
+
+    #[test]
+    fn test_same_networks() {
+        let current = vec!["192.0.2.0/24".to_string(), "2001:DB8::/32".to_string()];
+        let wanted = vec!["2001:db8::/32".to_string(),
+                          "192.0.2.0/24".to_string(),
+                          "192.0.2.0/24".to_string()];
+        assert!(same_networks(&current, &wanted));
+        assert!(!same_networks(&current, &wanted[..1]));
+    }
+}
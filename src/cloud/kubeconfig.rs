@@ -0,0 +1,440 @@
+//! # Kubeconfig
+//!
+//! Merge the kubeconfig of a Managed Kubernetes cluster into a kubeconfig file
+//! (`~/.kube/config` by default), under deterministic names: the cluster, the user
+//! and the context are all named `ovh-{region}-{cluster}`.
+//!
+//! Merging again replaces the entries of that name, so the certificates rotated
+//! by a reset of the cluster replace the old ones, and the other entries of the
+//! file are kept as they are.
+//!
+//! The file is handled in the block style written by `kubectl`: the items of
+//! `clusters`, `contexts` and `users` are replaced as a whole, the rest of the
+//! file is not parsed.
+//!
+
+use client::OVHClient;
+use cloud::kube;
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the cluster, user and context of a cluster: `ovh-{region}-{cluster}`,
+/// lowercase, with anything else than letters, digits and `-` replaced by `-`.
+pub fn context_name(region: &str, cluster: &str) -> String {
+    format!("ovh-{}-{}", region, cluster)
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect()
+}
+
+/// What a kubeconfig gives access to a cluster.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ClusterAccess {
+    pub server: String,
+    pub certificate_authority_data: Option<String>,
+    pub client_certificate_data: Option<String>,
+    pub client_key_data: Option<String>,
+    pub token: Option<String>,
+}
+
+impl ClusterAccess {
+    /// Read the kubeconfig of a single cluster, as generated by the API.
+    pub fn parse(content: &str) -> io::Result<ClusterAccess> {
+        let mut access = ClusterAccess::default();
+        for line in content.lines() {
+            let mut parts = line.trim().splitn(2, ':');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next()
+                .map(|value| value.trim().trim_matches('"').trim_matches('\'').to_string());
+            match (key, value) {
+                ("server", Some(value)) => access.server = value,
+                ("certificate-authority-data", value) => access.certificate_authority_data = value,
+                ("client-certificate-data", value) => access.client_certificate_data = value,
+                ("client-key-data", value) => access.client_key_data = value,
+                ("token", value) => access.token = value,
+                _ => {}
+            }
+        }
+        if access.server.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no server in the kubeconfig"));
+        }
+        Ok(access)
+    }
+
+    /// The `clusters`, `contexts` and `users` items named `name`.
+    fn items(&self, name: &str) -> [Item; 3] {
+        let mut cluster = format!("- cluster:\n    server: {}\n", self.server);
+        if let Some(ref data) = self.certificate_authority_data {
+            cluster.push_str(&format!("    certificate-authority-data: {}\n", data));
+        }
+        cluster.push_str(&format!("  name: {}\n", name));
+        let context = format!("- context:\n    cluster: {}\n    user: {}\n  name: {}\n",
+                              name,
+                              name,
+                              name);
+        let mut user = format!("- name: {}\n  user:\n", name);
+        for &(key, value) in &[("client-certificate-data", &self.client_certificate_data),
+                                   ("client-key-data", &self.client_key_data),
+                                   ("token", &self.token)] {
+            if let Some(ref value) = *value {
+                user.push_str(&format!("    {}: {}\n", key, value));
+            }
+        }
+        [Item::new(name, cluster), Item::new(name, context), Item::new(name, user)]
+    }
+}
+
+/// An item of a list, its lines without the indentation of the list.
+#[derive(Debug,Clone,PartialEq)]
+struct Item {
+    name: String,
+    text: String,
+}
+
+impl Item {
+    fn new(name: &str, text: String) -> Item {
+        Item {
+            name: name.to_string(),
+            text,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Section {
+    /// Lines kept as they are.
+    Raw(String),
+    List { key: String, indent: usize, items: Vec<Item> },
+}
+
+/// How the entries of a cluster changed in the file.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum MergeOutcome {
+    Added,
+    /// Replaced, e.g. after a reset rotated the certificates.
+    Updated,
+    Unchanged,
+}
+
+/// A kubeconfig file.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Kubeconfig {
+    sections: Vec<Section>,
+}
+
+const LISTS: [&str; 3] = ["clusters", "contexts", "users"];
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn parse_list(key: &str, lines: &[&str]) -> Section {
+    let indent = lines.iter()
+        .find(|line| line.trim_start().starts_with("- ") || line.trim() == "-")
+        .map_or(0, |line| indent_of(line));
+    let mut items: Vec<Item> = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line = if line.len() >= indent { &line[indent..] } else { line.trim_start() };
+        if line.starts_with('-') {
+            items.push(Item::new("", String::new()));
+        }
+        if let Some(item) = items.last_mut() {
+            let field = line.trim_start_matches('-').trim_start();
+            if indent_of(line.trim_start_matches('-')) <= 2 && field.starts_with("name:") {
+                item.name = field["name:".len()..].trim().trim_matches('"').to_string();
+            }
+            item.text.push_str(line);
+            item.text.push('\n');
+        }
+    }
+    Section::List {
+        key: key.to_string(),
+        indent,
+        items,
+    }
+}
+
+impl Kubeconfig {
+    /// An empty kubeconfig.
+    pub fn new() -> Kubeconfig {
+        Kubeconfig::parse("apiVersion: v1\nkind: Config\npreferences: {}\n")
+    }
+
+    pub fn parse(content: &str) -> Kubeconfig {
+        let lines = content.lines().collect::<Vec<_>>();
+        let mut sections = Vec::new();
+        let mut start = 0;
+        while start < lines.len() {
+            let mut end = start + 1;
+            while end < lines.len() &&
+                  (lines[end].trim().is_empty() || lines[end].starts_with(' ') ||
+                   lines[end].starts_with('-')) {
+                end += 1;
+            }
+            let mut parts = lines[start].splitn(2, ':');
+            let key = parts.next().unwrap_or("").trim();
+            let inline = parts.next().unwrap_or("").trim();
+            let empty = inline.is_empty() || inline == "[]" || inline == "null";
+            if LISTS.contains(&key) && empty {
+                sections.push(parse_list(key, &lines[start + 1..end]));
+            } else {
+                sections.push(Section::Raw(lines[start..end].join("\n") + "\n"));
+            }
+            start = end;
+        }
+        let mut config = Kubeconfig { sections };
+        for key in &LISTS {
+            config.list(key);
+        }
+        config
+    }
+
+    /// Items of the list `key`, added at the end when missing.
+    fn list(&mut self, key: &str) -> &mut Vec<Item> {
+        let position = self.sections.iter().position(|section| match *section {
+            Section::List { key: ref k, .. } => k == key,
+            Section::Raw(_) => false,
+        });
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::List {
+                    key: key.to_string(),
+                    indent: 0,
+                    items: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        };
+        match self.sections[index] {
+            Section::List { ref mut items, .. } => items,
+            Section::Raw(_) => unreachable!(),
+        }
+    }
+
+    /// Names of the items of `clusters`, `contexts` or `users`.
+    pub fn names(&mut self, key: &str) -> Vec<String> {
+        self.list(key).iter().map(|item| item.name.clone()).collect()
+    }
+
+    /// Add or replace the cluster, user and context named `name`.
+    pub fn merge(&mut self, name: &str, access: &ClusterAccess) -> MergeOutcome {
+        let mut outcome = MergeOutcome::Unchanged;
+        for (key, item) in LISTS.iter().zip(access.items(name).iter()) {
+            let items = self.list(key);
+            match items.iter().position(|existing| existing.name == name) {
+                Some(index) if items[index] == *item => {}
+                Some(index) => {
+                    items[index] = item.clone();
+                    if outcome == MergeOutcome::Unchanged {
+                        outcome = MergeOutcome::Updated;
+                    }
+                }
+                None => {
+                    items.push(item.clone());
+                    outcome = MergeOutcome::Added;
+                }
+            }
+        }
+        outcome
+    }
+
+    pub fn set_current_context(&mut self, name: &str) {
+        let line = format!("current-context: {}\n", name);
+        let position = self.sections.iter().position(|section| match *section {
+            Section::Raw(ref text) => text.starts_with("current-context:"),
+            Section::List { .. } => false,
+        });
+        match position {
+            Some(index) => self.sections[index] = Section::Raw(line),
+            None => self.sections.push(Section::Raw(line)),
+        }
+    }
+
+}
+
+impl Default for Kubeconfig {
+    fn default() -> Kubeconfig {
+        Kubeconfig::new()
+    }
+}
+
+impl fmt::Display for Kubeconfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for section in &self.sections {
+            match *section {
+                Section::Raw(ref text) => f.write_str(text)?,
+                Section::List { ref key, ref items, .. } if items.is_empty() => {
+                    writeln!(f, "{}: []", key)?;
+                }
+                Section::List { ref key, indent, ref items } => {
+                    writeln!(f, "{}:", key)?;
+                    for line in items.iter().flat_map(|item| item.text.lines()) {
+                        writeln!(f, "{:indent$}{}", "", line, indent = indent)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The file kubectl uses: the first one of `KUBECONFIG`, or `~/.kube/config`.
+pub fn default_path() -> io::Result<PathBuf> {
+    if let Some(path) = env::var_os("KUBECONFIG").and_then(|paths| {
+        env::split_paths(&paths).next()
+    }) {
+        return Ok(path);
+    }
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".kube").join("config"))
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "neither KUBECONFIG nor HOME is set")
+        })
+}
+
+/// Merge `access` into the kubeconfig file at `path`, created when missing.
+/// The file is replaced atomically, readable by its owner only.
+pub fn merge_into_file(path: &Path,
+                       name: &str,
+                       access: &ClusterAccess,
+                       set_current: bool)
+                       -> io::Result<MergeOutcome> {
+    let mut config = match File::open(path) {
+        Ok(mut file) => {
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            Kubeconfig::parse(&content)
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Kubeconfig::new(),
+        Err(err) => return Err(err),
+    };
+    let outcome = config.merge(name, access);
+    if set_current {
+        config.set_current_context(name);
+    } else if outcome == MergeOutcome::Unchanged {
+        return Ok(outcome);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    File::create(&tmp)?.write_all(config.to_string().as_bytes())?;
+    restrict(&tmp)?;
+    fs::rename(&tmp, path)?;
+    Ok(outcome)
+}
+
+#[cfg(unix)]
+fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Fetch the kubeconfig of a cluster and merge it into the file at `path`
+/// (`default_path` when `None`). Returns the context name and what changed.
+pub fn merge_cluster(client: &OVHClient,
+                     project_id: &str,
+                     kube_id: &str,
+                     path: Option<&Path>,
+                     set_current: bool)
+                     -> io::Result<(String, MergeOutcome)> {
+    let cluster = kube::cluster(client, project_id, kube_id)?;
+    let access = ClusterAccess::parse(&kube::kubeconfig(client, project_id, kube_id)?)?;
+    let name = context_name(&cluster.region, &cluster.name);
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => default_path()?,
+    };
+    let outcome = merge_into_file(&path, &name, &access, set_current)?;
+    Ok((name, outcome))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{context_name, ClusterAccess, Kubeconfig, MergeOutcome};
+
+    const GENERATED: &str = "apiVersion: v1
+clusters:
+- cluster:
+    certificate-authority-data: Q0E=
+    server: https://abc123.c1.gra9.k8s.ovh.net
+  name: prod
+contexts:
+- context:
+    cluster: prod
+    user: kubernetes-admin-prod
+  name: kubernetes-admin@prod
+current-context: kubernetes-admin@prod
+kind: Config
+preferences: {}
+users:
+- name: kubernetes-admin-prod
+  user:
+    client-certificate-data: Q0VSVA==
+    client-key-data: S0VZ
+";
+
+    const EXISTING: &str = "apiVersion: v1
+clusters:
+  - cluster:
+      server: https://127.0.0.1:6443
+    name: kind
+contexts:
+  - context:
+      cluster: kind
+      user: kind
+    name: kind
+current-context: kind
+kind: Config
+users:
+  - name: kind
+    user:
+      exec:
+        command: kind-auth
+        args:
+          - token
+";
+
+    #[test]
+    fn test_merge() {
+        assert_eq!("ovh-gra9-my-cluster", context_name("GRA9", "My cluster"));
+        let access = ClusterAccess::parse(GENERATED).unwrap();
+        assert_eq!(Some("S0VZ".to_string()), access.client_key_data);
+
+        let mut config = Kubeconfig::parse(EXISTING);
+        assert_eq!(MergeOutcome::Added, config.merge("ovh-gra9-prod", &access));
+        assert_eq!(MergeOutcome::Unchanged, config.merge("ovh-gra9-prod", &access));
+        config.set_current_context("ovh-gra9-prod");
+        let merged = config.to_string();
+        assert!(merged.contains("\n      exec:\n        command: kind-auth\n"));
+        assert!(merged.contains("  - context:\n      cluster: ovh-gra9-prod\n"));
+        assert!(merged.contains("current-context: ovh-gra9-prod\n"));
+
+        let mut config = Kubeconfig::parse(&merged);
+        assert_eq!(vec!["kind", "ovh-gra9-prod"], config.names("users"));
+        let rotated = ClusterAccess { client_key_data: Some("TkVX".to_string()), ..access };
+        assert_eq!(MergeOutcome::Updated, config.merge("ovh-gra9-prod", &rotated));
+        assert_eq!(vec!["kind", "ovh-gra9-prod"], config.names("users"));
+        assert!(config.to_string().contains("client-key-data: TkVX\n"));
+
+        let mut config = Kubeconfig::new();
+        config.merge("ovh-gra9-prod", &rotated);
+        assert_eq!(vec!["ovh-gra9-prod"], Kubeconfig::parse(&config.to_string()).names("contexts"));
+    }
+}
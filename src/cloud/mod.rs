@@ -0,0 +1,80 @@
+//! # Cloud
+//!
+//! Typed access to Public Cloud projects (`/cloud/project`).
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+pub mod bootstrap;
+pub mod database;
+pub mod flavor;
+pub mod image;
+pub mod instance;
+pub mod kube;
+pub mod kubeconfig;
+pub mod sshkey;
+pub mod userdata;
+pub mod volume;
+
+pub use self::bootstrap::bootstrap_project;
+pub use self::instance::{instance_rescue, instance_unrescue, vnc_console};
+
+/// A Public Cloud project.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Project {
+    pub id: String,
+    pub description: String,
+    pub status: String,
+}
+
+impl Model for Project {
+    const NAME: &'static str = "Project";
+    const FIELDS: &'static [&'static str] = &["project_id", "projectName", "description",
+                                              "planCode", "unleash", "expiration", "creationDate",
+                                              "orderId", "access", "status", "manualQuota", "iam"];
+
+    fn from_json(value: &Value) -> io::Result<Project> {
+        Ok(Project {
+            id: rest::str_field(value, "project_id")?,
+            description: rest::opt_str_field(value, "description").unwrap_or_default(),
+            status: rest::opt_str_field(value, "status").unwrap_or_default(),
+        })
+    }
+}
+
+/// Base route of a project.
+pub fn project_path(project_id: &str) -> String {
+    format!("/cloud/project/{}", rest::escape(project_id))
+}
+
+/// List the ids of the projects of the account.
+pub fn project_ids(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/cloud/project")?)
+}
+
+pub fn project(client: &OVHClient, project_id: &str) -> io::Result<Project> {
+    rest::decode(client, &rest::get(client, &project_path(project_id))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Project;
+    use rest::Model;
+
+    #[test]
+    fn test_project_from_json() {
+        let value = json!({
+            "project_id": "5a3c2f0e",
+            "description": "staging",
+            "status": "ok",
+        });
+        let project = Project::from_json(&value).unwrap();
+        assert_eq!("5a3c2f0e", project.id);
+        assert_eq!("staging", project.description);
+    }
+}
@@ -0,0 +1,67 @@
+//! # SSH keys
+//!
+//! Project-level SSH keys of Public Cloud (`/cloud/project/{id}/sshkey`),
+//! injected into the instances created in the project. Account keys, used by
+//! the dedicated server and VPS installations, are in `me::sshkey`.
+//!
+
+use client::OVHClient;
+use cloud::project_path;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// An SSH key registered in a project.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SshKey {
+    pub id: String,
+    pub name: String,
+    pub public_key: String,
+    pub regions: Vec<String>,
+}
+
+impl Model for SshKey {
+    const NAME: &'static str = "SshKey";
+    const FIELDS: &'static [&'static str] = &["id", "name", "publicKey", "fingerPrint", "regions"];
+
+    fn from_json(value: &Value) -> io::Result<SshKey> {
+        Ok(SshKey {
+            id: rest::str_field(value, "id")?,
+            name: rest::opt_str_field(value, "name").unwrap_or_default(),
+            public_key: rest::str_field(value, "publicKey")?,
+            regions: match value.get("regions") {
+                Some(regions) => rest::string_list(regions)?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+fn sshkey_path(project_id: &str) -> String {
+    format!("{}/sshkey", project_path(project_id))
+}
+
+pub fn ssh_keys(client: &OVHClient, project_id: &str) -> io::Result<Vec<SshKey>> {
+    rest::decode_list(client, &rest::get(client, &sshkey_path(project_id))?)
+}
+
+/// Register a key available in every region of the project.
+pub fn create_ssh_key(client: &OVHClient,
+                      project_id: &str,
+                      name: &str,
+                      public_key: &str)
+                      -> io::Result<SshKey> {
+    let body = json!({
+        "name": name,
+        "publicKey": public_key,
+    });
+    rest::decode(client, &rest::post(client, &sshkey_path(project_id), &body)?)
+}
+
+pub fn delete_ssh_key(client: &OVHClient, project_id: &str, key_id: &str) -> io::Result<()> {
+    rest::delete(client,
+                 &format!("{}/{}", sshkey_path(project_id), rest::escape(key_id)))?;
+    Ok(())
+}
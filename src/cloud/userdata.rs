@@ -0,0 +1,112 @@
+//! # User data
+//!
+//! cloud-init user data for new instances: `{{ variable }}` templating,
+//! base64 conversion and validation of the size accepted by OpenStack.
+//!
+
+use base64;
+
+use std::collections::HashMap;
+use std::io;
+
+/// Maximum size of the base64 encoded user data accepted by OpenStack.
+pub const MAX_ENCODED_SIZE: usize = 65535;
+
+/// User data passed to cloud-init when an instance boots for the first time.
+#[derive(Debug,Clone,PartialEq)]
+pub struct UserData {
+    content: String,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+impl UserData {
+    /// User data used as is, without templating.
+    pub fn new(content: &str) -> io::Result<UserData> {
+        let user_data = UserData { content: content.to_string() };
+        user_data.validate()?;
+        Ok(user_data)
+    }
+
+    /// Render a template, replacing every `{{ name }}` by its value in `vars`.
+    /// A variable missing from `vars` is an error rather than an empty string,
+    /// so a typo cannot silently produce a broken configuration.
+    pub fn render(template: &str, vars: &HashMap<String, String>) -> io::Result<UserData> {
+        let mut content = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            content.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find("}}")
+                .ok_or_else(|| invalid(format!("unclosed variable: {{{{{}", after)))?;
+            let name = after[..end].trim();
+            let value = vars.get(name)
+                .ok_or_else(|| invalid(format!("undefined template variable: {}", name)))?;
+            content.push_str(value);
+            rest = &after[end + 2..];
+        }
+        content.push_str(rest);
+        UserData::new(&content)
+    }
+
+    /// Decode user data which was already base64 encoded.
+    pub fn from_base64(encoded: &str) -> io::Result<UserData> {
+        let compact: String = encoded.split_whitespace().collect();
+        let bytes = base64::decode(&compact).map_err(|e| invalid(format!("invalid base64: {}", e)))?;
+        let content = String::from_utf8(bytes)
+            .map_err(|_| invalid("user data is not valid UTF-8".to_string()))?;
+        UserData::new(&content)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.content)
+    }
+
+    /// Check the encoded size against `MAX_ENCODED_SIZE`.
+    pub fn validate(&self) -> io::Result<()> {
+        let size = self.content.len().div_ceil(3) * 4;
+        if size > MAX_ENCODED_SIZE {
+            return Err(invalid(format!("user data is {} bytes once encoded, the limit is {}",
+                                       size,
+                                       MAX_ENCODED_SIZE)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UserData, MAX_ENCODED_SIZE};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render() {
+        let mut vars = HashMap::new();
+        vars.insert("hostname".to_string(), "web-1".to_string());
+        let user_data = UserData::render("#cloud-config\nhostname: {{ hostname }}\n", &vars)
+            .unwrap();
+        assert_eq!("#cloud-config\nhostname: web-1\n", user_data.as_str());
+
+        assert!(UserData::render("hostname: {{ hostnme }}", &vars).is_err());
+        assert!(UserData::render("hostname: {{ hostname", &vars).is_err());
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let user_data = UserData::new("#!/bin/sh\necho ok\n").unwrap();
+        let encoded = user_data.to_base64();
+        assert_eq!(user_data, UserData::from_base64(&encoded).unwrap());
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let content = "a".repeat(MAX_ENCODED_SIZE);
+        assert!(UserData::new(&content).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! # Volumes
+//!
+//! Block storage volumes of a project (`/cloud/project/{id}/volume`).
+//!
+//! Attaching a volume to an instance which is still building or rebooting,
+//! or a volume which is still detaching, fails intermittently: `attach` and
+//! `detach` wait for both ends to be in a stable state, send the call, then
+//! wait for the volume to reach its new status.
+//!
+
+use client::OVHClient;
+use cloud::instance::{self, Instance};
+use cloud::project_path;
+use rest::{self, Model};
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// A block storage volume.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Volume {
+    pub id: String,
+    pub name: String,
+    /// Size in GB.
+    pub size: u64,
+    pub region: String,
+    /// `available`, `in-use`, `attaching`, `detaching`, `creating`, `error`, ...
+    pub status: String,
+    /// `classic`, `high-speed`, ...
+    pub volume_type: String,
+    /// Ids of the instances the volume is attached to.
+    pub attached_to: Vec<String>,
+}
+
+impl Model for Volume {
+    const NAME: &'static str = "Volume";
+    const FIELDS: &'static [&'static str] = &["id", "name", "description", "size", "region",
+                                              "status", "type", "attachedTo", "bootable",
+                                              "creationDate", "planCode", "regionName"];
+
+    fn from_json(value: &Value) -> io::Result<Volume> {
+        let attached_to = match value.get("attachedTo") {
+            Some(ids) if !ids.is_null() => rest::string_list(ids)?,
+            _ => Vec::new(),
+        };
+        Ok(Volume {
+            id: rest::str_field(value, "id")?,
+            name: rest::opt_str_field(value, "name").unwrap_or_default(),
+            size: rest::opt_u64_field(value, "size").unwrap_or(0),
+            region: rest::opt_str_field(value, "region").unwrap_or_default(),
+            status: rest::opt_str_field(value, "status").unwrap_or_default(),
+            volume_type: rest::opt_str_field(value, "type").unwrap_or_default(),
+            attached_to,
+        })
+    }
+}
+
+/// Statuses a volume only passes through.
+fn is_transient(status: &str) -> bool {
+    matches!(status,
+             "creating" | "attaching" | "detaching" | "extending" | "downloading" | "reserved")
+}
+
+fn volume_path(project_id: &str) -> String {
+    format!("{}/volume", project_path(project_id))
+}
+
+pub fn volumes(client: &OVHClient, project_id: &str) -> io::Result<Vec<Volume>> {
+    rest::decode_list(client, &rest::get(client, &volume_path(project_id))?)
+}
+
+pub fn volume(client: &OVHClient, project_id: &str, volume_id: &str) -> io::Result<Volume> {
+    let path = format!("{}/{}", volume_path(project_id), rest::escape(volume_id));
+    rest::decode(client, &rest::get(client, &path)?)
+}
+
+/// Where an attached volume shows up in the guest.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Attachment {
+    pub volume: Volume,
+    pub instance_id: String,
+    /// Stable device path of a virtio disk, named after the volume id
+    /// (the guest name, `/dev/vdb`, `/dev/sdc`, ..., depends on the attach order).
+    pub device_by_id: String,
+}
+
+/// Stable device path of a volume in a Linux guest: virtio disks expose
+/// the first 20 characters of the volume id as serial.
+pub fn device_by_id(volume_id: &str) -> String {
+    format!("/dev/disk/by-id/virtio-{}", volume_id.chars().take(20).collect::<String>())
+}
+
+/// Interval between two polls, in seconds.
+const POLL_INTERVAL: u64 = 3;
+
+/// Poll a volume until `done` accepts it. Fails on `error` status or after `timeout`.
+fn wait_volume<F>(client: &OVHClient,
+                  project_id: &str,
+                  volume_id: &str,
+                  what: &str,
+                  timeout: Duration,
+                  done: F)
+                  -> io::Result<Volume>
+    where F: Fn(&Volume) -> bool
+{
+    let started = Instant::now();
+    loop {
+        let current = volume(client, project_id, volume_id)?;
+        if done(&current) {
+            return Ok(current);
+        }
+        if current.status.starts_with("error") {
+            return Err(io::Error::other(format!("volume {} is in {}", volume_id, current.status)));
+        }
+        if started.elapsed() >= timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                      format!("volume {} still {} waiting to be {}",
+                                              volume_id,
+                                              current.status,
+                                              what)));
+        }
+        thread::sleep(Duration::from_secs(POLL_INTERVAL));
+    }
+}
+
+/// Wait for an instance to leave transitional statuses (`BUILD`, `REBOOT`, ...).
+fn wait_instance_stable(client: &OVHClient,
+                        project_id: &str,
+                        instance_id: &str,
+                        timeout: Duration)
+                        -> io::Result<Instance> {
+    let started = Instant::now();
+    loop {
+        let current = instance::instance(client, project_id, instance_id)?;
+        match current.status.as_str() {
+            "ACTIVE" | "SHUTOFF" | "RESCUE" => return Ok(current),
+            "ERROR" => {
+                return Err(io::Error::other(format!("instance {} is in error", instance_id)))
+            }
+            _ => {}
+        }
+        if started.elapsed() >= timeout {
+            let message = format!("instance {} still {}", instance_id, current.status);
+            return Err(io::Error::new(io::ErrorKind::TimedOut, message));
+        }
+        thread::sleep(Duration::from_secs(POLL_INTERVAL));
+    }
+}
+
+/// Attach a volume once both ends are stable, and wait until it is `in-use`.
+/// Attaching a volume already attached to the instance only returns its attachment.
+pub fn attach(client: &OVHClient,
+              project_id: &str,
+              volume_id: &str,
+              instance_id: &str,
+              timeout: Duration)
+              -> io::Result<Attachment> {
+    let current = wait_volume(client,
+                              project_id,
+                              volume_id,
+                              "attachable",
+                              timeout,
+                              |volume| !is_transient(&volume.status))?;
+    if !current.attached_to.iter().any(|id| id == instance_id) {
+        wait_instance_stable(client, project_id, instance_id, timeout)?;
+        rest::post(client,
+                   &format!("{}/{}/attach", volume_path(project_id), rest::escape(volume_id)),
+                   &json!({ "instanceId": instance_id }))?;
+    }
+    let volume = wait_volume(client, project_id, volume_id, "in-use", timeout, |volume| {
+        volume.status == "in-use" && volume.attached_to.iter().any(|id| id == instance_id)
+    })?;
+    Ok(Attachment {
+        device_by_id: device_by_id(&volume.id),
+        volume,
+        instance_id: instance_id.to_string(),
+    })
+}
+
+/// Detach a volume from an instance and wait until it is `available`.
+pub fn detach(client: &OVHClient,
+              project_id: &str,
+              volume_id: &str,
+              instance_id: &str,
+              timeout: Duration)
+              -> io::Result<Volume> {
+    let current = wait_volume(client,
+                              project_id,
+                              volume_id,
+                              "detachable",
+                              timeout,
+                              |volume| !is_transient(&volume.status))?;
+    if current.attached_to.iter().any(|id| id == instance_id) {
+        wait_instance_stable(client, project_id, instance_id, timeout)?;
+        rest::post(client,
+                   &format!("{}/{}/detach", volume_path(project_id), rest::escape(volume_id)),
+                   &json!({ "instanceId": instance_id }))?;
+    }
+    wait_volume(client, project_id, volume_id, "detached", timeout, |volume| {
+        !is_transient(&volume.status) && !volume.attached_to.iter().any(|id| id == instance_id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{device_by_id, is_transient, Volume};
+    use rest::Model;
+
+    #[test]
+    fn test_volume_from_json() {
+        let volume = Volume::from_json(&json!({
+            "id": "9c3f5e1a-2b7d-4e0f-8a6b-1d2c3e4f5a6b",
+            "name": "data",
+            "size": 100,
+            "region": "GRA7",
+            "status": "in-use",
+            "type": "classic",
+            "attachedTo": ["instance-1"],
+        }))
+            .unwrap();
+        assert_eq!(vec!["instance-1".to_string()], volume.attached_to);
+        assert!(!is_transient(&volume.status));
+        assert!(is_transient("detaching"));
+        assert_eq!("/dev/disk/by-id/virtio-9c3f5e1a-2b7d-4e0f-8", device_by_id(&volume.id));
+    }
+}
@@ -0,0 +1,198 @@
+//! # Concurrency
+//!
+//! Adaptive limit for parallel crawls (AIMD): start with a couple of requests
+//! in flight, add one each time a full window of requests answered quickly,
+//! and halve the limit as soon as the API throttles (`429`) or slows down.
+//! The ceiling is derived from the size of the crawl, so small accounts are
+//! not hammered and large ones don't need a hand-picked limit.
+//!
+
+use client::OVHClient;
+use rest;
+use retry;
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// Answers slower than this count as congestion.
+pub const DEFAULT_SLOW_THRESHOLD_MS: u64 = 2000;
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    in_flight: usize,
+    /// Fast answers since the last change of the limit.
+    successes: usize,
+}
+
+/// An AIMD concurrency limit, shared by the workers of a crawl.
+#[derive(Debug)]
+pub struct AimdLimiter {
+    state: Mutex<State>,
+    released: Condvar,
+    min: usize,
+    max: usize,
+    slow_threshold: Duration,
+}
+
+impl AimdLimiter {
+    /// A limit starting at `initial` and moving between 1 and `max`.
+    pub fn new(initial: usize, max: usize) -> AimdLimiter {
+        let max = cmp::max(max, 1);
+        AimdLimiter {
+            state: Mutex::new(State {
+                limit: cmp::min(cmp::max(initial, 1), max),
+                in_flight: 0,
+                successes: 0,
+            }),
+            released: Condvar::new(),
+            min: 1,
+            max,
+            slow_threshold: Duration::from_millis(DEFAULT_SLOW_THRESHOLD_MS),
+        }
+    }
+
+    /// A limit for a crawl of `items` requests: starts at 2 and grows
+    /// up to one worker per 10 items, at most 32.
+    pub fn for_items(items: usize) -> AimdLimiter {
+        AimdLimiter::new(2, (items / 10).clamp(1, 32))
+    }
+
+    pub fn set_slow_threshold(&mut self, threshold: Duration) {
+        self.slow_threshold = threshold;
+    }
+
+    /// Current number of requests allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Wait for a slot; the slot is released when the permit is dropped.
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut state = self.state.lock().unwrap();
+        while state.in_flight >= state.limit {
+            state = self.released.wait(state).unwrap();
+        }
+        state.in_flight += 1;
+        Permit { limiter: self }
+    }
+
+    /// Adjust the limit after an answer.
+    pub fn record(&self, latency: Duration, throttled: bool) {
+        let mut state = self.state.lock().unwrap();
+        if throttled || latency > self.slow_threshold {
+            state.limit = cmp::max(state.limit / 2, self.min);
+            state.successes = 0;
+            debug!("Concurrency limit decreased to {}", state.limit);
+        } else {
+            state.successes += 1;
+            if state.successes >= state.limit && state.limit < self.max {
+                state.limit += 1;
+                state.successes = 0;
+                debug!("Concurrency limit increased to {}", state.limit);
+            }
+        }
+        self.released.notify_all();
+    }
+}
+
+/// A slot of an `AimdLimiter`.
+pub struct Permit<'a> {
+    limiter: &'a AimdLimiter,
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.released.notify_all();
+    }
+}
+
+/// Fetch the details of the ids listed by `path` in parallel, within the limit.
+/// Throttled requests are retried within the global retry budget;
+/// the first other error stops the crawl. Items keep the order of the listing.
+pub fn fetch_all(client: &OVHClient,
+                 path: &str,
+                 limiter: &AimdLimiter)
+                 -> io::Result<Vec<(String, Value)>> {
+    let ids = rest::string_list(&rest::get(client, path)?)?;
+    let pending: Mutex<VecDeque<(usize, String)>> =
+        Mutex::new(ids.into_iter().enumerate().collect());
+    let results: Mutex<Vec<(usize, String, Value)>> = Mutex::new(Vec::new());
+    let failure: Mutex<Option<io::Error>> = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..limiter.max() {
+            scope.spawn(|| loop {
+                if failure.lock().unwrap().is_some() {
+                    return;
+                }
+                let (index, id) = match pending.lock().unwrap().pop_front() {
+                    Some(item) => item,
+                    None => return,
+                };
+                let permit = limiter.acquire();
+                let started = Instant::now();
+                let result = rest::get(client, &format!("{}/{}", path, rest::escape(&id)));
+                let throttled = result.as_ref().err().is_some_and(rest::is_throttled);
+                limiter.record(started.elapsed(), throttled);
+                drop(permit);
+                match result {
+                    Ok(value) => results.lock().unwrap().push((index, id, value)),
+                    Err(ref err) if throttled && retry::global().try_retry() => {
+                        debug!("Throttled on {}/{}: {}", path, id, err);
+                        pending.lock().unwrap().push_back((index, id));
+                    }
+                    Err(err) => {
+                        *failure.lock().unwrap() = Some(err);
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    if let Some(err) = failure.into_inner().unwrap() {
+        return Err(err);
+    }
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(index, _, _)| index);
+    Ok(results.into_iter().map(|(_, id, value)| (id, value)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AimdLimiter;
+    use std::time::Duration;
+
+    #[test]
+    fn test_aimd() {
+        let limiter = AimdLimiter::new(2, 4);
+        let fast = Duration::from_millis(10);
+        for _ in 0..2 {
+            limiter.record(fast, false);
+        }
+        assert_eq!(3, limiter.limit());
+        for _ in 0..10 {
+            limiter.record(fast, false);
+        }
+        assert_eq!(4, limiter.limit());
+        limiter.record(fast, true);
+        assert_eq!(2, limiter.limit());
+        limiter.record(Duration::from_secs(5), false);
+        assert_eq!(1, limiter.limit());
+        limiter.record(fast, true);
+        assert_eq!(1, limiter.limit());
+        assert_eq!(1, AimdLimiter::for_items(3).max());
+        assert_eq!(32, AimdLimiter::for_items(10_000).max());
+        let _permit = limiter.acquire();
+    }
+}
@@ -4,212 +4,1213 @@
 //! authentification tokens and
 //! load into a credential struct for request signing.
 //!
+//! A file can hold several profiles, one section each, e.g. `[prod]` and
+//! `[staging]` with their own `endpoint` and keys; the profile is chosen with
+//! `Credential::from_profile` or the `OVH_PROFILE` environment variable, and
+//! defaults to the section of `default.endpoint`.
+//!
+//! `Credential::new` looks for the config file in this order, and falls back to
+//! the environment then to `ovh.conf` when there is none:
+//!
+//! 1. `$OVH_CONFIG`, which must exist when set,
+//! 2. `./Config.toml`,
+//! 3. `$XDG_CONFIG_HOME/ovh/config.toml` (`~/.config/ovh/config.toml`),
+//! 4. `~/.ovh.toml`,
+//! 5. `/etc/ovh.toml`.
+//!
+//! `Credential::source` tells which one was used.
+//!
+//! The INI `ovh.conf` files of the Python and Go clients are read too, from
+//! their usual locations (see `ovh_conf_paths`), to share one set of secrets.
+//!
+//! Once a consumer key is validated, `Credential::save` writes it back to the
+//! profile of the config file, leaving the rest of the file untouched.
+//!
+//! A profile with `auth = "oauth2"` holds the `client_id` and `client_secret`
+//! of a service account instead of keys, see `oauth2`.
+//!
+//! With the `keyring` feature, a profile can be kept in the secret store of the
+//! platform (Secret Service, macOS Keychain or Windows Credential Manager)
+//! instead of a plain file: `Credential::save_to_keyring` stores it and
+//! `Credential::from_keyring` loads it back.
+//!
 extern crate toml;
 
+use oauth2::{self, OAuth2};
+use seal::{self, Kms};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs::File;
-use std::path::Path;
-use std::io::{Read, Error};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::io::Read;
+
+use error::Error;
+
+#[cfg(feature = "keyring")]
+use keyring;
 
-const DEFAULT_CONFIG_PATH: &'static str = "Config.toml";
+const DEFAULT_CONFIG_PATH: &str = "Config.toml";
+
+/// Where a credential was loaded from, for diagnostics.
+#[derive(Debug,Clone,PartialEq)]
+pub enum CredentialSource {
+    /// A TOML config file.
+    File(PathBuf),
+    /// The `OVH_*` environment variables.
+    Environment,
+    /// The `ovh.conf` files of the Python and Go clients which exist.
+    OvhConf(Vec<PathBuf>),
+    /// Keys given by the application.
+    Code,
+    /// An entry of the secret store of the platform: its service and profile.
+    Keyring(String, String),
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CredentialSource::File(ref path) => write!(f, "{}", path.display()),
+            CredentialSource::Environment => write!(f, "environment"),
+            CredentialSource::OvhConf(ref paths) => {
+                let names: Vec<String> = paths.iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "{}", names.join(", "))
+            }
+            CredentialSource::Code => write!(f, "application"),
+            CredentialSource::Keyring(ref service, ref profile) => {
+                write!(f, "keyring {}/{}", service, profile)
+            }
+        }
+    }
+}
 
 /// OVH API application credentials, including application key, application secret key,
 /// consumer key, a temporary access token with access control to user API.
-#[derive(Debug,Clone)]
+///
+/// Its `Debug` output masks the application secret and the consumer key, so a
+/// credential can be logged; `expose_secret` gives the application secret.
+#[derive(Clone)]
 pub struct Credential {
-    path: Option<String>,
-    toml: Option<toml::Value>,
+    source: CredentialSource,
+    /// Section of the config file the credential was read from.
+    profile: Option<String>,
     pub host: String,
     pub application_key: String,
-    pub application_secret: String,
+    pub(crate) application_secret: String,
     pub consumer_key: String,
+    /// The service account authenticating the requests instead of the keys.
+    oauth2: Option<Arc<OAuth2>>,
 }
 
-/// Utility fonction to read toml file by path
-/// Currently only considere api on subsidiary : ovh-eu, ovh-ca.
-fn read_from_path<'a, P: AsRef<Path>>(owner: &'a mut String,
-                                      path: P)
-                                      -> Result<(String, toml::Value), Error> {
-    let mut fd = match File::open(path) {
-        Err(_) => panic!("Cannot open given path"),
-        Ok(fd) => fd,
-    };
-    match fd.read_to_string(owner) {
-        Err(_) => panic!("Cannot read file"),
-        Ok(s) => s,
+/// Hide a secret but its first characters when it is long enough for them not to matter.
+fn mask(secret: &str) -> String {
+    if secret.is_empty() {
+        String::new()
+    } else if secret.chars().count() <= 8 {
+        "***".to_string()
+    } else {
+        format!("{}***", secret.chars().take(2).collect::<String>())
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credential")
+            .field("source", &self.source)
+            .field("profile", &self.profile)
+            .field("host", &self.host)
+            .field("application_key", &self.application_key)
+            .field("application_secret", &mask(&self.application_secret))
+            .field("consumer_key", &mask(&self.consumer_key))
+            .field("oauth2", &self.oauth2)
+            .finish()
+    }
+}
+
+/// Utility fonction to read toml file by path, opening it first when sealed (see `seal`).
+/// Returns the name of the section of the profile (`default.endpoint` by default),
+/// its host and the section.
+fn read_from_path<P: AsRef<Path>>(path: P,
+                                  profile: Option<&str>,
+                                  kms: Option<&dyn Kms>)
+                                  -> Result<(String, String, toml::Value), Error> {
+    let name = path.as_ref().display().to_string();
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|mut fd| fd.read_to_string(&mut content))
+        .map_err(|err| Error::Io(name.clone(), err))?;
+    if seal::is_sealed(&content) {
+        content = seal::open(&content, kms).map_err(|err| Error::Sealed(name.clone(), err))?;
+    }
+    let toml = parse_toml(&name, &content)?;
+    let profile = match profile {
+        Some(profile) => profile,
+        None => toml.get("default")
+            .and_then(|default| default.lookup("endpoint"))
+            .and_then(toml::Value::as_str)
+            .ok_or_else(|| Error::MissingKey("default.endpoint".to_string()))?,
     };
-    let mut parser = toml::Parser::new(owner);
-    let toml = match parser.parse() {
-        None => panic!("Cannot parse toml content"),
-        Some(_toml) => _toml,
+    let auth = toml.get(profile).ok_or_else(|| Error::MissingKey(format!("[{}]", profile)))?;
+    let endpoint = auth.lookup("endpoint").and_then(toml::Value::as_str).unwrap_or(profile);
+    let endpoint: Endpoint = endpoint.parse()?;
+    Ok((profile.to_string(), endpoint.host().to_string(), auth.clone()))
+}
+
+/// Parse a TOML document; `name` tells where it comes from in errors.
+fn parse_toml(name: &str, content: &str) -> Result<toml::Table, Error> {
+    let mut parser = toml::Parser::new(content);
+    parser.parse().ok_or_else(|| {
+        let message = parser.errors
+            .first()
+            .map_or("cannot parse".to_string(), |err| err.to_string());
+        Error::Parse(name.to_string(), message)
+    })
+}
+
+type IniSections = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parse an INI file as Python's `ConfigParser`: `[section]` headers,
+/// `key = value` or `key: value` entries and `#` or `;` comments.
+fn parse_ini(content: &str, sections: &mut IniSections) -> Result<(), String> {
+    let mut current = None;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+            continue;
+        }
+        let separator = line.find(['=', ':'])
+            .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+        let section = current.as_ref()
+            .ok_or_else(|| format!("line {}: entry outside of a section", number + 1))?;
+        sections.get_mut(section)
+            .unwrap()
+            .insert(line[..separator].trim().to_lowercase(),
+                    line[separator + 1..].trim().to_string());
+    }
+    Ok(())
+}
+
+/// Locations of `ovh.conf`, as searched by the Python and Go clients:
+/// `./ovh.conf`, `~/.ovh.conf` then `/etc/ovh.conf`. The first ones take precedence.
+pub fn ovh_conf_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("ovh.conf")];
+    if let Some(home) = env::home_dir() {
+        paths.push(home.join(".ovh.conf"));
+    }
+    paths.push(PathBuf::from("/etc/ovh.conf"));
+    paths
+}
+
+/// Locations of the config file searched after `$OVH_CONFIG`, see the module documentation.
+pub fn config_paths() -> Vec<PathBuf> {
+    search_paths(env::var_os("XDG_CONFIG_HOME"), env::home_dir())
+}
+
+fn search_paths(xdg_config_home: Option<OsString>, home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(DEFAULT_CONFIG_PATH)];
+    match xdg_config_home.filter(|dir| !dir.is_empty()) {
+        Some(dir) => paths.push(PathBuf::from(dir).join("ovh").join("config.toml")),
+        None => {
+            if let Some(ref home) = home {
+                paths.push(home.join(".config").join("ovh").join("config.toml"));
+            }
+        }
+    }
+    if let Some(ref home) = home {
+        paths.push(home.join(".ovh.toml"));
+    }
+    paths.push(PathBuf::from("/etc/ovh.toml"));
+    paths
+}
+
+/// A `key = "value"` line.
+fn toml_entry(key: &str, value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{} = \"{}\"", key, escaped)
+}
+
+/// Set `key` to the string `value` in `[section]` of a TOML document, editing the lines
+/// so the rest of the document is kept as is. Existing keys are only replaced if `replace`.
+fn set_toml_key(content: &str, section: &str, key: &str, value: &str, replace: bool) -> String {
+    let entry = toml_entry(key, value);
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let start = match lines.iter().position(|line| line.trim() == header) {
+        Some(start) => start,
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(entry);
+            return lines.join("\n") + "\n";
+        }
     };
-    let endpoint: toml::Value = toml.get("default")
-        .unwrap()
-        .lookup("endpoint")
-        .unwrap()
-        .clone();
-    let _endpoint: &str = &endpoint.as_str().unwrap();
-    let host = endpoint2host(_endpoint);
+    let end = lines.iter()
+        .skip(start + 1)
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let existing = (start + 1..end).find(|&index| {
+        let line = lines[index].trim_start();
+        line.starts_with(key) && line[key.len()..].trim_start().starts_with('=')
+    });
+    match existing {
+        Some(index) => {
+            if replace {
+                lines[index] = entry;
+            }
+        }
+        None => {
+            // after the last entry of the section, before the blank lines
+            let last = (start..end).rev().find(|&index| !lines[index].trim().is_empty()).unwrap();
+            lines.insert(last + 1, entry);
+        }
+    }
+    lines.join("\n") + "\n"
+}
 
-    Ok((host, toml.get(_endpoint).unwrap().clone()))
+/// A string key of the section of the endpoint.
+fn lookup(auth: &toml::Value, endpoint: &str, key: &str) -> Result<String, Error> {
+    auth.lookup(key)
+        .and_then(toml::Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| Error::MissingKey(format!("{}.{}", endpoint, key)))
 }
 
 impl Credential {
-    /// Initialize a new `Credential` from default path a App Key, App secret, Consumer token.
-    pub fn new() -> Credential {
-        let toml = &mut String::new();
-        let (host, auth): (String, toml::Value) =
-            match read_from_path(toml, DEFAULT_CONFIG_PATH.to_owned()) {
-                Err(_) => panic!("Could not read auth"),
-                Ok(_auth) => _auth,
-            };
-        let r_app_key = auth.lookup("application_key").unwrap().clone();
-        let app_key = String::from(r_app_key.as_str().unwrap().clone());
-        let r_app_secret = auth.lookup("application_secret").unwrap().clone();
-        let app_secret = String::from(r_app_secret.as_str().unwrap().clone());
-        let r_cons_key = auth.lookup("consumer_key").unwrap().clone();
-        let cons_key = String::from(r_cons_key.as_str().unwrap().clone());
-
-        Credential {
-            toml: Some(auth),
-            path: Some(DEFAULT_CONFIG_PATH.to_owned()),
-            host: host,
-            application_key: app_key,
-            application_secret: app_secret,
-            consumer_key: cons_key,
+    /// Initialize a new `Credential` from the first config file found (see the module
+    /// documentation) a App Key, App secret, Consumer token, of the profile named by
+    /// `OVH_PROFILE` if set.
+    /// Without a config file, falls back to the environment (see `from_env`),
+    /// then to `ovh.conf` (see `find_ovh_conf`): a config file wins over the
+    /// environment, which wins over `ovh.conf`. `OVHClient::new` uses the same order.
+    pub fn new() -> Result<Credential, Error> {
+        Credential::discover(&config_paths(), env_var, &ovh_conf_paths())
+    }
+
+    /// Initialize a new `Credential` from a profile (a section) of the first config file found.
+    pub fn from_profile(profile: &str) -> Result<Credential, Error> {
+        Credential::find_file(&config_paths(), &env_var, Some(profile))?
+            .ok_or_else(|| not_found(&config_paths()))
+    }
+
+    /// Initialize a new `Credential` from a profile of the given path. The endpoint
+    /// of the profile is its `endpoint` key, or its name, e.g. `[ovh-ca]`.
+    pub fn from_profile_file<P: AsRef<Path>>(path: P, profile: &str) -> Result<Credential, Error> {
+        Credential::read(path, Some(profile), None)
+    }
+
+    fn discover<F: Fn(&str) -> Option<String>>(paths: &[PathBuf],
+                                               var: F,
+                                               conf_paths: &[PathBuf])
+                                               -> Result<Credential, Error> {
+        let profile = var("OVH_PROFILE");
+        if let Some(credential) = Credential::find_file(paths,
+                                                        &var,
+                                                        profile.as_deref())? {
+            return Ok(credential);
+        }
+        if let Some(credential) = Credential::from_vars(&var)? {
+            return Ok(credential);
+        }
+        Credential::from_ovh_conf_files(conf_paths)?.ok_or_else(|| not_found(paths))
+    }
+
+    /// The credential of the first config file which exists: `$OVH_CONFIG`, then `paths`.
+    fn find_file<F: Fn(&str) -> Option<String>>(paths: &[PathBuf],
+                                                var: &F,
+                                                profile: Option<&str>)
+                                                -> Result<Option<Credential>, Error> {
+        if let Some(path) = var("OVH_CONFIG") {
+            return Credential::read(path, profile, None).map(Some);
+        }
+        for path in paths {
+            match Credential::read(path, profile, None) {
+                Ok(credential) => return Ok(Some(credential)),
+                Err(Error::Io(_, ref err)) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
         }
+        Ok(None)
+    }
+
+    /// Where the credential was loaded from.
+    pub fn source(&self) -> &CredentialSource {
+        &self.source
+    }
+
+    /// The application secret, in full: keep it out of logs and error messages.
+    pub fn expose_secret(&self) -> &str {
+        &self.application_secret
+    }
+
+    /// The service account of OAuth2 credentials.
+    pub fn oauth2(&self) -> Option<&OAuth2> {
+        self.oauth2.as_deref()
     }
 
     /// Initialize a new `Credential` from given path a App Key, App secret, Consumer token.
-    pub fn new_from_file<P: AsRef<Path>>(path: P) -> Credential {
+    /// Fails when the file cannot be read, is not valid TOML or misses a key.
+    pub fn new_from_file<P: AsRef<Path>>(path: P) -> Result<Credential, Error> {
+        Credential::read(path, None, None)
+    }
+
+    /// Like `new_from_file`, for a file sealed through `kms` (see `seal`).
+    pub fn new_from_file_with_kms<P: AsRef<Path>>(path: P,
+                                                  kms: &dyn Kms)
+                                                  -> Result<Credential, Error> {
+        Credential::read(path, None, Some(kms))
+    }
 
-        let toml = &mut String::new();
-        let (host, auth): (String, toml::Value) = match read_from_path(toml, path) {
-            Err(_) => panic!("Could not read auth"),
-            Ok(_auth) => _auth,
+    fn read<P: AsRef<Path>>(path: P,
+                            profile: Option<&str>,
+                            kms: Option<&dyn Kms>)
+                            -> Result<Credential, Error> {
+        let name = path.as_ref().display().to_string();
+        let source = CredentialSource::File(path.as_ref().to_path_buf());
+        let (endpoint, host, auth) = read_from_path(path, profile, kms)?;
+        Credential::from_section(&name, source, &endpoint, host, &auth)
+    }
+
+    /// The credential of the section of a profile; `name` tells where it comes from in errors.
+    fn from_section(name: &str,
+                    source: CredentialSource,
+                    endpoint: &str,
+                    host: String,
+                    auth: &toml::Value)
+                    -> Result<Credential, Error> {
+        let optional = |key: &str| lookup(auth, endpoint, key).ok();
+        let oauth2 = match optional("auth").as_deref() {
+            None | Some("signature") => None,
+            Some("oauth2") => {
+                let token_url = optional("token_url").unwrap_or_else(|| oauth2::token_url(&host));
+                let oauth2 = OAuth2::new(&lookup(auth, endpoint, "client_id")?,
+                                         &lookup(auth, endpoint, "client_secret")?,
+                                         &token_url);
+                Some(Arc::new(match optional("scope") {
+                    Some(scope) => oauth2.scope(&scope),
+                    None => oauth2,
+                }))
+            }
+            Some(other) => {
+                let message = format!("unknown auth {} in [{}]", other, endpoint);
+                return Err(Error::Parse(name.to_string(), message));
+            }
+        };
+        let key = |key: &str| match oauth2 {
+            Some(_) => Ok(String::new()),
+            None => lookup(auth, endpoint, key),
         };
-        let r_app_key = auth.lookup("application_key").unwrap().clone();
-        let app_key = String::from(r_app_key.as_str().unwrap().clone());
-        let r_app_secret = auth.lookup("application_secret").unwrap().clone();
-        let app_secrets = String::from(r_app_secret.as_str().unwrap().clone());
-        let r_cons_key = auth.lookup("consumer_key").unwrap().clone();
-        let cons_key = String::from(r_cons_key.as_str().unwrap().clone());
+        Ok(Credential {
+            application_key: key("application_key")?,
+            application_secret: key("application_secret")?,
+            consumer_key: optional("consumer_key").unwrap_or_default(),
+            oauth2,
+            source,
+            profile: Some(endpoint.to_string()),
+            host,
+        })
+    }
+
+    /// Initialize a new `Credential` from the secret store of the platform: the entry
+    /// of `profile` in `service`, holding the keys of a profile of the config file.
+    #[cfg(feature = "keyring")]
+    pub fn from_keyring(service: &str, profile: &str) -> Result<Credential, Error> {
+        let content = keyring_entry(service, profile)?
+            .get_password()
+            .map_err(|err| keyring_error(service, profile, err))?;
+        Credential::from_keyring_entry(service, profile, &content)
+    }
+
+    #[cfg(feature = "keyring")]
+    fn from_keyring_entry(service: &str,
+                          profile: &str,
+                          content: &str)
+                          -> Result<Credential, Error> {
+        let name = format!("keyring {}/{}", service, profile);
+        let auth = toml::Value::Table(parse_toml(&name, content)?);
+        let endpoint = auth.lookup("endpoint").and_then(toml::Value::as_str).unwrap_or(profile);
+        let host = endpoint.parse::<Endpoint>()?.host().to_string();
+        let source = CredentialSource::Keyring(service.to_string(), profile.to_string());
+        Credential::from_section(&name, source, profile, host, &auth)
+    }
+
+    /// Store the keys in the secret store of the platform, as the entry of `profile`
+    /// in `service`, replacing the previous one.
+    #[cfg(feature = "keyring")]
+    pub fn save_to_keyring(&self, service: &str, profile: &str) -> Result<(), Error> {
+        keyring_entry(service, profile)?
+            .set_password(&self.keyring_content())
+            .map_err(|err| keyring_error(service, profile, err))
+    }
+
+    /// The keys, as the section of a profile.
+    #[cfg(feature = "keyring")]
+    fn keyring_content(&self) -> String {
+        let endpoint = Endpoint::from_alias(&self.host)
+            .unwrap_or_else(|| Endpoint::Custom(self.host.clone()));
+        let mut lines = vec![toml_entry("endpoint", &endpoint.to_string())];
+        match self.oauth2 {
+            Some(ref oauth2) => {
+                lines.push(toml_entry("auth", "oauth2"));
+                lines.push(toml_entry("client_id", &oauth2.client_id));
+                lines.push(toml_entry("client_secret", &oauth2.client_secret));
+                lines.push(toml_entry("token_url", &oauth2.token_url));
+                lines.push(toml_entry("scope", &oauth2.scope));
+            }
+            None => {
+                lines.push(toml_entry("application_key", &self.application_key));
+                lines.push(toml_entry("application_secret", &self.application_secret));
+                lines.push(toml_entry("consumer_key", &self.consumer_key));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Write the keys back to the config file or the keyring entry the credential
+    /// was read from.
+    pub fn save(&self) -> Result<(), Error> {
+        match self.source {
+            CredentialSource::File(ref path) => self.save_to(path),
+            #[cfg(feature = "keyring")]
+            CredentialSource::Keyring(ref service, ref profile) => {
+                self.save_to_keyring(service, profile)
+            }
+            ref source => {
+                let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                         "the credential was not read from a config file");
+                Err(Error::Io(source.to_string(), err))
+            }
+        }
+    }
+
+    /// Write the consumer key to its profile in the config file at `path`, atomically,
+    /// along with the application keys when the profile misses them.
+    /// The other keys, sections and comments are kept; the file is created if needed,
+    /// readable by its owner only. A custom endpoint is only saved to a named profile.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let mut content = String::new();
+        match File::open(path).and_then(|mut fd| fd.read_to_string(&mut content)) {
+            Ok(_) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Error::Io(name, err)),
+        }
+        if seal::is_sealed(&content) {
+            let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                     "cannot update a sealed file, save to a plain one");
+            return Err(Error::Sealed(name, err));
+        }
+        let endpoint = Endpoint::ALL.iter()
+            .find(|endpoint| endpoint.host() == self.host)
+            .cloned()
+            .unwrap_or_else(|| Endpoint::Custom(self.host.clone()));
+        let profile = match (self.profile.as_ref(), &endpoint) {
+            (Some(profile), _) => profile.as_str(),
+            (None, &Endpoint::Custom(_)) => {
+                let err = io::Error::new(io::ErrorKind::InvalidInput,
+                                         "a custom endpoint is only saved to a named profile");
+                return Err(Error::Io(name, err));
+            }
+            (None, endpoint) => endpoint.name(),
+        };
+        if content.trim().is_empty() {
+            content = format!("[default]\nendpoint = \"{}\"\n", profile);
+        }
+        if profile != endpoint.name() {
+            // a profile named after its endpoint needs no endpoint key
+            content = set_toml_key(&content, profile, "endpoint", &endpoint.to_string(), false);
+        }
+        content = set_toml_key(&content, profile, "application_key", &self.application_key, false);
+        content = set_toml_key(&content,
+                               profile,
+                               "application_secret",
+                               &self.application_secret,
+                               false);
+        content = set_toml_key(&content, profile, "consumer_key", &self.consumer_key, true);
+        seal::write_private(path, content.as_bytes()).map_err(|err| Error::Io(name, err))
+    }
+
+    /// Initialize a new `Credential` from an INI `ovh.conf` of the Python and Go clients:
+    /// the `endpoint` of its `[default]` section and the keys of the section of that endpoint.
+    pub fn from_ovh_conf<P: AsRef<Path>>(path: P) -> Result<Credential, Error> {
+        let path = path.as_ref().to_path_buf();
+        match Credential::from_ovh_conf_files(slice::from_ref(&path))? {
+            Some(credential) => Ok(credential),
+            None => {
+                let err = io::Error::new(io::ErrorKind::NotFound, "no such file");
+                Err(Error::Io(path.display().to_string(), err))
+            }
+        }
+    }
+
+    /// Initialize a new `Credential` from the `ovh.conf` files of `ovh_conf_paths`,
+    /// the first ones overriding the keys of the others.
+    /// `None` when there is none.
+    pub fn find_ovh_conf() -> Result<Option<Credential>, Error> {
+        Credential::from_ovh_conf_files(&ovh_conf_paths())
+    }
 
-        Credential {
-            toml: Some(auth),
-            path: Some("".to_string()),
-            host: host,
-            application_key: app_key,
-            application_secret: app_secrets,
-            consumer_key: cons_key,
+    fn from_ovh_conf_files(paths: &[PathBuf]) -> Result<Option<Credential>, Error> {
+        let mut sections = IniSections::new();
+        let mut found = Vec::new();
+        for path in paths.iter().rev() {
+            let name = path.display().to_string();
+            let mut content = String::new();
+            match File::open(path).and_then(|mut fd| fd.read_to_string(&mut content)) {
+                Ok(_) => found.insert(0, path.clone()),
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::Io(name, err)),
+            }
+            parse_ini(&content, &mut sections).map_err(|message| Error::Parse(name, message))?;
+        }
+        if found.is_empty() {
+            return Ok(None);
         }
+        let endpoint = sections.get("default")
+            .and_then(|default| default.get("endpoint"))
+            .ok_or_else(|| Error::MissingKey("default.endpoint".to_string()))?;
+        let section = sections.get(endpoint)
+            .ok_or_else(|| Error::MissingKey(format!("[{}]", endpoint)))?;
+        let key = |key: &str| {
+            section.get(key)
+                .cloned()
+                .ok_or_else(|| Error::MissingKey(format!("{}.{}", endpoint, key)))
+        };
+        let mut credential = if section.contains_key("client_id") {
+            Credential::new_with_oauth2(endpoint.parse()?,
+                                        &key("client_id")?,
+                                        &key("client_secret")?)?
+        } else {
+            Credential::new_with_credential(endpoint.parse()?,
+                                            &key("application_key")?,
+                                            &key("application_secret")?,
+                                            &key("consumer_key").unwrap_or_default())?
+        };
+        credential.source = CredentialSource::OvhConf(found);
+        Ok(Some(credential))
     }
 
     /// Initialize a new `Credential` from given an App Key and App secret.
-    pub fn new_with_application(endpoint: &str,
+    pub fn new_with_application(endpoint: Endpoint,
                                 application_key: &str,
                                 application_secret: &str)
-                                -> Credential {
-        let host = endpoint2host(endpoint);
-        Credential {
-            toml: None,
-            path: None,
-            host: String::from(host),
+                                -> Result<Credential, Error> {
+        Ok(Credential {
+            source: CredentialSource::Code,
+            profile: None,
+            host: endpoint.host().to_string(),
             application_key: String::from(application_key),
             application_secret: String::from(application_secret),
             consumer_key: String::from(""),
-        }
+            oauth2: None,
+        })
     }
 
     /// Initialize a new `Credential` from given an App Key, App Secret, and Consumer Key.
-    pub fn new_with_credential(endpoint: &str,
+    pub fn new_with_credential(endpoint: Endpoint,
                                application_key: &str,
                                application_secret: &str,
                                consumer_key: &str)
-                               -> Credential {
-        let host = endpoint2host(endpoint);
-        Credential {
-            toml: None,
-            path: None,
-            host: host,
+                               -> Result<Credential, Error> {
+        Ok(Credential {
+            source: CredentialSource::Code,
+            profile: None,
+            host: endpoint.host().to_string(),
             application_key: String::from(application_key),
             application_secret: String::from(application_secret),
             consumer_key: String::from(consumer_key),
+            oauth2: None,
+        })
+    }
+
+    /// Initialize a new `Credential` authenticating with an OAuth2 service account,
+    /// against the token URL of the endpoint.
+    pub fn new_with_oauth2(endpoint: Endpoint,
+                           client_id: &str,
+                           client_secret: &str)
+                           -> Result<Credential, Error> {
+        let oauth2 = OAuth2::new(client_id, client_secret, &oauth2::token_url(endpoint.host()));
+        let mut credential = Credential::new_with_application(endpoint, "", "")?;
+        credential.oauth2 = Some(Arc::new(oauth2));
+        Ok(credential)
+    }
+
+    /// Initialize a new `Credential` from the environment variables of lego
+    /// (`OVH_ENDPOINT`, `OVH_APPLICATION_KEY`, `OVH_APPLICATION_SECRET`, `OVH_CONSUMER_KEY`)
+    /// or acme.sh (`OVH_END_POINT`, `OVH_AK`, `OVH_AS`, `OVH_CK`).
+    /// The endpoint defaults to `ovh-eu` and accepts their aliases, see `Endpoint::from_str`.
+    /// `OVH_CLIENT_ID` and `OVH_CLIENT_SECRET` select OAuth2 instead.
+    /// `None` when neither an application key nor a client id is set.
+    pub fn from_env() -> Result<Option<Credential>, Error> {
+        Credential::from_vars(env_var)
+    }
+
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Option<Credential>, Error> {
+        let first = |names: &[&str]| names.iter().filter_map(|name| var(name)).next();
+        let application_key = first(&["OVH_APPLICATION_KEY", "OVH_AK"]);
+        let client_id = var("OVH_CLIENT_ID");
+        if application_key.is_none() && client_id.is_none() {
+            return Ok(None);
         }
+        let endpoint = match first(&["OVH_ENDPOINT", "OVH_END_POINT"]) {
+            Some(alias) => alias.parse()?,
+            None => Endpoint::OvhEu,
+        };
+        let mut credential = match (client_id, application_key) {
+            (Some(client_id), _) => {
+                let client_secret = var("OVH_CLIENT_SECRET")
+                    .ok_or_else(|| Error::MissingKey("OVH_CLIENT_SECRET".to_string()))?;
+                Credential::new_with_oauth2(endpoint, &client_id, &client_secret)?
+            }
+            (None, application_key) => {
+                let application_secret = first(&["OVH_APPLICATION_SECRET", "OVH_AS"])
+                    .ok_or_else(|| Error::MissingKey("OVH_APPLICATION_SECRET".to_string()))?;
+                let consumer_key = first(&["OVH_CONSUMER_KEY", "OVH_CK"]).unwrap_or_default();
+                Credential::new_with_credential(endpoint,
+                                                &application_key.unwrap_or_default(),
+                                                &application_secret,
+                                                &consumer_key)?
+            }
+        };
+        credential.source = CredentialSource::Environment;
+        Ok(Some(credential))
     }
 }
 
-fn endpoint2host(endpoint: &str) -> String {
-    match endpoint.as_ref() {
-        "ovh-ca" => "ca.api.ovh.com".to_string(), // OVH North America
-        "ovh-eu" => "eu.api.ovh.com".to_string(), // OVH Europe
-        "ovh-us" => "us.api.ovh.com".to_string(), // OVH US
+/// API endpoints, as named in the `[default]` section of the config file.
+///
+/// Parsing one with `str::parse` fails on unknown names rather than guessing a
+/// host, so a typo in a config file is reported instead of hitting the wrong API.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Endpoint {
+    OvhCa,
+    OvhEu,
+    OvhUs,
+    SoYouStartCa,
+    SoYouStartEu,
+    KimsufiCa,
+    KimsufiEu,
+    /// Another API host, e.g. a proxy or a test server, written `https://host` in config files.
+    Custom(String),
+}
+
+impl Endpoint {
+    pub const ALL: &'static [Endpoint] = &[Endpoint::OvhCa,
+                                           Endpoint::OvhEu,
+                                           Endpoint::OvhUs,
+                                           Endpoint::SoYouStartCa,
+                                           Endpoint::SoYouStartEu,
+                                           Endpoint::KimsufiCa,
+                                           Endpoint::KimsufiEu];
+
+    /// Parse an endpoint as written by other tools: a name (`ovh-eu`, case insensitive),
+    /// a host (`eu.api.ovh.com`) or an API URL (`https://eu.api.ovh.com/1.0`).
+    pub fn from_alias(alias: &str) -> Option<Endpoint> {
+        let alias = alias.trim().to_lowercase();
+        if let Some(endpoint) = Endpoint::ALL.iter().find(|&endpoint| endpoint.name() == alias) {
+            return Some(endpoint.clone());
+        }
+        let host = alias.trim_start_matches("https://").trim_start_matches("http://");
+        let host = host.split('/').next().unwrap_or("");
+        Endpoint::ALL.iter().find(|&endpoint| endpoint.host() == host).cloned()
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Endpoint::OvhCa => "ovh-ca",
+            Endpoint::OvhEu => "ovh-eu",
+            Endpoint::OvhUs => "ovh-us",
+            Endpoint::SoYouStartCa => "soyoustart-ca",
+            Endpoint::SoYouStartEu => "soyoustart-eu",
+            Endpoint::KimsufiCa => "kimsufi-ca",
+            Endpoint::KimsufiEu => "kimsufi-eu",
+            Endpoint::Custom(ref host) => host,
+        }
+    }
+
+    pub fn host(&self) -> &str {
+        match *self {
+            Endpoint::OvhCa => "ca.api.ovh.com", // OVH North America
+            Endpoint::OvhEu => "eu.api.ovh.com", // OVH Europe
+            Endpoint::OvhUs => "us.api.ovh.com", // OVH US
 
-        "soyoustart-ca" => "ca.api.soyoustart.com".to_string(), // So you Start North America
-        "soyoustart-eu" => "eu.api.soyoustart.com".to_string(), // So you Start Europe
+            Endpoint::SoYouStartCa => "ca.api.soyoustart.com", // So you Start North America
+            Endpoint::SoYouStartEu => "eu.api.soyoustart.com", // So you Start Europe
 
-        "kimsufi-ca" => "ca.api.kimsufi.com".to_string(), // Kimsufi North America
-        "kimsufi-eu" => "eu.api.kimsufi.com".to_string(), // Kimsufi Europe
+            Endpoint::KimsufiCa => "ca.api.kimsufi.com", // Kimsufi North America
+            Endpoint::KimsufiEu => "eu.api.kimsufi.com", // Kimsufi Europe
 
-        _ => "api.ovh.com".to_string(),
+            Endpoint::Custom(ref host) => host,
+        }
     }
 }
 
+impl FromStr for Endpoint {
+    type Err = Error;
+
+    /// A name or alias of `Endpoint::from_alias`, or `https://host` for a `Custom` one.
+    fn from_str(endpoint: &str) -> Result<Endpoint, Error> {
+        if let Some(known) = Endpoint::from_alias(endpoint) {
+            return Ok(known);
+        }
+        let trimmed = endpoint.trim();
+        if let Some(url) = trimmed.strip_prefix("https://") {
+            let host = url.split('/').next().unwrap_or("");
+            if !host.is_empty() {
+                return Ok(Endpoint::Custom(host.to_string()));
+            }
+        }
+        Err(Error::UnknownEndpoint(endpoint.to_string()))
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Endpoint::Custom(ref host) => write!(f, "https://{}", host),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(service: &str, profile: &str) -> Result<keyring::Entry, Error> {
+    keyring::Entry::new(service, profile).map_err(|err| keyring_error(service, profile, err))
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_error(service: &str, profile: &str, err: keyring::Error) -> Error {
+    let kind = match err {
+        keyring::Error::NoEntry => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    Error::Io(format!("keyring {}/{}", service, profile), io::Error::new(kind, err.to_string()))
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Error when no config file of `paths` exists.
+fn not_found(paths: &[PathBuf]) -> Error {
+    let names: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    Error::Io(names.join(" or "),
+              io::Error::new(io::ErrorKind::NotFound, "no config file found"))
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use super::Credential;
+    use super::{search_paths, Credential, CredentialSource, Endpoint};
+    use std::path::PathBuf;
+    use error::Error;
+    use testutil;
+    use std::fs::{self, File};
+    use std::io::{self, Write};
+    use std::slice;
+
+    #[test]
+    fn test_from_vars() {
+        let mut vars = HashMap::new();
+        assert!(Credential::from_vars(|name| vars.get(name).cloned()).unwrap().is_none());
+        vars.insert("OVH_AK", "ak".to_string());
+        assert!(Credential::from_vars(|name| vars.get(name).cloned()).is_err());
+        vars.insert("OVH_AS", "as".to_string());
+        vars.insert("OVH_CK", "ck".to_string());
+        vars.insert("OVH_END_POINT", "https://ca.api.ovh.com/1.0".to_string());
+        let cred = Credential::from_vars(|name| vars.get(name).cloned()).unwrap().unwrap();
+        assert_eq!(("ak", "as", "ck", "ca.api.ovh.com"),
+                   (&cred.application_key[..],
+                    &cred.application_secret[..],
+                    &cred.consumer_key[..],
+                    &cred.host[..]));
+        vars.insert("OVH_ENDPOINT", "Kimsufi-EU".to_string());
+        let cred = Credential::from_vars(|name| vars.get(name).cloned()).unwrap().unwrap();
+        assert_eq!("eu.api.kimsufi.com", cred.host);
+        vars.insert("OVH_ENDPOINT", "runabove-ca".to_string());
+        assert!(Credential::from_vars(|name| vars.get(name).cloned()).is_err());
+
+        vars.insert("OVH_ENDPOINT", "ovh-us".to_string());
+        vars.insert("OVH_CLIENT_ID", "id".to_string());
+        assert!(Credential::from_vars(|name| vars.get(name).cloned()).is_err());
+        vars.insert("OVH_CLIENT_SECRET", "secret".to_string());
+        let cred = Credential::from_vars(|name| vars.get(name).cloned()).unwrap().unwrap();
+        let oauth2 = cred.oauth2().unwrap();
+        assert_eq!(("id", "https://us.ovhcloud.com/auth/oauth2/token"),
+                   (&oauth2.client_id[..], &oauth2.token_url[..]));
+    }
+
+    #[test]
+    fn test_discover() {
+        let mut vars = HashMap::new();
+        let missing = PathBuf::from("ovh-rs-test-missing-config.toml");
+        let paths = vec![missing.clone(), PathBuf::from("Config.toml.dist")];
+        let cred = Credential::discover(&paths, |name| vars.get(name).cloned(), &[]).unwrap();
+        assert_eq!(&CredentialSource::File(PathBuf::from("Config.toml.dist")), cred.source());
+
+        // a config file wins over the environment
+        vars.insert("OVH_APPLICATION_KEY", "env-ak".to_string());
+        vars.insert("OVH_APPLICATION_SECRET", "env-as".to_string());
+        let cred = Credential::discover(&paths, |name| vars.get(name).cloned(), &[]).unwrap();
+        assert_eq!(&CredentialSource::File(PathBuf::from("Config.toml.dist")), cred.source());
+        assert!(cred.application_key != "env-ak");
+        vars.clear();
+
+        let paths = vec![missing.clone()];
+        match Credential::discover(&paths, |name| vars.get(name).cloned(), &[]) {
+            Err(Error::Io(ref name, _)) => assert_eq!("ovh-rs-test-missing-config.toml", name),
+            other => panic!("unexpected {:?}", other),
+        }
+        vars.insert("OVH_APPLICATION_KEY", "ak".to_string());
+        vars.insert("OVH_APPLICATION_SECRET", "as".to_string());
+        let cred = Credential::discover(&paths, |name| vars.get(name).cloned(), &[]).unwrap();
+        assert_eq!(("ak", "", "eu.api.ovh.com"),
+                   (&cred.application_key[..], &cred.consumer_key[..], &cred.host[..]));
+        assert_eq!(&CredentialSource::Environment, cred.source());
+
+        // an explicit $OVH_CONFIG must exist
+        vars.insert("OVH_CONFIG", "ovh-rs-test-missing-config.toml".to_string());
+        assert!(Credential::discover(&paths, |name| vars.get(name).cloned(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_search_paths() {
+        let home = Some(PathBuf::from("/home/me"));
+        let paths = search_paths(None, home.clone());
+        assert_eq!(vec![PathBuf::from("Config.toml"),
+                        PathBuf::from("/home/me/.config/ovh/config.toml"),
+                        PathBuf::from("/home/me/.ovh.toml"),
+                        PathBuf::from("/etc/ovh.toml")],
+                   paths);
+        let paths = search_paths(Some("/xdg".into()), home);
+        assert_eq!(PathBuf::from("/xdg/ovh/config.toml"), paths[1]);
+        // an empty XDG_CONFIG_HOME is ignored, as the spec says
+        assert_eq!(search_paths(None, None), search_paths(Some("".into()), None));
+        assert_eq!(vec![PathBuf::from("Config.toml"), PathBuf::from("/etc/ovh.toml")],
+                   search_paths(None, None));
+    }
+
+    #[test]
+    fn test_search_order() {
+        let first = testutil::temp_path("ovh-rs-test-first.toml");
+        let second = testutil::temp_path("ovh-rs-test-second.toml");
+        for (path, key) in &[(&first, "first"), (&second, "second")] {
+            File::create(path)
+                .and_then(|mut fd| {
+                    write!(fd,
+                           "[default]\nendpoint = \"ovh-eu\"\n[ovh-eu]\n\
+                            application_key = \"{}\"\napplication_secret = \"as\"\n",
+                           key)
+                })
+                .unwrap();
+        }
+        let missing = PathBuf::from("ovh-rs-test-missing-config.toml");
+        let no_vars = |_: &str| None;
+        let paths = vec![missing, second.clone(), first.clone()];
+        let cred = Credential::discover(&paths, no_vars, &[]).unwrap();
+        assert_eq!("second", cred.application_key);
+        assert_eq!(&CredentialSource::File(second.clone()), cred.source());
+        assert_eq!(second.display().to_string(), cred.source().to_string());
+
+        // $OVH_CONFIG comes before the others
+        let first_name = first.display().to_string();
+        let cred = Credential::discover(&paths,
+                                        |name| match name {
+                                            "OVH_CONFIG" => Some(first_name.clone()),
+                                            _ => None,
+                                        },
+                                        &[])
+            .unwrap();
+        assert_eq!("first", cred.application_key);
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn test_profiles() {
+        let path = testutil::temp_path("ovh-rs-test-profiles.toml");
+        File::create(&path)
+            .and_then(|mut fd| {
+                fd.write_all(b"[default]\nendpoint = \"ovh-eu\"\n\
+                               [ovh-eu]\napplication_key = \"ak\"\n\
+                               application_secret = \"as\"\nconsumer_key = \"ck\"\n\
+                               [staging]\nendpoint = \"ovh-ca\"\napplication_key = \"sak\"\n\
+                               application_secret = \"sas\"\nconsumer_key = \"sck\"\n\
+                               [robot]\nendpoint = \"ovh-eu\"\nauth = \"oauth2\"\n\
+                               client_id = \"id\"\nclient_secret = \"secret\"\n\
+                               scope = \"account/all\"\n\
+                               [typo]\nendpoint = \"ovh-eu\"\nauth = \"oauth\"\n")
+            })
+            .unwrap();
+        let cred = Credential::from_profile_file(&path, "staging").unwrap();
+        assert_eq!(("sak", "ca.api.ovh.com"), (&cred.application_key[..], &cred.host[..]));
+        let cred = Credential::from_profile_file(&path, "ovh-eu").unwrap();
+        assert_eq!(("ak", "eu.api.ovh.com"), (&cred.application_key[..], &cred.host[..]));
+        assert!(Credential::from_profile_file(&path, "prod").is_err());
+        let cred = Credential::from_profile_file(&path, "robot").unwrap();
+        let oauth2 = cred.oauth2().unwrap();
+        assert_eq!(("id", "account/all"), (&oauth2.client_id[..], &oauth2.scope[..]));
+        assert!(Credential::from_profile_file(&path, "staging").unwrap().oauth2().is_none());
+        assert!(Credential::from_profile_file(&path, "typo").is_err());
+
+        let mut vars = HashMap::new();
+        vars.insert("OVH_PROFILE", "staging".to_string());
+        let cred = Credential::discover(slice::from_ref(&path), |name| vars.get(name).cloned(), &[])
+            .unwrap();
+        assert_eq!("sck", cred.consumer_key);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ovh_conf() {
+        let local = testutil::temp_path("ovh-rs-test-ovh.conf");
+        let global = testutil::temp_path("ovh-rs-test-etc.conf");
+        File::create(&global)
+            .and_then(|mut fd| {
+                fd.write_all(b"[default]\nendpoint=ovh-eu\n\n[ovh-eu]\napplication_key=ak\n\
+                               application_secret=as\nconsumer_key=global\n")
+            })
+            .unwrap();
+        File::create(&local)
+            .and_then(|mut fd| fd.write_all(b"; local keys\n[ovh-eu]\nconsumer_key: local\n"))
+            .unwrap();
+        let missing = testutil::temp_path("ovh-rs-test-missing.conf");
+        let paths = vec![local.clone(), missing, global.clone()];
+        let cred = Credential::from_ovh_conf_files(&paths).unwrap().unwrap();
+        assert_eq!(("ak", "as", "local", "eu.api.ovh.com"),
+                   (&cred.application_key[..],
+                    &cred.application_secret[..],
+                    &cred.consumer_key[..],
+                    &cred.host[..]));
+        assert!(Credential::from_ovh_conf(&local).is_err());
+        fs::remove_file(&local).unwrap();
+        fs::remove_file(&global).unwrap();
+        assert!(Credential::from_ovh_conf_files(&paths).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_set_toml_key() {
+        let content = "# keys\n[default]\nendpoint = \"ovh-eu\"\n\n\
+                       [ovh-eu]\napplication_key = \"ak\"\nconsumer_key = \"old\"\n\n\
+                       [staging]\napplication_key = \"sak\"\n";
+        assert_eq!("# keys\n[default]\nendpoint = \"ovh-eu\"\n\n\
+                    [ovh-eu]\napplication_key = \"ak\"\nconsumer_key = \"new\"\n\n\
+                    [staging]\napplication_key = \"sak\"\n",
+                   super::set_toml_key(content, "ovh-eu", "consumer_key", "new", true));
+        assert_eq!(content.to_string(),
+                   super::set_toml_key(content, "ovh-eu", "application_key", "other", false));
+        assert_eq!("# keys\n[default]\nendpoint = \"ovh-eu\"\n\n\
+                    [ovh-eu]\napplication_key = \"ak\"\nconsumer_key = \"old\"\n\n\
+                    [staging]\napplication_key = \"sak\"\nconsumer_key = \"a\\\"b\"\n",
+                   super::set_toml_key(content, "staging", "consumer_key", "a\"b", true));
+    }
+
+    #[test]
+    fn test_save() {
+        let path = testutil::temp_path("ovh-rs-test-save.toml");
+        File::create(&path)
+            .and_then(|mut fd| {
+                fd.write_all(b"[default]\nendpoint = \"ovh-eu\"\n\n[ovh-eu]\n\
+                               application_key = \"ak\"\napplication_secret = \"as\"\n")
+            })
+            .unwrap();
+        let mut cred = Credential::new_from_file(&path).unwrap();
+        assert_eq!("", cred.consumer_key);
+        cred.consumer_key = "validated".to_string();
+        cred.save().unwrap();
+        assert_eq!("validated", Credential::new_from_file(&path).unwrap().consumer_key);
+        fs::remove_file(&path).unwrap();
+
+        let cred = Credential::new_with_credential(Endpoint::OvhEu, "ak", "as", "ck").unwrap();
+        assert!(cred.save().is_err());
+    }
+
+    #[test]
+    fn test_save_custom() {
+        let path = testutil::temp_path("ovh-rs-test-save-custom.toml");
+        let copy = testutil::temp_path("ovh-rs-test-save-custom-copy.toml");
+        let _ = fs::remove_file(&copy);
+        let custom = "https://api.example.net".parse().unwrap();
+        let cred = Credential::new_with_credential(custom, "ak", "as", "ck").unwrap();
+        assert!(cred.save_to(&copy).is_err());
+        assert!(!copy.exists());
+
+        File::create(&path)
+            .and_then(|mut fd| {
+                fd.write_all(b"[lab]\nendpoint = \"https://api.example.net\"\n\
+                               application_key = \"ak\"\napplication_secret = \"as\"\n")
+            })
+            .unwrap();
+        let mut cred = Credential::from_profile_file(&path, "lab").unwrap();
+        cred.consumer_key = "ck".to_string();
+        cred.save_to(&copy).unwrap();
+        let read = Credential::new_from_file(&copy).unwrap();
+        assert_eq!(("api.example.net", "ck"), (&read.host[..], &read.consumer_key[..]));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(0o600, fs::metadata(&copy).unwrap().permissions().mode() & 0o777);
+            fs::set_permissions(&copy, fs::Permissions::from_mode(0o640)).unwrap();
+            read.save_to(&copy).unwrap();
+            assert_eq!(0o640, fs::metadata(&copy).unwrap().permissions().mode() & 0o777);
+        }
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&copy).unwrap();
+    }
 
     #[test]
     fn test_application_key() {
-        let cred = Credential::new_from_file("Config.toml.dist");
+        let cred = Credential::new_from_file("Config.toml.dist").unwrap();
         let res = cred.application_key;
         assert_eq!("ak", res);
     }
 
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_keyring_entry() {
+        let cred = Credential::new_with_credential("https://api.example.net".parse().unwrap(),
+                                                   "ak",
+                                                   "a\"s",
+                                                   "ck")
+            .unwrap();
+        let content = cred.keyring_content();
+        assert_eq!("endpoint = \"https://api.example.net\"\napplication_key = \"ak\"\n\
+                    application_secret = \"a\\\"s\"\nconsumer_key = \"ck\"\n",
+                   content);
+        let read = Credential::from_keyring_entry("ovh", "prod", &content).unwrap();
+        assert_eq!(("api.example.net", "a\"s"), (&read.host[..], read.expose_secret()));
+        assert_eq!(&CredentialSource::Keyring("ovh".to_string(), "prod".to_string()),
+                   read.source());
+
+        let robot = Credential::new_with_oauth2(Endpoint::OvhEu, "id", "secret").unwrap();
+        let read = Credential::from_keyring_entry("ovh", "robot", &robot.keyring_content())
+            .unwrap();
+        assert_eq!("secret", read.oauth2().unwrap().client_secret);
+    }
+
+    #[test]
+    fn test_debug_masks_secrets() {
+        let cred = Credential::new_with_credential(Endpoint::OvhEu,
+                                                   "ak",
+                                                   "secret-of-the-app",
+                                                   "ck")
+            .unwrap();
+        let debug = format!("{:?}", cred);
+        assert!(debug.contains("application_secret: \"se***\""), "{}", debug);
+        assert!(debug.contains("consumer_key: \"***\""), "{}", debug);
+        assert!(!debug.contains("secret-of-the-app"));
+        assert_eq!("secret-of-the-app", cred.expose_secret());
+    }
+
     #[test]
     fn test_application_secret() {
-        let cred = Credential::new_from_file("Config.toml.dist");
+        let cred = Credential::new_from_file("Config.toml.dist").unwrap();
         let res = cred.application_secret;
         assert_eq!("as", res);
     }
 
     #[test]
     fn test_consumer_key() {
-        let cred = Credential::new_from_file("Config.toml.dist");
+        let cred = Credential::new_from_file("Config.toml.dist").unwrap();
         let res = cred.consumer_key;
         assert_eq!("ck", res);
     }
 
     #[test]
     fn test_host() {
-        let cred = Credential::new_from_file("Config.toml.dist");
+        let cred = Credential::new_from_file("Config.toml.dist").unwrap();
         let res = cred.host;
         assert_eq!("eu.api.ovh.com", res);
     }
 
     #[test]
-    fn test_endpoint2host() {
+    fn test_endpoint_from_str() {
 
         let mut test_hosts: HashMap<&str, String> = HashMap::new();
         test_hosts.insert("ovh-ca", "ca.api.ovh.com".to_string());
         test_hosts.insert("ovh-eu", "eu.api.ovh.com".to_string());
         test_hosts.insert("ovh-us", "us.api.ovh.com".to_string());
-        
+
         test_hosts.insert("soyoustart-ca", "ca.api.soyoustart.com".to_string());
         test_hosts.insert("soyoustart-eu", "eu.api.soyoustart.com".to_string());
 
         test_hosts.insert("kimsufi-ca", "ca.api.kimsufi.com".to_string());
         test_hosts.insert("kimsufi-eu", "eu.api.kimsufi.com".to_string());
 
-        test_hosts.insert("idontexist-nw", "api.ovh.com".to_string());
-
-        
         for (endpoint, expected_host) in test_hosts {
-            let cred = Credential::new_with_application(endpoint, "test", "test");
+            let parsed: Endpoint = endpoint.parse().unwrap();
+            let cred = Credential::new_with_application(parsed.clone(), "test", "test").unwrap();
             assert_eq!(expected_host.to_string(), cred.host);
+            assert_eq!(endpoint, parsed.to_string());
+        }
+
+        match "idontexist-nw".parse::<Endpoint>() {
+            Err(Error::UnknownEndpoint(name)) => assert_eq!("idontexist-nw", name),
+            other => panic!("unexpected {:?}", other),
+        }
+        let custom: Endpoint = "https://api.example.test/1.0".parse().unwrap();
+        assert_eq!(Endpoint::Custom("api.example.test".to_string()), custom);
+        assert_eq!("https://api.example.test", custom.to_string());
+        assert_eq!(custom, custom.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn test_unknown_endpoint() {
+        // a typo used to fall back to api.ovh.com
+        let path = testutil::temp_path("ovh-rs-test-endpoint.toml");
+        File::create(&path)
+            .and_then(|mut fd| {
+                fd.write_all(b"[default]\nendpoint = \"ovh-ue\"\n[ovh-ue]\n\
+                               application_key = \"ak\"\napplication_secret = \"as\"\n\
+                               [lab]\nendpoint = \"https://api.example.test/1.0\"\n\
+                               application_key = \"ak\"\napplication_secret = \"as\"\n\
+                               [plain]\nendpoint = \"http://api.example.test\"\n\
+                               application_key = \"ak\"\napplication_secret = \"as\"\n")
+            })
+            .unwrap();
+        match Credential::new_from_file(&path) {
+            Err(Error::UnknownEndpoint(name)) => assert_eq!("ovh-ue", name),
+            other => panic!("unexpected {:?}", other),
         }
+        let lab = Credential::from_profile_file(&path, "lab").unwrap();
+        assert_eq!("api.example.test", lab.host);
+        // only https custom endpoints
+        assert!(Credential::from_profile_file(&path, "plain").is_err());
+        fs::remove_file(&path).unwrap();
 
+        let err = io::Error::from("ovh-mars".parse::<Endpoint>().unwrap_err());
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!("unknown OVH endpoint ovh-mars", err.to_string());
+        assert!("".parse::<Endpoint>().is_err());
+        assert!("https://".parse::<Endpoint>().is_err());
+    }
 
+    #[test]
+    fn test_endpoint_names() {
+        for endpoint in Endpoint::ALL {
+            assert_eq!(Some(endpoint.clone()), Endpoint::from_alias(endpoint.name()));
+            assert_eq!(Some(endpoint.clone()), Endpoint::from_alias(endpoint.host()));
+            assert_eq!(endpoint, &endpoint.name().parse::<Endpoint>().unwrap());
+        }
+        assert_eq!(Endpoint::SoYouStartEu, "soyoustart-eu".parse::<Endpoint>().unwrap());
+        assert_eq!(Some(Endpoint::OvhEu), Endpoint::from_alias(" OVH-EU "));
+        assert_eq!(Some(Endpoint::OvhUs), Endpoint::from_alias("https://us.api.ovh.com/1.0"));
+        assert_eq!(None, Endpoint::from_alias("api.example.test"));
     }
 
 }
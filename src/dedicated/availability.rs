@@ -0,0 +1,95 @@
+//! # Availability
+//!
+//! Stock of dedicated servers per datacenter
+//! (`/dedicated/server/datacenter/availabilities`), for OVH, So you Start and Kimsufi.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// Delivery estimate of a server in a datacenter.
+#[derive(Debug,Clone,PartialEq)]
+pub struct DatacenterAvailability {
+    pub datacenter: String,
+    /// Delivery estimate such as `1H-low`, `72H`, or `unavailable`.
+    pub availability: String,
+}
+
+impl DatacenterAvailability {
+    pub fn is_available(&self) -> bool {
+        self.availability != "unavailable" && self.availability != "unknown"
+    }
+}
+
+/// Stock of a server configuration.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ServerAvailability {
+    /// Fully qualified name: plan code, memory and storage options.
+    pub fqn: String,
+    pub plan_code: String,
+    pub server: String,
+    pub memory: Option<String>,
+    pub storage: Option<String>,
+    pub datacenters: Vec<DatacenterAvailability>,
+}
+
+impl Model for ServerAvailability {
+    const NAME: &'static str = "ServerAvailability";
+    const FIELDS: &'static [&'static str] = &["fqn", "planCode", "server", "memory", "storage",
+                                              "systemStorage", "gpu", "datacenters"];
+
+    fn from_json(value: &Value) -> io::Result<ServerAvailability> {
+        let mut datacenters = Vec::new();
+        if let Some(items) = value.get("datacenters").and_then(Value::as_array) {
+            for item in items {
+                datacenters.push(DatacenterAvailability {
+                    datacenter: rest::str_field(item, "datacenter")?,
+                    availability: rest::str_field(item, "availability")?,
+                });
+            }
+        }
+        Ok(ServerAvailability {
+            fqn: rest::opt_str_field(value, "fqn").unwrap_or_default(),
+            plan_code: rest::str_field(value, "planCode")?,
+            server: rest::opt_str_field(value, "server").unwrap_or_default(),
+            memory: rest::opt_str_field(value, "memory"),
+            storage: rest::opt_str_field(value, "storage"),
+            datacenters,
+        })
+    }
+}
+
+/// Stock of every configuration, or of a single plan code.
+pub fn availabilities(client: &OVHClient, plan_code: Option<&str>) -> io::Result<Vec<ServerAvailability>> {
+    let mut query = "/dedicated/server/datacenter/availabilities".to_string();
+    if let Some(plan_code) = plan_code {
+        query = query + "?planCode=" + &rest::escape(plan_code);
+    }
+    rest::decode_list(client, &rest::get(client, &query)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServerAvailability;
+    use rest::Model;
+
+    #[test]
+    fn test_availability_from_json() {
+        let value = json!({
+            "fqn": "24sk10.ram-16g.softraid-2x2000sa",
+            "planCode": "24sk10",
+            "server": "ks-1",
+            "datacenters": [
+                { "datacenter": "gra", "availability": "unavailable" },
+                { "datacenter": "rbx", "availability": "72H" },
+            ],
+        });
+        let res = ServerAvailability::from_json(&value).unwrap();
+        assert!(!res.datacenters[0].is_available());
+        assert!(res.datacenters[1].is_available());
+    }
+}
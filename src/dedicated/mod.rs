@@ -0,0 +1,94 @@
+//! # Dedicated
+//!
+//! Typed access to dedicated servers (`/dedicated/server`).
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+pub mod availability;
+pub mod network;
+pub mod vmac;
+
+/// A dedicated server.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Server {
+    pub name: String,
+    pub reverse: Option<String>,
+    pub ip: Option<String>,
+    pub datacenter: Option<String>,
+    pub os: Option<String>,
+    pub state: Option<String>,
+    pub rescue_mail: Option<String>,
+    /// Public key injected when the server boots in rescue mode.
+    pub rescue_ssh_key: Option<String>,
+}
+
+impl Model for Server {
+    const NAME: &'static str = "Server";
+    const FIELDS: &'static [&'static str] = &["serverId", "name", "reverse", "ip", "datacenter",
+                                              "os", "state", "rescueMail", "rescueSshKey", "bootId",
+                                              "commercialRange", "linkSpeed", "monitoring",
+                                              "noIntervention", "professionalUse", "rack",
+                                              "rootDevice", "supportLevel", "newUpgradeSystem",
+                                              "powerState", "availabilityZone", "region", "iam"];
+
+    fn from_json(value: &Value) -> io::Result<Server> {
+        Ok(Server {
+            name: rest::str_field(value, "name")?,
+            reverse: rest::opt_str_field(value, "reverse"),
+            ip: rest::opt_str_field(value, "ip"),
+            datacenter: rest::opt_str_field(value, "datacenter"),
+            os: rest::opt_str_field(value, "os"),
+            state: rest::opt_str_field(value, "state"),
+            rescue_mail: rest::opt_str_field(value, "rescueMail"),
+            rescue_ssh_key: rest::opt_str_field(value, "rescueSshKey")
+                .and_then(|key| if key.is_empty() { None } else { Some(key) }),
+        })
+    }
+}
+
+/// Base route of a server.
+pub fn server_path(name: &str) -> String {
+    format!("/dedicated/server/{}", rest::escape(name))
+}
+
+/// List the names of the dedicated servers of the account.
+pub fn server_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/dedicated/server")?)
+}
+
+pub fn server(client: &OVHClient, name: &str) -> io::Result<Server> {
+    rest::decode(client, &rest::get(client, &server_path(name))?)
+}
+
+/// Set the public key used in rescue mode, an empty key removes it.
+pub fn set_rescue_ssh_key(client: &OVHClient, name: &str, public_key: &str) -> io::Result<()> {
+    rest::put(client,
+              &server_path(name),
+              &json!({ "rescueSshKey": public_key }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Server;
+    use rest::Model;
+
+    #[test]
+    fn test_server_from_json() {
+        let value = json!({
+            "name": "ns3001234.ip-192-0-2.eu",
+            "datacenter": "rbx8",
+            "state": "ok",
+            "rescueSshKey": "",
+        });
+        let server = Server::from_json(&value).unwrap();
+        assert_eq!(Some("rbx8".to_string()), server.datacenter);
+        assert_eq!(None, server.rescue_ssh_key);
+    }
+}
@@ -0,0 +1,210 @@
+//! # Network
+//!
+//! Host configuration of the additional IPs of a dedicated server.
+//!
+//! `network_config` gathers the IPs routed to a server, the virtual MACs
+//! they are assigned to and the gateway; the configuration is then rendered
+//! as netplan or systemd-networkd snippets:
+//!
+//! * IPs without virtual MAC are aliases of the host interface;
+//! * IPs with a virtual MAC belong to a virtual machine, which routes
+//!   through the gateway of the host.
+//!
+//! Only IPv4 is covered: IPv6 is configured from the block of the server.
+//!
+
+use client::OVHClient;
+use dedicated::{self, server_path};
+use dedicated::vmac::{self, VirtualMac};
+use rest;
+
+use std::io;
+use std::net::Ipv4Addr;
+
+use serde_json::Value;
+
+/// Resolver of OVH, for virtual machines.
+pub const OVH_RESOLVER: &str = "213.186.33.99";
+
+/// IPv4 routing of a server.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NetworkConfig {
+    pub server: String,
+    pub main_ip: Option<String>,
+    pub gateway: Option<String>,
+    /// Additional IPs to alias on the host interface.
+    pub host_aliases: Vec<String>,
+    pub virtual_macs: Vec<VirtualMac>,
+}
+
+/// Addresses of an IPv4 block (`198.51.100.8/30`), up to a /24.
+fn expand_block(block: &str) -> io::Result<Vec<String>> {
+    let invalid = || rest::invalid_data(&format!("bad IP block {}", block));
+    let mut parts = block.splitn(2, '/');
+    let address: Ipv4Addr = parts.next().unwrap_or("").parse().map_err(|_| invalid())?;
+    let prefix: u32 = parts.next().unwrap_or("32").parse().map_err(|_| invalid())?;
+    if !(24..=32).contains(&prefix) {
+        return Err(invalid());
+    }
+    let first = u32::from(address);
+    Ok((0..(1u32 << (32 - prefix)))
+        .map(|offset| Ipv4Addr::from(first + offset).to_string())
+        .collect())
+}
+
+/// Gather the IPv4 configuration of a server.
+pub fn network_config(client: &OVHClient, server: &str) -> io::Result<NetworkConfig> {
+    let base = server_path(server);
+    let main_ip = dedicated::server(client, server)?.ip;
+    let routing = rest::get(client, &format!("{}/specifications/network", base))?;
+    let gateway = routing.pointer("/routing/ipv4/gateway")
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    let virtual_macs = vmac::virtual_macs(client, server)?;
+
+    let mut host_aliases = Vec::new();
+    for block in rest::string_list(&rest::get(client, &format!("{}/ips", base))?)? {
+        if block.contains(':') {
+            continue;
+        }
+        for ip in expand_block(&block)? {
+            if Some(&ip) != main_ip.as_ref() && vmac::mac_of(&virtual_macs, &ip).is_none() {
+                host_aliases.push(ip);
+            }
+        }
+    }
+    Ok(NetworkConfig {
+        server: server.to_string(),
+        main_ip,
+        gateway,
+        host_aliases,
+        virtual_macs,
+    })
+}
+
+impl NetworkConfig {
+    /// Netplan snippet adding the aliases to `interface`,
+    /// e.g. `/etc/netplan/60-ovh-additional-ips.yaml`.
+    pub fn host_netplan(&self, interface: &str) -> String {
+        let mut out = format!("network:\n  version: 2\n  ethernets:\n    {}:\n",
+                              interface);
+        out.push_str("      addresses:\n");
+        for ip in &self.host_aliases {
+            out.push_str(&format!("        - {}/32\n", ip));
+        }
+        out
+    }
+
+    /// systemd-networkd drop-in adding the aliases to the network of the host interface,
+    /// e.g. `/etc/systemd/network/10-eno1.network.d/ovh-additional-ips.conf`.
+    pub fn host_networkd(&self) -> String {
+        let mut out = "[Network]\n".to_string();
+        for ip in &self.host_aliases {
+            out.push_str(&format!("Address={}/32\n", ip));
+        }
+        out
+    }
+
+    fn virtual_mac(&self, mac: &str) -> io::Result<&VirtualMac> {
+        self.virtual_macs
+            .iter()
+            .find(|vmac| vmac.mac.eq_ignore_ascii_case(mac))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound,
+                               format!("no virtual MAC {} on {}", mac, self.server))
+            })
+    }
+
+    fn vm_gateway(&self) -> io::Result<&str> {
+        self.gateway.as_deref()
+            .ok_or_else(|| rest::invalid_data(&format!("no IPv4 gateway for {}", self.server)))
+    }
+
+    /// Netplan configuration of the virtual machine owning a virtual MAC.
+    pub fn vm_netplan(&self, mac: &str, interface: &str) -> io::Result<String> {
+        let vmac = self.virtual_mac(mac)?;
+        let mut lines = vec!["network:".to_string(),
+                             "  version: 2".to_string(),
+                             "  ethernets:".to_string(),
+                             format!("    {}:", interface),
+                             "      match:".to_string(),
+                             format!("        macaddress: \"{}\"", vmac.mac),
+                             "      addresses:".to_string()];
+        for address in &vmac.addresses {
+            lines.push(format!("        - {}/32", address.ip));
+        }
+        lines.push("      routes:".to_string());
+        lines.push("        - to: 0.0.0.0/0".to_string());
+        lines.push(format!("          via: {}", self.vm_gateway()?));
+        lines.push("          on-link: true".to_string());
+        lines.push("      nameservers:".to_string());
+        lines.push(format!("        addresses: [{}]", OVH_RESOLVER));
+        Ok(lines.join("\n") + "\n")
+    }
+
+    /// systemd-networkd configuration of the virtual machine owning a virtual MAC.
+    pub fn vm_networkd(&self, mac: &str) -> io::Result<String> {
+        let vmac = self.virtual_mac(mac)?;
+        let mut lines = vec!["[Match]".to_string(),
+                             format!("MACAddress={}", vmac.mac),
+                             String::new(),
+                             "[Network]".to_string(),
+                             format!("DNS={}", OVH_RESOLVER)];
+        for address in &vmac.addresses {
+            lines.push(format!("Address={}/32", address.ip));
+        }
+        lines.push(String::new());
+        lines.push("[Route]".to_string());
+        lines.push("Destination=0.0.0.0/0".to_string());
+        lines.push(format!("Gateway={}", self.vm_gateway()?));
+        lines.push("GatewayOnLink=yes".to_string());
+        Ok(lines.join("\n") + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_block, NetworkConfig};
+    use dedicated::vmac::{VirtualAddress, VirtualMac};
+
+    fn config() -> NetworkConfig {
+        NetworkConfig {
+            server: "ns1.example.net".to_string(),
+            main_ip: Some("51.75.0.10".to_string()),
+            gateway: Some("51.75.0.254".to_string()),
+            host_aliases: vec!["198.51.100.4".to_string()],
+            virtual_macs: vec![VirtualMac {
+                                   mac: "02:00:00:aa:bb:cc".to_string(),
+                                   mac_type: "ovh".to_string(),
+                                   addresses: vec![VirtualAddress {
+                                                       ip: "198.51.100.5".to_string(),
+                                                       vm_name: Some("web".to_string()),
+                                                   }],
+                               }],
+        }
+    }
+
+    #[test]
+    fn test_expand_block() {
+        assert_eq!(vec!["198.51.100.4".to_string()], expand_block("198.51.100.4/32").unwrap());
+        assert_eq!(4, expand_block("198.51.100.8/30").unwrap().len());
+        assert!(expand_block("198.51.0.0/16").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let config = config();
+        assert_eq!("network:\n  version: 2\n  ethernets:\n    eno1:\n      addresses:\n        \
+                    - 198.51.100.4/32\n",
+                   config.host_netplan("eno1"));
+        assert_eq!("[Network]\nAddress=198.51.100.4/32\n", config.host_networkd());
+        let vm = config.vm_networkd("02:00:00:AA:BB:CC").unwrap();
+        assert!(vm.contains("Address=198.51.100.5/32\n"));
+        assert!(vm.contains("Gateway=51.75.0.254\nGatewayOnLink=yes\n"));
+        let vm = config.vm_netplan("02:00:00:aa:bb:cc", "eth0").unwrap();
+        assert!(vm.contains("        - 198.51.100.5/32\n      routes:\n"));
+        assert!(vm.contains("via: 51.75.0.254"));
+        assert!(config.vm_netplan("02:00:00:00:00:00", "eth0").is_err());
+    }
+}
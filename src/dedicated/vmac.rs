@@ -0,0 +1,159 @@
+//! # Virtual MACs
+//!
+//! Virtual MACs of a dedicated server (`/dedicated/server/{name}/virtualMac`):
+//! additional IPs routed to a virtual machine get a MAC generated by OVH,
+//! to be set on the network interface of the VM (Proxmox, VMware ESXi, ...).
+//!
+//! Changes run as dedicated server tasks; follow them with
+//! `task::TaskHandle::new(TaskFamily::DedicatedServer, server, task.id)`.
+//!
+
+use client::OVHClient;
+use dedicated::server_path;
+use domain::Task;
+use rest;
+
+use std::io;
+
+/// A virtual MAC and its IPs.
+#[derive(Debug,Clone,PartialEq)]
+pub struct VirtualMac {
+    pub mac: String,
+    /// `ovh` for most hypervisors, `vmware` for ESXi.
+    pub mac_type: String,
+    pub addresses: Vec<VirtualAddress>,
+}
+
+/// An IP assigned to a virtual MAC.
+#[derive(Debug,Clone,PartialEq)]
+pub struct VirtualAddress {
+    pub ip: String,
+    pub vm_name: Option<String>,
+}
+
+/// Kind of MAC to generate.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum MacType {
+    Ovh,
+    Vmware,
+}
+
+impl MacType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MacType::Ovh => "ovh",
+            MacType::Vmware => "vmware",
+        }
+    }
+}
+
+fn vmac_path(server: &str) -> String {
+    format!("{}/virtualMac", server_path(server))
+}
+
+fn address_path(server: &str, mac: &str) -> String {
+    format!("{}/{}/virtualAddress", vmac_path(server), rest::escape(mac))
+}
+
+pub fn mac_addresses(client: &OVHClient, server: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &vmac_path(server))?)
+}
+
+/// A virtual MAC with the details of its IPs.
+pub fn virtual_mac(client: &OVHClient, server: &str, mac: &str) -> io::Result<VirtualMac> {
+    let value = rest::get(client, &format!("{}/{}", vmac_path(server), rest::escape(mac)))?;
+    let mut addresses = Vec::new();
+    for ip in rest::string_list(&rest::get(client, &address_path(server, mac))?)? {
+        let address = rest::get(client,
+                                &format!("{}/{}", address_path(server, mac), rest::escape(&ip)))?;
+        addresses.push(VirtualAddress {
+            vm_name: rest::opt_str_field(&address, "virtualMachineName"),
+            ip,
+        });
+    }
+    Ok(VirtualMac {
+        mac: rest::opt_str_field(&value, "macAddress").unwrap_or_else(|| mac.to_string()),
+        mac_type: rest::opt_str_field(&value, "type").unwrap_or_default(),
+        addresses,
+    })
+}
+
+pub fn virtual_macs(client: &OVHClient, server: &str) -> io::Result<Vec<VirtualMac>> {
+    mac_addresses(client, server)?
+        .iter()
+        .map(|mac| virtual_mac(client, server, mac))
+        .collect()
+}
+
+/// Generate a virtual MAC for an additional IP routed to the server.
+pub fn create_virtual_mac(client: &OVHClient,
+                          server: &str,
+                          ip: &str,
+                          mac_type: MacType,
+                          vm_name: &str)
+                          -> io::Result<Task> {
+    let body = json!({
+        "ipAddress": ip,
+        "type": mac_type.name(),
+        "virtualMachineName": vm_name,
+    });
+    rest::decode(client, &rest::post(client, &vmac_path(server), &body)?)
+}
+
+/// Assign another additional IP to an existing virtual MAC (same VM).
+pub fn add_virtual_address(client: &OVHClient,
+                           server: &str,
+                           mac: &str,
+                           ip: &str,
+                           vm_name: &str)
+                           -> io::Result<Task> {
+    let body = json!({
+        "ipAddress": ip,
+        "virtualMachineName": vm_name,
+    });
+    rest::decode(client, &rest::post(client, &address_path(server, mac), &body)?)
+}
+
+/// Remove an IP from a virtual MAC; the MAC is deleted with its last IP.
+pub fn remove_virtual_address(client: &OVHClient,
+                              server: &str,
+                              mac: &str,
+                              ip: &str)
+                              -> io::Result<Task> {
+    let path = format!("{}/{}", address_path(server, mac), rest::escape(ip));
+    rest::decode(client, &rest::delete(client, &path)?)
+}
+
+/// Delete a virtual MAC by removing all its IPs. Returns the tasks started.
+pub fn delete_virtual_mac(client: &OVHClient, server: &str, mac: &str) -> io::Result<Vec<Task>> {
+    rest::string_list(&rest::get(client, &address_path(server, mac))?)?
+        .iter()
+        .map(|ip| remove_virtual_address(client, server, mac, ip))
+        .collect()
+}
+
+/// The virtual MAC an IP is assigned to, if any.
+pub fn mac_of(macs: &[VirtualMac], ip: &str) -> Option<String> {
+    macs.iter()
+        .find(|vmac| vmac.addresses.iter().any(|address| address.ip == ip))
+        .map(|vmac| vmac.mac.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mac_of, MacType, VirtualAddress, VirtualMac};
+
+    #[test]
+    fn test_mac_of() {
+        let macs = vec![VirtualMac {
+                            mac: "02:00:00:aa:bb:cc".to_string(),
+                            mac_type: MacType::Ovh.name().to_string(),
+                            addresses: vec![VirtualAddress {
+                                                ip: "198.51.100.5".to_string(),
+                                                vm_name: None,
+                                            }],
+                        }];
+        assert_eq!(Some("02:00:00:aa:bb:cc".to_string()), mac_of(&macs, "198.51.100.5"));
+        assert_eq!(None, mac_of(&macs, "198.51.100.6"));
+    }
+}
@@ -0,0 +1,250 @@
+//! # DNS
+//!
+//! Typed access to the records of a zone hosted by OVH
+//! (`/domain/zone/{zone}/record`).
+//!
+
+use client::OVHClient;
+#[cfg(feature = "async")]
+use nonblocking::AsyncClient;
+#[cfg(feature = "async")]
+use pagination::Listing;
+use pagination::{self, Paginator};
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+pub mod provider;
+pub mod security;
+pub mod soa;
+pub mod sync;
+
+/// A record as stored in the zone.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Record {
+    pub id: u64,
+    pub zone: String,
+    pub field_type: String,
+    pub sub_domain: String,
+    pub target: String,
+    pub ttl: u32,
+}
+
+impl Record {
+    /// The content of the record, without its id.
+    pub fn spec(&self) -> RecordSpec {
+        RecordSpec {
+            field_type: self.field_type.clone(),
+            sub_domain: self.sub_domain.clone(),
+            target: self.target.clone(),
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl Model for Record {
+    const NAME: &'static str = "Record";
+    const FIELDS: &'static [&'static str] = &["id", "zone", "fieldType", "subDomain", "target",
+                                              "ttl"];
+
+    fn from_json(value: &Value) -> io::Result<Record> {
+        Ok(Record {
+            id: rest::u64_field(value, "id")?,
+            zone: rest::str_field(value, "zone")?,
+            field_type: rest::str_field(value, "fieldType")?,
+            sub_domain: rest::opt_str_field(value, "subDomain").unwrap_or_default(),
+            target: rest::str_field(value, "target")?,
+            ttl: rest::opt_u64_field(value, "ttl").unwrap_or(0) as u32,
+        })
+    }
+}
+
+/// Content of a record to create or update.
+/// A `ttl` of 0 lets OVH apply the zone default.
+#[derive(Debug,Clone,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct RecordSpec {
+    pub field_type: String,
+    pub sub_domain: String,
+    pub target: String,
+    pub ttl: u32,
+}
+
+impl RecordSpec {
+    pub fn new(field_type: &str, sub_domain: &str, target: &str, ttl: u32) -> RecordSpec {
+        RecordSpec {
+            field_type: field_type.to_uppercase(),
+            sub_domain: sub_domain.to_string(),
+            target: target.to_string(),
+            ttl,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "fieldType": self.field_type,
+            "subDomain": self.sub_domain,
+            "target": self.target,
+            "ttl": self.ttl,
+        })
+    }
+}
+
+fn records_path(zone: &str) -> String {
+    format!("/domain/zone/{}/record", rest::escape(zone))
+}
+
+/// List the ids of the records of a zone.
+pub fn record_ids(client: &OVHClient, zone: &str) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, &records_path(zone))?)
+}
+
+/// List the ids of the records of a type, optionally restricted to a sub-domain
+/// (`""` is the apex), filtered by the API.
+pub fn find_record_ids(client: &OVHClient,
+                       zone: &str,
+                       field_type: &str,
+                       sub_domain: Option<&str>)
+                       -> io::Result<Vec<u64>> {
+    let mut path = format!("{}?fieldType={}", records_path(zone), rest::escape(field_type));
+    if let Some(sub_domain) = sub_domain {
+        path.push_str(&format!("&subDomain={}", rest::escape(sub_domain)));
+    }
+    rest::u64_list(&rest::get(client, &path)?)
+}
+
+/// Names of the zones hosted on the account.
+pub fn zone_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/domain/zone")?)
+}
+
+/// Fetch a single record.
+pub fn record(client: &OVHClient, zone: &str, id: u64) -> io::Result<Record> {
+    let value = rest::get(client, &format!("{}/{}", records_path(zone), id))?;
+    rest::decode(client, &value)
+}
+
+/// Fetch all the records of a zone.
+pub fn records(client: &OVHClient, zone: &str) -> io::Result<Vec<Record>> {
+    record_ids(client, zone)?
+        .into_iter()
+        .map(|id| record(client, zone, id))
+        .collect()
+}
+
+/// Fetch the records of a zone lazily, one call per record consumed.
+pub fn iter_records<'a>(client: &'a OVHClient,
+                        zone: &str)
+                        -> io::Result<impl Iterator<Item = io::Result<Record>> + 'a> {
+    Ok(pagination::decoded(Paginator::new(client, &records_path(zone))?))
+}
+
+/// Stream the records of a zone, one call per record polled.
+#[cfg(feature = "async")]
+pub fn stream_records(client: &AsyncClient, zone: &str) -> Listing<Record> {
+    pagination::listing(client, &records_path(zone))
+}
+
+/// Export the zone in BIND format.
+pub fn export_zone(client: &OVHClient, zone: &str) -> io::Result<String> {
+    rest::get_text(client, &format!("/domain/zone/{}/export", rest::escape(zone)))
+}
+
+/// Create a record. The zone must be refreshed for the change to be served.
+pub fn create_record(client: &OVHClient, zone: &str, spec: &RecordSpec) -> io::Result<Record> {
+    let value = rest::post(client, &records_path(zone), &spec.to_json())?;
+    rest::decode(client, &value)
+}
+
+/// Update the sub-domain, target and TTL of a record (its type cannot change).
+pub fn update_record(client: &OVHClient, zone: &str, id: u64, spec: &RecordSpec) -> io::Result<()> {
+    let body = json!({
+        "subDomain": spec.sub_domain,
+        "target": spec.target,
+        "ttl": spec.ttl,
+    });
+    rest::put(client, &format!("{}/{}", records_path(zone), id), &body)?;
+    Ok(())
+}
+
+pub fn delete_record(client: &OVHClient, zone: &str, id: u64) -> io::Result<()> {
+    rest::delete(client, &format!("{}/{}", records_path(zone), id))?;
+    Ok(())
+}
+
+/// Apply pending record changes to the served zone.
+pub fn refresh(client: &OVHClient, zone: &str) -> io::Result<()> {
+    rest::call(client,
+               "POST",
+               &format!("/domain/zone/{}/refresh", rest::escape(zone)),
+               None)?;
+    Ok(())
+}
+
+/// Set the TTL of the records matching `filter` and refresh the zone once,
+/// e.g. lower the TTLs before a migration. Returns the number of records changed.
+pub fn set_ttl_for<F>(client: &OVHClient, zone: &str, filter: F, ttl: u32) -> io::Result<usize>
+    where F: Fn(&Record) -> bool
+{
+    let mut changed = 0;
+    for record in records(client, zone)? {
+        if record.ttl == ttl || !filter(&record) {
+            continue;
+        }
+        let mut spec = record.spec();
+        spec.ttl = ttl;
+        update_record(client, zone, record.id, &spec)?;
+        changed += 1;
+    }
+    if changed > 0 {
+        refresh(client, zone)?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Record;
+    use rest::Model;
+
+    #[test]
+    fn test_record_from_json() {
+        let value = json!({
+            "id": 1234,
+            "zone": "example.com",
+            "fieldType": "A",
+            "subDomain": "www",
+            "target": "192.0.2.1",
+            "ttl": 3600,
+        });
+        let record = Record::from_json(&value).unwrap();
+        assert_eq!(1234, record.id);
+        assert_eq!("www", record.sub_domain);
+        assert_eq!(3600, record.spec().ttl);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_stream_records() {
+        use super::stream_records;
+        use futures_util::StreamExt;
+        use nonblocking::AsyncClient;
+        use testutil;
+
+        let (host, server) = testutil::serve(vec![(200, "[7, 8]"),
+                                                  (200,
+                                                   r#"{"id": 7, "zone": "example.com",
+                                                       "fieldType": "A", "subDomain": "www",
+                                                       "target": "192.0.2.1", "ttl": 60}"#)]);
+        let client = AsyncClient::from_client(testutil::local_client(&host));
+        let mut records = stream_records(&client, "example.com");
+        let first = testutil::block_on(records.next()).unwrap().unwrap();
+        assert_eq!((7, "www"), (first.id, first.sub_domain.as_str()));
+        // the second record is only fetched when polled
+        drop(records);
+        let requests = server.join().unwrap();
+        assert_eq!(2, requests.len());
+        assert!(requests[1].starts_with("GET /1.0/domain/zone/example.com/record/7 HTTP/1.1"));
+    }
+}
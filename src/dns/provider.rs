@@ -0,0 +1,164 @@
+//! # DNS provider
+//!
+//! A vendor-neutral `DnsProvider` trait, shaped like the provider hooks of
+//! ACME clients (create a record for a fully qualified name, delete it once the
+//! challenge is validated), and its OVH implementation.
+//!
+//! Names are fully qualified, with or without the trailing dot
+//! (`_acme-challenge.www.example.com.`); the zone is found among the zones
+//! of the account unless given. Every change refreshes the zone.
+//!
+
+use client::OVHClient;
+use dns::{self, RecordSpec};
+
+use std::io;
+use std::net::Ipv4Addr;
+
+/// Minimal record management needed by ACME and service discovery tools.
+pub trait DnsProvider {
+    fn create_txt_record(&self, fqdn: &str, value: &str) -> io::Result<()>;
+    /// Delete the TXT records of `fqdn` holding `value`, leaving the others.
+    fn delete_txt_record(&self, fqdn: &str, value: &str) -> io::Result<()>;
+    fn create_a_record(&self, fqdn: &str, address: Ipv4Addr) -> io::Result<()>;
+    fn delete_a_record(&self, fqdn: &str, address: Ipv4Addr) -> io::Result<()>;
+    fn create_cname_record(&self, fqdn: &str, target: &str) -> io::Result<()>;
+    fn delete_cname_record(&self, fqdn: &str, target: &str) -> io::Result<()>;
+}
+
+/// Sub-domain of `fqdn` in `zone`, `""` for the apex, `None` if outside the zone.
+pub fn sub_domain_of(fqdn: &str, zone: &str) -> Option<String> {
+    let fqdn = fqdn.trim_end_matches('.').to_lowercase();
+    let zone = zone.trim_end_matches('.').to_lowercase();
+    if fqdn == zone {
+        return Some(String::new());
+    }
+    if fqdn.ends_with(&format!(".{}", zone)) {
+        return Some(fqdn[..fqdn.len() - zone.len() - 1].to_string());
+    }
+    None
+}
+
+/// The most specific zone containing `fqdn`, with the sub-domain in it.
+pub fn find_zone(fqdn: &str, zones: &[String]) -> Option<(String, String)> {
+    zones.iter()
+        .filter_map(|zone| sub_domain_of(fqdn, zone).map(|sub_domain| (zone.clone(), sub_domain)))
+        .max_by_key(|(zone, _)| zone.len())
+}
+
+/// TXT targets are returned quoted by the API.
+fn same_target(field_type: &str, stored: &str, target: &str) -> bool {
+    match field_type {
+        "TXT" => stored.trim_matches('"') == target.trim_matches('"'),
+        "CNAME" => stored.trim_end_matches('.') == target.trim_end_matches('.'),
+        _ => stored == target,
+    }
+}
+
+/// `DnsProvider` backed by the zones hosted by OVH.
+pub struct OvhDnsProvider<'a> {
+    client: &'a OVHClient,
+    zone: Option<String>,
+    ttl: u32,
+}
+
+impl<'a> OvhDnsProvider<'a> {
+    /// A provider looking the zone of each name up among the account zones.
+    pub fn new(client: &'a OVHClient) -> OvhDnsProvider<'a> {
+        OvhDnsProvider {
+            client,
+            zone: None,
+            ttl: 60,
+        }
+    }
+
+    /// Restrict the provider to a zone, sparing the listing of the zones.
+    pub fn set_zone(&mut self, zone: &str) {
+        self.zone = Some(zone.to_string());
+    }
+
+    /// TTL of the created records, 60s by default.
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+
+    fn locate(&self, fqdn: &str) -> io::Result<(String, String)> {
+        let zones = match self.zone {
+            Some(ref zone) => vec![zone.clone()],
+            None => dns::zone_names(self.client)?,
+        };
+        find_zone(fqdn, &zones).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound,
+                           format!("no zone of the account contains {}", fqdn))
+        })
+    }
+
+    fn create(&self, field_type: &str, fqdn: &str, target: &str) -> io::Result<()> {
+        let (zone, sub_domain) = self.locate(fqdn)?;
+        dns::create_record(self.client,
+                           &zone,
+                           &RecordSpec::new(field_type, &sub_domain, target, self.ttl))?;
+        dns::refresh(self.client, &zone)
+    }
+
+    fn delete(&self, field_type: &str, fqdn: &str, target: &str) -> io::Result<()> {
+        let (zone, sub_domain) = self.locate(fqdn)?;
+        let mut deleted = 0;
+        for id in dns::find_record_ids(self.client, &zone, field_type, Some(&sub_domain))? {
+            let record = dns::record(self.client, &zone, id)?;
+            if same_target(field_type, &record.target, target) {
+                dns::delete_record(self.client, &zone, id)?;
+                deleted += 1;
+            }
+        }
+        if deleted > 0 {
+            dns::refresh(self.client, &zone)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> DnsProvider for OvhDnsProvider<'a> {
+    fn create_txt_record(&self, fqdn: &str, value: &str) -> io::Result<()> {
+        self.create("TXT", fqdn, value)
+    }
+
+    fn delete_txt_record(&self, fqdn: &str, value: &str) -> io::Result<()> {
+        self.delete("TXT", fqdn, value)
+    }
+
+    fn create_a_record(&self, fqdn: &str, address: Ipv4Addr) -> io::Result<()> {
+        self.create("A", fqdn, &address.to_string())
+    }
+
+    fn delete_a_record(&self, fqdn: &str, address: Ipv4Addr) -> io::Result<()> {
+        self.delete("A", fqdn, &address.to_string())
+    }
+
+    fn create_cname_record(&self, fqdn: &str, target: &str) -> io::Result<()> {
+        self.create("CNAME", fqdn, target)
+    }
+
+    fn delete_cname_record(&self, fqdn: &str, target: &str) -> io::Result<()> {
+        self.delete("CNAME", fqdn, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_zone, same_target, sub_domain_of};
+
+    #[test]
+    fn test_find_zone() {
+        assert_eq!(Some("_acme-challenge.www".to_string()),
+                   sub_domain_of("_acme-challenge.www.example.com.", "example.com"));
+        assert_eq!(Some(String::new()), sub_domain_of("Example.com", "example.com"));
+        assert_eq!(None, sub_domain_of("badexample.com", "example.com"));
+        let zones = vec!["example.com".to_string(), "lab.example.com".to_string()];
+        assert_eq!(Some(("lab.example.com".to_string(), "_acme-challenge".to_string())),
+                   find_zone("_acme-challenge.lab.example.com", &zones));
+        assert_eq!(None, find_zone("example.org", &zones));
+        assert!(same_target("TXT", "\"token\"", "token"));
+        assert!(same_target("CNAME", "target.example.com.", "target.example.com"));
+    }
+}
@@ -0,0 +1,167 @@
+//! # Security records
+//!
+//! Builders for CAA, TLSA and SSHFP records which check the target format
+//! before anything is sent: the API stores malformed targets as they are,
+//! and a broken security record is usually worse than none.
+//!
+
+use dns::RecordSpec;
+
+use std::io;
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Check that `data` is hexadecimal, of `expected` characters if given.
+fn check_hex(name: &str, data: &str, expected: Option<usize>) -> io::Result<String> {
+    if data.is_empty() || !data.len().is_multiple_of(2) || !data.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid_input(format!("{} must be an even number of hexadecimal digits", name)));
+    }
+    match expected {
+        Some(len) if data.len() != len => {
+            Err(invalid_input(format!("{} must be {} hexadecimal digits, not {}",
+                                      name,
+                                      len,
+                                      data.len())))
+        }
+        _ => Ok(data.to_lowercase()),
+    }
+}
+
+impl RecordSpec {
+    /// A CAA record: which certificate authorities may issue for the name.
+    /// `tag` is `issue`, `issuewild` or `iodef`; `flags` is 0, or 128 for critical.
+    pub fn caa(sub_domain: &str,
+               flags: u8,
+               tag: &str,
+               value: &str,
+               ttl: u32)
+               -> io::Result<RecordSpec> {
+        if flags != 0 && flags != 128 {
+            return Err(invalid_input(format!("CAA flags must be 0 or 128, not {}", flags)));
+        }
+        let tag = tag.to_lowercase();
+        match tag.as_str() {
+            "issue" | "issuewild" => {
+                let domain = value.split(';').next().unwrap_or("").trim();
+                if domain.contains(' ') || domain.contains('"') {
+                    return Err(invalid_input(format!("invalid CAA issuer: {}", value)));
+                }
+            }
+            "iodef" => {
+                if !value.starts_with("mailto:") && !value.starts_with("https://") &&
+                   !value.starts_with("http://") {
+                    return Err(invalid_input(format!("CAA iodef must be a mailto: or http(s) URL: {}",
+                                                     value)));
+                }
+            }
+            _ => return Err(invalid_input(format!("unknown CAA tag: {}", tag))),
+        }
+        Ok(RecordSpec::new("CAA",
+                           sub_domain,
+                           &format!("{} {} \"{}\"", flags, tag, value),
+                           ttl))
+    }
+
+    /// A TLSA record (DANE). The sub-domain names the service, e.g. `_443._tcp.www`.
+    /// `matching_type` 1 and 2 expect SHA-256 and SHA-512 hex digests.
+    pub fn tlsa(sub_domain: &str,
+                usage: u8,
+                selector: u8,
+                matching_type: u8,
+                data: &str,
+                ttl: u32)
+                -> io::Result<RecordSpec> {
+        let mut labels = sub_domain.split('.');
+        let port = labels.next().unwrap_or("");
+        let protocol = labels.next().unwrap_or("");
+        if !port.starts_with('_') || port[1..].parse::<u16>().is_err() || !protocol.starts_with('_') {
+            return Err(invalid_input(format!("TLSA sub-domain must start with _port._protocol: {}",
+                                             sub_domain)));
+        }
+        if usage > 3 {
+            return Err(invalid_input(format!("TLSA usage must be 0 to 3, not {}", usage)));
+        }
+        if selector > 1 {
+            return Err(invalid_input(format!("TLSA selector must be 0 or 1, not {}", selector)));
+        }
+        let expected = match matching_type {
+            0 => None,
+            1 => Some(64),
+            2 => Some(128),
+            _ => {
+                return Err(invalid_input(format!("TLSA matching type must be 0 to 2, not {}",
+                                                 matching_type)))
+            }
+        };
+        let data = check_hex("TLSA data", data, expected)?;
+        Ok(RecordSpec::new("TLSA",
+                           sub_domain,
+                           &format!("{} {} {} {}", usage, selector, matching_type, data),
+                           ttl))
+    }
+
+    /// A SSHFP record: the fingerprint of a host key.
+    /// `algorithm` is 1 (RSA), 2 (DSA), 3 (ECDSA), 4 (Ed25519) or 6 (Ed448);
+    /// `fingerprint_type` is 1 (SHA-1) or 2 (SHA-256).
+    pub fn sshfp(sub_domain: &str,
+                 algorithm: u8,
+                 fingerprint_type: u8,
+                 fingerprint: &str,
+                 ttl: u32)
+                 -> io::Result<RecordSpec> {
+        match algorithm {
+            1..=4 | 6 => {}
+            _ => return Err(invalid_input(format!("unknown SSHFP algorithm: {}", algorithm))),
+        }
+        let expected = match fingerprint_type {
+            1 => 40,
+            2 => 64,
+            _ => {
+                return Err(invalid_input(format!("unknown SSHFP fingerprint type: {}",
+                                                 fingerprint_type)))
+            }
+        };
+        let fingerprint = check_hex("SSHFP fingerprint", fingerprint, Some(expected))?;
+        Ok(RecordSpec::new("SSHFP",
+                           sub_domain,
+                           &format!("{} {} {}", algorithm, fingerprint_type, fingerprint),
+                           ttl))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dns::RecordSpec;
+
+    #[test]
+    fn test_caa() {
+        let spec = RecordSpec::caa("", 0, "issue", "letsencrypt.org", 3600).unwrap();
+        assert_eq!("0 issue \"letsencrypt.org\"", spec.target);
+        assert!(RecordSpec::caa("", 0, "iodef", "mailto:security@example.com", 3600).is_ok());
+        assert!(RecordSpec::caa("", 1, "issue", "letsencrypt.org", 3600).is_err());
+        assert!(RecordSpec::caa("", 0, "issuer", "letsencrypt.org", 3600).is_err());
+        assert!(RecordSpec::caa("", 0, "iodef", "security@example.com", 3600).is_err());
+    }
+
+    #[test]
+    fn test_tlsa() {
+        let digest = "0C72AC70B745AC19998811B131D662C9AC69DBDBE7CB23E5B514B56664C5D3D6";
+        let spec = RecordSpec::tlsa("_443._tcp.www", 3, 1, 1, digest, 3600).unwrap();
+        assert_eq!(format!("3 1 1 {}", digest.to_lowercase()), spec.target);
+        assert!(RecordSpec::tlsa("www", 3, 1, 1, digest, 3600).is_err());
+        assert!(RecordSpec::tlsa("_443._tcp", 3, 1, 2, digest, 3600).is_err());
+        assert!(RecordSpec::tlsa("_443._tcp", 4, 1, 1, digest, 3600).is_err());
+    }
+
+    #[test]
+    fn test_sshfp() {
+        let sha1 = "123456789abcdef67890123456789abcdef67890";
+        assert_eq!("4 1 123456789abcdef67890123456789abcdef67890",
+                   RecordSpec::sshfp("host", 4, 1, sha1, 3600).unwrap().target);
+        assert!(RecordSpec::sshfp("host", 5, 1, sha1, 3600).is_err());
+        assert!(RecordSpec::sshfp("host", 4, 2, sha1, 3600).is_err());
+        assert!(RecordSpec::sshfp("host", 4, 1, "xyz", 3600).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! # SOA
+//!
+//! Start of authority of a zone (`/domain/zone/{zone}/soa`): primary server,
+//! contact and the timers secondaries and resolvers follow.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// The SOA record of a zone. Timers are in seconds.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Soa {
+    /// Primary name server.
+    pub server: String,
+    /// Contact, as an email address.
+    pub email: String,
+    pub serial: u64,
+    pub refresh: u32,
+    pub expire: u32,
+    /// TTL of negative answers.
+    pub nx_domain_ttl: u32,
+    /// Default TTL of the zone.
+    pub ttl: u32,
+}
+
+impl Model for Soa {
+    const NAME: &'static str = "Soa";
+    const FIELDS: &'static [&'static str] = &["server", "email", "serial", "refresh", "expire",
+                                              "nxDomainTtl", "ttl"];
+
+    fn from_json(value: &Value) -> io::Result<Soa> {
+        let timer = |key: &str| rest::u64_field(value, key).map(|v| v as u32);
+        Ok(Soa {
+            server: rest::str_field(value, "server")?,
+            email: rest::str_field(value, "email")?,
+            serial: rest::u64_field(value, "serial")?,
+            refresh: timer("refresh")?,
+            expire: timer("expire")?,
+            nx_domain_ttl: timer("nxDomainTtl")?,
+            ttl: timer("ttl")?,
+        })
+    }
+}
+
+impl Soa {
+    /// Fields which can be updated; the server and serial are managed by OVH.
+    fn to_json(&self) -> Value {
+        json!({
+            "email": self.email,
+            "refresh": self.refresh,
+            "expire": self.expire,
+            "nxDomainTtl": self.nx_domain_ttl,
+            "ttl": self.ttl,
+        })
+    }
+}
+
+fn soa_path(zone: &str) -> String {
+    format!("/domain/zone/{}/soa", rest::escape(zone))
+}
+
+pub fn soa(client: &OVHClient, zone: &str) -> io::Result<Soa> {
+    rest::decode(client, &rest::get(client, &soa_path(zone))?)
+}
+
+/// Update the contact and timers of the zone.
+pub fn update_soa(client: &OVHClient, zone: &str, soa: &Soa) -> io::Result<()> {
+    rest::put(client, &soa_path(zone), &soa.to_json())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Soa;
+    use rest::Model;
+
+    #[test]
+    fn test_soa_json() {
+        let value = json!({
+            "server": "dns10.ovh.net",
+            "email": "tech@example.com",
+            "serial": 2017050101u64,
+            "refresh": 86400,
+            "expire": 3600000,
+            "nxDomainTtl": 86400,
+            "ttl": 86400,
+        });
+        let soa = Soa::from_json(&value).unwrap();
+        assert_eq!(3600000, soa.expire);
+        assert!(soa.to_json().get("serial").is_none());
+    }
+}
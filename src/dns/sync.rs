@@ -0,0 +1,467 @@
+//! # Sync
+//!
+//! Delta synchronization of a zone against a desired record set.
+//! The hash of every applied record is journaled in a local file so that
+//! subsequent runs only call the API for the records which changed.
+//!
+//! Only the records created or adopted by the engine are managed:
+//! records added by other means (console, other tools) are left untouched.
+//!
+
+use client::OVHClient;
+use dns::{self, RecordSpec};
+use progress::{Progress, ProgressEvent};
+use trace::Operation;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+
+use serde_json::{self, Map, Value};
+
+/// Remote record applied for a given spec hash.
+#[derive(Debug,Clone,PartialEq)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub field_type: String,
+    pub sub_domain: String,
+}
+
+/// Last record set applied to a zone.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Journal {
+    pub zone: String,
+    /// Hash of the whole record set, empty while a sync is in progress.
+    pub hash: String,
+    /// Applied records, keyed by spec hash.
+    pub records: BTreeMap<String, JournalEntry>,
+}
+
+impl Journal {
+    pub fn new(zone: &str) -> Journal {
+        Journal {
+            zone: zone.to_string(),
+            hash: String::new(),
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Read a journal, `None` if the file does not exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<Journal>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        let mut journal = Journal::new(value.get("zone").and_then(Value::as_str).unwrap_or(""));
+        journal.hash = value.get("hash").and_then(Value::as_str).unwrap_or("").to_string();
+        if let Some(records) = value.get("records").and_then(Value::as_object) {
+            for (hash, entry) in records {
+                let id = match entry.get("id").and_then(Value::as_u64) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                journal.records.insert(hash.clone(),
+                                       JournalEntry {
+                                           id,
+                                           field_type: entry.get("fieldType")
+                                               .and_then(Value::as_str)
+                                               .unwrap_or("")
+                                               .to_string(),
+                                           sub_domain: entry.get("subDomain")
+                                               .and_then(Value::as_str)
+                                               .unwrap_or("")
+                                               .to_string(),
+                                       });
+            }
+        }
+        Ok(Some(journal))
+    }
+
+    /// Write the journal atomically.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut records = Map::new();
+        for (hash, entry) in &self.records {
+            records.insert(hash.clone(),
+                           json!({
+                               "id": entry.id,
+                               "fieldType": entry.field_type,
+                               "subDomain": entry.sub_domain,
+                           }));
+        }
+        let value = json!({
+            "zone": self.zone,
+            "hash": self.hash,
+            "records": records,
+        });
+
+        let mut tmp = path.to_path_buf().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut fd = File::create(&tmp)?;
+            fd.write_all(value.to_string().as_bytes())?;
+            fd.sync_all()?;
+        }
+        fs::rename(&tmp, path)
+    }
+}
+
+/// Stable hash of a record content.
+pub fn spec_hash(spec: &RecordSpec) -> String {
+    let mut hasher = Sha1::new();
+    hasher.input_str(&format!("{}\t{}\t{}\t{}",
+                              spec.field_type,
+                              spec.sub_domain,
+                              spec.target,
+                              spec.ttl));
+    hasher.result_str()
+}
+
+/// Stable hash of a record set, independent of the records order.
+pub fn set_hash(specs: &[RecordSpec]) -> String {
+    let mut hashes: Vec<String> = specs.iter().map(spec_hash).collect();
+    hashes.sort();
+    hashes.dedup();
+    let mut hasher = Sha1::new();
+    for hash in &hashes {
+        hasher.input_str(hash);
+    }
+    hasher.result_str()
+}
+
+/// API calls needed to converge a zone.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SyncPlan {
+    pub create: Vec<RecordSpec>,
+    /// Records updated in place: same type and sub-domain, new target or TTL.
+    pub update: Vec<(u64, RecordSpec)>,
+    pub delete: Vec<u64>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// Compute the changes between the journaled state and the desired record set.
+pub fn plan(journal: &Journal, desired: &[RecordSpec]) -> SyncPlan {
+    let mut wanted: BTreeMap<String, &RecordSpec> = BTreeMap::new();
+    for spec in desired {
+        wanted.insert(spec_hash(spec), spec);
+    }
+
+    let mut stale: Vec<&JournalEntry> = journal.records
+        .iter()
+        .filter(|&(hash, _)| !wanted.contains_key(hash))
+        .map(|(_, entry)| entry)
+        .collect();
+
+    let mut plan = SyncPlan::default();
+    for (hash, spec) in &wanted {
+        if journal.records.contains_key(hash) {
+            continue;
+        }
+        let reusable = stale.iter()
+            .position(|entry| {
+                entry.field_type == spec.field_type && entry.sub_domain == spec.sub_domain
+            });
+        match reusable {
+            Some(index) => plan.update.push((stale.remove(index).id, (*spec).clone())),
+            None => plan.create.push((*spec).clone()),
+        }
+    }
+    plan.delete = stale.iter().map(|entry| entry.id).collect();
+    plan
+}
+
+/// Outcome of `ZoneSync::sync`.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// Whether the zone was refreshed.
+    pub refreshed: bool,
+}
+
+/// Synchronize a zone against a desired record set, journaling applied records.
+pub struct ZoneSync<'a> {
+    client: &'a OVHClient,
+    zone: String,
+    journal_path: PathBuf,
+    progress: Progress,
+}
+
+impl<'a> ZoneSync<'a> {
+    pub fn new<P: AsRef<Path>>(client: &'a OVHClient, zone: &str, journal_path: P) -> ZoneSync<'a> {
+        ZoneSync {
+            client,
+            zone: zone.to_string(),
+            journal_path: journal_path.as_ref().to_path_buf(),
+            progress: Progress::none(),
+        }
+    }
+
+    /// Report the changes applied by `sync`.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    fn step(&self, done: usize, total: usize, message: String) {
+        self.progress.emit(ProgressEvent::Step {
+            operation: self.operation(),
+            done,
+            total: Some(total),
+            message,
+        });
+    }
+
+    fn operation(&self) -> String {
+        format!("sync of zone {}", self.zone)
+    }
+
+    /// Load the journal, or rebuild one by adopting the live records
+    /// matching the desired set when there is none yet.
+    fn journal(&self, desired: &[RecordSpec]) -> io::Result<Journal> {
+        if let Some(journal) = Journal::load(&self.journal_path)? {
+            if journal.zone == self.zone {
+                return Ok(journal);
+            }
+            warn!("Journal {:?} belongs to zone {}, rebuilding it",
+                  self.journal_path,
+                  journal.zone);
+        }
+        let wanted: Vec<String> = desired.iter().map(spec_hash).collect();
+        let mut journal = Journal::new(&self.zone);
+        for record in dns::records(self.client, &self.zone)? {
+            let hash = spec_hash(&record.spec());
+            if wanted.contains(&hash) {
+                journal.records.insert(hash,
+                                       JournalEntry {
+                                           id: record.id,
+                                           field_type: record.field_type,
+                                           sub_domain: record.sub_domain,
+                                       });
+            }
+        }
+        Ok(journal)
+    }
+
+    /// Changes which the next `sync` would apply.
+    pub fn plan(&self, desired: &[RecordSpec]) -> io::Result<SyncPlan> {
+        let journal = self.journal(desired)?;
+        if !journal.hash.is_empty() && journal.hash == set_hash(desired) {
+            return Ok(SyncPlan::default());
+        }
+        Ok(plan(&journal, desired))
+    }
+
+    /// Apply the desired record set and refresh the zone if anything changed.
+    /// The journal is saved after every call so an interrupted run can resume.
+    pub fn sync(&self, desired: &[RecordSpec]) -> io::Result<SyncReport> {
+        let _operation = Operation::start(&self.operation());
+        let result = self.apply(desired);
+        match result {
+            Ok(_) => self.progress.emit(ProgressEvent::Finished { operation: self.operation() }),
+            Err(ref err) => {
+                self.progress.emit(ProgressEvent::Failed {
+                    operation: self.operation(),
+                    error: err.to_string(),
+                })
+            }
+        }
+        result
+    }
+
+    fn apply(&self, desired: &[RecordSpec]) -> io::Result<SyncReport> {
+        let mut journal = self.journal(desired)?;
+        let hash = set_hash(desired);
+        let mut report = SyncReport::default();
+        if !journal.hash.is_empty() && journal.hash == hash {
+            debug!("Zone {} unchanged", self.zone);
+            return Ok(report);
+        }
+
+        let plan = plan(&journal, desired);
+        journal.hash = String::new();
+        let total = plan.delete.len() + plan.update.len() + plan.create.len();
+        self.progress.emit(ProgressEvent::Started {
+            operation: self.operation(),
+            total: Some(total),
+        });
+        let mut done = 0;
+
+        for id in &plan.delete {
+            dns::delete_record(self.client, &self.zone, *id)?;
+            journal.records.retain(|_, entry| entry.id != *id);
+            journal.save(&self.journal_path)?;
+            report.deleted += 1;
+            done += 1;
+            self.step(done, total, format!("deleted record {}", id));
+        }
+        for &(id, ref spec) in &plan.update {
+            dns::update_record(self.client, &self.zone, id, spec)?;
+            journal.records.retain(|_, entry| entry.id != id);
+            journal.records.insert(spec_hash(spec),
+                                   JournalEntry {
+                                       id,
+                                       field_type: spec.field_type.clone(),
+                                       sub_domain: spec.sub_domain.clone(),
+                                   });
+            journal.save(&self.journal_path)?;
+            report.updated += 1;
+            done += 1;
+            self.step(done, total, format!("updated record {}", id));
+        }
+        for spec in &plan.create {
+            let record = dns::create_record(self.client, &self.zone, spec)?;
+            journal.records.insert(spec_hash(spec),
+                                   JournalEntry {
+                                       id: record.id,
+                                       field_type: record.field_type,
+                                       sub_domain: record.sub_domain,
+                                   });
+            journal.save(&self.journal_path)?;
+            report.created += 1;
+            done += 1;
+            self.step(done, total,
+                      format!("created {} record {}", spec.field_type, spec.sub_domain));
+        }
+
+        if !plan.is_empty() {
+            dns::refresh(self.client, &self.zone)?;
+            report.refreshed = true;
+        }
+        journal.hash = hash;
+        journal.save(&self.journal_path)?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan, set_hash, spec_hash, Journal, JournalEntry, SyncReport, ZoneSync};
+    use dns::RecordSpec;
+    use testutil;
+    use std::fs;
+    use std::slice;
+
+    fn journal_with(spec: &RecordSpec, id: u64) -> Journal {
+        let mut journal = Journal::new("example.com");
+        journal.records.insert(spec_hash(spec),
+                               JournalEntry {
+                                   id,
+                                   field_type: spec.field_type.clone(),
+                                   sub_domain: spec.sub_domain.clone(),
+                               });
+        journal
+    }
+
+    #[test]
+    fn test_set_hash_ignores_order() {
+        let a = RecordSpec::new("A", "www", "192.0.2.1", 3600);
+        let b = RecordSpec::new("TXT", "", "\"v=spf1 -all\"", 0);
+        assert_eq!(set_hash(&[a.clone(), b.clone()]), set_hash(&[b, a]));
+    }
+
+    #[test]
+    fn test_plan() {
+        let www = RecordSpec::new("A", "www", "192.0.2.1", 3600);
+        let journal = journal_with(&www, 1);
+
+        assert!(plan(&journal, slice::from_ref(&www)).is_empty());
+
+        let moved = RecordSpec::new("A", "www", "192.0.2.2", 3600);
+        let res = plan(&journal, slice::from_ref(&moved));
+        assert_eq!(vec![(1, moved)], res.update);
+        assert!(res.create.is_empty() && res.delete.is_empty());
+
+        let mail = RecordSpec::new("MX", "", "10 mx.example.com.", 0);
+        let res = plan(&journal, slice::from_ref(&mail));
+        assert_eq!(vec![mail], res.create);
+        assert_eq!(vec![1], res.delete);
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let path = testutil::temp_path("ovh-rs-test-dns-journal.json");
+        let mut journal = journal_with(&RecordSpec::new("A", "www", "192.0.2.1", 3600), 42);
+        journal.hash = "abc".to_string();
+        journal.save(&path).unwrap();
+        assert_eq!(Some(journal), Journal::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sync_resumes_from_journal() {
+        let path = testutil::temp_path("ovh-rs-test-dns-sync.json");
+        let www = RecordSpec::new("A", "www", "192.0.2.1", 3600);
+        let old = RecordSpec::new("TXT", "old", "\"stale\"", 0);
+        let mut journal = journal_with(&www, 1);
+        journal.records.insert(spec_hash(&old),
+                               JournalEntry {
+                                   id: 2,
+                                   field_type: old.field_type.clone(),
+                                   sub_domain: old.sub_domain.clone(),
+                               });
+        journal.hash = set_hash(&[www, old]);
+        journal.save(&path).unwrap();
+
+        let (host, server) =
+            testutil::serve(vec![(200, "null"),
+                                 (200, "null"),
+                                 (403, r#"{"message": "This call has not been granted"}"#),
+                                 (200,
+                                  r#"{"id": 3, "zone": "example.com", "fieldType": "MX",
+                                      "subDomain": "", "target": "10 mx.example.com.",
+                                      "ttl": 0}"#),
+                                 (200, "null")]);
+        let client = testutil::local_client(&host);
+        let sync = ZoneSync::new(&client, "example.com", &path);
+        let moved = RecordSpec::new("A", "www", "192.0.2.2", 3600);
+        let mail = RecordSpec::new("MX", "", "10 mx.example.com.", 0);
+        let desired = [moved.clone(), mail.clone()];
+
+        // the creation fails after the deletion and the update were applied
+        assert!(sync.sync(&desired).is_err());
+        let journal = Journal::load(&path).unwrap().unwrap();
+        assert_eq!("", journal.hash);
+        assert_eq!(vec![spec_hash(&moved)], journal.records.keys().cloned().collect::<Vec<_>>());
+
+        // the next run only creates the missing record, then refreshes the zone
+        let report = sync.sync(&desired).unwrap();
+        assert_eq!(SyncReport { created: 1, updated: 0, deleted: 0, refreshed: true }, report);
+        let journal = Journal::load(&path).unwrap().unwrap();
+        assert_eq!(set_hash(&desired), journal.hash);
+        assert_eq!(Some(3), journal.records.get(&spec_hash(&mail)).map(|entry| entry.id));
+
+        // up to date: no call at all
+        assert_eq!(SyncReport::default(), sync.sync(&desired).unwrap());
+
+        let requests = server.join().unwrap();
+        let route = "/1.0/domain/zone/example.com";
+        let expected = [format!("DELETE {}/record/2 ", route),
+                        format!("PUT {}/record/1 ", route),
+                        format!("POST {}/record ", route),
+                        format!("POST {}/record ", route),
+                        format!("POST {}/refresh ", route)];
+        assert_eq!(expected.len(), requests.len());
+        for (request, expected) in requests.iter().zip(&expected) {
+            assert!(request.starts_with(expected.as_str()), "{}", request);
+        }
+        assert!(requests[1].ends_with(r#""target":"192.0.2.2","ttl":3600}"#), "{}", requests[1]);
+        assert!(requests[3].contains(r#""fieldType":"MX""#), "{}", requests[3]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! # Glue records
+//!
+//! Glue records of a domain (`/domain/{domain}/glueRecord`): the IPs
+//! published by the registry for name servers inside the domain itself,
+//! e.g. `ns1.example.com` serving `example.com`.
+//!
+
+use client::OVHClient;
+use domain::{domain_path, Task};
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A glue record: a host of the domain and its IPs.
+#[derive(Debug,Clone,PartialEq)]
+pub struct GlueRecord {
+    pub host: String,
+    pub ips: Vec<String>,
+}
+
+impl Model for GlueRecord {
+    const NAME: &'static str = "GlueRecord";
+    const FIELDS: &'static [&'static str] = &["host", "ips"];
+
+    fn from_json(value: &Value) -> io::Result<GlueRecord> {
+        Ok(GlueRecord {
+            host: rest::str_field(value, "host")?,
+            ips: match value.get("ips") {
+                Some(ips) => rest::string_list(ips)?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+fn glue_path(domain: &str) -> String {
+    format!("{}/glueRecord", domain_path(domain))
+}
+
+/// Hosts having a glue record.
+pub fn glue_hosts(client: &OVHClient, domain: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &glue_path(domain))?)
+}
+
+pub fn glue_record(client: &OVHClient, domain: &str, host: &str) -> io::Result<GlueRecord> {
+    rest::decode(client,
+                 &rest::get(client, &format!("{}/{}", glue_path(domain), rest::escape(host)))?)
+}
+
+pub fn glue_records(client: &OVHClient, domain: &str) -> io::Result<Vec<GlueRecord>> {
+    glue_hosts(client, domain)?
+        .iter()
+        .map(|host| glue_record(client, domain, host))
+        .collect()
+}
+
+/// Create a glue record; the registry applies it through the returned task.
+pub fn create_glue_record(client: &OVHClient,
+                          domain: &str,
+                          host: &str,
+                          ips: &[&str])
+                          -> io::Result<Task> {
+    let body = json!({ "host": host, "ips": ips });
+    rest::decode(client, &rest::post(client, &glue_path(domain), &body)?)
+}
+
+/// Replace the IPs of a glue record.
+pub fn update_glue_record(client: &OVHClient,
+                          domain: &str,
+                          host: &str,
+                          ips: &[&str])
+                          -> io::Result<Task> {
+    let path = format!("{}/{}/update", glue_path(domain), rest::escape(host));
+    rest::decode(client, &rest::post(client, &path, &json!({ "ips": ips }))?)
+}
+
+pub fn delete_glue_record(client: &OVHClient, domain: &str, host: &str) -> io::Result<Task> {
+    let path = format!("{}/{}", glue_path(domain), rest::escape(host));
+    rest::decode(client, &rest::delete(client, &path)?)
+}
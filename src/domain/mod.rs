@@ -0,0 +1,142 @@
+//! # Domain
+//!
+//! Registrar side of domains (`/domain/{domain}`): glue records and
+//! name servers, whose updates run as asynchronous domain tasks.
+//!
+
+use client::OVHClient;
+use progress::Progress;
+use rest::{self, Model};
+use task::{self, TaskFamily, TaskHandle};
+
+use std::io;
+use std::time::Duration;
+
+use serde_json::Value;
+
+pub mod glue;
+pub mod nameserver;
+
+/// Status of a task.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum TaskStatus {
+    Todo,
+    Doing,
+    Done,
+    Error,
+    Cancelled,
+    Other(String),
+}
+
+impl TaskStatus {
+    pub fn parse(status: &str) -> TaskStatus {
+        match status {
+            "init" | "todo" => TaskStatus::Todo,
+            "doing" => TaskStatus::Doing,
+            "done" => TaskStatus::Done,
+            "error" | "customerError" | "ovhError" => TaskStatus::Error,
+            "cancelled" => TaskStatus::Cancelled,
+            other => TaskStatus::Other(other.to_string()),
+        }
+    }
+
+    /// Whether the task will not progress anymore.
+    pub fn is_final(&self) -> bool {
+        matches!(*self, TaskStatus::Done | TaskStatus::Error | TaskStatus::Cancelled)
+    }
+}
+
+/// An operation run by the registry on a domain, or by another product
+/// (see `task::TaskHandle`).
+#[derive(Debug,Clone,PartialEq)]
+pub struct Task {
+    pub id: u64,
+    /// Kind of operation, e.g. `DomainDnsUpdate` or `DomainHostCreate`.
+    pub function: String,
+    pub status: TaskStatus,
+    pub comment: Option<String>,
+    pub creation_date: Option<String>,
+    pub done_date: Option<String>,
+}
+
+impl Model for Task {
+    const NAME: &'static str = "Task";
+    const FIELDS: &'static [&'static str] = &["id", "function", "status", "comment", "creationDate",
+                                              "todoDate", "lastUpdate", "doneDate", "canAccelerate",
+                                              "canCancel", "canRelaunch", "taskId", "startDate",
+                                              "needSchedule", "plannedInterventionId", "note",
+                                              "tags", "ticketReference"];
+
+    fn from_json(value: &Value) -> io::Result<Task> {
+        Ok(Task {
+            // dedicated server tasks name their id `taskId`
+            id: rest::u64_field(value, "id").or_else(|_| rest::u64_field(value, "taskId"))?,
+            function: rest::opt_str_field(value, "function").unwrap_or_default(),
+            status: TaskStatus::parse(&rest::str_field(value, "status")?),
+            comment: rest::opt_str_field(value, "comment"),
+            creation_date: rest::opt_str_field(value, "creationDate"),
+            done_date: rest::opt_str_field(value, "doneDate"),
+        })
+    }
+}
+
+pub fn domain_path(domain: &str) -> String {
+    format!("/domain/{}", rest::escape(domain))
+}
+
+/// Ids of the tasks of a domain.
+pub fn task_ids(client: &OVHClient, domain: &str) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, &format!("{}/task", domain_path(domain)))?)
+}
+
+pub fn task(client: &OVHClient, domain: &str, id: u64) -> io::Result<Task> {
+    rest::decode(client,
+                 &rest::get(client, &format!("{}/task/{}", domain_path(domain), id))?)
+}
+
+/// Poll a task every `interval` until it is over. Gives up after `timeout`.
+/// A task ending in error is returned as is, its `comment` tells why.
+pub fn wait_task(client: &OVHClient,
+                 domain: &str,
+                 id: u64,
+                 interval: Duration,
+                 timeout: Duration)
+                 -> io::Result<Task> {
+    watch_task(client, domain, id, interval, timeout, &Progress::none())
+}
+
+/// `wait_task` reporting the status changes of the task.
+pub fn watch_task(client: &OVHClient,
+                  domain: &str,
+                  id: u64,
+                  interval: Duration,
+                  timeout: Duration,
+                  progress: &Progress)
+                  -> io::Result<Task> {
+    task::watch(client, &task_handle(domain, id), interval, timeout, progress)
+}
+
+/// Handle on a domain task, to resume waiting on it from another process.
+pub fn task_handle(domain: &str, id: u64) -> TaskHandle {
+    TaskHandle::new(TaskFamily::Domain, domain, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Task, TaskStatus};
+    use rest::Model;
+
+    #[test]
+    fn test_task_from_json() {
+        let task = Task::from_json(&json!({
+            "id": 42,
+            "function": "DomainHostCreate",
+            "status": "doing",
+            "creationDate": "2017-05-01T00:00:00+02:00",
+        }))
+            .unwrap();
+        assert_eq!(TaskStatus::Doing, task.status);
+        assert!(!task.status.is_final());
+        assert!(TaskStatus::parse("error").is_final());
+    }
+}
@@ -0,0 +1,119 @@
+//! # Name servers
+//!
+//! Name servers declared at the registry for a domain (`/domain/{domain}/nameServer`).
+//! Hosts inside the domain need a glue record, see `domain::glue`.
+//!
+
+use client::OVHClient;
+use domain::{domain_path, Task};
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A name server of the domain.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NameServer {
+    pub id: u64,
+    pub host: String,
+    /// Set for hosts inside the domain.
+    pub ip: Option<String>,
+    pub is_used: bool,
+    /// Whether the name server is being removed.
+    pub to_delete: bool,
+}
+
+impl Model for NameServer {
+    const NAME: &'static str = "NameServer";
+    const FIELDS: &'static [&'static str] = &["id", "host", "ip", "isUsed", "toDelete",
+                                              "nameServerType"];
+
+    fn from_json(value: &Value) -> io::Result<NameServer> {
+        let flag = |key: &str| value.get(key).and_then(Value::as_bool).unwrap_or(false);
+        Ok(NameServer {
+            id: rest::u64_field(value, "id")?,
+            host: rest::str_field(value, "host")?,
+            ip: rest::opt_str_field(value, "ip"),
+            is_used: flag("isUsed"),
+            to_delete: flag("toDelete"),
+        })
+    }
+}
+
+/// A name server to declare.
+#[derive(Debug,Clone,PartialEq)]
+pub struct NameServerSpec {
+    pub host: String,
+    pub ip: Option<String>,
+}
+
+impl NameServerSpec {
+    pub fn new(host: &str, ip: Option<&str>) -> NameServerSpec {
+        NameServerSpec {
+            host: host.to_string(),
+            ip: ip.map(String::from),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self.ip {
+            Some(ref ip) => json!({ "host": self.host, "ip": ip }),
+            None => json!({ "host": self.host }),
+        }
+    }
+}
+
+fn name_server_path(domain: &str) -> String {
+    format!("{}/nameServer", domain_path(domain))
+}
+
+pub fn name_server_ids(client: &OVHClient, domain: &str) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, &name_server_path(domain))?)
+}
+
+pub fn name_server(client: &OVHClient, domain: &str, id: u64) -> io::Result<NameServer> {
+    rest::decode(client,
+                 &rest::get(client, &format!("{}/{}", name_server_path(domain), id))?)
+}
+
+pub fn name_servers(client: &OVHClient, domain: &str) -> io::Result<Vec<NameServer>> {
+    name_server_ids(client, domain)?
+        .into_iter()
+        .map(|id| name_server(client, domain, id))
+        .collect()
+}
+
+/// Replace the name servers of the domain; the registry applies them through the returned task.
+pub fn update_name_servers(client: &OVHClient,
+                           domain: &str,
+                           name_servers: &[NameServerSpec])
+                           -> io::Result<Task> {
+    let body = json!({
+        "nameServers": name_servers.iter().map(NameServerSpec::to_json).collect::<Vec<Value>>(),
+    });
+    rest::decode(client,
+                 &rest::post(client, &format!("{}/nameServers/update", domain_path(domain)), &body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NameServer, NameServerSpec};
+    use rest::Model;
+
+    #[test]
+    fn test_name_server_json() {
+        let ns = NameServer::from_json(&json!({
+            "id": 7,
+            "host": "ns1.example.com",
+            "ip": "192.0.2.1",
+            "isUsed": true,
+            "toDelete": false,
+        }))
+            .unwrap();
+        assert_eq!(Some("192.0.2.1".to_string()), ns.ip);
+        assert!(ns.is_used);
+        assert_eq!(json!({ "host": "dns.ovh.net" }),
+                   NameServerSpec::new("dns.ovh.net", None).to_json());
+    }
+}
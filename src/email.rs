@@ -0,0 +1,228 @@
+//! # Email
+//!
+//! Mail authentication records of a domain: SPF, DKIM and DMARC.
+//!
+//! `recommended_records` builds the records from the mail configuration of the
+//! domain (`/email/domain/{domain}`) and a `MailPolicy`, `plan` compares them with
+//! the zone and `MailPlan::preview` shows the diff before `apply` writes it.
+//!
+
+use client::OVHClient;
+use dns::{self, Record, RecordSpec};
+use rest;
+
+use std::fmt::Write;
+use std::io;
+
+use serde_json::Value;
+
+/// What the SPF and DMARC records should say.
+#[derive(Debug,Clone,PartialEq)]
+pub struct MailPolicy {
+    /// Domains allowed to send mail, as SPF includes.
+    pub spf_includes: Vec<String>,
+    /// SPF qualifier for other senders: `~all` (soft fail) or `-all`.
+    pub spf_all: String,
+    /// DMARC policy: `none`, `quarantine` or `reject`.
+    pub dmarc_policy: String,
+    /// Address receiving the DMARC aggregate reports.
+    pub dmarc_report: Option<String>,
+    pub ttl: u32,
+}
+
+impl Default for MailPolicy {
+    /// Mail sent by OVH only, DMARC in monitoring mode.
+    fn default() -> MailPolicy {
+        MailPolicy {
+            spf_includes: vec!["mx.ovh.com".to_string()],
+            spf_all: "~all".to_string(),
+            dmarc_policy: "none".to_string(),
+            dmarc_report: None,
+            ttl: 3600,
+        }
+    }
+}
+
+impl MailPolicy {
+    pub fn spf(&self) -> String {
+        let mut spf = "v=spf1".to_string();
+        for include in &self.spf_includes {
+            spf.push_str(&format!(" include:{}", include));
+        }
+        spf.push(' ');
+        spf.push_str(&self.spf_all);
+        spf
+    }
+
+    pub fn dmarc(&self) -> String {
+        match self.dmarc_report {
+            Some(ref report) => format!("v=DMARC1; p={}; rua=mailto:{}", self.dmarc_policy, report),
+            None => format!("v=DMARC1; p={}", self.dmarc_policy),
+        }
+    }
+}
+
+/// DKIM records recommended by OVH for the mail offer of the domain.
+/// Domains without an OVH mail offer have none.
+pub fn dkim_records(client: &OVHClient, domain: &str) -> io::Result<Vec<RecordSpec>> {
+    let path = format!("/email/domain/{}/recommendedDNSRecords", rest::escape(domain));
+    let value = match rest::get(client, &path) {
+        Ok(value) => value,
+        Err(err) => {
+            debug!("No mail offer for {}: {}", domain, err);
+            return Ok(Vec::new());
+        }
+    };
+    Ok(rest::array(&value)?
+        .iter()
+        .filter_map(|item| {
+            let sub_domain = rest::opt_str_field(item, "subDomain").unwrap_or_default();
+            if !sub_domain.contains("_domainkey") {
+                return None;
+            }
+            Some(RecordSpec::new(&rest::opt_str_field(item, "fieldType")?,
+                                 &sub_domain,
+                                 &rest::opt_str_field(item, "target")?,
+                                 item.get("ttl").and_then(Value::as_u64).unwrap_or(3600) as u32))
+        })
+        .collect())
+}
+
+/// SPF, DMARC and DKIM records the domain should have.
+pub fn recommended_records(client: &OVHClient,
+                           domain: &str,
+                           policy: &MailPolicy)
+                           -> io::Result<Vec<RecordSpec>> {
+    let mut records = vec![RecordSpec::new("TXT", "", &quoted(&policy.spf()), policy.ttl),
+                           RecordSpec::new("TXT", "_dmarc", &quoted(&policy.dmarc()), policy.ttl)];
+    records.extend(dkim_records(client, domain)?);
+    Ok(records)
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+/// Which mail record a record is, if any: SPF and DMARC records share
+/// their name with unrelated TXT records.
+fn mail_key(field_type: &str, sub_domain: &str, target: &str) -> Option<String> {
+    let text = target.trim_matches('"');
+    if field_type == "TXT" && text.starts_with("v=spf1") {
+        Some(format!("spf:{}", sub_domain))
+    } else if field_type == "TXT" && text.starts_with("v=DMARC1") {
+        Some(format!("dmarc:{}", sub_domain))
+    } else if sub_domain.contains("_domainkey") {
+        Some(format!("dkim:{}", sub_domain))
+    } else {
+        None
+    }
+}
+
+/// Changes to bring the zone to the recommended mail records.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct MailPlan {
+    pub create: Vec<RecordSpec>,
+    /// Existing records and their new content.
+    pub update: Vec<(Record, RecordSpec)>,
+    pub unchanged: Vec<Record>,
+}
+
+impl MailPlan {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty()
+    }
+
+    /// Human readable diff, one line per record.
+    pub fn preview(&self) -> String {
+        let mut preview = String::new();
+        for spec in &self.create {
+            let _ = writeln!(preview, "+ {} {} {}", spec.sub_domain, spec.field_type, spec.target);
+        }
+        for (record, spec) in &self.update {
+            let _ = writeln!(preview, "- {} {} {}", record.sub_domain, record.field_type, record.target);
+            let _ = writeln!(preview, "+ {} {} {}", spec.sub_domain, spec.field_type, spec.target);
+        }
+        for record in &self.unchanged {
+            let _ = writeln!(preview, "= {} {} {}", record.sub_domain, record.field_type, record.target);
+        }
+        preview
+    }
+}
+
+/// Compare the desired mail records with the records of the zone.
+pub fn diff(existing: &[Record], desired: &[RecordSpec]) -> MailPlan {
+    let mut plan = MailPlan::default();
+    for spec in desired {
+        let key = mail_key(&spec.field_type, &spec.sub_domain, &spec.target);
+        let current = existing.iter().find(|record| {
+            record.field_type == spec.field_type &&
+            mail_key(&record.field_type, &record.sub_domain, &record.target) == key
+        });
+        match current {
+            Some(record) if record.target == spec.target => plan.unchanged.push(record.clone()),
+            Some(record) => plan.update.push((record.clone(), spec.clone())),
+            None => plan.create.push(spec.clone()),
+        }
+    }
+    plan
+}
+
+/// Plan the mail records of a domain whose zone is hosted by OVH.
+pub fn plan(client: &OVHClient, domain: &str, policy: &MailPolicy) -> io::Result<MailPlan> {
+    let desired = recommended_records(client, domain, policy)?;
+    Ok(diff(&dns::records(client, domain)?, &desired))
+}
+
+/// Apply a plan and refresh the zone.
+pub fn apply(client: &OVHClient, domain: &str, plan: &MailPlan) -> io::Result<()> {
+    if plan.is_empty() {
+        return Ok(());
+    }
+    for (record, spec) in &plan.update {
+        dns::update_record(client, domain, record.id, spec)?;
+    }
+    for spec in &plan.create {
+        dns::create_record(client, domain, spec)?;
+    }
+    dns::refresh(client, domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, MailPolicy};
+    use dns::{Record, RecordSpec};
+
+    fn record(id: u64, field_type: &str, sub_domain: &str, target: &str) -> Record {
+        Record {
+            id,
+            zone: "example.com".to_string(),
+            field_type: field_type.to_string(),
+            sub_domain: sub_domain.to_string(),
+            target: target.to_string(),
+            ttl: 3600,
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let mut policy = MailPolicy::default();
+        assert_eq!("v=spf1 include:mx.ovh.com ~all", policy.spf());
+        policy.dmarc_report = Some("dmarc@example.com".to_string());
+        assert_eq!("v=DMARC1; p=none; rua=mailto:dmarc@example.com", policy.dmarc());
+    }
+
+    #[test]
+    fn test_diff() {
+        let existing = vec![record(1, "TXT", "", "\"google-site-verification=abc\""),
+                            record(2, "TXT", "", "\"v=spf1 a ~all\""),
+                            record(3, "TXT", "_dmarc", "\"v=DMARC1; p=none\"")];
+        let desired = vec![RecordSpec::new("TXT", "", "\"v=spf1 include:mx.ovh.com ~all\"", 3600),
+                           RecordSpec::new("TXT", "_dmarc", "\"v=DMARC1; p=none\"", 3600),
+                           RecordSpec::new("CNAME", "ovhmo1._domainkey", "ovhmo1.example.net.", 3600)];
+        let plan = diff(&existing, &desired);
+        assert_eq!(2, plan.update[0].0.id);
+        assert_eq!(3, plan.unchanged[0].id);
+        assert_eq!("ovhmo1._domainkey", plan.create[0].sub_domain);
+        assert!(plan.preview().contains("-  TXT \"v=spf1 a ~all\""));
+    }
+}
@@ -0,0 +1,322 @@
+//! # Endpoint
+//!
+//! Typed endpoint definitions, for routes the crate does not cover yet.
+//! An `ApiEndpoint` describes its method, path template, request and answer types;
+//! `execute` sends it through the same path as the built-in modules
+//! (correlation ids, audit log, retry budget) and `paginate` crawls listings.
+//!
+//! ```no_run
+//! use ovh::endpoint::{self, ApiEndpoint};
+//! use ovh::OVHClient;
+//! use std::io;
+//! use ovh::Value;
+//!
+//! struct VrackDescription {
+//!     vrack: String,
+//! }
+//!
+//! impl ApiEndpoint for VrackDescription {
+//!     type Request = ();
+//!     type Response = String;
+//!     const METHOD: &'static str = "GET";
+//!     const PATH: &'static str = "/vrack/{serviceName}";
+//!
+//!     fn path_params(&self) -> Vec<(&'static str, String)> {
+//!         vec![("serviceName", self.vrack.clone())]
+//!     }
+//!
+//!     fn parse_response(value: &Value) -> io::Result<String> {
+//!         Ok(value.get("description").and_then(Value::as_str).unwrap_or("").to_string())
+//!     }
+//! }
+//!
+//! let client = OVHClient::new();
+//! let description = endpoint::execute(&client, &VrackDescription { vrack: "pn-123".to_string() });
+//! ```
+//!
+//! With the `derive` feature, `#[derive(OvhEndpoint)]` writes the implementation
+//! from attributes; the body is a JSON object of the `body` fields and the answer
+//! is decoded through `FromResponse`:
+//!
+//! ```ignore
+//! #[derive(OvhEndpoint)]
+//! #[ovh(method = "POST", path = "/vrack/{serviceName}/ip", response = "ovh::Value")]
+//! struct AddVrackBlock {
+//!     #[ovh(path = "serviceName")]
+//!     vrack: String,
+//!     #[ovh(body)]
+//!     block: String,
+//! }
+//! ```
+//!
+
+use client::OVHClient;
+use pagination::Paginator;
+use rest::{self, Model};
+
+use std::io;
+
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+
+/// A typed API route.
+pub trait ApiEndpoint {
+    /// Body of the request, `()` for routes without one.
+    type Request: Serialize;
+    type Response;
+
+    const METHOD: &'static str;
+    /// Path with `{name}` placeholders, e.g. `/dedicated/server/{serviceName}`.
+    const PATH: &'static str;
+
+    /// Values of the placeholders of `PATH`.
+    fn path_params(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Query string parameters.
+    fn query_params(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// The body of the request, none by default.
+    fn request(&self) -> io::Result<Option<Self::Request>> {
+        Ok(None)
+    }
+
+    fn parse_response(value: &Value) -> io::Result<Self::Response>;
+
+    /// The path with placeholders replaced and the query string appended.
+    fn path(&self) -> io::Result<String> {
+        let mut path = render_path(Self::PATH, &self.path_params())?;
+        let query = self.query_params();
+        for (index, &(key, ref value)) in query.iter().enumerate() {
+            path.push(if index == 0 { '?' } else { '&' });
+            path.push_str(&format!("{}={}", key, rest::escape(value)));
+        }
+        Ok(path)
+    }
+}
+
+/// Replace the `{name}` placeholders of a template with escaped values.
+pub fn render_path(template: &str, params: &[(&str, String)]) -> io::Result<String> {
+    let mut path = String::with_capacity(template.len());
+    let mut rest_of = template;
+    while let Some(start) = rest_of.find('{') {
+        let end = rest_of[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| invalid_input(format!("unclosed placeholder in {}", template)))?;
+        let name = &rest_of[start + 1..end];
+        let value = params.iter()
+            .find(|&&(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| invalid_input(format!("missing {} for {}", name, template)))?;
+        path.push_str(&rest_of[..start]);
+        path.push_str(&rest::escape(value));
+        rest_of = &rest_of[end + 1..];
+    }
+    path.push_str(rest_of);
+    Ok(path)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Answers which can be decoded without a custom `parse_response`.
+pub trait FromResponse: Sized {
+    fn from_response(value: &Value) -> io::Result<Self>;
+}
+
+impl FromResponse for Value {
+    fn from_response(value: &Value) -> io::Result<Value> {
+        Ok(value.clone())
+    }
+}
+
+impl FromResponse for () {
+    fn from_response(_: &Value) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FromResponse for String {
+    fn from_response(value: &Value) -> io::Result<String> {
+        value.as_str().map(str::to_string).ok_or_else(|| rest::invalid_data("expected a string"))
+    }
+}
+
+impl FromResponse for u64 {
+    fn from_response(value: &Value) -> io::Result<u64> {
+        value.as_u64().ok_or_else(|| rest::invalid_data("expected an integer"))
+    }
+}
+
+impl FromResponse for bool {
+    fn from_response(value: &Value) -> io::Result<bool> {
+        value.as_bool().ok_or_else(|| rest::invalid_data("expected a boolean"))
+    }
+}
+
+impl FromResponse for Vec<String> {
+    fn from_response(value: &Value) -> io::Result<Vec<String>> {
+        rest::string_list(value)
+    }
+}
+
+impl FromResponse for Vec<u64> {
+    fn from_response(value: &Value) -> io::Result<Vec<u64>> {
+        rest::u64_list(value)
+    }
+}
+
+/// Curated models, so a derived endpoint can answer one.
+/// Unknown fields are ignored, as with the lenient field policy.
+impl<T: Model> FromResponse for T {
+    fn from_response(value: &Value) -> io::Result<T> {
+        T::from_json(value)
+    }
+}
+
+/// Serialize a request, or a field of a derived endpoint body.
+#[doc(hidden)]
+pub fn to_json<T: Serialize>(value: &T) -> io::Result<Value> {
+    serde_json::to_value(value).map_err(|err| invalid_input(format!("cannot serialize: {}", err)))
+}
+
+/// Build the body of a derived endpoint.
+#[doc(hidden)]
+pub fn json_object(fields: Vec<(&str, Value)>) -> Value {
+    let mut map = Map::new();
+    for (key, value) in fields {
+        map.insert(key.to_string(), value);
+    }
+    Value::Object(map)
+}
+
+/// Send the request of an endpoint and parse its answer.
+pub fn execute<E: ApiEndpoint>(client: &OVHClient, endpoint: &E) -> io::Result<E::Response> {
+    let body = match endpoint.request()? {
+        Some(request) => Some(to_json(&request)?),
+        None => None,
+    };
+    let value = rest::call(client, E::METHOD, &endpoint.path()?, body.as_ref())?;
+    E::parse_response(&value)
+}
+
+/// Crawl a listing endpoint: list the ids it returns, then fetch them lazily.
+pub fn paginate<'a, E: ApiEndpoint>(client: &'a OVHClient,
+                                    endpoint: &E)
+                                    -> io::Result<Paginator<'a>> {
+    if E::METHOD != "GET" {
+        return Err(invalid_input(format!("cannot paginate {} {}", E::METHOD, E::PATH)));
+    }
+    Paginator::new(client, &endpoint.path()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{execute, json_object, render_path, to_json, ApiEndpoint, FromResponse};
+    use me::subaccount::SubAccount;
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+    use std::io;
+    use testutil;
+
+    struct ServerTasks {
+        name: String,
+        status: Option<String>,
+    }
+
+    impl ApiEndpoint for ServerTasks {
+        type Request = ();
+        type Response = Value;
+        const METHOD: &'static str = "GET";
+        const PATH: &'static str = "/dedicated/server/{serviceName}/task";
+
+        fn path_params(&self) -> Vec<(&'static str, String)> {
+            vec![("serviceName", self.name.clone())]
+        }
+
+        fn query_params(&self) -> Vec<(&'static str, String)> {
+            self.status.iter().map(|status| ("status", status.clone())).collect()
+        }
+
+        fn parse_response(value: &Value) -> io::Result<Value> {
+            Ok(value.clone())
+        }
+    }
+
+    /// A typed request and answer.
+    struct CreateRecord {
+        zone: String,
+        target: String,
+    }
+
+    impl ApiEndpoint for CreateRecord {
+        type Request = BTreeMap<&'static str, String>;
+        type Response = u64;
+        const METHOD: &'static str = "POST";
+        const PATH: &'static str = "/domain/zone/{zoneName}/record";
+
+        fn path_params(&self) -> Vec<(&'static str, String)> {
+            vec![("zoneName", self.zone.clone())]
+        }
+
+        fn request(&self) -> io::Result<Option<Self::Request>> {
+            let mut request = BTreeMap::new();
+            request.insert("fieldType", "A".to_string());
+            request.insert("target", self.target.clone());
+            Ok(Some(request))
+        }
+
+        fn parse_response(value: &Value) -> io::Result<u64> {
+            value.get("id").and_then(Value::as_u64).ok_or_else(|| io::ErrorKind::InvalidData.into())
+        }
+    }
+
+    #[test]
+    fn test_execute_request() {
+        let (host, server) = testutil::serve(vec![(200, "{\"id\":42}")]);
+        let client = testutil::local_client(&host);
+        let endpoint = CreateRecord {
+            zone: "example.com".to_string(),
+            target: "192.0.2.1".to_string(),
+        };
+        assert_eq!(42, execute(&client, &endpoint).unwrap());
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /1.0/domain/zone/example.com/record "));
+        assert!(requests[0].ends_with("{\"fieldType\":\"A\",\"target\":\"192.0.2.1\"}"),
+                "{}",
+                requests[0]);
+    }
+
+    #[test]
+    fn test_path() {
+        let endpoint = ServerTasks {
+            name: "ns1.example.com".to_string(),
+            status: Some("todo".to_string()),
+        };
+        assert_eq!("/dedicated/server/ns1.example.com/task?status=todo",
+                   endpoint.path().unwrap());
+        assert!(render_path("/a/{b}", &[]).is_err());
+        assert!(render_path("/a/{b", &[("b", "c".to_string())]).is_err());
+        assert_eq!("/a/x%20y/c", render_path("/a/{b}/c", &[("b", "x y".to_string())]).unwrap());
+    }
+
+    #[test]
+    fn test_derive_helpers() {
+        let body = json_object(vec![("block", to_json(&"10.0.0.0/24").unwrap()),
+                                    ("count", to_json(&Some(2)).unwrap())]);
+        assert_eq!(json!({"block": "10.0.0.0/24", "count": 2}), body);
+        // no silent null for what JSON cannot hold
+        let mut by_pair = BTreeMap::new();
+        by_pair.insert((1, 2), "pair");
+        assert_eq!(io::ErrorKind::InvalidInput, to_json(&by_pair).unwrap_err().kind());
+        assert_eq!(vec![1, 2], Vec::<u64>::from_response(&json!([1, 2])).unwrap());
+        assert!(String::from_response(&json!(3)).is_err());
+        let account = SubAccount::from_response(&json!({"id": 7, "description": "shop"})).unwrap();
+        assert_eq!(Some("shop".to_string()), account.description);
+    }
+}
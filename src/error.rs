@@ -0,0 +1,355 @@
+//! # Error
+//!
+//! Errors of the credential loading, so applications can report a missing
+//! or invalid `Config.toml` instead of crashing, and errors answered by the API.
+//!
+//! The calls return `io::Result`; an error answered by the API carries an
+//! `ApiError`, found with `api_error`:
+//!
+//! ```no_run
+//! # fn run(client: &ovh::client::OVHClient) {
+//! if let Err(err) = client.send_raw("GET", "/me", "") {
+//!     match ovh::error::api_error(&err) {
+//!         Some(api) if api.is("INVALID_CREDENTIAL") => println!("validate the consumer key"),
+//!         Some(api) => println!("API error {}: {}", api.http_code, api.message),
+//!         None => println!("network error: {}", err),
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! The messages are in the language of the account. `ApiError::code` is a
+//! stable code to match on instead, and `set_message_mode` (or
+//! `OVH_ERROR_MESSAGES`: `localized`, `english` or `code`) chooses what the
+//! errors display, e.g. so that alerts do not depend on the language:
+//!
+//! ```no_run
+//! use ovh::error::{self, MessageMode};
+//!
+//! error::set_message_mode(MessageMode::Code);
+//! // "DELETE /ip/192.0.2.1 failed (404): Client::NotFound"
+//! ```
+//!
+//! `ClientBuilder::language` asks the API for messages in another language
+//! where it supports it.
+//!
+
+use throttle::RateLimitInfo;
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{self, Value};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The config file cannot be read.
+    Io(String, io::Error),
+    /// The config file is sealed and cannot be opened (see `seal`).
+    Sealed(String, io::Error),
+    /// The config file is not valid TOML.
+    Parse(String, String),
+    /// A key is missing from the config file or the environment, e.g. `default.endpoint`.
+    MissingKey(String),
+    /// The endpoint is neither a known name nor an `https://` URL.
+    UnknownEndpoint(String),
+    /// The proxy URL cannot be parsed (credentials hidden), see `proxy`.
+    InvalidProxy(String),
+    /// The API answered with an error status, boxed to keep `Error` small.
+    Api(Box<ApiError>),
+    /// A call failed without an answer of the API, or its answer cannot be used.
+    Call(io::Error),
+}
+
+/// Error answered by the API: a status >= 400 and its JSON body,
+/// `{"class": "...", "errorCode": "...", "httpCode": "...", "message": "..."}`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ApiError {
+    pub method: String,
+    pub query: String,
+    pub http_code: u16,
+    /// E.g. `INVALID_CREDENTIAL`, `NOT_CREDENTIAL`, `QUERY_TIME_OUT`, when the body has one.
+    pub error_code: Option<String>,
+    /// E.g. `Client::NotFound`, `Client::BadRequest`, when the body has one.
+    pub class: Option<String>,
+    /// Message of the body as answered, in the language of the account,
+    /// or the whole body when it is not JSON.
+    pub message: String,
+    pub correlation_id: Option<String>,
+    /// `X-Ovh-QueryId` of the answer, to quote to the OVH support.
+    pub query_id: Option<String>,
+    /// Rate limit communicated with the error, e.g. on `429`.
+    pub rate_limit: Option<RateLimitInfo>,
+}
+
+impl ApiError {
+    pub fn from_response(method: &str, query: &str, status: u16, body: &str) -> ApiError {
+        let value = serde_json::from_str::<Value>(body).unwrap_or(Value::Null);
+        let field = |key: &str| value.get(key).and_then(Value::as_str).map(String::from);
+        ApiError {
+            method: method.to_string(),
+            query: query.to_string(),
+            http_code: status,
+            error_code: field("errorCode"),
+            class: field("class"),
+            message: field("message").unwrap_or_else(|| body.to_string()),
+            correlation_id: None,
+            query_id: None,
+            rate_limit: None,
+        }
+    }
+
+    /// Whether the body has the given `errorCode`.
+    pub fn is(&self, error_code: &str) -> bool {
+        self.error_code.as_ref().is_some_and(|code| code == error_code)
+    }
+
+    /// A code which does not depend on the language of the account: the
+    /// `errorCode`, else the `class`, else `HTTP_` and the status.
+    pub fn code(&self) -> String {
+        self.error_code
+            .clone()
+            .or_else(|| self.class.clone())
+            .unwrap_or_else(|| format!("HTTP_{}", self.http_code))
+    }
+
+    /// An English message for the code, or the status, of the error; `None`
+    /// when there is none and only `message` describes it.
+    pub fn english_message(&self) -> Option<&'static str> {
+        let by_code = self.error_code.as_ref().and_then(|code| {
+            ENGLISH_MESSAGES.iter().find(|&&(known, _)| known == code).map(|&(_, text)| text)
+        });
+        by_code.or_else(|| {
+            Some(match self.http_code {
+                400 => "Invalid request",
+                401 => "Authentication failed",
+                403 => "This call is not allowed",
+                404 => "The requested object does not exist",
+                409 => "The request conflicts with the state of the object",
+                429 => "Too many requests",
+                500..=599 => "Internal error of the API",
+                _ => return None,
+            })
+        })
+    }
+
+    /// The error as displayed with `mode`.
+    pub fn describe(&self, mode: MessageMode) -> String {
+        let message = match mode {
+            MessageMode::Localized => self.message.clone(),
+            MessageMode::English => {
+                self.english_message().map_or_else(|| self.message.clone(), String::from)
+            }
+            MessageMode::Code => self.code(),
+        };
+        let mut text = format!("{} {} failed ({}): {}",
+                               self.method,
+                               self.query,
+                               self.http_code,
+                               message);
+        if let Some(ref id) = self.correlation_id {
+            text.push_str(&format!(" [correlation id {}]", id));
+        }
+        text
+    }
+}
+
+/// English messages of the `errorCode`s of the authentication.
+const ENGLISH_MESSAGES: &[(&str, &str)] =
+    &[("INVALID_KEY", "This application key is invalid"),
+      ("INVALID_CREDENTIAL", "This credential is not valid"),
+      ("NOT_CREDENTIAL", "This credential does not exist"),
+      ("NOT_GRANTED_CALL", "This call has not been granted"),
+      ("INVALID_SIGNATURE", "Invalid signature"),
+      ("QUERY_TIME_OUT", "The query is out of time")];
+
+/// What the errors answered by the API display.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum MessageMode {
+    /// The message of the API, in the language of the account.
+    Localized,
+    /// An English message where the code or the status has one, else the
+    /// message of the API.
+    English,
+    /// The stable code only, see `ApiError::code`.
+    Code,
+}
+
+impl MessageMode {
+    fn parse(value: &str) -> Option<MessageMode> {
+        match value.to_lowercase().as_str() {
+            "localized" => Some(MessageMode::Localized),
+            "english" | "en" => Some(MessageMode::English),
+            "code" => Some(MessageMode::Code),
+            _ => None,
+        }
+    }
+
+    fn to_index(self) -> usize {
+        match self {
+            MessageMode::Localized => 1,
+            MessageMode::English => 2,
+            MessageMode::Code => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<MessageMode> {
+        match index {
+            1 => Some(MessageMode::Localized),
+            2 => Some(MessageMode::English),
+            3 => Some(MessageMode::Code),
+            _ => None,
+        }
+    }
+}
+
+/// Mode chosen by `set_message_mode`, 0 when unset.
+static MESSAGE_MODE: AtomicUsize = AtomicUsize::new(0);
+
+/// Choose what the errors of the whole process display.
+pub fn set_message_mode(mode: MessageMode) {
+    MESSAGE_MODE.store(mode.to_index(), Ordering::SeqCst);
+}
+
+/// The mode set with `set_message_mode`, else `OVH_ERROR_MESSAGES`
+/// (`localized`, `english` or `code`), else `Localized`.
+pub fn message_mode() -> MessageMode {
+    if let Some(mode) = MessageMode::from_index(MESSAGE_MODE.load(Ordering::SeqCst)) {
+        return mode;
+    }
+    env::var("OVH_ERROR_MESSAGES")
+        .ok()
+        .and_then(|value| MessageMode::parse(&value))
+        .unwrap_or(MessageMode::Localized)
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.describe(message_mode()))
+    }
+}
+
+/// The `ApiError` carried by `err`, if it was answered by the API.
+pub fn api_error(err: &io::Error) -> Option<&ApiError> {
+    match err.get_ref().and_then(|inner| inner.downcast_ref::<Error>()) {
+        Some(Error::Api(api)) => Some(&**api),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref err) => write!(f, "cannot read {}: {}", path, err),
+            Error::Sealed(ref path, ref err) => write!(f, "cannot open sealed {}: {}", path, err),
+            Error::Parse(ref path, ref message) => {
+                write!(f, "invalid TOML in {}: {}", path, message)
+            }
+            Error::MissingKey(ref key) => write!(f, "{} is not set", key),
+            Error::UnknownEndpoint(ref endpoint) => write!(f, "unknown OVH endpoint {}", endpoint),
+            Error::InvalidProxy(ref url) => write!(f, "invalid proxy URL {}", url),
+            Error::Api(ref api) => api.fmt(f),
+            Error::Call(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(_, ref err) |
+            Error::Sealed(_, ref err) |
+            Error::Call(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// For the functions of the crate returning `io::Result`.
+/// The error is kept inside, see `api_error`.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        let kind = match err {
+            Error::Io(_, ref err) => err.kind(),
+            Error::MissingKey(_) => io::ErrorKind::NotFound,
+            Error::UnknownEndpoint(_) |
+            Error::InvalidProxy(_) => io::ErrorKind::InvalidInput,
+            Error::Sealed(..) |
+            Error::Parse(..) => io::ErrorKind::InvalidData,
+            Error::Api(_) => io::ErrorKind::Other,
+            Error::Call(err) => return err,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// For `?` on the calls in functions returning `Error`, e.g. with the
+/// `Result` of the `prelude`. The error of the crate inside, if any, is taken out.
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let inner = err.into_inner().and_then(|inner| inner.downcast::<Error>().ok());
+            return *inner.expect("an error of the crate");
+        }
+        Error::Call(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{api_error, ApiError, Error, MessageMode};
+    use std::io;
+
+    #[test]
+    fn test_error() {
+        let err = Error::Io("Config.toml".to_string(),
+                            io::Error::new(io::ErrorKind::NotFound, "No such file"));
+        assert_eq!("cannot read Config.toml: No such file", err.to_string());
+        assert_eq!(io::ErrorKind::NotFound, io::Error::from(err).kind());
+        let err = io::Error::from(Error::MissingKey("ovh-eu.consumer_key".to_string()));
+        assert_eq!("ovh-eu.consumer_key is not set", err.to_string());
+        assert_eq!(None, api_error(&err));
+    }
+
+    #[test]
+    fn test_api_error() {
+        let body = r#"{"errorCode":"INVALID_CREDENTIAL","httpCode":"403 Forbidden",
+                       "message":"This credential is not valid"}"#;
+        let mut api = ApiError::from_response("GET", "/me", 403, body);
+        assert!(api.is("INVALID_CREDENTIAL"));
+        api.correlation_id = Some("run-1".to_string());
+        let err = io::Error::from(Error::Api(Box::new(api.clone())));
+        assert_eq!("GET /me failed (403): This credential is not valid [correlation id run-1]",
+                   err.to_string());
+        assert_eq!(Some(&api), api_error(&err));
+
+        let api = ApiError::from_response("POST", "/me", 502, "Bad Gateway");
+        assert_eq!((None, "Bad Gateway"), (api.error_code, api.message.as_str()));
+    }
+
+    #[test]
+    fn test_message_mode() {
+        let body = r#"{"class":"Client::NotFound","message":"L'objet n'existe pas"}"#;
+        let api = ApiError::from_response("GET", "/ip/192.0.2.1", 404, body);
+        assert_eq!("Client::NotFound", api.code());
+        assert_eq!("GET /ip/192.0.2.1 failed (404): L'objet n'existe pas",
+                   api.describe(MessageMode::Localized));
+        assert_eq!("GET /ip/192.0.2.1 failed (404): The requested object does not exist",
+                   api.describe(MessageMode::English));
+        assert_eq!("GET /ip/192.0.2.1 failed (404): Client::NotFound",
+                   api.describe(MessageMode::Code));
+
+        let body = r#"{"errorCode":"NOT_GRANTED_CALL","message":"Cet appel n'est pas autorisé"}"#;
+        let api = ApiError::from_response("GET", "/me", 403, body);
+        assert_eq!(("NOT_GRANTED_CALL".to_string(), Some("This call has not been granted")),
+                   (api.code(), api.english_message()));
+
+        let api = ApiError::from_response("GET", "/me", 418, "teapot");
+        assert_eq!(("HTTP_418".to_string(), None), (api.code(), api.english_message()));
+        assert_eq!("GET /me failed (418): teapot", api.describe(MessageMode::English));
+        assert_eq!(Some(MessageMode::English), MessageMode::parse("English"));
+    }
+}
@@ -0,0 +1,183 @@
+//! # Guard
+//!
+//! Opt-in safety layer against accidental destruction: with
+//! `client.require_confirmation` set (or `OVH_REQUIRE_CONFIRMATION=1`), destructive
+//! calls — service termination, reinstallation, deletion of instances, volumes,
+//! clusters, ... — are refused unless they run within `confirmed`, naming the
+//! resource they destroy.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::client::OVHClient;
+//! use ovh::cloud::instance;
+//! use ovh::guard::{self, Confirmation};
+//!
+//! let mut client = OVHClient::try_new()?;
+//! client.require_confirmation = true;
+//! // refused
+//! assert!(instance::delete_instance(&client, "project", "i-123").is_err());
+//! guard::confirmed(Confirmation::IAmSure("i-123".to_string()),
+//!                  || instance::delete_instance(&client, "project", "i-123"))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The check is done on the route, whatever the call path (typed functions,
+//! `OVHClient::send_raw`, ...), so generic code cannot bypass it.
+//!
+//! The futures of `AsyncClient` are checked against the confirmations in
+//! effect where they are created: `confirmed(.., || client.delete(..))` returns
+//! a call which may be awaited afterwards, on any thread.
+//!
+
+use std::cell::RefCell;
+use std::io;
+
+/// Destructive routes: method and path, `*` matching one segment.
+/// The resource is the last segment matched by a `*`.
+const DESTRUCTIVE: &[(&str, &str)] =
+    &[("POST", "/services/*/terminate"),
+      ("POST", "/*/*/*/terminate"),
+      ("POST", "/*/*/terminate"),
+      ("POST", "/cloud/project/*/instance/*/reinstall"),
+      ("POST", "/dedicated/server/*/install/start"),
+      ("DELETE", "/cloud/project/*"),
+      ("DELETE", "/cloud/project/*/instance/*"),
+      ("DELETE", "/cloud/project/*/volume/*"),
+      ("DELETE", "/cloud/project/*/volume/snapshot/*"),
+      ("DELETE", "/cloud/project/*/kube/*"),
+      ("DELETE", "/cloud/project/*/database/*/*"),
+      ("DELETE", "/cloud/project/*/storage/*"),
+      ("DELETE", "/domain/zone/*"),
+      ("DELETE", "/me/installationTemplate/*")];
+
+/// Explicit consent to destroy a resource.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Confirmation {
+    /// The name (or id) of the resource, e.g. the instance id or the zone name.
+    IAmSure(String),
+}
+
+thread_local!(static CONFIRMED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) });
+
+/// Removes the confirmation when `confirmed` returns, or panics.
+struct Pop;
+
+impl Drop for Pop {
+    fn drop(&mut self) {
+        CONFIRMED.with(|confirmed| confirmed.borrow_mut().pop());
+    }
+}
+
+/// Run `f` with the destruction of the named resource allowed on this thread.
+pub fn confirmed<T, F: FnOnce() -> T>(confirmation: Confirmation, f: F) -> T {
+    let Confirmation::IAmSure(name) = confirmation;
+    CONFIRMED.with(|confirmed| confirmed.borrow_mut().push(name));
+    let _pop = Pop;
+    f()
+}
+
+/// The resource destroyed by a call, if the call is destructive.
+pub fn destructive_resource(method: &str, query: &str) -> Option<String> {
+    let path = query.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    DESTRUCTIVE.iter()
+        .filter(|&&(pattern_method, _)| pattern_method == method)
+        .filter_map(|&(_, pattern)| {
+            let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+            if pattern.len() != segments.len() {
+                return None;
+            }
+            let mut resource = None;
+            for (expected, segment) in pattern.iter().zip(&segments) {
+                if *expected == "*" {
+                    resource = Some(*segment);
+                } else if expected != segment {
+                    return None;
+                }
+            }
+            resource
+        })
+        .next()
+        .map(unescape)
+}
+
+/// Decode the `%XX` escapes of a path segment, see `rest::escape`.
+fn unescape(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = segment.get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The resources whose destruction is allowed on this thread.
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+pub(crate) fn confirmations() -> Vec<String> {
+    CONFIRMED.with(|confirmed| confirmed.borrow().clone())
+}
+
+/// Refuse a destructive call which was not confirmed on this thread.
+pub(crate) fn check(method: &str, query: &str) -> io::Result<()> {
+    CONFIRMED.with(|confirmed| check_against(method, query, &confirmed.borrow()))
+}
+
+/// Refuse a destructive call whose resource is not in `confirmed`.
+pub(crate) fn check_against(method: &str, query: &str, confirmed: &[String]) -> io::Result<()> {
+    let resource = match destructive_resource(method, query) {
+        Some(resource) => resource,
+        None => return Ok(()),
+    };
+    if confirmed.contains(&resource) {
+        return Ok(());
+    }
+    Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                       format!("{} {} destroys {}, run it within \
+                                guard::confirmed(Confirmation::IAmSure(\"{}\"), ...)",
+                               method,
+                               query,
+                               resource,
+                               resource)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, confirmed, destructive_resource, Confirmation};
+
+    #[test]
+    fn test_destructive_resource() {
+        assert_eq!(Some("i-123".to_string()),
+                   destructive_resource("DELETE", "/cloud/project/p1/instance/i-123"));
+        assert_eq!(Some("example.com".to_string()),
+                   destructive_resource("POST", "/domain/zone/example.com/terminate"));
+        assert_eq!(Some("42".to_string()), destructive_resource("POST", "/services/42/terminate"));
+        assert_eq!(Some("a b".to_string()), destructive_resource("DELETE", "/domain/zone/a%20b"));
+        assert_eq!(None, destructive_resource("GET", "/cloud/project/p1/instance/i-123"));
+        assert_eq!(None, destructive_resource("DELETE", "/domain/zone/example.com/record/1"));
+    }
+
+    #[test]
+    fn test_confirmed() {
+        let query = "/cloud/project/p1/instance/i-123";
+        assert!(check("DELETE", query).is_err());
+        assert!(check("GET", query).is_ok());
+        confirmed(Confirmation::IAmSure("i-123".to_string()),
+                  || assert!(check("DELETE", query).is_ok()));
+        confirmed(Confirmation::IAmSure("i-456".to_string()),
+                  || assert!(check("DELETE", query).is_err()));
+        assert!(check("DELETE", query).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! # Health
+//!
+//! Bring-up diagnostics: whether the endpoint is reachable over TLS,
+//! whether the local clock drifts from the API clock, and whether the
+//! credential is valid and what its rules allow, gathered into one report.
+//!
+//! `ovh doctor` prints this report.
+//!
+
+use auth::{self, ApiCredential};
+use client::OVHClient;
+
+use std::fmt;
+use std::io;
+
+use chrono::Local;
+
+use serde_json::Value;
+
+/// Drift of the local clock tolerated before warning, in seconds.
+pub const MAX_DRIFT: i64 = 30;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+    /// Not run, as a previous check failed.
+    Skipped,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Failed => "failed",
+            CheckStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// Outcome of a single check.
+#[derive(Debug,Clone,PartialEq)]
+pub struct HealthCheck {
+    /// `endpoint`, `tls`, `clock`, `credential` or `rules`.
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: &str) -> HealthCheck {
+        HealthCheck {
+            name,
+            status,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct HealthReport {
+    pub host: String,
+    /// Where the credential was loaded from, see `Credential::source`.
+    pub credential_source: String,
+    pub checks: Vec<HealthCheck>,
+    /// API clock minus local clock, in seconds, when the API could be asked.
+    pub time_drift: Option<i64>,
+    /// The consumer key, when the API accepted it.
+    pub credential: Option<ApiCredential>,
+}
+
+impl HealthReport {
+    /// Whether no check failed; warnings don't prevent the client from working.
+    pub fn is_healthy(&self) -> bool {
+        self.checks.iter().all(|check| check.status != CheckStatus::Failed)
+    }
+
+    pub fn check(&self, name: &str) -> Option<&HealthCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let checks: Vec<Value> = self.checks
+            .iter()
+            .map(|check| {
+                json!({
+                    "name": check.name,
+                    "status": check.status.as_str(),
+                    "detail": check.detail,
+                })
+            })
+            .collect();
+        json!({
+            "host": self.host,
+            "credentialSource": self.credential_source,
+            "healthy": self.is_healthy(),
+            "timeDrift": self.time_drift,
+            "credentialId": self.credential.as_ref().map(|credential| credential.id),
+            "checks": checks,
+        })
+    }
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} (credentials from {})", self.host, self.credential_source)?;
+        for check in &self.checks {
+            writeln!(f, "  [{:<7}] {:<10} {}", check.status.as_str(), check.name, check.detail)?;
+        }
+        write!(f, "{}", if self.is_healthy() { "healthy" } else { "unhealthy" })
+    }
+}
+
+/// Whether a transport error comes from the TLS layer rather than the network.
+fn is_tls_error(err: &io::Error) -> bool {
+    let message = err.to_string().to_lowercase();
+    ["ssl", "tls", "certificate", "handshake"].iter().any(|word| message.contains(word))
+}
+
+/// Checks of the connection to the API, from the outcome of `/auth/time`.
+fn connection_checks(host: &str, server_time: &io::Result<u64>) -> Vec<HealthCheck> {
+    match *server_time {
+        Ok(_) => {
+            vec![HealthCheck::new("endpoint", CheckStatus::Ok, &format!("{} answers", host)),
+                 HealthCheck::new("tls", CheckStatus::Ok, "certificate verified")]
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+            vec![HealthCheck::new("endpoint", CheckStatus::Failed, &err.to_string()),
+                 HealthCheck::new("tls", CheckStatus::Ok, "certificate verified")]
+        }
+        Err(ref err) if is_tls_error(err) => {
+            vec![HealthCheck::new("endpoint",
+                                  CheckStatus::Ok,
+                                  &format!("{} accepts connections", host)),
+                 HealthCheck::new("tls", CheckStatus::Failed, &err.to_string())]
+        }
+        Err(ref err) => {
+            vec![HealthCheck::new("endpoint", CheckStatus::Failed, &err.to_string()),
+                 HealthCheck::new("tls", CheckStatus::Skipped, "endpoint unreachable")]
+        }
+    }
+}
+
+fn clock_check(drift: Option<i64>) -> HealthCheck {
+    match drift {
+        None => HealthCheck::new("clock", CheckStatus::Skipped, "API clock unknown"),
+        Some(drift) if drift.abs() <= MAX_DRIFT => {
+            HealthCheck::new("clock", CheckStatus::Ok, &format!("drift of {}s", drift))
+        }
+        Some(drift) => {
+            // Requests are signed with the API clock, but a cached or stale delta
+            // makes them fail with "Invalid signature".
+            HealthCheck::new("clock",
+                             CheckStatus::Warning,
+                             &format!("drift of {}s, synchronize the local clock (NTP)", drift))
+        }
+    }
+}
+
+/// Summary of the rules of a credential, e.g. `GET /me, GET,PUT /domain/*`.
+pub fn rules_summary(credential: &ApiCredential) -> String {
+    let mut paths: Vec<(&str, Vec<&str>)> = Vec::new();
+    for rule in &credential.rules {
+        match paths.iter_mut().find(|entry| entry.0 == rule.path) {
+            Some(entry) => entry.1.push(&rule.method),
+            None => paths.push((&rule.path, vec![&rule.method])),
+        }
+    }
+    paths.iter()
+        .map(|&(path, ref methods)| format!("{} {}", methods.join(","), path))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn credential_checks(credential: &io::Result<ApiCredential>) -> Vec<HealthCheck> {
+    let credential = match *credential {
+        Ok(ref credential) => credential,
+        Err(ref err) => {
+            return vec![HealthCheck::new("credential", CheckStatus::Failed, &err.to_string()),
+                        HealthCheck::new("rules", CheckStatus::Skipped, "credential unknown")];
+        }
+    };
+    let mut detail = format!("credential {} is {}", credential.id, credential.status);
+    if let Some(expiration) = credential.expiration {
+        detail.push_str(&format!(", expires on {}", expiration));
+    }
+    let status = if credential.status == "validated" {
+        CheckStatus::Ok
+    } else {
+        CheckStatus::Failed
+    };
+    let rules = if credential.rules.is_empty() {
+        HealthCheck::new("rules", CheckStatus::Warning, "no rule, every call will be refused")
+    } else if credential.is_over_privileged() {
+        HealthCheck::new("rules",
+                         CheckStatus::Warning,
+                         &format!("{} (full access, consider narrower rules)",
+                                  rules_summary(credential)))
+    } else {
+        HealthCheck::new("rules", CheckStatus::Ok, &rules_summary(credential))
+    };
+    vec![HealthCheck::new("credential", status, &detail), rules]
+}
+
+impl OVHClient {
+    /// Run the bring-up diagnostics against the endpoint of the credential.
+    ///
+    /// Never fails: every problem is reported as a failed check.
+    pub fn healthcheck(&self) -> HealthReport {
+        let host = self.credential.host.clone();
+        let server_time = OVHClient::server_time_via(&self.network, &host);
+        let mut checks = connection_checks(&host, &server_time);
+        let time_drift = server_time.as_ref()
+            .ok()
+            .map(|time| *time as i64 - Local::now().timestamp());
+        checks.push(clock_check(time_drift));
+
+        let mut credential = None;
+        if server_time.is_err() {
+            checks.push(HealthCheck::new("credential",
+                                         CheckStatus::Skipped,
+                                         "endpoint unreachable"));
+            checks.push(HealthCheck::new("rules", CheckStatus::Skipped, "credential unknown"));
+        } else if let Some(oauth2) = self.credential.oauth2() {
+            let (status, detail) = match oauth2.access_token_via(&self.network) {
+                Ok(_) => (CheckStatus::Ok, format!("OAuth2 client {}", oauth2.client_id)),
+                Err(err) => (CheckStatus::Failed, err.to_string()),
+            };
+            checks.push(HealthCheck::new("credential", status, &detail));
+            checks.push(HealthCheck::new("rules", CheckStatus::Skipped, "granted by IAM policies"));
+        } else if self.credential.application_key.is_empty() {
+            checks.push(HealthCheck::new("credential", CheckStatus::Failed, "no application key"));
+            checks.push(HealthCheck::new("rules", CheckStatus::Skipped, "credential unknown"));
+        } else if self.credential.consumer_key.is_empty() {
+            checks.push(HealthCheck::new("credential",
+                                         CheckStatus::Failed,
+                                         "no consumer key, request one first"));
+            checks.push(HealthCheck::new("rules", CheckStatus::Skipped, "credential unknown"));
+        } else {
+            let current = auth::current_credential(self);
+            checks.extend(credential_checks(&current));
+            credential = current.ok();
+        }
+
+        HealthReport {
+            host,
+            credential_source: self.credential.source().to_string(),
+            checks,
+            time_drift,
+            credential,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clock_check, connection_checks, credential_checks, rules_summary, CheckStatus,
+                HealthReport};
+    use auth::{AccessRule, ApiCredential};
+    use std::io;
+
+    fn credential(rules: &[(&str, &str)]) -> ApiCredential {
+        ApiCredential {
+            id: 42,
+            application_id: 1,
+            status: "validated".to_string(),
+            creation: None,
+            last_use: None,
+            expiration: None,
+            rules: rules.iter()
+                .map(|&(method, path)| {
+                    AccessRule {
+                        method: method.to_string(),
+                        path: path.to_string(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_rules_summary() {
+        let credential = credential(&[("GET", "/me"), ("GET", "/domain/*"), ("PUT", "/domain/*")]);
+        assert_eq!("GET /me, GET,PUT /domain/*", rules_summary(&credential));
+    }
+
+    #[test]
+    fn test_report() {
+        let unreachable: io::Result<u64> = Err(io::Error::other("Couldn't connect to server"));
+        let checks = connection_checks("eu.api.ovh.com", &unreachable);
+        assert_eq!(CheckStatus::Failed, checks[0].status);
+        assert_eq!(CheckStatus::Skipped, checks[1].status);
+
+        let tls: io::Result<u64> = Err(io::Error::other("SSL certificate problem: expired"));
+        let checks = connection_checks("eu.api.ovh.com", &tls);
+        assert_eq!(CheckStatus::Ok, checks[0].status);
+        assert_eq!(CheckStatus::Failed, checks[1].status);
+
+        let mut checks = connection_checks("eu.api.ovh.com", &Ok(1500000000));
+        checks.push(clock_check(Some(-120)));
+        checks.extend(credential_checks(&Ok(credential(&[("GET", "/*")]))));
+        let report = HealthReport {
+            host: "eu.api.ovh.com".to_string(),
+            credential_source: "Config.toml".to_string(),
+            checks,
+            time_drift: Some(-120),
+            credential: None,
+        };
+        assert!(report.is_healthy());
+        assert_eq!(CheckStatus::Warning, report.check("clock").unwrap().status);
+        assert_eq!(CheckStatus::Warning, report.check("rules").unwrap().status);
+        assert_eq!("credential 42 is validated", report.check("credential").unwrap().detail);
+        assert_eq!(Some(false), report.to_json()["checks"].as_array().map(|c| c.is_empty()));
+        assert!(report.to_string().ends_with("healthy"));
+        assert!(report.to_string().starts_with("eu.api.ovh.com (credentials from Config.toml)\n"));
+        assert_eq!("Config.toml", report.to_json()["credentialSource"]);
+
+        let refused = credential_checks(&Err(io::Error::other("Invalid signature")));
+        assert_eq!(CheckStatus::Failed, refused[0].status);
+    }
+}
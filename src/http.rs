@@ -0,0 +1,130 @@
+//! # Http
+//!
+//! Unsigned calls to third-party HTTP endpoints (webhooks, chat services),
+//! going through the same backend and network settings as the API client.
+//!
+
+use client::{Network, RawResponse};
+
+use std::io;
+
+#[cfg(all(feature = "reqwest", not(feature = "curl")))]
+use client::{perform, read_response, Timeouts};
+
+#[cfg(feature = "curl")]
+use client::{curl_error, pin_keys, resolve_host, resolve_ip_family, transport_error,
+             trust_roots, use_proxy};
+#[cfg(feature = "curl")]
+use curl::easy::{Easy, List};
+#[cfg(feature = "curl")]
+use std::io::Read;
+#[cfg(feature = "curl")]
+use std::time::Duration;
+
+/// POST a JSON document to `url`.
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack"))]
+pub fn post_json(network: &Network, url: &str, body: &str) -> io::Result<RawResponse> {
+    post_json_with_headers(network, url, body, &[])
+}
+
+/// POST a JSON document to `url`, with additional headers.
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack"))]
+pub fn post_json_with_headers(network: &Network,
+                              url: &str,
+                              body: &str,
+                              extra: &[(&'static str, String)])
+                              -> io::Result<RawResponse> {
+    send(network, "POST", url, "application/json", body, extra)
+}
+
+/// PUT a JSON document to `url`, with additional headers.
+#[cfg(feature = "notify-matrix")]
+pub fn put_json_with_headers(network: &Network,
+                             url: &str,
+                             body: &str,
+                             extra: &[(&'static str, String)])
+                             -> io::Result<RawResponse> {
+    send(network, "PUT", url, "application/json", body, extra)
+}
+
+/// POST a document of the given content type to `url`, with additional headers.
+#[cfg(feature = "metrics")]
+pub fn post_with_headers(network: &Network,
+                         url: &str,
+                         content_type: &str,
+                         body: &str,
+                         extra: &[(&'static str, String)])
+                         -> io::Result<RawResponse> {
+    send(network, "POST", url, content_type, body, extra)
+}
+
+/// Send a document to `url` with `POST` or `PUT`.
+#[cfg(all(feature = "reqwest", not(feature = "curl")))]
+fn send(network: &Network,
+        method: &str,
+        url: &str,
+        content_type: &str,
+        body: &str,
+        extra: &[(&'static str, String)])
+        -> io::Result<RawResponse> {
+    if method != "POST" && method != "PUT" {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "bad method"));
+    }
+    let mut headers = vec![("Content-Type", content_type)];
+    for &(name, ref value) in extra {
+        headers.push((name, value));
+    }
+
+    read_response(perform(network, method, url, &headers, Some(body), Timeouts::default())?)
+}
+
+/// Send a document to `url` with `POST` or `PUT`.
+#[cfg(feature = "curl")]
+fn send(network: &Network,
+        method: &str,
+        url: &str,
+        content_type: &str,
+        body: &str,
+        extra: &[(&'static str, String)])
+        -> io::Result<RawResponse> {
+    let mut data = body.as_bytes();
+    let mut headers = List::new();
+    headers.append(&format!("Content-Type: {}", content_type)).map_err(transport_error)?;
+    headers.append("User-Agent: OVH-rs/curl-rust/0.4").map_err(transport_error)?;
+    for &(name, ref value) in extra {
+        headers.append(&format!("{}: {}", name, value)).map_err(transport_error)?;
+    }
+
+    let mut client = Easy::new();
+    client.timeout(Duration::from_secs(20)).map_err(transport_error)?;
+    resolve_ip_family(&mut client, network)?;
+    resolve_host(&mut client, network, url)?;
+    trust_roots(&mut client, network)?;
+    pin_keys(&mut client, network, url)?;
+    use_proxy(&mut client, network, url)?;
+    client.url(&network.target(url)).map_err(transport_error)?;
+    match method {
+        "POST" => client.post(true).map_err(transport_error)?,
+        "PUT" => client.put(true).map_err(transport_error)?,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "bad method")),
+    }
+    client.post_field_size(data.len() as u64).map_err(transport_error)?;
+    client.http_headers(headers).map_err(transport_error)?;
+
+    let mut response_data = Vec::new();
+    {
+        let mut transfer = client.transfer();
+        transfer.read_function(|buf| Ok(data.read(buf).unwrap_or(0))).map_err(transport_error)?;
+        transfer.write_function(|buf| {
+            response_data.extend_from_slice(buf);
+            Ok(buf.len())
+        }).map_err(transport_error)?;
+        transfer.perform().map_err(curl_error)?;
+    }
+    let status = client.response_code().map_err(transport_error)? as u16;
+    Ok(RawResponse {
+        status,
+        headers: Vec::new(),
+        body: String::from_utf8_lossy(&response_data).into_owned(),
+    })
+}
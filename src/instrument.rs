@@ -0,0 +1,130 @@
+//! # Instrument
+//!
+//! With the `tracing` feature, every request to the API runs in an
+//! `ovh.request` span carrying its method, path, attempt, status, duration
+//! and `X-Ovh-QueryId`, retries and fetched pages are `debug` events, and
+//! slow calls (see `telemetry`) are `warn` events.
+//! Without it, these functions do nothing.
+//!
+
+use client::RawResponse;
+use telemetry::SlowCall;
+
+use std::io;
+use std::time::Duration;
+
+/// The path of a query, without its query string.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+fn path(query: &str) -> &str {
+    query.split('?').next().unwrap_or(query)
+}
+
+/// Run `send` in the span of one request and record its duration.
+#[cfg(feature = "tracing")]
+pub(crate) fn request<F>(method: &str,
+                         query: &str,
+                         attempt: u32,
+                         send: F)
+                         -> io::Result<RawResponse>
+    where F: FnOnce() -> io::Result<RawResponse>
+{
+    use std::time::Instant;
+    use tracing::field::Empty;
+
+    let span = ::tracing::info_span!("ovh.request",
+                                     method = method,
+                                     path = path(query),
+                                     attempt = attempt,
+                                     status = Empty,
+                                     duration_ms = Empty,
+                                     query_id = Empty);
+    let started = Instant::now();
+    let answer = span.in_scope(send);
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    if let Err(ref err) = answer {
+        span.in_scope(|| ::tracing::debug!(error = %err, "OVH API request failed"));
+    }
+    answer
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn request<F>(_method: &str,
+                         _query: &str,
+                         _attempt: u32,
+                         send: F)
+                         -> io::Result<RawResponse>
+    where F: FnOnce() -> io::Result<RawResponse>
+{
+    send()
+}
+
+/// Record the status and query id of an answer, whatever its status, on the
+/// span of its request.
+#[cfg(feature = "tracing")]
+pub(crate) fn answer(res: &RawResponse) {
+    let span = ::tracing::Span::current();
+    span.record("status", res.status);
+    if let Some(id) = res.header("X-Ovh-QueryId") {
+        span.record("query_id", id);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn answer(_res: &RawResponse) {}
+
+/// A failed request about to be retried after `delay`.
+#[cfg(feature = "tracing")]
+pub(crate) fn retry(method: &str, query: &str, attempt: u32, delay: Duration, err: &io::Error) {
+    ::tracing::debug!(method = method,
+                      path = path(query),
+                      attempt = attempt,
+                      delay_ms = delay.as_millis() as u64,
+                      error = %err,
+                      "retrying OVH API request");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn retry(_method: &str,
+                     _query: &str,
+                     _attempt: u32,
+                     _delay: Duration,
+                     _err: &io::Error) {
+}
+
+/// A request slower than its latency budget.
+#[cfg(feature = "tracing")]
+pub(crate) fn slow_call(report: &SlowCall) {
+    ::tracing::warn!(method = report.sample.method.as_str(),
+                     path = path(&report.sample.query),
+                     route = report.sample.route.as_str(),
+                     duration_ms = report.sample.elapsed.as_millis() as u64,
+                     budget_ms = report.budget.as_millis() as u64,
+                     query_id = report.sample.query_id.as_ref().map_or("", |id| id.as_str()),
+                     "slow OVH API call");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn slow_call(_report: &SlowCall) {}
+
+/// A page of `items` fetched from a listing route.
+#[cfg(feature = "tracing")]
+pub(crate) fn page(query: &str, items: usize, last: bool) {
+    ::tracing::debug!(path = path(query),
+                      items = items as u64,
+                      last = last,
+                      "fetched OVH API page");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn page(_query: &str, _items: usize, _last: bool) {}
+
+#[cfg(test)]
+mod tests {
+    use super::path;
+
+    #[test]
+    fn test_path() {
+        assert_eq!("/me/bill", path("/me/bill?date.from=2017-01-01"));
+        assert_eq!("/me", path("/me"));
+    }
+}
@@ -0,0 +1,373 @@
+//! # Integrity
+//!
+//! Referential checks before deletions: an IP block routed to a server, a
+//! private network instances are still plugged into, a vRack a project still
+//! carries networks through, ... are found before a cleanup script breaks them.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::client::OVHClient;
+//! use ovh::integrity::{self, Target};
+//!
+//! let client = OVHClient::try_new()?;
+//! let network = Target::PrivateNetwork {
+//!     project: "project".to_string(),
+//!     network: "pn-123_0".to_string(),
+//! };
+//! for dependency in integrity::dependents(&client, &network)? {
+//!     println!("{}", dependency);
+//! }
+//! // refused while an instance has an address in the network
+//! integrity::checked_delete(&client, &network)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Blocking dependencies refuse the deletion, the others are logged as
+//! warnings. The deletion itself still goes through the `guard`.
+//!
+
+use client::OVHClient;
+use cloud::{kube, project_path};
+use error;
+use rest;
+
+use std::fmt;
+use std::io;
+
+use serde_json::Value;
+
+/// A resource whose deletion is checked.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Target {
+    /// An IP block, e.g. `192.0.2.0/28`, terminated with `/ip/{ip}/terminate`.
+    Ip(String),
+    /// A service attached to a vRack: the kind of the route (`cloudProject`,
+    /// `ipLoadbalancing`, `ip`, `dedicatedServerInterface`, ...) and its id.
+    /// Only the networks of projects and load balancers are checked.
+    VrackAttachment {
+        vrack: String,
+        kind: String,
+        id: String,
+    },
+    /// A private network of a Public Cloud project.
+    PrivateNetwork {
+        project: String,
+        network: String,
+    },
+}
+
+impl Target {
+    /// Method and route of the deletion.
+    pub fn deletion(&self) -> (&'static str, String) {
+        match *self {
+            Target::Ip(ref ip) => ("POST", format!("/ip/{}/terminate", rest::escape(ip))),
+            Target::VrackAttachment { ref vrack, ref kind, ref id } => {
+                ("DELETE",
+                 format!("/vrack/{}/{}/{}", rest::escape(vrack), kind, rest::escape(id)))
+            }
+            Target::PrivateNetwork { ref project, ref network } => {
+                ("DELETE",
+                 format!("{}/network/private/{}", project_path(project), rest::escape(network)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Ip(ref ip) => write!(f, "IP block {}", ip),
+            Target::VrackAttachment { ref vrack, ref kind, ref id } => {
+                write!(f, "{} {} of vRack {}", kind, id, vrack)
+            }
+            Target::PrivateNetwork { ref project, ref network } => {
+                write!(f, "private network {} of project {}", network, project)
+            }
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Severity {
+    /// The deletion breaks the dependent resource and is refused.
+    Blocking,
+    /// The deletion loses something, it is logged.
+    Warning,
+}
+
+/// A resource which depends on the target of a deletion.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Dependency {
+    pub severity: Severity,
+    /// The dependent resource, e.g. `instance web-1`.
+    pub resource: String,
+    /// What the deletion does to it.
+    pub reason: String,
+}
+
+impl Dependency {
+    fn new(severity: Severity, resource: String, reason: &str) -> Dependency {
+        Dependency {
+            severity,
+            resource,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Blocking => "blocking",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {} {}", severity, self.resource, self.reason)
+    }
+}
+
+fn is_not_found(err: &io::Error) -> bool {
+    error::api_error(err).is_some_and(|api| api.http_code == 404)
+}
+
+/// The answer of `route`, none if the route does not exist.
+fn get_optional(client: &OVHClient, route: &str) -> io::Result<Option<Value>> {
+    match rest::get(client, route) {
+        Ok(value) => Ok(Some(value)),
+        Err(ref err) if is_not_found(err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The ids listed at `route`, none if the route does not exist.
+fn list(client: &OVHClient, route: &str) -> io::Result<Vec<String>> {
+    match get_optional(client, route)? {
+        Some(value) => rest::string_list(&value),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Dependencies of an IP block, from its description, its reverses and its firewalls.
+fn ip_dependencies(ip: &Value, reverses: &[String], firewalls: &[String]) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    let routed_to = ip.get("routedTo")
+        .and_then(|routed| rest::opt_str_field(routed, "serviceName"));
+    if let Some(service) = routed_to {
+        dependencies.push(Dependency::new(Severity::Blocking,
+                                          format!("service {}", service),
+                                          "loses the addresses routed to it"));
+    }
+    for address in reverses {
+        dependencies.push(Dependency::new(Severity::Warning,
+                                          format!("reverse of {}", address),
+                                          "is deleted"));
+    }
+    for address in firewalls {
+        dependencies.push(Dependency::new(Severity::Warning,
+                                          format!("firewall of {}", address),
+                                          "is deleted with its rules"));
+    }
+    dependencies
+}
+
+/// OpenStack ids of a private network, one per region, and its own id.
+fn network_ids(network: &Value) -> Vec<String> {
+    let mut ids: Vec<String> = rest::opt_str_field(network, "id").into_iter().collect();
+    if let Some(regions) = network.get("regions").and_then(Value::as_array) {
+        ids.extend(regions.iter().filter_map(|region| rest::opt_str_field(region, "openstackId")));
+    }
+    ids
+}
+
+/// Instances with an address in a network, given its ids.
+fn attached_instances(instances: &[Value], network_ids: &[String]) -> Vec<Dependency> {
+    instances.iter()
+        .filter(|instance| {
+            instance.get("ipAddresses")
+                .and_then(Value::as_array)
+                .is_some_and(|ips| {
+                    ips.iter()
+                        .filter_map(|ip| rest::opt_str_field(ip, "networkId"))
+                        .any(|id| network_ids.contains(&id))
+                })
+        })
+        .map(|instance| {
+            let name = rest::opt_str_field(instance, "name")
+                .or_else(|| rest::opt_str_field(instance, "id"))
+                .unwrap_or_default();
+            Dependency::new(Severity::Blocking,
+                            format!("instance {}", name),
+                            "has an address in the network")
+        })
+        .collect()
+}
+
+fn private_network_dependencies(client: &OVHClient,
+                                project: &str,
+                                network: &str)
+                                -> io::Result<Vec<Dependency>> {
+    let base = project_path(project);
+    let route = format!("{}/network/private/{}", base, rest::escape(network));
+    let network = match get_optional(client, &route)? {
+        Some(network) => network,
+        None => return Ok(Vec::new()),
+    };
+    let ids = network_ids(&network);
+    let instances = rest::get(client, &format!("{}/instance", base))?;
+    let mut dependencies = attached_instances(rest::array(&instances)?, &ids);
+    for kube_id in kube::cluster_ids(client, project)? {
+        let cluster = kube::cluster(client, project, &kube_id)?;
+        if cluster.private_network_id.is_some_and(|id| ids.contains(&id)) {
+            dependencies.push(Dependency::new(Severity::Blocking,
+                                              format!("Kubernetes cluster {}", cluster.name),
+                                              "runs its nodes in the network"));
+        }
+    }
+    let subnets = get_optional(client, &format!("{}/subnet", route))?.unwrap_or(Value::Null);
+    for subnet in subnets.as_array().map_or(&[][..], |subnets| &subnets[..]) {
+        let cidr = rest::opt_str_field(subnet, "cidr")
+            .or_else(|| rest::opt_str_field(subnet, "id"))
+            .unwrap_or_default();
+        dependencies.push(Dependency::new(Severity::Warning,
+                                          format!("subnet {}", cidr),
+                                          "is deleted with the network"));
+    }
+    Ok(dependencies)
+}
+
+fn vrack_dependencies(client: &OVHClient, kind: &str, id: &str) -> io::Result<Vec<Dependency>> {
+    let mut dependencies = Vec::new();
+    match kind {
+        "cloudProject" => {
+            let route = format!("{}/network/private", project_path(id));
+            let networks = get_optional(client, &route)?.unwrap_or(Value::Null);
+            for network in networks.as_array().map_or(&[][..], |networks| &networks[..]) {
+                let name = rest::opt_str_field(network, "name").unwrap_or_default();
+                let vlan = rest::opt_u64_field(network, "vlanId").unwrap_or(0);
+                let network = format!("private network {} (VLAN {})", name, vlan);
+                dependencies.push(Dependency::new(Severity::Blocking,
+                                                  network,
+                                                  "goes through the vRack"));
+            }
+        }
+        "ipLoadbalancing" => {
+            let route = format!("/ipLoadbalancing/{}/vrack/network", rest::escape(id));
+            for network in list(client, &route)? {
+                dependencies.push(Dependency::new(Severity::Blocking,
+                                                  format!("load balancer network {}", network),
+                                                  "reaches its servers through the vRack"));
+            }
+        }
+        _ => {}
+    }
+    Ok(dependencies)
+}
+
+/// The resources depending on `target`, none if it does not exist.
+pub fn dependents(client: &OVHClient, target: &Target) -> io::Result<Vec<Dependency>> {
+    match *target {
+        Target::Ip(ref ip) => {
+            let route = format!("/ip/{}", rest::escape(ip));
+            let ip = match get_optional(client, &route)? {
+                Some(ip) => ip,
+                None => return Ok(Vec::new()),
+            };
+            let reverses = list(client, &format!("{}/reverse", route))?;
+            let firewalls = list(client, &format!("{}/firewall", route))?;
+            Ok(ip_dependencies(&ip, &reverses, &firewalls))
+        }
+        Target::VrackAttachment { ref kind, ref id, .. } => vrack_dependencies(client, kind, id),
+        Target::PrivateNetwork { ref project, ref network } => {
+            private_network_dependencies(client, project, network)
+        }
+    }
+}
+
+/// Refuse the deletion of `target` if a dependency blocks it, log the others.
+fn verdict(target: &Target, dependencies: &[Dependency]) -> io::Result<()> {
+    for dependency in dependencies.iter().filter(|dep| dep.severity == Severity::Warning) {
+        warn!("deleting {}: {} {}", target, dependency.resource, dependency.reason);
+    }
+    let blocking: Vec<String> = dependencies.iter()
+        .filter(|dep| dep.severity == Severity::Blocking)
+        .map(|dep| format!("{} {}", dep.resource, dep.reason))
+        .collect();
+    if blocking.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                       format!("refusing to delete {}: {}", target, blocking.join(", "))))
+}
+
+/// Check the dependencies of `target`: an error if one blocks the deletion,
+/// else the warnings, which are also logged.
+pub fn check(client: &OVHClient, target: &Target) -> io::Result<Vec<Dependency>> {
+    let dependencies = dependents(client, target)?;
+    verdict(target, &dependencies)?;
+    Ok(dependencies)
+}
+
+/// Delete `target` once `check` allows it.
+pub fn checked_delete(client: &OVHClient, target: &Target) -> io::Result<Value> {
+    check(client, target)?;
+    let (method, route) = target.deletion();
+    if method == "POST" {
+        rest::post(client, &route, &json!({}))
+    } else {
+        rest::delete(client, &route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attached_instances, ip_dependencies, network_ids, verdict, Severity, Target};
+
+    #[test]
+    fn test_ip_dependencies() {
+        let ip = json!({"ip": "192.0.2.0/28", "routedTo": {"serviceName": "ns123.ip-1-2-3.eu"}});
+        let dependencies = ip_dependencies(&ip, &["192.0.2.1".to_string()], &[]);
+        assert_eq!(2, dependencies.len());
+        assert_eq!(Severity::Blocking, dependencies[0].severity);
+        assert_eq!("blocking: service ns123.ip-1-2-3.eu loses the addresses routed to it",
+                   dependencies[0].to_string());
+        assert_eq!(Severity::Warning, dependencies[1].severity);
+
+        let parked = json!({"ip": "192.0.2.0/28", "routedTo": {"serviceName": null}});
+        assert!(ip_dependencies(&parked, &[], &[]).is_empty());
+        assert_eq!(("POST", "/ip/192.0.2.0%2F28/terminate".to_string()),
+                   Target::Ip("192.0.2.0/28".to_string()).deletion());
+    }
+
+    #[test]
+    fn test_attached_instances() {
+        let network = json!({"id": "pn-1_0",
+                             "regions": [{"region": "GRA7", "openstackId": "os-1"}]});
+        let ids = network_ids(&network);
+        assert_eq!(vec!["pn-1_0".to_string(), "os-1".to_string()], ids);
+        let instances = vec![
+            json!({"id": "i-1", "name": "web-1", "ipAddresses": [
+                {"ip": "198.51.100.1", "type": "public", "networkId": "ext"},
+                {"ip": "10.0.0.2", "type": "private", "networkId": "os-1"}]}),
+            json!({"id": "i-2", "name": "web-2", "ipAddresses": [
+                {"ip": "198.51.100.2", "type": "public", "networkId": "ext"}]}),
+        ];
+        let dependencies = attached_instances(&instances, &ids);
+        assert_eq!(1, dependencies.len());
+        assert_eq!("instance web-1", dependencies[0].resource);
+    }
+
+    #[test]
+    fn test_verdict() {
+        let target = Target::PrivateNetwork {
+            project: "p1".to_string(),
+            network: "pn-1_0".to_string(),
+        };
+        let warning = ip_dependencies(&json!({}), &["192.0.2.1".to_string()], &[]);
+        assert!(verdict(&target, &warning).is_ok());
+        let blocking = ip_dependencies(&json!({"routedTo": {"serviceName": "vps-1"}}), &[], &[]);
+        let err = verdict(&target, &blocking).unwrap_err();
+        assert_eq!("refusing to delete private network pn-1_0 of project p1: \
+                    service vps-1 loses the addresses routed to it",
+                   err.to_string());
+    }
+}
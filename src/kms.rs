@@ -0,0 +1,151 @@
+//! # KMS
+//!
+//! Service keys of an OVHcloud KMS (`/okms/resource/{okmsId}`), wrapping the data
+//! keys of sealed credential files (see `seal`).
+//!
+//! ```no_run
+//! # use ovh::{Credential, OVHClient};
+//! # use ovh::kms::OvhKms;
+//! # use ovh::seal::{self, SealKey};
+//! // the client of the KMS has its own credential, e.g. from the environment
+//! let kms = OvhKms::new(OVHClient::new(), "okms-id", "key-id");
+//! seal::seal_file("Config.toml", SealKey::Kms(&kms)).unwrap();
+//! let credential = Credential::new_from_file_with_kms("Config.toml", &kms).unwrap();
+//! ```
+//!
+
+use client::OVHClient;
+use rest;
+use seal::Kms;
+
+use std::io;
+
+use base64;
+
+/// A service key of an OVHcloud KMS, used through the API.
+#[derive(Debug,Clone)]
+pub struct OvhKms {
+    client: OVHClient,
+    okms_id: String,
+    key_id: String,
+}
+
+impl OvhKms {
+    pub fn new(client: OVHClient, okms_id: &str, key_id: &str) -> OvhKms {
+        OvhKms {
+            client,
+            okms_id: okms_id.to_string(),
+            key_id: key_id.to_string(),
+        }
+    }
+}
+
+fn route(okms_id: &str, key_id: &str, action: &str) -> String {
+    format!("/okms/resource/{}/serviceKey/{}/{}",
+            rest::escape(okms_id),
+            rest::escape(key_id),
+            action)
+}
+
+impl Kms for OvhKms {
+    /// `{okmsId}/{keyId}`, so that a file names the KMS it was sealed with.
+    fn key_id(&self) -> String {
+        format!("{}/{}", self.okms_id, self.key_id)
+    }
+
+    fn wrap(&self, key: &[u8]) -> io::Result<Vec<u8>> {
+        let answer = rest::post(&self.client,
+                                &route(&self.okms_id, &self.key_id, "encrypt"),
+                                &json!({ "plaintext": base64::encode(key) }))?;
+        rest::str_field(&answer, "ciphertext").map(String::into_bytes)
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> io::Result<Vec<u8>> {
+        let mut parts = key_id.splitn(2, '/');
+        let (okms_id, key_id) = match (parts.next(), parts.next()) {
+            (Some(okms_id), Some(key_id)) => (okms_id, key_id),
+            _ => return Err(rest::invalid_data(&format!("invalid KMS key id {}", key_id))),
+        };
+        let ciphertext = String::from_utf8(wrapped.to_vec())
+            .map_err(|_| rest::invalid_data("wrapped key is not a KMS ciphertext"))?;
+        let answer = rest::post(&self.client,
+                                &route(okms_id, key_id, "decrypt"),
+                                &json!({ "ciphertext": ciphertext }))?;
+        base64::decode(&rest::str_field(&answer, "plaintext")?)
+            .map_err(|e| rest::invalid_data(&format!("invalid plaintext: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OvhKms;
+    use base64;
+    use config::Credential;
+    use seal::{self, Kms, SealKey};
+    use testutil;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::io;
+
+    const KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    /// Seals like the KMS `okms-1` would, keeping the data key to answer with.
+    struct Recorder(RefCell<Vec<u8>>);
+
+    impl Kms for Recorder {
+        fn key_id(&self) -> String {
+            "okms-1/key-1".to_string()
+        }
+
+        fn wrap(&self, key: &[u8]) -> io::Result<Vec<u8>> {
+            *self.0.borrow_mut() = key.to_vec();
+            Ok(b"okms:v1:wrapped".to_vec())
+        }
+
+        fn unwrap(&self, _: &str, _: &[u8]) -> io::Result<Vec<u8>> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_wrap_and_unwrap() {
+        let (host, server) = testutil::serve(vec![(200, "{\"ciphertext\":\"okms:v1:wrapped\"}"),
+                                                  (200, "{\"plaintext\":\"BwcHBwcHBwcHBwcHBwcHBwcH\
+                                                         BwcHBwcHBwcHBwcHBwc=\"}")]);
+        let kms = OvhKms::new(testutil::local_client(&host), "okms-1", "key-1");
+        assert_eq!("okms-1/key-1", kms.key_id());
+        assert_eq!(b"okms:v1:wrapped".to_vec(), kms.wrap(&[7; 32]).unwrap());
+        assert_eq!(vec![7; 32], kms.unwrap("okms-1/key-1", b"okms:v1:wrapped").unwrap());
+        assert!(kms.unwrap("key-1", b"okms:v1:wrapped").is_err());
+
+        let requests = server.join().unwrap();
+        let route = "POST /1.0/okms/resource/okms-1/serviceKey/key-1";
+        assert!(requests[0].starts_with(&format!("{}/encrypt ", route)), "{}", requests[0]);
+        assert!(requests[0].ends_with(&format!("{{\"plaintext\":\"{}\"}}", KEY)));
+        assert!(requests[1].starts_with(&format!("{}/decrypt ", route)), "{}", requests[1]);
+        assert!(requests[1].ends_with("{\"ciphertext\":\"okms:v1:wrapped\"}"));
+    }
+
+    #[test]
+    fn test_credential_sealed_with_ovh_kms() {
+        let path = testutil::temp_path("ovh-rs-test-kms.toml");
+        fs::write(&path,
+                  "[default]\nendpoint = \"ovh-eu\"\n\n[ovh-eu]\napplication_key = \"ak\"\n\
+                   application_secret = \"as\"\nconsumer_key = \"ck\"\n")
+            .unwrap();
+        let recorder = Recorder(RefCell::new(Vec::new()));
+        seal::seal_file(&path, SealKey::Kms(&recorder)).unwrap();
+        let answer = format!("{{\"plaintext\":\"{}\"}}", base64::encode(&*recorder.0.borrow()));
+        let (host, server) = testutil::serve(vec![(200, Box::leak(answer.into_boxed_str()))]);
+        let kms = OvhKms::new(testutil::local_client(&host), "okms-2", "key-2");
+
+        assert!(Credential::new_from_file(&path).is_err());
+        let credential = Credential::new_from_file_with_kms(&path, &kms).unwrap();
+        assert_eq!("ck", credential.consumer_key);
+        // the service key is the one the file was sealed with
+        let requests = server.join().unwrap();
+        let route = "POST /1.0/okms/resource/okms-1/serviceKey/key-1/decrypt ";
+        assert!(requests[0].starts_with(route), "{}", requests[0]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! # Labels
+//!
+//! Client-side labels on services (`project:website`, `team:data`, ...),
+//! as many OVH products have no tags. Labels are kept by a `LabelBackend`,
+//! a JSON file by default, and used to filter inventories and billing
+//! exports, e.g. for cost attribution.
+//!
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Map, Value};
+
+/// Labels of each service, by service name.
+pub type LabelMap = BTreeMap<String, BTreeSet<String>>;
+
+/// Where labels are kept.
+pub trait LabelBackend {
+    fn load(&self) -> io::Result<LabelMap>;
+    fn save(&self, labels: &LabelMap) -> io::Result<()>;
+}
+
+/// Labels kept in memory, e.g. for tests or labels computed at startup.
+#[derive(Debug,Default)]
+pub struct MemoryBackend {
+    labels: RefCell<LabelMap>,
+}
+
+impl LabelBackend for MemoryBackend {
+    fn load(&self) -> io::Result<LabelMap> {
+        Ok(self.labels.borrow().clone())
+    }
+
+    fn save(&self, labels: &LabelMap) -> io::Result<()> {
+        *self.labels.borrow_mut() = labels.clone();
+        Ok(())
+    }
+}
+
+/// Labels kept in a JSON file: `{"service": ["label", ...]}`.
+#[derive(Debug,Clone)]
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileBackend {
+        FileBackend { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl LabelBackend for FileBackend {
+    /// A missing file holds no labels.
+    fn load(&self) -> io::Result<LabelMap> {
+        let mut content = String::new();
+        match File::open(&self.path) {
+            Ok(mut fd) => fd.read_to_string(&mut content)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(LabelMap::new()),
+            Err(err) => return Err(err),
+        };
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let mut labels = LabelMap::new();
+        if let Some(map) = value.as_object() {
+            for (service, list) in map {
+                let set = list.as_array()
+                    .map(|items| items.iter().filter_map(Value::as_str).map(String::from).collect())
+                    .unwrap_or_default();
+                labels.insert(service.clone(), set);
+            }
+        }
+        Ok(labels)
+    }
+
+    /// Write the file atomically.
+    fn save(&self, labels: &LabelMap) -> io::Result<()> {
+        let mut map = Map::new();
+        for (service, set) in labels {
+            map.insert(service.clone(), json!(set.iter().collect::<Vec<&String>>()));
+        }
+        let content = serde_json::to_string_pretty(&Value::Object(map))
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        File::create(&tmp)?.write_all(content.as_bytes())?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Labels of services, saved through the backend on every change.
+pub struct LabelStore<B: LabelBackend> {
+    backend: B,
+    labels: LabelMap,
+}
+
+impl<B: LabelBackend> LabelStore<B> {
+    pub fn open(backend: B) -> io::Result<LabelStore<B>> {
+        let labels = backend.load()?;
+        Ok(LabelStore {
+            backend,
+            labels,
+        })
+    }
+
+    pub fn add(&mut self, service: &str, label: &str) -> io::Result<()> {
+        let added = self.labels
+            .entry(service.to_string())
+            .or_default()
+            .insert(label.to_string());
+        if added {
+            self.backend.save(&self.labels)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, service: &str, label: &str) -> io::Result<()> {
+        let removed = match self.labels.get_mut(service) {
+            Some(set) => set.remove(label),
+            None => false,
+        };
+        if removed {
+            if self.labels[service].is_empty() {
+                self.labels.remove(service);
+            }
+            self.backend.save(&self.labels)?;
+        }
+        Ok(())
+    }
+
+    pub fn labels_of(&self, service: &str) -> Vec<&str> {
+        self.labels.get(service).map_or(Vec::new(), |set| set.iter().map(|l| l.as_str()).collect())
+    }
+
+    pub fn has(&self, service: &str, label: &str) -> bool {
+        self.labels.get(service).is_some_and(|set| set.contains(label))
+    }
+
+    /// Names of the services having the label.
+    pub fn services_with(&self, label: &str) -> Vec<&str> {
+        self.labels
+            .iter()
+            .filter(|&(_, set)| set.contains(label))
+            .map(|(service, _)| service.as_str())
+            .collect()
+    }
+
+    /// Keep the items (services, bills, export lines, ...) whose service has the label.
+    pub fn filter<T, F>(&self, items: Vec<T>, label: &str, service_of: F) -> Vec<T>
+        where F: Fn(&T) -> &str
+    {
+        items.into_iter().filter(|item| self.has(service_of(item), label)).collect()
+    }
+
+    pub fn all(&self) -> &LabelMap {
+        &self.labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileBackend, LabelStore, MemoryBackend};
+    use testutil;
+    use std::fs;
+
+    #[test]
+    fn test_label_store() {
+        let mut store = LabelStore::open(MemoryBackend::default()).unwrap();
+        store.add("ns123.ip-1-2-3.eu", "team:data").unwrap();
+        store.add("example.com", "team:web").unwrap();
+        assert_eq!(vec!["ns123.ip-1-2-3.eu"], store.services_with("team:data"));
+        let names = vec!["example.com", "ns123.ip-1-2-3.eu", "other.net"];
+        assert_eq!(vec!["example.com"], store.filter(names, "team:web", |name| name));
+        store.remove("example.com", "team:web").unwrap();
+        assert!(store.labels_of("example.com").is_empty());
+    }
+
+    #[test]
+    fn test_file_backend() {
+        let path = testutil::temp_path("ovh-rs-test-labels.json");
+        {
+            let mut store = LabelStore::open(FileBackend::new(&path)).unwrap();
+            store.add("example.com", "project:website").unwrap();
+        }
+        let store = LabelStore::open(FileBackend::new(&path)).unwrap();
+        assert!(store.has("example.com", "project:website"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -7,12 +7,20 @@
 //! It handles for you credential management
 //! and requests signing.
 //!
+extern crate base64;
 extern crate chrono;
 extern crate crypto;
+extern crate aes_gcm;
+extern crate pbkdf2;
+extern crate sha2;
+extern crate rpassword;
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
 
-#[macro_use] extern crate hyper;
 #[cfg(feature= "reqwest")]
-#[macro_use] extern crate reqwest;
+extern crate reqwest;
 
 #[macro_use]
 extern crate log;
@@ -20,8 +28,106 @@ extern crate log;
 #[cfg(feature= "curl")]
 extern crate curl;
 
-pub use config::Credential;
-pub use client::OVHClient;
+#[cfg(all(feature = "rustls", feature = "native-tls"))]
+compile_error!("the rustls and native-tls features are exclusive, enable only one of them");
+#[cfg(all(feature = "curl", not(any(feature = "rustls", feature = "native-tls"))))]
+compile_error!("the curl transport needs a TLS library, enable the native-tls or rustls feature");
+#[cfg(not(any(feature = "reqwest", feature = "curl")))]
+compile_error!("no transport selected, enable the reqwest feature (the default), or native-tls or \
+                rustls for curl");
+
+#[cfg(feature= "select-regex")]
+extern crate regex;
+
+#[cfg(feature= "keyring")]
+extern crate keyring;
+
+#[cfg(feature= "tracing")]
+extern crate tracing;
 
+#[cfg(feature= "derive")]
+extern crate ovh_derive;
+#[cfg(any(test, feature = "reqwest"))]
+extern crate rustls;
+#[cfg(feature = "reqwest")]
+extern crate rustls_native_certs;
+#[cfg(any(feature = "reqwest", feature = "scheduler"))]
+extern crate tokio;
+#[cfg(feature = "async")]
+extern crate futures_util;
+#[cfg(feature= "derive")]
+pub use ovh_derive::OvhEndpoint;
+
+pub use config::{Credential, CredentialSource, Endpoint};
+pub use client::{sign_request, CallBuilder, ClientBuilder, Network, OVHClient, SignedRequest,
+                 Timeouts};
+#[cfg(feature = "async")]
+pub use nonblocking::AsyncClient;
+pub use error::{ApiError, Error};
+pub use serde_json::Value;
+
+pub mod api;
+pub mod auth;
+pub mod backup;
+pub mod body;
 pub mod client;
+pub mod cloud;
+pub mod concurrency;
 pub mod config;
+pub mod dedicated;
+pub mod dns;
+pub mod domain;
+pub mod email;
+pub mod endpoint;
+pub mod error;
+pub mod guard;
+pub mod health;
+pub mod integrity;
+pub mod kms;
+pub mod labels;
+pub mod logs;
+pub mod me;
+pub mod metrics;
+pub mod middleware;
+pub mod monitor;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod oauth2;
+pub mod pagination;
+pub mod pin;
+pub mod pool;
+pub mod prelude;
+pub mod progress;
+pub mod proxy;
+pub mod raw;
+pub mod queue;
+pub mod resolve;
+pub mod retry;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod scope;
+pub mod seal;
+pub mod select;
+pub mod services;
+pub mod sms;
+pub mod snapshot;
+pub mod ssh;
+pub mod stock;
+pub mod task;
+pub mod telemetry;
+pub mod telephony;
+pub mod throttle;
+pub mod tls;
+pub mod trace;
+#[cfg(feature = "notify-webhook")]
+pub mod webhook;
+pub mod window;
+
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack", feature = "notify-matrix",
+          feature = "metrics"))]
+mod http;
+mod instrument;
+mod rest;
+#[cfg(test)]
+mod testutil;
+mod timecache;
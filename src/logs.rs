@@ -0,0 +1,523 @@
+//! # Logs
+//!
+//! Logs Data Platform (`/dbaas/logs`): the streams of a service and their write
+//! tokens, and, with the `ldp` feature, `GelfSender` to ship log records to a
+//! stream, so provisioning and log shipping live in one crate.
+//!
+//! Records are sent as GELF over TLS (port `12202` of the cluster), each one
+//! carrying the `X-OVH-TOKEN` of its stream. The sender batches them on a
+//! background thread; its queue is bounded, so producers faster than the
+//! network are slowed down (`send`) or told so (`try_send`) instead of
+//! buffering without limit.
+//!
+//! The TLS connections are opened by the transport of the crate: curl with
+//! the `native-tls` or `rustls` features, rustls otherwise.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use chrono::Local;
+
+use serde_json::{Map, Value};
+
+/// A stream of a Logs Data Platform service.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Stream {
+    pub id: String,
+    pub title: String,
+    /// Value of the `X-OVH-TOKEN` field of the records sent to the stream.
+    pub write_token: Option<String>,
+}
+
+impl Model for Stream {
+    const NAME: &'static str = "Stream";
+    const FIELDS: &'static [&'static str] = &["streamId", "title", "description", "writeToken",
+                                              "createdAt", "updatedAt", "isEditable",
+                                              "isShareable", "nbArchive", "nbAlertCondition",
+                                              "coldStorageEnabled", "indexingEnabled",
+                                              "webSocketEnabled", "parentStreamId",
+                                              "retentionId", "canAlert"];
+
+    fn from_json(value: &Value) -> io::Result<Stream> {
+        Ok(Stream {
+            id: rest::str_field(value, "streamId")?,
+            title: rest::opt_str_field(value, "title").unwrap_or_default(),
+            write_token: rest::opt_str_field(value, "writeToken"),
+        })
+    }
+}
+
+fn service_path(service: &str) -> String {
+    format!("/dbaas/logs/{}", rest::escape(service))
+}
+
+pub fn service_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/dbaas/logs")?)
+}
+
+pub fn stream_ids(client: &OVHClient, service: &str) -> io::Result<Vec<String>> {
+    let path = format!("{}/output/graylog/stream", service_path(service));
+    rest::string_list(&rest::get(client, &path)?)
+}
+
+pub fn stream(client: &OVHClient, service: &str, stream_id: &str) -> io::Result<Stream> {
+    let path = format!("{}/output/graylog/stream/{}",
+                       service_path(service),
+                       rest::escape(stream_id));
+    rest::decode(client, &rest::get(client, &path)?)
+}
+
+/// Host name of the cluster of a service, where the records are sent.
+pub fn cluster_hostname(client: &OVHClient, service: &str) -> io::Result<String> {
+    let path = format!("{}/cluster", service_path(service));
+    let ids = rest::string_list(&rest::get(client, &path)?)?;
+    let id = ids.first().ok_or_else(|| rest::invalid_data("the service has no cluster"))?;
+    let cluster = rest::get(client, &format!("{}/{}", path, rest::escape(id)))?;
+    rest::str_field(&cluster, "hostname")
+}
+
+/// Syslog severity of a record.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Level {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+/// A log record, in GELF 1.1.
+#[derive(Debug,Clone,PartialEq)]
+pub struct GelfRecord {
+    pub host: String,
+    pub short_message: String,
+    pub full_message: Option<String>,
+    pub level: Level,
+    /// Seconds since the epoch, with decimals.
+    pub timestamp: f64,
+    /// Additional fields, sent with a `_` prefix.
+    pub fields: Vec<(String, Value)>,
+}
+
+impl GelfRecord {
+    /// A record of the local host, timestamped now.
+    pub fn new(host: &str, level: Level, message: &str) -> GelfRecord {
+        let now = Local::now();
+        GelfRecord {
+            host: host.to_string(),
+            short_message: message.to_string(),
+            full_message: None,
+            level,
+            timestamp: now.timestamp() as f64 + now.timestamp_subsec_millis() as f64 / 1000.0,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field; `id` is reserved by GELF and renamed `id_`.
+    pub fn field(mut self, name: &str, value: Value) -> GelfRecord {
+        let name = if name == "id" { "id_" } else { name };
+        self.fields.push((name.trim_start_matches('_').to_string(), value));
+        self
+    }
+
+    /// The GELF document, with the write token of the stream.
+    pub fn to_json(&self, token: &str) -> Value {
+        let mut map = Map::new();
+        map.insert("version".to_string(), json!("1.1"));
+        map.insert("host".to_string(), json!(self.host));
+        map.insert("short_message".to_string(), json!(self.short_message));
+        if let Some(ref full) = self.full_message {
+            map.insert("full_message".to_string(), json!(full));
+        }
+        map.insert("level".to_string(), json!(self.level as u8));
+        map.insert("timestamp".to_string(), json!(self.timestamp));
+        for (name, value) in &self.fields {
+            map.insert(format!("_{}", name), value.clone());
+        }
+        map.insert("_X-OVH-TOKEN".to_string(), json!(token));
+        Value::Object(map)
+    }
+}
+
+/// GELF over TCP: documents terminated by a null byte.
+pub fn frame(records: &[GelfRecord], token: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for record in records {
+        bytes.extend_from_slice(record.to_json(token).to_string().as_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+#[cfg(feature = "ldp")]
+pub use self::sender::GelfSender;
+
+#[cfg(feature = "ldp")]
+mod sender {
+    use self::connection::Connection;
+    use super::{frame, GelfRecord};
+    use client::Network;
+
+    use std::io;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    /// Port of the GELF over TLS input of the clusters.
+    pub const GELF_TLS_PORT: u16 = 12202;
+
+    const QUEUE_SIZE: usize = 10000;
+    const BATCH_SIZE: usize = 200;
+    const FLUSH_INTERVAL_MS: u64 = 500;
+    const MAX_ATTEMPTS: u32 = 5;
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Ships records to a stream from a background thread.
+    pub struct GelfSender {
+        queue: Option<SyncSender<GelfRecord>>,
+        worker: Option<JoinHandle<()>>,
+    }
+
+    impl GelfSender {
+        /// Send to the stream of `token` on the cluster `host`
+        /// (e.g. `gra2.logs.ovh.com`, see `cluster_hostname`).
+        pub fn new(host: &str, token: &str) -> io::Result<GelfSender> {
+            GelfSender::with_port(host, GELF_TLS_PORT, token)
+        }
+
+        pub fn with_port(host: &str, port: u16, token: &str) -> io::Result<GelfSender> {
+            GelfSender::with_network(host, port, token, Network::default())
+        }
+
+        /// Like `with_port`, with the resolution, certificate authorities and pins of `network`.
+        pub fn with_network(host: &str,
+                            port: u16,
+                            token: &str,
+                            network: Network)
+                            -> io::Result<GelfSender> {
+            let mut connection = Connection::new(host, port, network);
+            connection.connect()?;
+            let (queue, records) = mpsc::sync_channel(QUEUE_SIZE);
+            let token = token.to_string();
+            let worker = thread::spawn(move || ship(connection, records, &token));
+            Ok(GelfSender {
+                queue: Some(queue),
+                worker: Some(worker),
+            })
+        }
+
+        fn queue(&self) -> &SyncSender<GelfRecord> {
+            self.queue.as_ref().expect("sender closed")
+        }
+
+        /// Queue a record, waiting while the queue is full.
+        pub fn send(&self, record: GelfRecord) -> io::Result<()> {
+            self.queue()
+                .send(record)
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "GELF sender stopped"))
+        }
+
+        /// Queue a record, failing with `WouldBlock` when the queue is full.
+        pub fn try_send(&self, record: GelfRecord) -> io::Result<()> {
+            self.queue().try_send(record).map_err(|err| match err {
+                TrySendError::Full(_) => {
+                    io::Error::new(io::ErrorKind::WouldBlock, "GELF queue is full")
+                }
+                TrySendError::Disconnected(_) => {
+                    io::Error::new(io::ErrorKind::BrokenPipe, "GELF sender stopped")
+                }
+            })
+        }
+
+        /// Send the queued records and close the connection.
+        pub fn close(mut self) {
+            self.stop();
+        }
+
+        fn stop(&mut self) {
+            self.queue.take();
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
+
+    impl Drop for GelfSender {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    #[cfg(feature = "curl")]
+    mod connection {
+        use client::{pin_keys, resolve_host, resolve_ip_family, transport_error, trust_roots,
+                     Network};
+
+        use std::io;
+        use std::thread;
+        use std::time::Duration;
+
+        use curl::easy::Easy;
+
+        pub(super) struct Connection {
+            url: String,
+            network: Network,
+            easy: Option<Easy>,
+        }
+
+        impl Connection {
+            pub(super) fn new(host: &str, port: u16, network: Network) -> Connection {
+                Connection {
+                    url: format!("https://{}:{}", host, port),
+                    network,
+                    easy: None,
+                }
+            }
+
+            /// Open the TLS connection, without any HTTP exchange.
+            pub(super) fn connect(&mut self) -> io::Result<()> {
+                let mut easy = Easy::new();
+                easy.url(&self.url).map_err(transport_error)?;
+                resolve_ip_family(&mut easy, &self.network)?;
+                resolve_host(&mut easy, &self.network, &self.url)?;
+                trust_roots(&mut easy, &self.network)?;
+                pin_keys(&mut easy, &self.network, &self.url)?;
+                easy.connect_only(true).map_err(transport_error)?;
+                easy.connect_timeout(super::CONNECT_TIMEOUT).map_err(transport_error)?;
+                easy.perform().map_err(transport_error)?;
+                self.easy = Some(easy);
+                Ok(())
+            }
+
+            pub(super) fn disconnect(&mut self) {
+                self.easy = None;
+            }
+
+            pub(super) fn write_all(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+                if self.easy.is_none() {
+                    self.connect()?;
+                }
+                let easy = self.easy.as_mut().unwrap();
+                while !bytes.is_empty() {
+                    match easy.send(bytes) {
+                        Ok(sent) => bytes = &bytes[sent..],
+                        Err(ref err) if err.is_again() => {
+                            thread::sleep(Duration::from_millis(10))
+                        }
+                        Err(err) => return Err(transport_error(err)),
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The connection without curl: rustls over a socket to the first
+    /// address which answers.
+    #[cfg(all(feature = "reqwest", not(feature = "curl")))]
+    mod connection {
+        use client::Network;
+        use resolve;
+        use tls;
+
+        use rustls::pki_types::ServerName;
+        use rustls::{ClientConnection, StreamOwned};
+
+        use std::convert::TryFrom;
+        use std::io::{self, Write};
+        use std::net::{SocketAddr, TcpStream};
+        use std::sync::Arc;
+
+        pub(super) struct Connection {
+            host: String,
+            port: u16,
+            network: Network,
+            stream: Option<StreamOwned<ClientConnection, TcpStream>>,
+        }
+
+        impl Connection {
+            pub(super) fn new(host: &str, port: u16, network: Network) -> Connection {
+                Connection {
+                    host: host.to_string(),
+                    port,
+                    network,
+                    stream: None,
+                }
+            }
+
+            fn open(&self) -> io::Result<TcpStream> {
+                let addresses = resolve::addresses(&self.host,
+                                                   self.network.family(),
+                                                   self.network.resolver.as_ref())?;
+                let mut failure = None;
+                for address in addresses {
+                    let address = SocketAddr::new(address, self.port);
+                    match TcpStream::connect_timeout(&address, super::CONNECT_TIMEOUT) {
+                        Ok(socket) => return Ok(socket),
+                        Err(err) => failure = Some(err),
+                    }
+                }
+                Err(failure.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotFound)))
+            }
+
+            /// Open the TLS connection and complete its handshake.
+            pub(super) fn connect(&mut self) -> io::Result<()> {
+                let url = format!("https://{}:{}", self.host, self.port);
+                let roots = tls::extra_roots(&self.network.root_certificates)?;
+                let config = tls::client_config(&roots, self.network.pins_for(&url)?)?;
+                let name = ServerName::try_from(self.host.clone())
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                let connection = ClientConnection::new(Arc::new(config), name)
+                    .map_err(io::Error::other)?;
+                let mut stream = StreamOwned::new(connection, self.open()?);
+                while stream.conn.is_handshaking() {
+                    stream.conn.complete_io(&mut stream.sock)?;
+                }
+                self.stream = Some(stream);
+                Ok(())
+            }
+
+            pub(super) fn disconnect(&mut self) {
+                if let Some(mut stream) = self.stream.take() {
+                    stream.conn.send_close_notify();
+                    let _ = stream.flush();
+                }
+            }
+
+            pub(super) fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+                if self.stream.is_none() {
+                    self.connect()?;
+                }
+                let stream = self.stream.as_mut().unwrap();
+                stream.write_all(bytes)?;
+                stream.flush()
+            }
+        }
+
+        impl Drop for Connection {
+            fn drop(&mut self) {
+                self.disconnect();
+            }
+        }
+    }
+
+    impl Connection {
+        /// Send a batch, reconnecting with backoff on failure.
+        fn send_batch(&mut self, bytes: &[u8]) -> io::Result<()> {
+            let mut delay = Duration::from_millis(200);
+            let mut attempt = 1;
+            loop {
+                match self.write_all(bytes) {
+                    Ok(()) => return Ok(()),
+                    Err(err) => {
+                        self.disconnect();
+                        if attempt >= MAX_ATTEMPTS {
+                            return Err(err);
+                        }
+                        info!("GELF send failed ({}), retrying in {:?}", err, delay);
+                        thread::sleep(delay);
+                        delay *= 2;
+                        attempt += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Batch the records until the sender is dropped.
+    fn ship(mut connection: Connection, records: Receiver<GelfRecord>, token: &str) {
+        let interval = Duration::from_millis(FLUSH_INTERVAL_MS);
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut deadline = Instant::now() + interval;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            let closed = match records.recv_timeout(wait) {
+                Ok(record) => {
+                    batch.push(record);
+                    false
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+            if batch.len() >= BATCH_SIZE || Instant::now() >= deadline || closed {
+                if !batch.is_empty() {
+                    if let Err(err) = connection.send_batch(&frame(&batch, token)) {
+                        warn!("dropping {} GELF records: {}", batch.len(), err);
+                    }
+                    batch.clear();
+                }
+                deadline = Instant::now() + interval;
+            }
+            if closed {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, GelfRecord, Level, Stream};
+    #[cfg(feature = "ldp")]
+    use super::GelfSender;
+    #[cfg(feature = "ldp")]
+    use client::Network;
+    use rest::Model;
+    #[cfg(feature = "ldp")]
+    use testutil;
+    #[cfg(feature = "ldp")]
+    use tls;
+
+    #[test]
+    fn test_gelf() {
+        let mut record = GelfRecord::new("web-1", Level::Warning, "disk almost full")
+            .field("id", json!(42))
+            .field("_mount", json!("/var"));
+        record.timestamp = 1500000000.5;
+        let value = record.to_json("t0k3n");
+        assert_eq!(json!({
+                       "version": "1.1",
+                       "host": "web-1",
+                       "short_message": "disk almost full",
+                       "level": 4,
+                       "timestamp": 1500000000.5,
+                       "_id_": 42,
+                       "_mount": "/var",
+                       "_X-OVH-TOKEN": "t0k3n",
+                   }),
+                   value);
+        let bytes = frame(&[record.clone(), record], "t0k3n");
+        assert_eq!(2, bytes.iter().filter(|byte| **byte == 0).count());
+        assert_eq!(Some(&0), bytes.last());
+
+        let stream = Stream::from_json(&json!({ "streamId": "s1", "writeToken": "t0k3n" }))
+            .unwrap();
+        assert_eq!(Some("t0k3n".to_string()), stream.write_token);
+    }
+
+    #[cfg(feature = "ldp")]
+    #[test]
+    fn test_gelf_sender() {
+        let (host, server) = testutil::serve_tls(1);
+        let port = host.rsplit(':').next().unwrap().parse().unwrap();
+        let network = Network {
+            root_certificates: tls::pem_certificates(testutil::TEST_CA),
+            ..Network::default()
+        };
+        let sender = GelfSender::with_network("localhost", port, "t0k3n", network).unwrap();
+        sender.send(GelfRecord::new("web-1", Level::Informational, "started")).unwrap();
+        sender.send(GelfRecord::new("web-1", Level::Error, "stopped")).unwrap();
+        sender.close();
+        let received = server.join().unwrap().remove(0);
+        let records: Vec<&str> = received.split_terminator('\0').collect();
+        assert_eq!(2, records.len());
+        assert!(records[1].contains("\"short_message\":\"stopped\""));
+        assert!(records[1].contains("\"_X-OVH-TOKEN\":\"t0k3n\""));
+    }
+}
@@ -0,0 +1,152 @@
+//! # Agreements
+//!
+//! Contracts to accept on the account (`/me/agreements`).
+//! Automated orders fail while a contract is pending.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// Acceptance state of an agreement.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum AgreementState {
+    Todo,
+    Ok,
+    Ko,
+    Obsolete,
+    Other(String),
+}
+
+impl AgreementState {
+    pub fn parse(state: &str) -> AgreementState {
+        match state {
+            "todo" => AgreementState::Todo,
+            "ok" => AgreementState::Ok,
+            "ko" => AgreementState::Ko,
+            "obsolete" => AgreementState::Obsolete,
+            other => AgreementState::Other(other.to_string()),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match *self {
+            AgreementState::Todo => "todo",
+            AgreementState::Ok => "ok",
+            AgreementState::Ko => "ko",
+            AgreementState::Obsolete => "obsolete",
+            AgreementState::Other(ref other) => other,
+        }
+    }
+}
+
+/// A contract agreement of the account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Agreement {
+    pub id: u64,
+    pub state: AgreementState,
+    pub contract_id: u64,
+    pub date: Option<String>,
+}
+
+impl Model for Agreement {
+    const NAME: &'static str = "Agreement";
+    const FIELDS: &'static [&'static str] = &["id", "agreed", "contract", "date"];
+
+    fn from_json(value: &Value) -> io::Result<Agreement> {
+        Ok(Agreement {
+            id: rest::u64_field(value, "id")?,
+            state: AgreementState::parse(&rest::str_field(value, "agreed")?),
+            contract_id: rest::opt_u64_field(value, "contract").unwrap_or(0),
+            date: rest::opt_str_field(value, "date"),
+        })
+    }
+}
+
+/// The contract behind an agreement.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Contract {
+    pub name: String,
+    pub pdf_url: Option<String>,
+    pub text: Option<String>,
+    pub active: bool,
+}
+
+fn agreement_path(id: u64) -> String {
+    format!("/me/agreements/{}", id)
+}
+
+/// Ids of the agreements, optionally restricted to a state.
+pub fn agreement_ids(client: &OVHClient, state: Option<&AgreementState>) -> io::Result<Vec<u64>> {
+    let query = match state {
+        Some(state) => format!("/me/agreements?agreed={}", rest::escape(state.as_str())),
+        None => "/me/agreements".to_string(),
+    };
+    rest::u64_list(&rest::get(client, &query)?)
+}
+
+pub fn agreement(client: &OVHClient, id: u64) -> io::Result<Agreement> {
+    rest::decode(client, &rest::get(client, &agreement_path(id))?)
+}
+
+/// Agreements waiting to be accepted.
+pub fn pending_agreements(client: &OVHClient) -> io::Result<Vec<Agreement>> {
+    agreement_ids(client, Some(&AgreementState::Todo))?
+        .into_iter()
+        .map(|id| agreement(client, id))
+        .collect()
+}
+
+pub fn contract(client: &OVHClient, agreement_id: u64) -> io::Result<Contract> {
+    let value = rest::get(client, &format!("{}/contract", agreement_path(agreement_id)))?;
+    Ok(Contract {
+        name: rest::str_field(&value, "name")?,
+        pdf_url: rest::opt_str_field(&value, "pdf"),
+        text: rest::opt_str_field(&value, "text"),
+        active: value.get("active").and_then(Value::as_bool).unwrap_or(false),
+    })
+}
+
+pub fn accept(client: &OVHClient, agreement_id: u64) -> io::Result<()> {
+    rest::call(client,
+               "POST",
+               &format!("{}/accept", agreement_path(agreement_id)),
+               None)?;
+    Ok(())
+}
+
+/// Accept every pending agreement and return the contracts which were accepted.
+/// Meant for automation where contracts were reviewed beforehand.
+pub fn accept_all_pending(client: &OVHClient) -> io::Result<Vec<Contract>> {
+    let mut accepted = Vec::new();
+    for agreement in pending_agreements(client)? {
+        let contract = contract(client, agreement.id)?;
+        accept(client, agreement.id)?;
+        info!("Accepted contract {}", contract.name);
+        accepted.push(contract);
+    }
+    Ok(accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Agreement, AgreementState};
+    use rest::Model;
+
+    #[test]
+    fn test_agreement_from_json() {
+        let agreement = Agreement::from_json(&json!({
+            "id": 12,
+            "agreed": "todo",
+            "contract": 34,
+            "date": "2017-05-01T00:00:00+02:00",
+        }))
+            .unwrap();
+        assert_eq!(AgreementState::Todo, agreement.state);
+        assert_eq!(34, agreement.contract_id);
+        assert_eq!("obsolete", AgreementState::parse("obsolete").as_str());
+    }
+}
@@ -0,0 +1,363 @@
+//! # Billing
+//!
+//! Invoices of the account (`/me/bill`) and their archival: `download_invoices`
+//! saves the PDFs of a date range into a directory, several at a time, and
+//! records each one in `manifest.jsonl` (id, date, file, size, amount).
+//!
+//! A second run only downloads what the manifest does not have yet, and a PDF
+//! interrupted halfway (`<id>.pdf.part`) is resumed where it stopped, so an
+//! archive of hundreds of invoices can be built over several runs.
+//!
+//! ```no_run
+//! # extern crate chrono;
+//! # extern crate ovh;
+//! use chrono::NaiveDate;
+//! use ovh::OVHClient;
+//! use ovh::me::billing;
+//!
+//! # fn run() -> std::io::Result<()> {
+//! let client = OVHClient::try_new()?;
+//! let year = NaiveDate::from_ymd(2016, 1, 1)..NaiveDate::from_ymd(2017, 1, 1);
+//! let report = billing::download_invoices(&client, year, "invoices/2016")?;
+//! println!("{} downloaded, {} failed", report.downloaded.len(), report.failed.len());
+//! # Ok(())
+//! # }
+//! # fn main() {}
+//! ```
+//!
+
+use client::{Network, OVHClient};
+use me::Price;
+use rest::{self, Model};
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use chrono::NaiveDate;
+
+use serde_json::{self, Value};
+
+/// Invoices downloaded at the same time.
+pub const PARALLEL_DOWNLOADS: usize = 8;
+
+/// Name of the manifest in the archive directory.
+pub const MANIFEST: &str = "manifest.jsonl";
+
+/// An invoice.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Bill {
+    pub id: String,
+    pub date: String,
+    pub order_id: Option<u64>,
+    pub price_with_tax: Option<Price>,
+    pub price_without_tax: Option<Price>,
+    pub pdf_url: Option<String>,
+}
+
+impl Model for Bill {
+    const NAME: &'static str = "Bill";
+    const FIELDS: &'static [&'static str] = &["billId", "date", "orderId", "password", "pdfUrl",
+                                              "url", "priceWithTax", "priceWithoutTax", "tax",
+                                              "category"];
+
+    fn from_json(value: &Value) -> io::Result<Bill> {
+        Ok(Bill {
+            id: rest::str_field(value, "billId")?,
+            date: rest::opt_str_field(value, "date").unwrap_or_default(),
+            order_id: rest::opt_u64_field(value, "orderId"),
+            price_with_tax: Price::field(value, "priceWithTax")?,
+            price_without_tax: Price::field(value, "priceWithoutTax")?,
+            pdf_url: rest::opt_str_field(value, "pdfUrl"),
+        })
+    }
+}
+
+/// Route listing the invoices issued from `range.start` included to `range.end` excluded.
+fn bills_query(range: &Range<NaiveDate>) -> String {
+    format!("/me/bill?date.from={}&date.to={}",
+            range.start.format("%Y-%m-%d"),
+            range.end.pred().format("%Y-%m-%d"))
+}
+
+pub fn bill_ids(client: &OVHClient, range: Range<NaiveDate>) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &bills_query(&range))?)
+}
+
+pub fn bill(client: &OVHClient, id: &str) -> io::Result<Bill> {
+    rest::decode(client, &rest::get(client, &format!("/me/bill/{}", rest::escape(id)))?)
+}
+
+/// An invoice saved in the archive.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ManifestEntry {
+    pub id: String,
+    pub date: String,
+    /// File name, relative to the archive directory.
+    pub file: String,
+    pub size: u64,
+    pub price_with_tax: Option<Price>,
+}
+
+impl ManifestEntry {
+    fn to_json(&self) -> Value {
+        let price = self.price_with_tax.as_ref();
+        json!({
+            "billId": self.id,
+            "date": self.date,
+            "file": self.file,
+            "size": self.size,
+            "priceWithTax": price.map(|price| price.value),
+            "currency": price.map(|price| price.currency_code.clone()),
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<ManifestEntry> {
+        let price = value.get("priceWithTax").and_then(Value::as_f64).map(|amount| {
+            Price {
+                value: amount,
+                currency_code: rest::opt_str_field(value, "currency").unwrap_or_default(),
+                text: String::new(),
+            }
+        });
+        Some(ManifestEntry {
+            id: rest::opt_str_field(value, "billId")?,
+            date: rest::opt_str_field(value, "date").unwrap_or_default(),
+            file: rest::opt_str_field(value, "file")?,
+            size: value.get("size").and_then(Value::as_u64)?,
+            price_with_tax: price,
+        })
+    }
+}
+
+/// Entries of the manifest of `dir`, by invoice id; none if there is no manifest yet.
+pub fn read_manifest<P: AsRef<Path>>(dir: P) -> io::Result<BTreeMap<String, ManifestEntry>> {
+    let path = dir.as_ref().join(MANIFEST);
+    let mut entries = BTreeMap::new();
+    if !path.exists() {
+        return Ok(entries);
+    }
+    for line in BufReader::new(File::open(&path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|value| ManifestEntry::from_json(&value));
+        match entry {
+            Some(entry) => {
+                entries.insert(entry.id.clone(), entry);
+            }
+            None => warn!("Skipping corrupted manifest entry: {}", line),
+        }
+    }
+    Ok(entries)
+}
+
+/// Rewrite the manifest sorted by date, then id, atomically.
+fn write_manifest(dir: &Path, entries: &BTreeMap<String, ManifestEntry>) -> io::Result<()> {
+    let mut sorted: Vec<&ManifestEntry> = entries.values().collect();
+    sorted.sort_by(|a, b| (&a.date, &a.id).cmp(&(&b.date, &b.id)));
+    let tmp = dir.join(format!("{}.tmp", MANIFEST));
+    {
+        let mut fd = File::create(&tmp)?;
+        for entry in sorted {
+            writeln!(fd, "{}", entry.to_json())?;
+        }
+        fd.sync_all()?;
+    }
+    fs::rename(&tmp, dir.join(MANIFEST))
+}
+
+/// Outcome of `download_invoices`.
+#[derive(Debug,Clone,Default)]
+pub struct DownloadReport {
+    /// Invoices downloaded by this run.
+    pub downloaded: Vec<String>,
+    /// Invoices already in the manifest.
+    pub skipped: Vec<String>,
+    /// Invoices which could not be downloaded, with the error; the next run retries them.
+    pub failed: Vec<(String, String)>,
+}
+
+/// File name of the PDF of an invoice.
+fn pdf_name(id: &str) -> String {
+    let id: String = id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_".contains(c) { c } else { '_' })
+        .collect();
+    format!("{}.pdf", id)
+}
+
+/// Whether the start of a download is a PDF rather than, e.g., an HTML error page.
+fn is_pdf(head: &[u8]) -> bool {
+    head.starts_with(b"%PDF-")
+}
+
+/// Download `url` into `path`, resuming the `.part` file of a previous attempt.
+/// Returns the size of the document.
+fn download_to(network: &Network, url: &str, path: &Path) -> io::Result<u64> {
+    let mut part = path.to_path_buf().into_os_string();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let offset = fs::metadata(&part).map(|meta| meta.len()).unwrap_or(0);
+    let (status, bytes) = match OVHClient::download(network, url, offset) {
+        Ok(answer) => answer,
+        // The server does not support ranges: start over.
+        Err(_) if offset > 0 => OVHClient::download(network, url, 0)?,
+        Err(err) => return Err(err),
+    };
+    let mut file = match status {
+        206 => OpenOptions::new().append(true).open(&part)?,
+        200 => File::create(&part)?,
+        status if status >= 400 => {
+            return Err(rest::api_failure("GET", url, status, &String::from_utf8_lossy(&bytes)));
+        }
+        _ => {
+            let message = format!("download of {} failed ({})", path.display(), status);
+            return Err(io::Error::other(message));
+        }
+    };
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    let mut head = [0; 5];
+    let read = File::open(&part)?.read(&mut head)?;
+    if !is_pdf(&head[..read]) {
+        let _ = fs::remove_file(&part);
+        return Err(rest::invalid_data(&format!("{} is not a PDF", path.display())));
+    }
+    fs::rename(&part, path)?;
+    Ok(fs::metadata(path)?.len())
+}
+
+/// Fetch an invoice and save its PDF into `dir`.
+fn archive(client: &OVHClient, dir: &Path, id: &str) -> io::Result<ManifestEntry> {
+    let bill = bill(client, id)?;
+    let url = bill.pdf_url
+        .ok_or_else(|| rest::invalid_data(&format!("bill {} has no PDF", id)))?;
+    let file = pdf_name(id);
+    let size = download_to(&client.network, &url, &dir.join(&file))?;
+    Ok(ManifestEntry {
+        id: bill.id,
+        date: bill.date,
+        file,
+        size,
+        price_with_tax: bill.price_with_tax,
+    })
+}
+
+/// Save the PDFs of the invoices issued from `range.start` included to
+/// `range.end` excluded into `dir`, `PARALLEL_DOWNLOADS` at a time, skipping
+/// those of the manifest. A failed invoice does not stop the others.
+pub fn download_invoices<P: AsRef<Path>>(client: &OVHClient,
+                                         range: Range<NaiveDate>,
+                                         dir: P)
+                                         -> io::Result<DownloadReport> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let manifest = read_manifest(dir)?;
+    let mut report = DownloadReport::default();
+    let mut pending = VecDeque::new();
+    for id in bill_ids(client, range)? {
+        let complete = manifest.get(&id).is_some_and(|entry| {
+            fs::metadata(dir.join(&entry.file))
+                .map(|meta| meta.len() == entry.size)
+                .unwrap_or(false)
+        });
+        if complete {
+            report.skipped.push(id);
+        } else {
+            pending.push_back(id);
+        }
+    }
+    let pending = Mutex::new(pending);
+    let manifest = Mutex::new(manifest);
+    let report = Mutex::new(report);
+    let journal = OpenOptions::new().create(true).append(true).open(dir.join(MANIFEST))?;
+    let journal = Mutex::new(journal);
+    thread::scope(|scope| {
+        for _ in 0..PARALLEL_DOWNLOADS {
+            scope.spawn(|| loop {
+                let id = match pending.lock().unwrap().pop_front() {
+                    Some(id) => id,
+                    None => return,
+                };
+                match archive(client, dir, &id) {
+                    Ok(entry) => {
+                        // Appended at once, so an interrupted run keeps what it saved.
+                        let line = format!("{}\n", entry.to_json());
+                        if let Err(err) = journal.lock().unwrap().write_all(line.as_bytes()) {
+                            warn!("Cannot record invoice {} in the manifest: {}", id, err);
+                        }
+                        manifest.lock().unwrap().insert(id.clone(), entry);
+                        report.lock().unwrap().downloaded.push(id);
+                    }
+                    Err(err) => {
+                        warn!("Cannot download invoice {}: {}", id, err);
+                        report.lock().unwrap().failed.push((id, err.to_string()));
+                    }
+                }
+            });
+        }
+    });
+    drop(journal);
+    write_manifest(dir, &manifest.into_inner().unwrap())?;
+    let mut report = report.into_inner().unwrap();
+    report.downloaded.sort();
+    report.failed.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bills_query, is_pdf, pdf_name, read_manifest, write_manifest, ManifestEntry};
+    use me::Price;
+    use std::collections::BTreeMap;
+    use testutil;
+    use std::fs;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_bills_query() {
+        let range = NaiveDate::from_ymd(2017, 1, 1)..NaiveDate::from_ymd(2017, 2, 1);
+        assert_eq!("/me/bill?date.from=2017-01-01&date.to=2017-01-31", bills_query(&range));
+        assert_eq!("FR123.pdf", pdf_name("FR123"));
+        assert_eq!("a_b.pdf", pdf_name("a/b"));
+        assert!(is_pdf(b"%PDF-1.4\n"));
+        assert!(!is_pdf(b"<html>"));
+    }
+
+    #[test]
+    fn test_manifest() {
+        let dir = testutil::temp_path("ovh-rs-test-billing");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(read_manifest(&dir).unwrap().is_empty());
+
+        let entry = |id: &str, date: &str| {
+            ManifestEntry {
+                id: id.to_string(),
+                date: date.to_string(),
+                file: pdf_name(id),
+                size: 1234,
+                price_with_tax: Some(Price {
+                    value: 12.5,
+                    currency_code: "EUR".to_string(),
+                    text: String::new(),
+                }),
+            }
+        };
+        let mut entries = BTreeMap::new();
+        entries.insert("FR2".to_string(), entry("FR2", "2017-01-02T00:00:00+01:00"));
+        entries.insert("FR1".to_string(), entry("FR1", "2017-01-09T00:00:00+01:00"));
+        write_manifest(&dir, &entries).unwrap();
+        let text = fs::read_to_string(dir.join("manifest.jsonl")).unwrap();
+        assert!(text.starts_with(r#"{"billId":"FR2","currency":"EUR""#));
+        assert_eq!(entries, read_manifest(&dir).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
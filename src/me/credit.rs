@@ -0,0 +1,251 @@
+//! # Credits
+//!
+//! Money flowing back to the account: refunds (`/me/refund`),
+//! deposits (`/me/deposit`) and credit balances with their movements
+//! (`/me/credit/balance`), so accounting exports reconcile more than invoices.
+//!
+
+use client::OVHClient;
+use me::Price;
+#[cfg(feature = "async")]
+use nonblocking::AsyncClient;
+#[cfg(feature = "async")]
+use pagination::Listing;
+use pagination::{self, Paginator};
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A refund, the counterpart of an invoice.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Refund {
+    pub id: String,
+    pub date: String,
+    pub order_id: Option<u64>,
+    /// Invoice the refund applies to.
+    pub original_bill_id: Option<String>,
+    pub price_with_tax: Option<Price>,
+    pub price_without_tax: Option<Price>,
+    pub pdf_url: Option<String>,
+}
+
+impl Model for Refund {
+    const NAME: &'static str = "Refund";
+    const FIELDS: &'static [&'static str] = &["refundId", "date", "orderId", "originalBillId",
+                                              "password", "pdfUrl", "url", "priceWithTax",
+                                              "priceWithoutTax", "tax"];
+
+    fn from_json(value: &Value) -> io::Result<Refund> {
+        Ok(Refund {
+            id: rest::str_field(value, "refundId")?,
+            date: rest::opt_str_field(value, "date").unwrap_or_default(),
+            order_id: rest::opt_u64_field(value, "orderId"),
+            original_bill_id: rest::opt_str_field(value, "originalBillId"),
+            price_with_tax: Price::field(value, "priceWithTax")?,
+            price_without_tax: Price::field(value, "priceWithoutTax")?,
+            pdf_url: rest::opt_str_field(value, "pdfUrl"),
+        })
+    }
+}
+
+/// A deposit made on the account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Deposit {
+    pub id: String,
+    pub date: String,
+    pub order_id: Option<u64>,
+    pub amount: Option<Price>,
+    pub pdf_url: Option<String>,
+}
+
+impl Model for Deposit {
+    const NAME: &'static str = "Deposit";
+    const FIELDS: &'static [&'static str] = &["depositId", "date", "orderId", "amount", "password",
+                                              "paymentInfo", "pdfUrl", "url"];
+
+    fn from_json(value: &Value) -> io::Result<Deposit> {
+        Ok(Deposit {
+            id: rest::str_field(value, "depositId")?,
+            date: rest::opt_str_field(value, "date").unwrap_or_default(),
+            order_id: rest::opt_u64_field(value, "orderId"),
+            amount: Price::field(value, "amount")?,
+            pdf_url: rest::opt_str_field(value, "pdfUrl"),
+        })
+    }
+}
+
+/// A credit balance (deposit, prepaid account, voucher, bonus).
+#[derive(Debug,Clone,PartialEq)]
+pub struct CreditBalance {
+    pub name: String,
+    /// `DEPOSIT`, `PREPAID_ACCOUNT`, `VOUCHER`, `BONUS`, ...
+    pub balance_type: String,
+    pub amount: Option<Price>,
+    pub creation_date: Option<String>,
+    pub last_update: Option<String>,
+}
+
+impl Model for CreditBalance {
+    const NAME: &'static str = "CreditBalance";
+    const FIELDS: &'static [&'static str] = &["balanceName", "type", "status", "amount", "booked",
+                                              "expiring", "expiringSummary", "creationDate",
+                                              "lastUpdate"];
+
+    fn from_json(value: &Value) -> io::Result<CreditBalance> {
+        Ok(CreditBalance {
+            name: rest::str_field(value, "balanceName")?,
+            balance_type: rest::opt_str_field(value, "type").unwrap_or_default(),
+            amount: Price::field(value, "amount")?,
+            creation_date: rest::opt_str_field(value, "creationDate"),
+            last_update: rest::opt_str_field(value, "lastUpdate"),
+        })
+    }
+}
+
+/// A credit or debit of a balance.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CreditMovement {
+    pub id: u64,
+    pub balance_name: String,
+    pub movement_type: String,
+    pub amount: Option<Price>,
+    pub creation_date: Option<String>,
+    pub expiration_date: Option<String>,
+    pub order_id: Option<u64>,
+    /// Object the credit comes from or goes to (order, invoice, ...), by id.
+    pub source: Option<String>,
+    pub destination: Option<String>,
+}
+
+impl Model for CreditMovement {
+    const NAME: &'static str = "CreditMovement";
+    const FIELDS: &'static [&'static str] = &["movementId", "balanceName", "type", "amount",
+                                              "creationDate", "expirationDate", "lastUpdate",
+                                              "orderId", "sourceObject", "destinationObject"];
+
+    fn from_json(value: &Value) -> io::Result<CreditMovement> {
+        let object_id = |key: &str| {
+            value.get(key).and_then(|object| {
+                rest::opt_str_field(object, "id").or_else(|| rest::opt_str_field(object, "name"))
+            })
+        };
+        Ok(CreditMovement {
+            id: rest::u64_field(value, "movementId")?,
+            balance_name: rest::opt_str_field(value, "balanceName").unwrap_or_default(),
+            movement_type: rest::opt_str_field(value, "type").unwrap_or_default(),
+            amount: Price::field(value, "amount")?,
+            creation_date: rest::opt_str_field(value, "creationDate"),
+            expiration_date: rest::opt_str_field(value, "expirationDate"),
+            order_id: rest::opt_u64_field(value, "orderId"),
+            source: object_id("sourceObject"),
+            destination: object_id("destinationObject"),
+        })
+    }
+}
+
+pub fn refund_ids(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/me/refund")?)
+}
+
+pub fn refund(client: &OVHClient, id: &str) -> io::Result<Refund> {
+    rest::decode(client, &rest::get(client, &format!("/me/refund/{}", rest::escape(id)))?)
+}
+
+pub fn refunds(client: &OVHClient) -> io::Result<Vec<Refund>> {
+    refund_ids(client)?.iter().map(|id| refund(client, id)).collect()
+}
+
+/// Fetch the refunds lazily, one call per refund consumed.
+pub fn iter_refunds<'a>(client: &'a OVHClient)
+                        -> io::Result<impl Iterator<Item = io::Result<Refund>> + 'a> {
+    Ok(pagination::decoded(Paginator::new(client, "/me/refund")?))
+}
+
+/// Stream the refunds, one call per refund polled.
+#[cfg(feature = "async")]
+pub fn stream_refunds(client: &AsyncClient) -> Listing<Refund> {
+    pagination::listing(client, "/me/refund")
+}
+
+pub fn deposit_ids(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/me/deposit")?)
+}
+
+pub fn deposit(client: &OVHClient, id: &str) -> io::Result<Deposit> {
+    rest::decode(client, &rest::get(client, &format!("/me/deposit/{}", rest::escape(id)))?)
+}
+
+pub fn deposits(client: &OVHClient) -> io::Result<Vec<Deposit>> {
+    deposit_ids(client)?.iter().map(|id| deposit(client, id)).collect()
+}
+
+/// Fetch the deposits lazily, one call per deposit consumed.
+pub fn iter_deposits<'a>(client: &'a OVHClient)
+                         -> io::Result<impl Iterator<Item = io::Result<Deposit>> + 'a> {
+    Ok(pagination::decoded(Paginator::new(client, "/me/deposit")?))
+}
+
+/// Stream the deposits, one call per deposit polled.
+#[cfg(feature = "async")]
+pub fn stream_deposits(client: &AsyncClient) -> Listing<Deposit> {
+    pagination::listing(client, "/me/deposit")
+}
+
+fn balance_path(name: &str) -> String {
+    format!("/me/credit/balance/{}", rest::escape(name))
+}
+
+pub fn balance_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/me/credit/balance")?)
+}
+
+pub fn balance(client: &OVHClient, name: &str) -> io::Result<CreditBalance> {
+    rest::decode(client, &rest::get(client, &balance_path(name))?)
+}
+
+pub fn balances(client: &OVHClient) -> io::Result<Vec<CreditBalance>> {
+    balance_names(client)?.iter().map(|name| balance(client, name)).collect()
+}
+
+/// Every movement of a balance.
+pub fn movements(client: &OVHClient, balance_name: &str) -> io::Result<Vec<CreditMovement>> {
+    let path = format!("{}/movement", balance_path(balance_name));
+    rest::u64_list(&rest::get(client, &path)?)?
+        .into_iter()
+        .map(|id| rest::decode(client, &rest::get(client, &format!("{}/{}", path, id))?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreditMovement, Refund};
+    use rest::Model;
+
+    #[test]
+    fn test_refund_from_json() {
+        let refund = Refund::from_json(&json!({
+            "refundId": "AFR123",
+            "date": "2017-05-01T00:00:00+02:00",
+            "originalBillId": "FR456",
+            "priceWithTax": { "value": 12.0, "currencyCode": "EUR", "text": "12.00 €" },
+        }))
+            .unwrap();
+        assert_eq!(Some("FR456".to_string()), refund.original_bill_id);
+        assert_eq!(12.0, refund.price_with_tax.unwrap().value);
+    }
+
+    #[test]
+    fn test_movement_from_json() {
+        let movement = CreditMovement::from_json(&json!({
+            "movementId": 3,
+            "balanceName": "VOUCHER_1",
+            "type": "USE",
+            "destinationObject": { "id": "FR789", "name": "invoice" },
+        }))
+            .unwrap();
+        assert_eq!(Some("FR789".to_string()), movement.destination);
+        assert_eq!(None, movement.source);
+    }
+}
@@ -0,0 +1,133 @@
+//! # Export
+//!
+//! Aggregate the personal and account data exposed under `/me`
+//! into a single JSON document, e.g. to answer a data access request.
+//!
+
+use client::OVHClient;
+use rest;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::Local;
+
+use serde_json::{self, Map, Value};
+
+/// A part of the account data.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ExportSection {
+    /// Key of the section in the export.
+    pub name: &'static str,
+    pub path: &'static str,
+    /// Whether `path` lists ids whose details are fetched one by one.
+    pub listing: bool,
+}
+
+/// Sections exported by default.
+pub const DEFAULT_SECTIONS: &[ExportSection] = &[
+    ExportSection { name: "account", path: "/me", listing: false },
+    ExportSection { name: "contacts", path: "/me/contact", listing: true },
+    ExportSection { name: "sshKeys", path: "/me/sshKey", listing: true },
+    ExportSection { name: "ipRestrictions", path: "/me/accessRestriction/ip", listing: true },
+    ExportSection { name: "identityUsers", path: "/me/identity/user", listing: true },
+    ExportSection { name: "apiApplications", path: "/me/api/application", listing: true },
+    ExportSection { name: "apiCredentials", path: "/me/api/credential", listing: true },
+    ExportSection { name: "paymentMethods", path: "/me/payment/method", listing: true },
+    ExportSection { name: "agreements", path: "/me/agreements", listing: true },
+    ExportSection { name: "subAccounts", path: "/me/subAccount", listing: true },
+    ExportSection { name: "orders", path: "/me/order", listing: true },
+    ExportSection { name: "bills", path: "/me/bill", listing: true },
+];
+
+fn fetch_section(client: &OVHClient, section: &ExportSection) -> io::Result<Value> {
+    let value = rest::get(client, section.path)?;
+    if !section.listing {
+        return Ok(value);
+    }
+    let mut items = Vec::new();
+    for id in rest::string_list(&value)? {
+        items.push(rest::get(client, &format!("{}/{}", section.path, rest::escape(&id)))?);
+    }
+    Ok(Value::Array(items))
+}
+
+/// Fetch the given sections into one document:
+/// `{"exportedAt": ..., "sections": {...}, "errors": {...}}`.
+/// A section which cannot be read (e.g. not allowed by the consumer key rules)
+/// is reported under `errors` instead of failing the whole export.
+pub fn export(client: &OVHClient, sections: &[ExportSection]) -> Value {
+    let mut data = Map::new();
+    let mut errors = Map::new();
+    for section in sections {
+        match fetch_section(client, section) {
+            Ok(value) => {
+                data.insert(section.name.to_string(), value);
+            }
+            Err(err) => {
+                warn!("Cannot export {}: {}", section.path, err);
+                errors.insert(section.name.to_string(), Value::String(err.to_string()));
+            }
+        }
+    }
+    json!({
+        "exportedAt": Local::now().to_rfc3339(),
+        "sections": data,
+        "errors": errors,
+    })
+}
+
+/// Export the default sections into a pretty-printed JSON file.
+pub fn export_to_file<P: AsRef<Path>>(client: &OVHClient, path: P) -> io::Result<Value> {
+    let value = export(client, DEFAULT_SECTIONS);
+    let content = serde_json::to_string_pretty(&value)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    File::create(path)?.write_all(content.as_bytes())?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, export_to_file, ExportSection};
+    use serde_json::{self, Value};
+    use std::fs::{self, File};
+    use testutil;
+
+    const SECTIONS: &[ExportSection] = &[
+        ExportSection { name: "account", path: "/me", listing: false },
+        ExportSection { name: "sshKeys", path: "/me/sshKey", listing: true },
+        ExportSection { name: "bills", path: "/me/bill", listing: true },
+    ];
+
+    #[test]
+    fn test_export() {
+        let (host, server) = testutil::serve(vec![(200, "{\"nichandle\":\"xx1-ovh\"}"),
+                                                  (200, "[\"laptop\"]"),
+                                                  (200, "{\"keyName\":\"laptop\"}"),
+                                                  (403, "{\"message\":\"not granted\"}")]);
+        let client = testutil::local_client(&host);
+        let value = export(&client, SECTIONS);
+        assert_eq!("xx1-ovh", value["sections"]["account"]["nichandle"]);
+        assert_eq!("laptop", value["sections"]["sshKeys"][0]["keyName"]);
+        // an unreadable section does not fail the export
+        assert!(value["sections"].get("bills").is_none());
+        assert!(value["errors"]["bills"].as_str().unwrap().contains("not granted"));
+        assert!(value["exportedAt"].is_string());
+        let requests = server.join().unwrap();
+        assert!(requests[2].starts_with("GET /1.0/me/sshKey/laptop HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_export_to_file() {
+        let path = testutil::temp_path("ovh-rs-test-export.json");
+        let (host, server) = testutil::serve(vec![(403, "{\"message\":\"not granted\"}"); 12]);
+        let client = testutil::local_client(&host);
+        let value = export_to_file(&client, &path).unwrap();
+        let written: Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(value, written);
+        assert_eq!(12, written["errors"].as_object().unwrap().len());
+        assert_eq!(12, server.join().unwrap().len());
+        fs::remove_file(&path).unwrap();
+    }
+}
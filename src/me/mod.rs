@@ -0,0 +1,63 @@
+//! # Me
+//!
+//! Typed access to the account routes (`/me`): orders, billing, contracts, SSH keys, ...
+//!
+
+use rest;
+
+use std::io;
+
+use serde_json::Value;
+
+pub mod agreement;
+pub mod billing;
+pub mod credit;
+pub mod export;
+pub mod order;
+pub mod payment;
+pub mod sshkey;
+pub mod template;
+pub mod subaccount;
+
+/// An amount as returned by the billing and ordering routes.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Price {
+    pub value: f64,
+    pub currency_code: String,
+    /// Formatted amount, e.g. `12.00 €`.
+    pub text: String,
+}
+
+impl Price {
+    pub fn from_json(value: &Value) -> io::Result<Price> {
+        Ok(Price {
+            value: value.get("value")
+                .and_then(Value::as_f64)
+                .ok_or_else(|| rest::invalid_data("missing price value"))?,
+            currency_code: rest::opt_str_field(value, "currencyCode").unwrap_or_default(),
+            text: rest::opt_str_field(value, "text").unwrap_or_default(),
+        })
+    }
+
+    /// Price held in field `key` of `value`, if any.
+    pub fn field(value: &Value, key: &str) -> io::Result<Option<Price>> {
+        match value.get(key) {
+            Some(price) if !price.is_null() => Price::from_json(price).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Price;
+
+    #[test]
+    fn test_price_from_json() {
+        let value = json!({ "value": 12.5, "currencyCode": "EUR", "text": "12.50 €" });
+        let price = Price::from_json(&value).unwrap();
+        assert_eq!(12.5, price.value);
+        assert_eq!("EUR", price.currency_code);
+        assert!(Price::from_json(&json!({})).is_err());
+    }
+}
@@ -0,0 +1,715 @@
+//! # Orders
+//!
+//! Track an order from payment to delivery (`/me/order/{id}`):
+//! status, details, payment and delivery/installation follow-up.
+//!
+//! Before ordering, `simulate` prices a cart without validating it: the lines
+//! and taxes of the checkout, and what each product costs on renewal, so
+//! tools can show an estimate and refuse to go over a budget.
+//!
+//! ```no_run
+//! # fn run(client: &ovh::OVHClient) -> std::io::Result<()> {
+//! use ovh::me::order::{self, CartItem, CartSpec};
+//!
+//! let spec = CartSpec::new("FR")
+//!     .item(CartItem::new("vps", "vps-starter-1-2-20").configure("vps_datacenter", "GRA"));
+//! let simulation = order::simulate(client, &spec)?;
+//! simulation.check_budget(20.0)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Vouchers are applied to a cart (`/order/cart/{id}/coupon`) and
+//! the checkout summary shows the reductions they bring.
+//!
+
+use client::OVHClient;
+use me::Price;
+use me::credit::{self, CreditBalance};
+use rest::{self, Model};
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// An order of the account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Order {
+    pub id: u64,
+    pub date: String,
+    pub expiration_date: Option<String>,
+    /// Link to the order in the customer console.
+    pub url: Option<String>,
+    pub pdf_url: Option<String>,
+    pub price_with_tax: Option<Price>,
+    pub price_without_tax: Option<Price>,
+}
+
+impl Model for Order {
+    const NAME: &'static str = "Order";
+    const FIELDS: &'static [&'static str] = &["orderId", "date", "expirationDate", "retractionDate",
+                                              "url", "pdfUrl", "password", "priceWithTax",
+                                              "priceWithoutTax", "tax"];
+
+    fn from_json(value: &Value) -> io::Result<Order> {
+        Ok(Order {
+            id: rest::u64_field(value, "orderId")?,
+            date: rest::opt_str_field(value, "date").unwrap_or_default(),
+            expiration_date: rest::opt_str_field(value, "expirationDate"),
+            url: rest::opt_str_field(value, "url"),
+            pdf_url: rest::opt_str_field(value, "pdfUrl"),
+            price_with_tax: Price::field(value, "priceWithTax")?,
+            price_without_tax: Price::field(value, "priceWithoutTax")?,
+        })
+    }
+}
+
+/// Processing status of an order.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum OrderStatus {
+    NotPaid,
+    Checking,
+    DocumentsRequested,
+    Delivering,
+    Delivered,
+    Cancelling,
+    Cancelled,
+    /// Status unknown to this version of the crate.
+    Other(String),
+}
+
+impl OrderStatus {
+    pub fn parse(status: &str) -> OrderStatus {
+        match status {
+            "notPaid" => OrderStatus::NotPaid,
+            "checking" => OrderStatus::Checking,
+            "documentsRequested" => OrderStatus::DocumentsRequested,
+            "delivering" => OrderStatus::Delivering,
+            "delivered" => OrderStatus::Delivered,
+            "cancelling" => OrderStatus::Cancelling,
+            "cancelled" => OrderStatus::Cancelled,
+            other => OrderStatus::Other(other.to_string()),
+        }
+    }
+
+    /// Whether the order will not progress anymore.
+    pub fn is_final(&self) -> bool {
+        *self == OrderStatus::Delivered || *self == OrderStatus::Cancelled
+    }
+}
+
+/// A reduction of a line of an order, e.g. brought by a voucher.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Reduction {
+    /// E.g. `percentage`, `fixed_amount` or `forced_amount`.
+    pub reduction_type: String,
+    pub description: String,
+    pub value: Option<Price>,
+}
+
+impl Model for Reduction {
+    const NAME: &'static str = "Reduction";
+    const FIELDS: &'static [&'static str] = &["type", "description", "reductionDescription",
+                                              "context", "value", "price"];
+
+    fn from_json(value: &Value) -> io::Result<Reduction> {
+        Ok(Reduction {
+            reduction_type: rest::opt_str_field(value, "type").unwrap_or_default(),
+            description: rest::opt_str_field(value, "description")
+                .or_else(|| rest::opt_str_field(value, "reductionDescription"))
+                .unwrap_or_default(),
+            value: match Price::field(value, "price")? {
+                Some(price) => Some(price),
+                None => Price::field(value, "value")?,
+            },
+        })
+    }
+}
+
+/// A line of an order.
+#[derive(Debug,Clone,PartialEq)]
+pub struct OrderDetail {
+    pub id: u64,
+    pub description: String,
+    /// Service the line applies to.
+    pub domain: String,
+    pub quantity: u64,
+    pub total_price: Option<Price>,
+    /// Price before the reductions.
+    pub original_total_price: Option<Price>,
+    pub reduction_total_price: Option<Price>,
+    pub reductions: Vec<Reduction>,
+}
+
+impl Model for OrderDetail {
+    const NAME: &'static str = "OrderDetail";
+    const FIELDS: &'static [&'static str] = &["orderDetailId", "description", "domain", "quantity",
+                                              "detailType", "unitPrice", "totalPrice",
+                                              "originalTotalPrice", "reductionTotalPrice",
+                                              "reductions", "cancelled"];
+
+    fn from_json(value: &Value) -> io::Result<OrderDetail> {
+        Ok(OrderDetail {
+            id: rest::u64_field(value, "orderDetailId")?,
+            description: rest::opt_str_field(value, "description").unwrap_or_default(),
+            domain: rest::opt_str_field(value, "domain").unwrap_or_default(),
+            quantity: match value.get("quantity") {
+                Some(Value::String(s)) => s.parse().unwrap_or(1),
+                Some(quantity) => quantity.as_u64().unwrap_or(1),
+                None => 1,
+            },
+            total_price: Price::field(value, "totalPrice")?,
+            original_total_price: Price::field(value, "originalTotalPrice")?,
+            reduction_total_price: Price::field(value, "reductionTotalPrice")?,
+            reductions: match value.get("reductions") {
+                Some(reductions) if !reductions.is_null() => {
+                    rest::array(reductions)?
+                        .iter()
+                        .map(Reduction::from_json)
+                        .collect::<io::Result<_>>()?
+                }
+                _ => Vec::new(),
+            },
+        })
+    }
+}
+
+/// What validating a cart would order, as shown before the checkout.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CheckoutSummary {
+    pub details: Vec<OrderDetail>,
+    pub price_with_tax: Option<Price>,
+    pub price_without_tax: Option<Price>,
+    /// Price without tax before the reductions.
+    pub original_price_without_tax: Option<Price>,
+    /// Total of the reductions, e.g. of the vouchers applied to the cart.
+    pub reduction: Option<Price>,
+}
+
+impl Model for CheckoutSummary {
+    const NAME: &'static str = "CheckoutSummary";
+    const FIELDS: &'static [&'static str] = &["orderId", "url", "details", "prices", "contracts"];
+
+    fn from_json(value: &Value) -> io::Result<CheckoutSummary> {
+        let details: Vec<OrderDetail> = match value.get("details") {
+            Some(details) if !details.is_null() => {
+                rest::array(details)?
+                    .iter()
+                    .map(OrderDetail::from_json)
+                    .collect::<io::Result<_>>()?
+            }
+            _ => Vec::new(),
+        };
+        let prices = value.get("prices").cloned().unwrap_or(Value::Null);
+        let reduction = match Price::field(&prices, "reduction")? {
+            Some(reduction) => Some(reduction),
+            None => total_reduction(&details),
+        };
+        Ok(CheckoutSummary {
+            price_with_tax: Price::field(&prices, "withTax")?,
+            price_without_tax: Price::field(&prices, "withoutTax")?,
+            original_price_without_tax: Price::field(&prices, "originalWithoutTax")?,
+            reduction,
+            details,
+        })
+    }
+}
+
+/// Sum of the reductions of the lines, positive, `None` if there is none.
+fn total_reduction(details: &[OrderDetail]) -> Option<Price> {
+    let prices: Vec<&Price> = details.iter()
+        .filter_map(|detail| detail.reduction_total_price.as_ref())
+        .filter(|price| price.value != 0.0)
+        .collect();
+    let currency_code = prices.first()?.currency_code.clone();
+    let value = prices.iter().map(|price| price.value.abs()).sum();
+    Some(Price {
+        text: format!("{:.2} {}", value, currency_code),
+        value,
+        currency_code,
+    })
+}
+
+/// Payment registered for an order.
+#[derive(Debug,Clone,PartialEq)]
+pub struct OrderPayment {
+    pub payment_date: Option<String>,
+    pub payment_type: Option<String>,
+    pub payment_identifier: Option<String>,
+}
+
+/// A step of the delivery/installation follow-up.
+#[derive(Debug,Clone,PartialEq)]
+pub struct FollowUpStep {
+    /// Step name, e.g. `validation`, `preparation`, `delivery`.
+    pub step: String,
+    /// `todo`, `doing`, `done` or `error`.
+    pub status: String,
+    /// Dated labels of the events of the step, oldest first.
+    pub history: Vec<(String, String)>,
+}
+
+impl Model for FollowUpStep {
+    const NAME: &'static str = "FollowUpStep";
+    const FIELDS: &'static [&'static str] = &["step", "status", "history"];
+
+    fn from_json(value: &Value) -> io::Result<FollowUpStep> {
+        let mut history = Vec::new();
+        if let Some(events) = value.get("history").and_then(Value::as_array) {
+            for event in events {
+                history.push((rest::opt_str_field(event, "date").unwrap_or_default(),
+                              rest::opt_str_field(event, "label")
+                                  .or_else(|| rest::opt_str_field(event, "description"))
+                                  .unwrap_or_default()));
+            }
+        }
+        Ok(FollowUpStep {
+            step: rest::str_field(value, "step")?,
+            status: rest::str_field(value, "status")?,
+            history,
+        })
+    }
+}
+
+fn order_path(order_id: u64) -> String {
+    format!("/me/order/{}", order_id)
+}
+
+/// Ids of the orders of the account, optionally between two dates (`YYYY-MM-DD`).
+pub fn order_ids(client: &OVHClient, from: Option<&str>, to: Option<&str>) -> io::Result<Vec<u64>> {
+    let mut params = Vec::new();
+    if let Some(from) = from {
+        params.push(format!("date.from={}", rest::escape(from)));
+    }
+    if let Some(to) = to {
+        params.push(format!("date.to={}", rest::escape(to)));
+    }
+    let mut query = "/me/order".to_string();
+    if !params.is_empty() {
+        query = query + "?" + &params.join("&");
+    }
+    rest::u64_list(&rest::get(client, &query)?)
+}
+
+pub fn order(client: &OVHClient, order_id: u64) -> io::Result<Order> {
+    rest::decode(client, &rest::get(client, &order_path(order_id))?)
+}
+
+pub fn status(client: &OVHClient, order_id: u64) -> io::Result<OrderStatus> {
+    let value = rest::get(client, &format!("{}/status", order_path(order_id)))?;
+    let status = value.as_str().ok_or_else(|| rest::invalid_data("expected a status string"))?;
+    Ok(OrderStatus::parse(status))
+}
+
+pub fn details(client: &OVHClient, order_id: u64) -> io::Result<Vec<OrderDetail>> {
+    let path = format!("{}/details", order_path(order_id));
+    rest::u64_list(&rest::get(client, &path)?)?
+        .into_iter()
+        .map(|id| rest::decode(client, &rest::get(client, &format!("{}/{}", path, id))?))
+        .collect()
+}
+
+/// Delivery and installation progress of the order.
+pub fn follow_up(client: &OVHClient, order_id: u64) -> io::Result<Vec<FollowUpStep>> {
+    rest::decode_list(client, &rest::get(client, &format!("{}/followUp", order_path(order_id)))?)
+}
+
+/// Payment of the order, `None` while it is not paid.
+pub fn payment(client: &OVHClient, order_id: u64) -> io::Result<Option<OrderPayment>> {
+    let value = rest::get(client, &format!("{}/payment", order_path(order_id)))?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(OrderPayment {
+        payment_date: rest::opt_str_field(&value, "paymentDate"),
+        payment_type: rest::opt_str_field(&value, "paymentType"),
+        payment_identifier: rest::opt_str_field(&value, "paymentIdentifier"),
+    }))
+}
+
+/// Pay the order with a payment method registered on the account.
+pub fn pay(client: &OVHClient, order_id: u64, payment_method_id: u64) -> io::Result<()> {
+    let body = json!({ "paymentMethod": { "id": payment_method_id } });
+    rest::post(client, &format!("{}/pay", order_path(order_id)), &body)?;
+    Ok(())
+}
+
+/// A product to put in a cart.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CartItem {
+    /// Product of the cart routes, e.g. `vps`, `domain`, `dedicated`.
+    pub product: String,
+    pub plan_code: String,
+    /// Period of the first invoice, e.g. `P1M`.
+    pub duration: String,
+    /// E.g. `default`, `upfront12`.
+    pub pricing_mode: String,
+    pub quantity: u64,
+    /// Labels and values of the configuration, e.g. `vps_datacenter`.
+    pub configuration: Vec<(String, String)>,
+}
+
+impl CartItem {
+    /// One `plan_code` of `product` for a month, with the default pricing.
+    pub fn new(product: &str, plan_code: &str) -> CartItem {
+        CartItem {
+            product: product.to_string(),
+            plan_code: plan_code.to_string(),
+            duration: "P1M".to_string(),
+            pricing_mode: "default".to_string(),
+            quantity: 1,
+            configuration: Vec::new(),
+        }
+    }
+
+    pub fn duration(mut self, duration: &str) -> Self {
+        self.duration = duration.to_string();
+        self
+    }
+
+    pub fn pricing_mode(mut self, pricing_mode: &str) -> Self {
+        self.pricing_mode = pricing_mode.to_string();
+        self
+    }
+
+    pub fn quantity(mut self, quantity: u64) -> Self {
+        self.quantity = quantity;
+        self
+    }
+
+    pub fn configure(mut self, label: &str, value: &str) -> Self {
+        self.configuration.push((label.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// What to put in a cart, for the OVH subsidiary of the account (`FR`, `GB`, ...).
+#[derive(Debug,Clone,PartialEq)]
+pub struct CartSpec {
+    pub subsidiary: String,
+    pub items: Vec<CartItem>,
+}
+
+impl CartSpec {
+    pub fn new(subsidiary: &str) -> CartSpec {
+        CartSpec {
+            subsidiary: subsidiary.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn item(mut self, item: CartItem) -> Self {
+        self.items.push(item);
+        self
+    }
+}
+
+/// Cost of renewing a product of a cart, once per `duration`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Renewal {
+    pub plan_code: String,
+    pub duration: String,
+    pub quantity: u64,
+    /// Price of one product, `None` when the catalog has no renewal price for it.
+    pub unit_price: Option<Price>,
+}
+
+/// Prices of a cart which was not validated.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PriceSimulation {
+    pub details: Vec<OrderDetail>,
+    pub price_with_tax: Option<Price>,
+    pub price_without_tax: Option<Price>,
+    pub tax: Option<Price>,
+    pub renewals: Vec<Renewal>,
+}
+
+impl PriceSimulation {
+    fn from_checkout(value: &Value, renewals: Vec<Renewal>) -> io::Result<PriceSimulation> {
+        let details = match value.get("details") {
+            Some(details) if !details.is_null() => {
+                rest::array(details)?
+                    .iter()
+                    .map(OrderDetail::from_json)
+                    .collect::<io::Result<_>>()?
+            }
+            _ => Vec::new(),
+        };
+        let prices = value.get("prices").cloned().unwrap_or(Value::Null);
+        Ok(PriceSimulation {
+            details,
+            price_with_tax: Price::field(&prices, "withTax")?,
+            price_without_tax: Price::field(&prices, "withoutTax")?,
+            tax: Price::field(&prices, "tax")?,
+            renewals,
+        })
+    }
+
+    /// Refuse a cart costing more than `limit`, taxes included.
+    pub fn check_budget(&self, limit: f64) -> io::Result<()> {
+        let price = match self.price_with_tax {
+            Some(ref price) => price,
+            None => return Err(rest::invalid_data("the simulation has no price with tax")),
+        };
+        if price.value > limit {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      format!("the order costs {}, over the budget of {:.2} {}",
+                                              price.text,
+                                              limit,
+                                              price.currency_code)));
+        }
+        Ok(())
+    }
+}
+
+/// The renewal price of an item among the offers of its product in a cart:
+/// the price with the `renew` capacity, for its duration and pricing mode.
+fn renewal_price(offers: &Value, item: &CartItem) -> io::Result<Option<Price>> {
+    for offer in rest::array(offers)? {
+        if rest::opt_str_field(offer, "planCode").as_ref() != Some(&item.plan_code) {
+            continue;
+        }
+        let prices = match offer.get("prices").and_then(Value::as_array) {
+            Some(prices) => prices,
+            None => continue,
+        };
+        for price in prices {
+            let renew = price.get("capacities")
+                .and_then(Value::as_array)
+                .is_some_and(|capacities| capacities.iter().any(|c| c == "renew"));
+            if renew && rest::opt_str_field(price, "duration").as_ref() == Some(&item.duration) &&
+               rest::opt_str_field(price, "pricingMode").as_ref() == Some(&item.pricing_mode) {
+                return Price::field(price, "price");
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Credit balances usable as promotions on orders: vouchers and bonuses not spent yet.
+pub fn eligible_promotions(client: &OVHClient) -> io::Result<Vec<CreditBalance>> {
+    Ok(credit::balances(client)?
+        .into_iter()
+        .filter(|balance| balance.balance_type == "VOUCHER" || balance.balance_type == "BONUS")
+        .filter(|balance| balance.amount.as_ref().is_some_and(|amount| amount.value > 0.0))
+        .collect())
+}
+
+fn cart_path(cart_id: &str) -> String {
+    format!("/order/cart/{}", rest::escape(cart_id))
+}
+
+/// Fill a cart with the items of `spec` and price it.
+fn price_cart(client: &OVHClient, cart_id: &str, spec: &CartSpec) -> io::Result<PriceSimulation> {
+    let path = cart_path(cart_id);
+    rest::post(client, &format!("{}/assign", path), &json!({}))?;
+    for item in &spec.items {
+        let body = json!({
+            "planCode": item.plan_code,
+            "duration": item.duration,
+            "pricingMode": item.pricing_mode,
+            "quantity": item.quantity,
+        });
+        let product = format!("{}/{}", path, rest::escape(&item.product));
+        let added = rest::post(client, &product, &body)?;
+        let item_id = rest::u64_field(&added, "itemId")?;
+        for (label, value) in &item.configuration {
+            rest::post(client,
+                       &format!("{}/item/{}/configuration", path, item_id),
+                       &json!({ "label": label, "value": value }))?;
+        }
+    }
+    let checkout = rest::get(client, &format!("{}/checkout", path))?;
+    let mut renewals = Vec::new();
+    for item in &spec.items {
+        let offers = rest::get(client,
+                               &format!("{}/{}?planCode={}",
+                                        path,
+                                        rest::escape(&item.product),
+                                        rest::escape(&item.plan_code)))?;
+        renewals.push(Renewal {
+            plan_code: item.plan_code.clone(),
+            duration: item.duration.clone(),
+            quantity: item.quantity,
+            unit_price: renewal_price(&offers, item)?,
+        });
+    }
+    PriceSimulation::from_checkout(&checkout, renewals)
+}
+
+/// Price what `spec` would order, in a cart which is deleted afterwards
+/// without being validated.
+pub fn simulate(client: &OVHClient, spec: &CartSpec) -> io::Result<PriceSimulation> {
+    let body = json!({ "ovhSubsidiary": spec.subsidiary, "description": "price simulation" });
+    let cart_id = rest::str_field(&rest::post(client, "/order/cart", &body)?, "cartId")?;
+    let simulation = price_cart(client, &cart_id, spec);
+    if let Err(err) = rest::delete(client, &cart_path(&cart_id)) {
+        warn!("Cannot delete the simulation cart {}: {}", cart_id, err);
+    }
+    simulation
+}
+
+/// Voucher codes applied to a cart.
+pub fn coupons(client: &OVHClient, cart_id: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &format!("{}/coupon", cart_path(cart_id)))?)
+}
+
+/// Apply a voucher code to a cart. Returns the codes now applied to it.
+pub fn apply_voucher(client: &OVHClient, cart_id: &str, code: &str) -> io::Result<Vec<String>> {
+    let path = format!("{}/coupon", cart_path(cart_id));
+    rest::string_list(&rest::post(client, &path, &json!({ "coupon": code }))?)
+}
+
+/// Prices and reductions of what validating the cart would order.
+pub fn checkout_summary(client: &OVHClient, cart_id: &str) -> io::Result<CheckoutSummary> {
+    rest::decode(client, &rest::get(client, &format!("{}/checkout", cart_path(cart_id)))?)
+}
+
+/// Validate a cart into an order, to be paid with `pay`. Returns the id of the order.
+pub fn checkout(client: &OVHClient, cart_id: &str) -> io::Result<u64> {
+    let body = json!({
+        "autoPayWithPreferredPaymentMethod": false,
+        "waiveRetractationPeriod": false,
+    });
+    let value = rest::post(client, &format!("{}/checkout", cart_path(cart_id)), &body)?;
+    rest::u64_field(&value, "orderId")
+}
+
+/// Poll the status of an order every `interval` until it is delivered or cancelled,
+/// calling `on_change` on every status change. Gives up after `timeout`.
+pub fn track<F>(client: &OVHClient,
+                order_id: u64,
+                interval: Duration,
+                timeout: Duration,
+                mut on_change: F)
+                -> io::Result<OrderStatus>
+    where F: FnMut(&OrderStatus)
+{
+    let started = Instant::now();
+    let mut last: Option<OrderStatus> = None;
+    loop {
+        let current = status(client, order_id)?;
+        if last.as_ref() != Some(&current) {
+            on_change(&current);
+        }
+        if current.is_final() {
+            return Ok(current);
+        }
+        if started.elapsed() >= timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                      format!("order {} still {:?}", order_id, current)));
+        }
+        last = Some(current);
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{renewal_price, CartItem, CheckoutSummary, FollowUpStep, OrderDetail,
+                OrderStatus, PriceSimulation, Renewal};
+    use me::Price;
+    use rest::Model;
+
+    #[test]
+    fn test_order_status() {
+        assert_eq!(OrderStatus::Delivered, OrderStatus::parse("delivered"));
+        assert!(OrderStatus::parse("cancelled").is_final());
+        assert!(!OrderStatus::parse("checking").is_final());
+        assert_eq!(OrderStatus::Other("new".to_string()), OrderStatus::parse("new"));
+    }
+
+    #[test]
+    fn test_detail_and_follow_up_from_json() {
+        let detail = OrderDetail::from_json(&json!({
+            "orderDetailId": 7,
+            "description": "Domain example.com",
+            "domain": "example.com",
+            "quantity": "1",
+        }))
+            .unwrap();
+        assert_eq!(1, detail.quantity);
+
+        let step = FollowUpStep::from_json(&json!({
+            "step": "delivery",
+            "status": "doing",
+            "history": [{ "date": "2017-05-01T10:00:00+02:00", "label": "Server prepared" }],
+        }))
+            .unwrap();
+        assert_eq!("Server prepared", step.history[0].1);
+    }
+
+    #[test]
+    fn test_price_simulation() {
+        let price = |value: f64| json!({ "value": value, "currencyCode": "EUR", "text": "" });
+        let item = CartItem::new("vps", "vps-starter").duration("P1M");
+        let offers = json!([
+            { "planCode": "vps-other", "prices": [] },
+            { "planCode": "vps-starter", "prices": [
+                { "capacities": ["installation"], "duration": "P0D", "pricingMode": "default",
+                  "price": price(0.0) },
+                { "capacities": ["renew"], "duration": "P12M", "pricingMode": "upfront12",
+                  "price": price(48.0) },
+                { "capacities": ["installation", "renew"], "duration": "P1M",
+                  "pricingMode": "default", "price": price(4.5) },
+            ] },
+        ]);
+        assert_eq!(4.5, renewal_price(&offers, &item).unwrap().unwrap().value);
+        let other = CartItem::new("vps", "vps-starter").pricing_mode("upfront24");
+        assert_eq!(None, renewal_price(&offers, &other).unwrap());
+
+        let renewal = Renewal {
+            plan_code: "vps-starter".to_string(),
+            duration: "P1M".to_string(),
+            quantity: 1,
+            unit_price: Some(Price::from_json(&price(4.5)).unwrap()),
+        };
+        let checkout = json!({
+            "orderId": null,
+            "details": [{ "orderDetailId": 1, "description": "VPS Starter", "quantity": "1",
+                          "totalPrice": price(4.5) }],
+            "prices": { "withTax": price(5.4), "withoutTax": price(4.5), "tax": price(0.9) },
+        });
+        let simulation = PriceSimulation::from_checkout(&checkout, vec![renewal]).unwrap();
+        assert_eq!("VPS Starter", simulation.details[0].description);
+        assert_eq!(0.9, simulation.tax.as_ref().unwrap().value);
+        assert!(simulation.check_budget(10.0).is_ok());
+        assert!(simulation.check_budget(5.0).is_err());
+    }
+
+    #[test]
+    fn test_checkout_summary_from_json() {
+        let price = |value: f64| json!({ "value": value, "currencyCode": "EUR", "text": "" });
+        let line = |id, reduction: f64| {
+            json!({
+                "orderDetailId": id,
+                "description": "VPS",
+                "quantity": 1,
+                "totalPrice": price(10.0 - reduction),
+                "originalTotalPrice": price(10.0),
+                "reductionTotalPrice": price(-reduction),
+                "reductions": [{ "type": "fixed_amount", "description": "RESELLER10",
+                                 "price": price(-reduction) }],
+            })
+        };
+        let summary = CheckoutSummary::from_json(&json!({
+            "orderId": null,
+            "details": [line(1, 2.5), line(2, 1.0)],
+            "prices": { "withTax": price(7.8), "withoutTax": price(6.5) },
+        }))
+            .unwrap();
+        assert_eq!("RESELLER10", summary.details[0].reductions[0].description);
+        let reduction = summary.reduction.unwrap();
+        assert_eq!((3.5, "EUR"), (reduction.value, &reduction.currency_code[..]));
+
+        let summary = CheckoutSummary::from_json(&json!({
+            "details": [],
+            "prices": { "withTax": price(12.0), "reduction": price(3.0) },
+        }))
+            .unwrap();
+        assert_eq!(3.0, summary.reduction.unwrap().value);
+        assert!(CheckoutSummary::from_json(&json!({ "prices": {} })).unwrap().reduction.is_none());
+    }
+}
@@ -0,0 +1,166 @@
+//! # Payment methods
+//!
+//! Payment methods registered on the account (`/me/payment/method`),
+//! and a pre-check run before automated orders.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A payment method of the account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PaymentMethod {
+    pub id: u64,
+    /// `CREDIT_CARD`, `SEPA_DIRECT_DEBIT`, `PAYPAL`, ...
+    pub payment_type: String,
+    /// `VALID`, `PENDING`, `EXPIRED`, `CANCELED`, ...
+    pub status: String,
+    pub default: bool,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub expiration_date: Option<String>,
+}
+
+impl PaymentMethod {
+    /// Whether the method can be charged.
+    pub fn is_valid(&self) -> bool {
+        self.status == "VALID"
+    }
+}
+
+impl Model for PaymentMethod {
+    const NAME: &'static str = "PaymentMethod";
+    const FIELDS: &'static [&'static str] = &["paymentMethodId", "paymentMeanId", "paymentType",
+                                              "paymentSubType", "status", "default", "label",
+                                              "description", "icon", "expirationDate",
+                                              "creationDate", "lastUpdate", "billingContactId",
+                                              "integration", "oneclick", "merchantId",
+                                              "formSessionId"];
+
+    fn from_json(value: &Value) -> io::Result<PaymentMethod> {
+        Ok(PaymentMethod {
+            id: rest::u64_field(value, "paymentMethodId")?,
+            payment_type: rest::str_field(value, "paymentType")?,
+            status: rest::str_field(value, "status")?,
+            default: value.get("default").and_then(Value::as_bool).unwrap_or(false),
+            label: rest::opt_str_field(value, "label"),
+            description: rest::opt_str_field(value, "description"),
+            expiration_date: rest::opt_str_field(value, "expirationDate"),
+        })
+    }
+}
+
+/// A payment method being registered, to be validated by the user at `validation_url`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PendingPaymentMethod {
+    pub id: u64,
+    pub validation_url: Option<String>,
+    pub validation_type: Option<String>,
+}
+
+const PAYMENT_METHOD_PATH: &str = "/me/payment/method";
+
+pub fn payment_method_ids(client: &OVHClient) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, PAYMENT_METHOD_PATH)?)
+}
+
+pub fn payment_method(client: &OVHClient, id: u64) -> io::Result<PaymentMethod> {
+    rest::decode(client, &rest::get(client, &format!("{}/{}", PAYMENT_METHOD_PATH, id))?)
+}
+
+pub fn payment_methods(client: &OVHClient) -> io::Result<Vec<PaymentMethod>> {
+    payment_method_ids(client)?
+        .into_iter()
+        .map(|id| payment_method(client, id))
+        .collect()
+}
+
+/// Start the registration of a payment method of the given type.
+/// The user is redirected to `success_url` once the method is validated.
+pub fn add_payment_method(client: &OVHClient,
+                          payment_type: &str,
+                          description: &str,
+                          success_url: &str,
+                          set_default: bool)
+                          -> io::Result<PendingPaymentMethod> {
+    let body = json!({
+        "paymentType": payment_type,
+        "description": description,
+        "default": set_default,
+        "callbackUrl": { "success": success_url },
+    });
+    let value = rest::post(client, PAYMENT_METHOD_PATH, &body)?;
+    Ok(PendingPaymentMethod {
+        id: rest::u64_field(&value, "paymentMethodId")?,
+        validation_url: rest::opt_str_field(&value, "url"),
+        validation_type: rest::opt_str_field(&value, "validationType"),
+    })
+}
+
+pub fn remove_payment_method(client: &OVHClient, id: u64) -> io::Result<()> {
+    rest::delete(client, &format!("{}/{}", PAYMENT_METHOD_PATH, id))?;
+    Ok(())
+}
+
+/// Make a method the one charged by default.
+pub fn set_default_payment_method(client: &OVHClient, id: u64) -> io::Result<()> {
+    rest::put(client,
+              &format!("{}/{}", PAYMENT_METHOD_PATH, id),
+              &json!({ "default": true }))?;
+    Ok(())
+}
+
+/// Default method among `methods`, provided it is valid.
+pub fn usable_default(methods: &[PaymentMethod]) -> Option<&PaymentMethod> {
+    methods.iter().find(|method| method.default && method.is_valid())
+}
+
+/// Check that the account has a valid default payment method before ordering,
+/// so a cart is not validated only to fail on payment.
+pub fn check_ready_to_order(client: &OVHClient) -> io::Result<PaymentMethod> {
+    let methods = payment_methods(client)?;
+    match usable_default(&methods) {
+        Some(method) => Ok(method.clone()),
+        None => {
+            let message = if methods.iter().any(PaymentMethod::is_valid) {
+                "no default payment method, set one of the valid methods as default"
+            } else {
+                "no valid payment method registered on the account"
+            };
+            Err(io::Error::other(message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{usable_default, PaymentMethod};
+    use rest::Model;
+
+    #[test]
+    fn test_usable_default() {
+        let card = PaymentMethod::from_json(&json!({
+            "paymentMethodId": 1,
+            "paymentType": "CREDIT_CARD",
+            "status": "EXPIRED",
+            "default": true,
+        }))
+            .unwrap();
+        let sepa = PaymentMethod::from_json(&json!({
+            "paymentMethodId": 2,
+            "paymentType": "SEPA_DIRECT_DEBIT",
+            "status": "VALID",
+            "default": false,
+        }))
+            .unwrap();
+        let mut methods = vec![card, sepa];
+        assert_eq!(None, usable_default(&methods));
+        methods[0].default = false;
+        methods[1].default = true;
+        assert_eq!(2, usable_default(&methods).unwrap().id);
+    }
+}
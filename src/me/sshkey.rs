@@ -0,0 +1,101 @@
+//! # Account SSH keys
+//!
+//! SSH keys of the account (`/me/sshKey`), offered when installing dedicated
+//! servers and VPS. The default key is used when an installation names none.
+//! Public Cloud projects have their own keys, see `cloud::sshkey`.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// An SSH key of the account, identified by its name.
+#[derive(Debug,Clone,PartialEq)]
+pub struct AccountSshKey {
+    pub name: String,
+    pub public_key: String,
+    pub default: bool,
+}
+
+impl Model for AccountSshKey {
+    const NAME: &'static str = "AccountSshKey";
+    const FIELDS: &'static [&'static str] = &["keyName", "key", "default"];
+
+    fn from_json(value: &Value) -> io::Result<AccountSshKey> {
+        Ok(AccountSshKey {
+            name: rest::str_field(value, "keyName")?,
+            public_key: rest::str_field(value, "key")?,
+            default: value.get("default").and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+const SSH_KEY_PATH: &str = "/me/sshKey";
+
+fn ssh_key_path(name: &str) -> String {
+    format!("{}/{}", SSH_KEY_PATH, rest::escape(name))
+}
+
+pub fn ssh_key_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, SSH_KEY_PATH)?)
+}
+
+pub fn ssh_key(client: &OVHClient, name: &str) -> io::Result<AccountSshKey> {
+    rest::decode(client, &rest::get(client, &ssh_key_path(name))?)
+}
+
+pub fn ssh_keys(client: &OVHClient) -> io::Result<Vec<AccountSshKey>> {
+    ssh_key_names(client)?
+        .iter()
+        .map(|name| ssh_key(client, name))
+        .collect()
+}
+
+pub fn add_ssh_key(client: &OVHClient, name: &str, public_key: &str) -> io::Result<()> {
+    let body = json!({
+        "keyName": name,
+        "key": public_key,
+    });
+    rest::post(client, SSH_KEY_PATH, &body)?;
+    Ok(())
+}
+
+pub fn delete_ssh_key(client: &OVHClient, name: &str) -> io::Result<()> {
+    rest::delete(client, &ssh_key_path(name))?;
+    Ok(())
+}
+
+/// Make a key the default of the installations; the previous default is unset by the API.
+pub fn set_default_ssh_key(client: &OVHClient, name: &str) -> io::Result<()> {
+    rest::put(client, &ssh_key_path(name), &json!({ "default": true }))?;
+    Ok(())
+}
+
+/// Default key among `keys`, if any.
+pub fn default_key(keys: &[AccountSshKey]) -> Option<&AccountSshKey> {
+    keys.iter().find(|key| key.default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_key, AccountSshKey};
+    use rest::Model;
+
+    #[test]
+    fn test_default_key() {
+        let laptop = AccountSshKey::from_json(&json!({
+            "keyName": "laptop",
+            "key": "ssh-ed25519 AAAAC3Nza jdoe@laptop",
+            "default": false,
+        }))
+            .unwrap();
+        let mut keys = vec![laptop.clone()];
+        assert_eq!(None, default_key(&keys));
+        keys.push(AccountSshKey { name: "ci".to_string(), default: true, ..laptop });
+        assert_eq!("ci", default_key(&keys).unwrap().name);
+        assert!(AccountSshKey::from_json(&json!({ "keyName": "ci" })).is_err());
+    }
+}
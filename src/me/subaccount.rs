@@ -0,0 +1,54 @@
+//! # Sub-accounts
+//!
+//! Sub-accounts of a reseller account (`/me/subAccount`).
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// A sub-account.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SubAccount {
+    pub id: u64,
+    pub description: Option<String>,
+    pub creation_date: Option<String>,
+}
+
+impl Model for SubAccount {
+    const NAME: &'static str = "SubAccount";
+    const FIELDS: &'static [&'static str] = &["id", "description", "creationDate"];
+
+    fn from_json(value: &Value) -> io::Result<SubAccount> {
+        Ok(SubAccount {
+            id: rest::u64_field(value, "id")?,
+            description: rest::opt_str_field(value, "description"),
+            creation_date: rest::opt_str_field(value, "creationDate"),
+        })
+    }
+}
+
+pub fn sub_account_ids(client: &OVHClient) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, "/me/subAccount")?)
+}
+
+pub fn sub_account(client: &OVHClient, id: u64) -> io::Result<SubAccount> {
+    rest::decode(client, &rest::get(client, &format!("/me/subAccount/{}", id))?)
+}
+
+/// Create a sub-account and return its id.
+pub fn create_sub_account(client: &OVHClient, description: &str) -> io::Result<u64> {
+    let value = rest::post(client, "/me/subAccount", &json!({ "description": description }))?;
+    value.as_u64().ok_or_else(|| rest::invalid_data("expected a sub-account id"))
+}
+
+/// Create a consumer key allowing to act on behalf of the sub-account.
+pub fn create_consumer_key(client: &OVHClient, id: u64) -> io::Result<String> {
+    let value = rest::post(client,
+                           &format!("/me/subAccount/{}/createConsumerKey", id),
+                           &json!({}))?;
+    rest::str_field(&value, "consumerKey")
+}
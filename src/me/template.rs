@@ -0,0 +1,400 @@
+//! # Installation templates
+//!
+//! Personal installation templates of dedicated servers (`/me/installationTemplate`):
+//! a copy of an OVH template with its own partition schemes, hardware RAID and
+//! customization, to be referenced by name when reinstalling servers.
+//!
+//! A scheme lists the partitions created on the disks; when a template has several,
+//! the installation uses the first one fitting the server, by decreasing `priority`.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use serde_json::Value;
+
+/// Settings applied at installation.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Customization {
+    pub custom_hostname: Option<String>,
+    /// Script downloaded and run once the installation is done.
+    pub post_installation_script_link: Option<String>,
+    /// Output the script prints on success, checked by the installer.
+    pub post_installation_script_return: Option<String>,
+    /// Name of an account key (`me::sshkey`) installed for root.
+    pub ssh_key_name: Option<String>,
+}
+
+impl Customization {
+    fn from_json(value: &Value) -> Customization {
+        Customization {
+            custom_hostname: rest::opt_str_field(value, "customHostname"),
+            post_installation_script_link: rest::opt_str_field(value,
+                                                               "postInstallationScriptLink"),
+            post_installation_script_return: rest::opt_str_field(value,
+                                                                 "postInstallationScriptReturn"),
+            ssh_key_name: rest::opt_str_field(value, "sshKeyName"),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "customHostname": self.custom_hostname,
+            "postInstallationScriptLink": self.post_installation_script_link,
+            "postInstallationScriptReturn": self.post_installation_script_return,
+            "sshKeyName": self.ssh_key_name,
+        })
+    }
+}
+
+/// A personal installation template.
+#[derive(Debug,Clone,PartialEq)]
+pub struct InstallationTemplate {
+    pub name: String,
+    /// OVH template the template was copied from.
+    pub base_template_name: Option<String>,
+    pub distribution: Option<String>,
+    pub family: Option<String>,
+    pub default_language: Option<String>,
+    pub customization: Customization,
+}
+
+impl Model for InstallationTemplate {
+    const NAME: &'static str = "InstallationTemplate";
+    const FIELDS: &'static [&'static str] = &["templateName", "baseTemplateName", "distribution",
+                                              "family", "defaultLanguage", "availableLanguages",
+                                              "description", "bitFormat", "category",
+                                              "customization", "filesystems",
+                                              "hardRaidConfiguration", "lastModification",
+                                              "softRaidOnlyMirroring", "supportsDistributionKernel",
+                                              "supportsRTM",
+                                              "supportsSqlServer", "beta", "deprecated",
+                                              "endOfInstall", "lvmReady"];
+
+    fn from_json(value: &Value) -> io::Result<InstallationTemplate> {
+        Ok(InstallationTemplate {
+            name: rest::str_field(value, "templateName")?,
+            base_template_name: rest::opt_str_field(value, "baseTemplateName"),
+            distribution: rest::opt_str_field(value, "distribution"),
+            family: rest::opt_str_field(value, "family"),
+            default_language: rest::opt_str_field(value, "defaultLanguage"),
+            customization: value.get("customization")
+                .map(Customization::from_json)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// A partition scheme of a template.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PartitionScheme {
+    pub name: String,
+    pub priority: u64,
+}
+
+impl Model for PartitionScheme {
+    const NAME: &'static str = "PartitionScheme";
+    const FIELDS: &'static [&'static str] = &["name", "priority"];
+
+    fn from_json(value: &Value) -> io::Result<PartitionScheme> {
+        Ok(PartitionScheme {
+            name: rest::str_field(value, "name")?,
+            priority: rest::opt_u64_field(value, "priority").unwrap_or(0),
+        })
+    }
+}
+
+/// A partition of a scheme.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Partition {
+    pub mountpoint: String,
+    /// E.g. `ext4`, `xfs`, `swap`.
+    pub filesystem: String,
+    /// Size in MB, 0 to fill the remaining space.
+    pub size_mb: u64,
+    /// Position of the partition on the disk.
+    pub order: u64,
+    /// `primary`, `logical` or `lv`.
+    pub partition_type: String,
+    /// Software RAID level, e.g. `1`.
+    pub raid: Option<String>,
+    /// Name of the logical volume, for `lv` partitions.
+    pub volume_name: Option<String>,
+}
+
+impl Model for Partition {
+    const NAME: &'static str = "Partition";
+    const FIELDS: &'static [&'static str] = &["mountpoint", "filesystem", "size", "order", "type",
+                                              "raid", "volumeName"];
+
+    fn from_json(value: &Value) -> io::Result<Partition> {
+        // the size is answered as {"unit": "MB", "value": ...}
+        let size = value.get("size").map(|size| size.get("value").unwrap_or(size));
+        Ok(Partition {
+            mountpoint: rest::str_field(value, "mountpoint")?,
+            filesystem: rest::str_field(value, "filesystem")?,
+            size_mb: size.and_then(Value::as_u64).unwrap_or(0),
+            order: rest::opt_u64_field(value, "order").unwrap_or(0),
+            partition_type: rest::opt_str_field(value, "type").unwrap_or_default(),
+            raid: match value.get("raid") {
+                Some(Value::Number(level)) => Some(level.to_string()),
+                Some(Value::String(level)) => Some(level.clone()),
+                _ => None,
+            },
+            volume_name: rest::opt_str_field(value, "volumeName"),
+        })
+    }
+}
+
+impl Partition {
+    fn to_json(&self) -> Value {
+        json!({
+            "mountpoint": self.mountpoint,
+            "filesystem": self.filesystem,
+            "size": self.size_mb,
+            "step": self.order,
+            "type": self.partition_type,
+            "raid": self.raid,
+            "volumeName": self.volume_name,
+        })
+    }
+}
+
+/// Hardware RAID of a scheme, for servers with a RAID controller.
+#[derive(Debug,Clone,PartialEq)]
+pub struct HardwareRaid {
+    pub name: String,
+    /// E.g. `raid1`, `raid10`.
+    pub mode: String,
+    /// Disks of the array, e.g. `[c0:d0,c0:d1]`.
+    pub disks: Vec<String>,
+    pub step: u64,
+}
+
+impl Model for HardwareRaid {
+    const NAME: &'static str = "HardwareRaid";
+    const FIELDS: &'static [&'static str] = &["name", "mode", "disks", "step"];
+
+    fn from_json(value: &Value) -> io::Result<HardwareRaid> {
+        Ok(HardwareRaid {
+            name: rest::str_field(value, "name")?,
+            mode: rest::str_field(value, "mode")?,
+            disks: match value.get("disks") {
+                Some(disks) => rest::string_list(disks)?,
+                None => Vec::new(),
+            },
+            step: rest::opt_u64_field(value, "step").unwrap_or(0),
+        })
+    }
+}
+
+impl HardwareRaid {
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "mode": self.mode,
+            "disks": self.disks,
+            "step": self.step,
+        })
+    }
+}
+
+const TEMPLATE_PATH: &str = "/me/installationTemplate";
+
+fn template_path(template: &str) -> String {
+    format!("{}/{}", TEMPLATE_PATH, rest::escape(template))
+}
+
+fn scheme_path(template: &str, scheme: &str) -> String {
+    format!("{}/partitionScheme/{}", template_path(template), rest::escape(scheme))
+}
+
+pub fn installation_template_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, TEMPLATE_PATH)?)
+}
+
+pub fn installation_template(client: &OVHClient, name: &str) -> io::Result<InstallationTemplate> {
+    rest::decode(client, &rest::get(client, &template_path(name))?)
+}
+
+/// Create a template as a copy of the OVH template `base_template`, e.g. `debian12_64`.
+pub fn create_installation_template(client: &OVHClient,
+                                    name: &str,
+                                    base_template: &str,
+                                    default_language: &str)
+                                    -> io::Result<()> {
+    let body = json!({
+        "name": name,
+        "baseTemplateName": base_template,
+        "defaultLanguage": default_language,
+    });
+    rest::post(client, TEMPLATE_PATH, &body)?;
+    Ok(())
+}
+
+pub fn set_customization(client: &OVHClient,
+                         name: &str,
+                         customization: &Customization)
+                         -> io::Result<()> {
+    let body = json!({
+        "templateName": name,
+        "customization": customization.to_json(),
+    });
+    rest::put(client, &template_path(name), &body)?;
+    Ok(())
+}
+
+pub fn delete_installation_template(client: &OVHClient, name: &str) -> io::Result<()> {
+    rest::delete(client, &template_path(name))?;
+    Ok(())
+}
+
+pub fn partition_scheme_names(client: &OVHClient, template: &str) -> io::Result<Vec<String>> {
+    let path = format!("{}/partitionScheme", template_path(template));
+    rest::string_list(&rest::get(client, &path)?)
+}
+
+pub fn partition_scheme(client: &OVHClient,
+                        template: &str,
+                        scheme: &str)
+                        -> io::Result<PartitionScheme> {
+    rest::decode(client, &rest::get(client, &scheme_path(template, scheme))?)
+}
+
+pub fn create_partition_scheme(client: &OVHClient,
+                               template: &str,
+                               scheme: &PartitionScheme)
+                               -> io::Result<()> {
+    let body = json!({
+        "name": scheme.name,
+        "priority": scheme.priority,
+    });
+    rest::post(client,
+               &format!("{}/partitionScheme", template_path(template)),
+               &body)?;
+    Ok(())
+}
+
+pub fn delete_partition_scheme(client: &OVHClient, template: &str, scheme: &str) -> io::Result<()> {
+    rest::delete(client, &scheme_path(template, scheme))?;
+    Ok(())
+}
+
+/// Partitions of a scheme, in disk order.
+pub fn partitions(client: &OVHClient, template: &str, scheme: &str) -> io::Result<Vec<Partition>> {
+    let path = format!("{}/partition", scheme_path(template, scheme));
+    let mut partitions = rest::string_list(&rest::get(client, &path)?)?
+        .iter()
+        .map(|mountpoint| {
+            rest::decode(client,
+                         &rest::get(client, &format!("{}/{}", path, rest::escape(mountpoint)))?)
+        })
+        .collect::<io::Result<Vec<Partition>>>()?;
+    partitions.sort_by_key(|partition| partition.order);
+    Ok(partitions)
+}
+
+pub fn add_partition(client: &OVHClient,
+                     template: &str,
+                     scheme: &str,
+                     partition: &Partition)
+                     -> io::Result<()> {
+    rest::post(client,
+               &format!("{}/partition", scheme_path(template, scheme)),
+               &partition.to_json())?;
+    Ok(())
+}
+
+pub fn delete_partition(client: &OVHClient,
+                        template: &str,
+                        scheme: &str,
+                        mountpoint: &str)
+                        -> io::Result<()> {
+    rest::delete(client,
+                 &format!("{}/partition/{}",
+                          scheme_path(template, scheme),
+                          rest::escape(mountpoint)))?;
+    Ok(())
+}
+
+pub fn hardware_raids(client: &OVHClient,
+                      template: &str,
+                      scheme: &str)
+                      -> io::Result<Vec<HardwareRaid>> {
+    let path = format!("{}/hardwareRaid", scheme_path(template, scheme));
+    rest::string_list(&rest::get(client, &path)?)?
+        .iter()
+        .map(|name| {
+            rest::decode(client,
+                         &rest::get(client, &format!("{}/{}", path, rest::escape(name)))?)
+        })
+        .collect()
+}
+
+pub fn add_hardware_raid(client: &OVHClient,
+                         template: &str,
+                         scheme: &str,
+                         raid: &HardwareRaid)
+                         -> io::Result<()> {
+    rest::post(client,
+               &format!("{}/hardwareRaid", scheme_path(template, scheme)),
+               &raid.to_json())?;
+    Ok(())
+}
+
+pub fn delete_hardware_raid(client: &OVHClient,
+                            template: &str,
+                            scheme: &str,
+                            name: &str)
+                            -> io::Result<()> {
+    rest::delete(client,
+                 &format!("{}/hardwareRaid/{}",
+                          scheme_path(template, scheme),
+                          rest::escape(name)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scheme_path, InstallationTemplate, Partition};
+    use rest::Model;
+
+    #[test]
+    fn test_partition() {
+        let value = json!({
+            "mountpoint": "/var",
+            "filesystem": "ext4",
+            "size": { "unit": "MB", "value": 20480 },
+            "order": 3,
+            "type": "lv",
+            "raid": 1,
+            "volumeName": "var",
+        });
+        let partition = Partition::from_json(&value).unwrap();
+        assert_eq!((20480, Some("1".to_string())), (partition.size_mb, partition.raid.clone()));
+        let body = partition.to_json();
+        assert_eq!((json!(20480), json!(3)), (body["size"].clone(), body["step"].clone()));
+        let plain = json!({
+            "mountpoint": "/var", "filesystem": "ext4", "size": 20480, "order": 3,
+            "type": "lv", "raid": "1", "volumeName": "var",
+        });
+        assert_eq!(partition, Partition::from_json(&plain).unwrap());
+
+        assert_eq!("/me/installationTemplate/web/partitionScheme/default",
+                   scheme_path("web", "default"));
+    }
+
+    #[test]
+    fn test_installation_template() {
+        let template = InstallationTemplate::from_json(&json!({
+            "templateName": "web",
+            "baseTemplateName": "debian12_64",
+            "customization": { "sshKeyName": "ci", "customHostname": null },
+        }))
+            .unwrap();
+        assert_eq!(Some("ci".to_string()), template.customization.ssh_key_name);
+        assert_eq!(None, template.customization.custom_hostname);
+    }
+}
@@ -0,0 +1,353 @@
+//! # Metrics
+//!
+//! Metrics Data Platform (`/metrics`): the read and write tokens of a service,
+//! and, with the `metrics` feature, `MetricsWriter` to push datapoints with them.
+//!
+//! Datapoints are encoded in the Warp10 input format
+//! (`<ts>// <class>{<labels>} <value>`) or in the Graphite plaintext format
+//! with tags (`<name>;<tag>=<value> <value> <ts>`), and pushed by batches over HTTPS.
+//!
+
+use client::OVHClient;
+use rest::{self, Model};
+
+use std::io;
+
+use chrono::Local;
+
+use serde_json::Value;
+
+/// A token of a Metrics service.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Token {
+    pub id: String,
+    /// `read` or `write`.
+    pub access: String,
+    pub description: Option<String>,
+    /// The token itself.
+    pub token: Option<String>,
+    pub labels: Vec<(String, String)>,
+    pub is_revoked: bool,
+}
+
+impl Token {
+    pub fn can_write(&self) -> bool {
+        self.access == "write" && !self.is_revoked
+    }
+}
+
+impl Model for Token {
+    const NAME: &'static str = "Token";
+    const FIELDS: &'static [&'static str] = &["id", "access", "description", "token", "labels",
+                                              "isRevoked", "createdAt", "expiryAt", "type"];
+
+    fn from_json(value: &Value) -> io::Result<Token> {
+        let labels = match value.get("labels") {
+            Some(labels) if !labels.is_null() => {
+                rest::array(labels)?
+                    .iter()
+                    .map(|label| {
+                        Ok((rest::str_field(label, "key")?, rest::str_field(label, "value")?))
+                    })
+                    .collect::<io::Result<Vec<(String, String)>>>()?
+            }
+            _ => Vec::new(),
+        };
+        Ok(Token {
+            id: rest::str_field(value, "id")?,
+            access: rest::str_field(value, "access")?,
+            description: rest::opt_str_field(value, "description"),
+            token: rest::opt_str_field(value, "token"),
+            labels,
+            is_revoked: value.get("isRevoked").and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+fn tokens_path(service: &str) -> String {
+    format!("/metrics/{}/token", rest::escape(service))
+}
+
+pub fn service_names(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/metrics")?)
+}
+
+pub fn token_ids(client: &OVHClient, service: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, &tokens_path(service))?)
+}
+
+pub fn token(client: &OVHClient, service: &str, token_id: &str) -> io::Result<Token> {
+    let path = format!("{}/{}", tokens_path(service), rest::escape(token_id));
+    rest::decode(client, &rest::get(client, &path)?)
+}
+
+/// Create a token; `access` is `read` or `write`. Labels of a write token are
+/// added to the datapoints pushed with it.
+pub fn create_token(client: &OVHClient,
+                    service: &str,
+                    access: &str,
+                    description: &str,
+                    labels: &[(&str, &str)])
+                    -> io::Result<Token> {
+    let labels: Vec<Value> = labels.iter()
+        .map(|&(key, value)| json!({ "key": key, "value": value }))
+        .collect();
+    let body = json!({
+        "access": access,
+        "description": description,
+        "labels": labels,
+    });
+    rest::decode(client, &rest::post(client, &tokens_path(service), &body)?)
+}
+
+/// A valid write token of the service with the given description,
+/// created when there is none.
+pub fn write_token(client: &OVHClient, service: &str, description: &str) -> io::Result<Token> {
+    for id in token_ids(client, service)? {
+        let token = token(client, service, &id)?;
+        let described = token.description.as_deref() == Some(description);
+        if token.can_write() && described {
+            return Ok(token);
+        }
+    }
+    create_token(client, service, "write", description, &[])
+}
+
+/// URL of the Warp10 input of a region, e.g. `gra1`.
+pub fn warp10_url(region: &str) -> String {
+    format!("https://warp10.{}.metrics.ovh.net/api/v0/update", region)
+}
+
+/// Line format of the pushed datapoints.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum LineFormat {
+    Warp10,
+    Graphite,
+}
+
+/// A datapoint of a series.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Point {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    /// Microseconds since the epoch.
+    pub timestamp: i64,
+}
+
+impl Point {
+    /// A datapoint timestamped now.
+    pub fn new(name: &str, value: f64) -> Point {
+        let now = Local::now();
+        Point {
+            name: name.to_string(),
+            labels: Vec::new(),
+            value,
+            timestamp: now.timestamp() * 1000000 + now.timestamp_subsec_micros() as i64,
+        }
+    }
+
+    pub fn label(mut self, key: &str, value: &str) -> Point {
+        self.labels.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// The datapoint as a line of the given format.
+    pub fn to_line(&self, format: LineFormat) -> String {
+        match format {
+            LineFormat::Warp10 => {
+                let labels: Vec<String> = self.labels
+                    .iter()
+                    .map(|(key, value)| {
+                        format!("{}={}", warp10_escape(key), warp10_escape(value))
+                    })
+                    .collect();
+                format!("{}// {}{{{}}} {:?}",
+                        self.timestamp,
+                        warp10_escape(&self.name),
+                        labels.join(","),
+                        self.value)
+            }
+            LineFormat::Graphite => {
+                let mut line = graphite_escape(&self.name);
+                for (key, value) in &self.labels {
+                    line.push_str(&format!(";{}={}", graphite_escape(key), graphite_escape(value)));
+                }
+                format!("{} {} {}", line, self.value, self.timestamp / 1000000)
+            }
+        }
+    }
+}
+
+/// Percent-encode the characters with a meaning in Warp10 lines.
+fn warp10_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '%' | ',' | '=' | '{' | '}' | ' ' | '\n' | '\r' => {
+                escaped.push_str(&format!("%{:02X}", c as u32))
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replace the characters separating the fields of Graphite lines.
+fn graphite_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' ' | ';' | '=' | '\n' | '\r' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+/// The lines of a batch of datapoints.
+pub fn encode(points: &[Point], format: LineFormat) -> String {
+    let mut body = String::new();
+    for point in points {
+        body.push_str(&point.to_line(format));
+        body.push('\n');
+    }
+    body
+}
+
+#[cfg(feature = "metrics")]
+pub use self::writer::MetricsWriter;
+
+#[cfg(feature = "metrics")]
+mod writer {
+    use super::{encode, warp10_url, LineFormat, Point};
+    use client::Network;
+    use http;
+
+    use std::io;
+
+    use base64;
+
+    const DEFAULT_BATCH_SIZE: usize = 500;
+
+    /// Buffers datapoints and pushes them by batches with a write token.
+    ///
+    /// The buffered datapoints are pushed when the batch is full, on `flush`
+    /// and when the writer is dropped.
+    pub struct MetricsWriter {
+        url: String,
+        format: LineFormat,
+        token: String,
+        batch_size: usize,
+        buffer: Vec<Point>,
+        network: Network,
+    }
+
+    impl MetricsWriter {
+        /// Push to `url` in `format`: Warp10 authenticates with the
+        /// `X-Warp10-Token` header, Graphite with the token as basic auth password.
+        pub fn new(url: &str, format: LineFormat, token: &str) -> MetricsWriter {
+            MetricsWriter {
+                url: url.to_string(),
+                format,
+                token: token.to_string(),
+                batch_size: DEFAULT_BATCH_SIZE,
+                buffer: Vec::new(),
+                network: Network::default(),
+            }
+        }
+
+        /// Push to the Warp10 input of `region`, e.g. `gra1`.
+        pub fn warp10(region: &str, token: &str) -> MetricsWriter {
+            MetricsWriter::new(&warp10_url(region), LineFormat::Warp10, token)
+        }
+
+        pub fn batch_size(mut self, size: usize) -> MetricsWriter {
+            self.batch_size = size.max(1);
+            self
+        }
+
+        /// Push through `network`, e.g. the one of a client, instead of the
+        /// proxy of the environment.
+        pub fn network(mut self, network: Network) -> MetricsWriter {
+            self.network = network;
+            self
+        }
+
+        pub fn push(&mut self, point: Point) -> io::Result<()> {
+            self.buffer.push(point);
+            if self.buffer.len() >= self.batch_size {
+                self.flush()?;
+            }
+            Ok(())
+        }
+
+        /// Push the buffered datapoints. They are kept on failure, to retry later.
+        pub fn flush(&mut self) -> io::Result<()> {
+            if self.buffer.is_empty() {
+                return Ok(());
+            }
+            let auth = match self.format {
+                LineFormat::Warp10 => ("X-Warp10-Token", self.token.clone()),
+                LineFormat::Graphite => {
+                    let credentials = format!("metrics:{}", self.token);
+                    ("Authorization", format!("Basic {}", base64::encode(credentials.as_bytes())))
+                }
+            };
+            let res = http::post_with_headers(&self.network,
+                                              &self.url,
+                                              "text/plain",
+                                              &encode(&self.buffer, self.format),
+                                              &[auth])?;
+            if res.status >= 300 {
+                return Err(io::Error::other(format!("metrics push answered {}: {}",
+                                                  res.status,
+                                                  res.body)));
+            }
+            self.buffer.clear();
+            Ok(())
+        }
+    }
+
+    impl Drop for MetricsWriter {
+        fn drop(&mut self) {
+            if let Err(err) = self.flush() {
+                warn!("dropping {} datapoints: {}", self.buffer.len(), err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, LineFormat, Point, Token};
+    use rest::Model;
+
+    #[test]
+    fn test_lines() {
+        let point = Point {
+            name: "http.requests".to_string(),
+            labels: vec![("host".to_string(), "web 1".to_string()),
+                         ("code".to_string(), "200".to_string())],
+            value: 42.0,
+            timestamp: 1500000000123456,
+        };
+        assert_eq!("1500000000123456// http.requests{host=web%201,code=200} 42.0",
+                   point.to_line(LineFormat::Warp10));
+        assert_eq!("http.requests;host=web_1;code=200 42 1500000000",
+                   point.to_line(LineFormat::Graphite));
+        assert_eq!(2, encode(&[point.clone(), point], LineFormat::Warp10).lines().count());
+    }
+
+    #[test]
+    fn test_token() {
+        let token = Token::from_json(&json!({
+                "id": "t1",
+                "access": "write",
+                "description": "shop",
+                "labels": [{ "key": "env", "value": "prod" }],
+                "isRevoked": false,
+            }))
+            .unwrap();
+        assert!(token.can_write());
+        assert_eq!(vec![("env".to_string(), "prod".to_string())], token.labels);
+    }
+}
@@ -0,0 +1,210 @@
+//! # Middleware
+//!
+//! Hooks around every call of a client, for what the client does not do
+//! itself: custom headers, audit logs, tracing spans, caching, ...
+//!
+//! A `Middleware` sees each request before it is signed and sent, and may
+//! change it or answer it itself; it then sees the answer, or the transport
+//! error. The middleware of a client run in the order they were added, on
+//! every attempt of a retried call.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::OVHClient;
+//! use ovh::client::RawResponse;
+//! use ovh::middleware::{After, Before, Request};
+//! use std::io;
+//!
+//! let mut client = OVHClient::try_new()?;
+//! client.middleware.push(Before(|request: &mut Request| {
+//!     request.headers.push(("X-Request-Origin".to_string(), "billing-agent".to_string()));
+//!     Ok(None)
+//! }));
+//! client.middleware.push(After(|request: &Request, answer: &io::Result<RawResponse>| {
+//!     if let Ok(ref response) = *answer {
+//!         println!("{} {} -> {}", request.method, request.query, response.status);
+//!     }
+//! }));
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use client::RawResponse;
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// A request about to be signed and sent.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path and query string, relative to `/1.0`.
+    pub query: String,
+    pub body: String,
+    /// Headers added to those of the client; they are not signed.
+    pub headers: Vec<(String, String)>,
+}
+
+pub trait Middleware: Send + Sync {
+    /// Inspect or change a request. An answer given here is used instead of
+    /// sending the request, e.g. from a cache; an error fails the call.
+    fn before(&self, _request: &mut Request) -> io::Result<Option<RawResponse>> {
+        Ok(None)
+    }
+
+    /// Observe the answer of a request, or its transport error.
+    fn after(&self, _request: &Request, _answer: &io::Result<RawResponse>) {}
+}
+
+/// A middleware only acting before requests.
+pub struct Before<F>(pub F);
+
+impl<F> Middleware for Before<F>
+    where F: Fn(&mut Request) -> io::Result<Option<RawResponse>> + Send + Sync
+{
+    fn before(&self, request: &mut Request) -> io::Result<Option<RawResponse>> {
+        (self.0)(request)
+    }
+}
+
+/// A middleware only observing answers.
+pub struct After<F>(pub F);
+
+impl<F> Middleware for After<F>
+    where F: Fn(&Request, &io::Result<RawResponse>) + Send + Sync
+{
+    fn after(&self, request: &Request, answer: &io::Result<RawResponse>) {
+        (self.0)(request, answer)
+    }
+}
+
+/// Ask the API for messages in a language, e.g. `en`, with `Accept-Language`;
+/// see `ClientBuilder::language`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct AcceptLanguage(pub String);
+
+impl Middleware for AcceptLanguage {
+    fn before(&self, request: &mut Request) -> io::Result<Option<RawResponse>> {
+        let set = request.headers.iter().any(|(name, _)| {
+            name.eq_ignore_ascii_case("Accept-Language")
+        });
+        if !set {
+            request.headers.push(("Accept-Language".to_string(), self.0.clone()));
+        }
+        Ok(None)
+    }
+}
+
+/// The middleware of a client.
+#[derive(Clone,Default)]
+pub struct Chain {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Chain({} middleware)", self.layers.len())
+    }
+}
+
+impl Chain {
+    pub fn push<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.layers.push(Arc::new(middleware));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Run `send` through the middleware: each one may change the request,
+    /// the first answer given by one stops the others and `send`.
+    pub(crate) fn run<F>(&self, mut request: Request, send: F) -> io::Result<RawResponse>
+        where F: FnOnce(&Request) -> io::Result<RawResponse>
+    {
+        let answer = match self.before(&mut request) {
+            Some(answer) => answer,
+            None => send(&request),
+        };
+        self.after(&request, &answer);
+        answer
+    }
+
+    /// The first half of `run`: the answer or error given by a middleware, if any.
+    pub(crate) fn before(&self, request: &mut Request) -> Option<io::Result<RawResponse>> {
+        for layer in &self.layers {
+            match layer.before(request) {
+                Ok(None) => {}
+                Ok(Some(response)) => return Some(Ok(response)),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+
+    /// The second half of `run`.
+    pub(crate) fn after(&self, request: &Request, answer: &io::Result<RawResponse>) {
+        for layer in &self.layers {
+            layer.after(request, answer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AcceptLanguage, After, Before, Chain, Middleware, Request};
+    use client::RawResponse;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    fn request() -> Request {
+        Request {
+            method: "GET".to_string(),
+            query: "/me".to_string(),
+            body: String::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    fn response(status: u16) -> RawResponse {
+        RawResponse {
+            status,
+            headers: Vec::new(),
+            body: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut chain = Chain::default();
+        chain.push(Before(|request: &mut Request| {
+            request.headers.push(("X-Origin".to_string(), "test".to_string()));
+            Ok(None)
+        }));
+        let log = seen.clone();
+        chain.push(After(move |request: &Request, answer: &io::Result<RawResponse>| {
+            let status = answer.as_ref().unwrap().status;
+            log.lock().unwrap().push(format!("{} {}", request.query, status));
+        }));
+        let answer = chain.run(request(), |request| {
+            assert_eq!(vec![("X-Origin".to_string(), "test".to_string())], request.headers);
+            Ok(response(200))
+        });
+        assert_eq!(200, answer.unwrap().status);
+
+        chain.push(Before(|_: &mut Request| Ok(Some(response(304)))));
+        let cached = chain.run(request(), |_| panic!("answered by the middleware"));
+        assert_eq!(304, cached.unwrap().status);
+        assert_eq!(vec!["/me 200".to_string(), "/me 304".to_string()], *seen.lock().unwrap());
+    }
+
+    #[test]
+    fn test_accept_language() {
+        let mut request = request();
+        AcceptLanguage("en".to_string()).before(&mut request).unwrap();
+        AcceptLanguage("fr".to_string()).before(&mut request).unwrap();
+        assert_eq!(vec![("Accept-Language".to_string(), "en".to_string())], request.headers);
+    }
+}
@@ -0,0 +1,315 @@
+//! # Activity
+//!
+//! Basic security monitoring of the account: sample its consumer keys and
+//! contact details periodically, and raise `ActivityEvent`s on suspicious
+//! changes between two samples, such as a new key allowed everything or a key
+//! of an application never seen before.
+//!
+//! The first sample is the baseline and raises nothing.
+//!
+
+use auth::{self, ApiCredential};
+use client::OVHClient;
+use rest;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+
+use serde_json::Value;
+
+const DEFAULT_DORMANT_DAYS: i64 = 90;
+
+/// Contact fields of `/me` whose changes are reported.
+const CONTACT_FIELDS: &[&str] = &["email", "phone", "cellNumber", "address",
+                                                  "city", "zip", "country", "firstname", "name",
+                                                  "organisation", "spareEmail"];
+
+/// State of the account at a given time.
+#[derive(Debug,Clone,PartialEq)]
+pub struct AccountSnapshot {
+    pub taken_on: NaiveDate,
+    pub credentials: BTreeMap<u64, ApiCredential>,
+    pub contact: BTreeMap<String, String>,
+}
+
+impl AccountSnapshot {
+    /// Keep the contact fields of a `/me` answer.
+    pub fn contact_of(me: &Value) -> BTreeMap<String, String> {
+        CONTACT_FIELDS.iter()
+            .filter_map(|&field| {
+                rest::opt_str_field(me, field).map(|value| (field.to_string(), value))
+            })
+            .collect()
+    }
+
+    /// Applications which own at least one key.
+    pub fn applications(&self) -> BTreeSet<u64> {
+        self.credentials.values().map(|credential| credential.application_id).collect()
+    }
+}
+
+/// A suspicious change of the account.
+#[derive(Debug,Clone,PartialEq)]
+pub enum ActivityEvent {
+    /// A key was created; `broad` when a rule allows every route.
+    NewCredential { credential: ApiCredential, broad: bool },
+    /// A key was created for an application which had no key before.
+    NewApplication { application_id: u64, credential_id: u64 },
+    /// A key unused for a long time was used again.
+    DormantCredentialUsed { credential: ApiCredential, idle_days: i64 },
+    /// A contact detail changed.
+    ContactChanged { field: String, old: Option<String>, new: Option<String> },
+}
+
+impl ActivityEvent {
+    /// Human readable summary of the event.
+    pub fn message(&self) -> String {
+        match *self {
+            ActivityEvent::NewCredential { ref credential, broad } => {
+                format!("New consumer key {} for application {}{}",
+                        credential.id,
+                        credential.application_id,
+                        if broad { ", allowed on every route" } else { "" })
+            }
+            ActivityEvent::NewApplication { application_id, credential_id } => {
+                format!("First consumer key {} of application {}", credential_id, application_id)
+            }
+            ActivityEvent::DormantCredentialUsed { ref credential, idle_days } => {
+                format!("Consumer key {} used again after {} day(s)", credential.id, idle_days)
+            }
+            ActivityEvent::ContactChanged { ref field, ref old, ref new } => {
+                format!("Contact {} changed from {} to {}",
+                        field,
+                        old.as_ref().map_or("(none)", |value| value.as_str()),
+                        new.as_ref().map_or("(none)", |value| value.as_str()))
+            }
+        }
+    }
+
+    /// Whether the event deserves immediate attention.
+    pub fn is_critical(&self) -> bool {
+        match *self {
+            ActivityEvent::NewCredential { broad, .. } => broad,
+            ActivityEvent::ContactChanged { ref field, .. } => field.ends_with("mail"),
+            _ => false,
+        }
+    }
+}
+
+/// Receiver of activity events.
+pub trait ActivityHook {
+    fn on_event(&self, event: &ActivityEvent) -> io::Result<()>;
+}
+
+impl<F> ActivityHook for F
+    where F: Fn(&ActivityEvent) -> io::Result<()>
+{
+    fn on_event(&self, event: &ActivityEvent) -> io::Result<()> {
+        self(event)
+    }
+}
+
+/// Compare two samples of the account.
+/// Keys unused for `dormant_days` or more which were used again are reported.
+pub fn compare(previous: &AccountSnapshot,
+               current: &AccountSnapshot,
+               dormant_days: i64)
+               -> Vec<ActivityEvent> {
+    let mut events = Vec::new();
+    let applications = previous.applications();
+    let mut new_applications = BTreeSet::new();
+    for (id, credential) in &current.credentials {
+        match previous.credentials.get(id) {
+            None => {
+                if !applications.contains(&credential.application_id) &&
+                   new_applications.insert(credential.application_id) {
+                    events.push(ActivityEvent::NewApplication {
+                        application_id: credential.application_id,
+                        credential_id: *id,
+                    });
+                }
+                events.push(ActivityEvent::NewCredential {
+                    credential: credential.clone(),
+                    broad: credential.is_over_privileged(),
+                });
+            }
+            Some(before) => {
+                if credential.last_use <= before.last_use {
+                    continue;
+                }
+                let idle_since = before.last_use.or(before.creation);
+                if let Some(idle_since) = idle_since {
+                    let idle_days = (previous.taken_on - idle_since).num_days();
+                    if idle_days >= dormant_days {
+                        events.push(ActivityEvent::DormantCredentialUsed {
+                            credential: credential.clone(),
+                            idle_days,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    let fields: BTreeSet<&String> = previous.contact.keys().chain(current.contact.keys()).collect();
+    for field in fields {
+        let (old, new) = (previous.contact.get(field), current.contact.get(field));
+        if old != new {
+            events.push(ActivityEvent::ContactChanged {
+                field: field.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+    events
+}
+
+/// Sample the account and report the suspicious changes since the previous sample.
+pub struct ActivityWatcher<'a> {
+    client: &'a OVHClient,
+    dormant_days: i64,
+    previous: Option<AccountSnapshot>,
+}
+
+impl<'a> ActivityWatcher<'a> {
+    /// Watcher reporting keys used again after 90 days.
+    pub fn new(client: &'a OVHClient) -> ActivityWatcher<'a> {
+        ActivityWatcher {
+            client,
+            dormant_days: DEFAULT_DORMANT_DAYS,
+            previous: None,
+        }
+    }
+
+    pub fn set_dormant_days(&mut self, days: i64) {
+        self.dormant_days = days;
+    }
+
+    /// Start from a sample taken earlier, e.g. by a previous run.
+    pub fn set_baseline(&mut self, snapshot: AccountSnapshot) {
+        self.previous = Some(snapshot);
+    }
+
+    /// The last sample.
+    pub fn baseline(&self) -> Option<&AccountSnapshot> {
+        self.previous.as_ref()
+    }
+
+    /// Fetch the keys and contact details of the account.
+    pub fn snapshot(&self) -> io::Result<AccountSnapshot> {
+        let mut credentials = BTreeMap::new();
+        for id in auth::credential_ids(self.client)? {
+            credentials.insert(id, auth::credential(self.client, id)?);
+        }
+        Ok(AccountSnapshot {
+            taken_on: Local::today().naive_local(),
+            credentials,
+            contact: AccountSnapshot::contact_of(&rest::get(self.client, "/me")?),
+        })
+    }
+
+    /// Take a sample and return the events since the previous one.
+    pub fn poll(&mut self) -> io::Result<Vec<ActivityEvent>> {
+        let current = self.snapshot()?;
+        let events = match self.previous {
+            Some(ref previous) => compare(previous, &current, self.dormant_days),
+            None => Vec::new(),
+        };
+        self.previous = Some(current);
+        Ok(events)
+    }
+
+    /// Poll and send every event to `hook`.
+    /// Hook failures are logged and do not stop the other events.
+    pub fn run<H: ActivityHook + ?Sized>(&mut self, hook: &H) -> io::Result<Vec<ActivityEvent>> {
+        let events = self.poll()?;
+        for event in &events {
+            if let Err(err) = hook.on_event(event) {
+                warn!("Cannot report account activity \"{}\": {}", event.message(), err);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Run every `interval`, forever. Failed samples are logged and retried
+    /// at the next interval.
+    pub fn watch<H: ActivityHook + ?Sized>(&mut self, interval: Duration, hook: &H) -> ! {
+        loop {
+            if let Err(err) = self.run(hook) {
+                warn!("Cannot sample account activity: {}", err);
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, AccountSnapshot, ActivityEvent};
+    use auth::{AccessRule, ApiCredential};
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
+
+    fn credential(id: u64, application_id: u64, last_use: Option<NaiveDate>) -> ApiCredential {
+        ApiCredential {
+            id,
+            application_id,
+            status: "validated".to_string(),
+            creation: Some(NaiveDate::from_ymd(2016, 1, 1)),
+            last_use,
+            expiration: None,
+            rules: vec![AccessRule::new("GET", "/me")],
+        }
+    }
+
+    fn snapshot(taken_on: NaiveDate,
+                credentials: Vec<ApiCredential>,
+                email: &str)
+                -> AccountSnapshot {
+        let mut contact = BTreeMap::new();
+        contact.insert("email".to_string(), email.to_string());
+        AccountSnapshot {
+            taken_on,
+            credentials: credentials.into_iter().map(|c| (c.id, c)).collect(),
+            contact,
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let day = NaiveDate::from_ymd(2017, 5, 1);
+        let previous = snapshot(day,
+                                vec![credential(1, 10, Some(NaiveDate::from_ymd(2017, 1, 1))),
+                                     credential(2, 20, Some(NaiveDate::from_ymd(2017, 4, 30)))],
+                                "me@example.com");
+        let mut broad = credential(3, 30, None);
+        broad.rules.push(AccessRule::new("DELETE", "/*"));
+        let current = snapshot(NaiveDate::from_ymd(2017, 5, 2),
+                               vec![credential(1, 10, Some(NaiveDate::from_ymd(2017, 5, 2))),
+                                    credential(2, 20, Some(NaiveDate::from_ymd(2017, 5, 2))),
+                                    broad.clone(),
+                                    credential(4, 10, None)],
+                               "attacker@example.com");
+        let events = compare(&previous, &current, 90);
+        assert_eq!(5, events.len());
+        assert_eq!(ActivityEvent::DormantCredentialUsed {
+                       credential: credential(1, 10, Some(NaiveDate::from_ymd(2017, 5, 2))),
+                       idle_days: 120,
+                   },
+                   events[0]);
+        assert_eq!(ActivityEvent::NewApplication { application_id: 30, credential_id: 3 },
+                   events[1]);
+        assert!(events[2].is_critical());
+        assert_eq!(ActivityEvent::NewCredential {
+                       credential: credential(4, 10, None),
+                       broad: false,
+                   },
+                   events[3]);
+        assert!(events[4].is_critical());
+        assert!(compare(&current, &current, 90).is_empty());
+    }
+}
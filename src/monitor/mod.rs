@@ -0,0 +1,175 @@
+//! # Monitor
+//!
+//! Watch the account services and raise alerts through a `Notifier`
+//! when they get close to their expiration date.
+//!
+//! See `activity` to watch the consumer keys and contact details of the account.
+//!
+
+use client::OVHClient;
+use services::{self, Service};
+
+use std::io;
+
+use chrono::{Local, NaiveDate};
+
+pub mod activity;
+pub mod notifier;
+
+const DEFAULT_THRESHOLDS: [u32; 3] = [1, 7, 30];
+
+/// A service which expires within one of the configured thresholds.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ExpiryAlert {
+    pub service: Service,
+    pub expiration: NaiveDate,
+    pub days_left: i64,
+    /// Smallest threshold, in days, the service fell below.
+    pub threshold: u32,
+}
+
+impl ExpiryAlert {
+    /// Human readable summary of the alert.
+    pub fn message(&self) -> String {
+        if self.days_left < 0 {
+            format!("Service {} ({}) expired on {}",
+                    self.service.display_name,
+                    self.service.product,
+                    self.expiration)
+        } else {
+            format!("Service {} ({}) expires in {} day(s), on {}",
+                    self.service.display_name,
+                    self.service.product,
+                    self.days_left,
+                    self.expiration)
+        }
+    }
+}
+
+/// Receiver of expiry alerts.
+pub trait Notifier {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()>;
+}
+
+impl<F> Notifier for F
+    where F: Fn(&ExpiryAlert) -> io::Result<()>
+{
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        self(alert)
+    }
+}
+
+/// Scan `/services` for services expiring within configurable day thresholds.
+pub struct ExpiryWatcher<'a> {
+    client: &'a OVHClient,
+    thresholds: Vec<u32>,
+    include_automatic_renewal: bool,
+}
+
+impl<'a> ExpiryWatcher<'a> {
+    /// Watcher alerting 30, 7 and 1 day(s) before expiration,
+    /// ignoring services renewed automatically.
+    pub fn new(client: &'a OVHClient) -> ExpiryWatcher<'a> {
+        ExpiryWatcher {
+            client,
+            thresholds: DEFAULT_THRESHOLDS.to_vec(),
+            include_automatic_renewal: false,
+        }
+    }
+
+    /// Days before expiration at which a service is reported.
+    pub fn set_thresholds(&mut self, thresholds: &[u32]) {
+        self.thresholds = thresholds.to_vec();
+        self.thresholds.sort();
+    }
+
+    /// Also report services which OVH will renew automatically.
+    pub fn set_include_automatic_renewal(&mut self, include: bool) {
+        self.include_automatic_renewal = include;
+    }
+
+    /// Alerts for the given services, as of `today`.
+    pub fn alerts_for(&self, services: Vec<Service>, today: NaiveDate) -> Vec<ExpiryAlert> {
+        let mut alerts = Vec::new();
+        for service in services {
+            if service.is_automatic_renewal() && !self.include_automatic_renewal {
+                continue;
+            }
+            let expiration = match service.expiration.or(service.next_billing) {
+                Some(date) => date,
+                None => continue,
+            };
+            let days_left = (expiration - today).num_days();
+            let threshold = self.thresholds
+                .iter()
+                .find(|&&threshold| days_left <= threshold as i64)
+                .cloned();
+            if let Some(threshold) = threshold {
+                alerts.push(ExpiryAlert {
+                    service,
+                    expiration,
+                    days_left,
+                    threshold,
+                });
+            }
+        }
+        alerts.sort_by_key(|alert| alert.days_left);
+        alerts
+    }
+
+    /// Fetch the services and return the ones below a threshold, soonest first.
+    pub fn scan(&self) -> io::Result<Vec<ExpiryAlert>> {
+        let services = services::services(self.client)?;
+        Ok(self.alerts_for(services, Local::today().naive_local()))
+    }
+
+    /// Scan and send every alert to `notifier`.
+    /// Notification failures are logged and do not stop the other alerts.
+    pub fn run<N: Notifier + ?Sized>(&self, notifier: &N) -> io::Result<Vec<ExpiryAlert>> {
+        let alerts = self.scan()?;
+        for alert in &alerts {
+            if let Err(err) = notifier.notify(alert) {
+                warn!("Cannot notify expiry of {}: {}", alert.service.name, err);
+            }
+        }
+        Ok(alerts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpiryWatcher;
+    use client::OVHClient;
+    use config::Credential;
+    use services::Service;
+    use chrono::NaiveDate;
+
+    fn service(name: &str, expiration: NaiveDate, renew_mode: &str) -> Service {
+        Service {
+            id: 1,
+            name: name.to_string(),
+            display_name: name.to_string(),
+            product: "domain".to_string(),
+            expiration: Some(expiration),
+            next_billing: None,
+            renew_mode: Some(renew_mode.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_alerts_for() {
+        let client = OVHClient::for_tests(Credential::new_from_file("Config.toml.dist").unwrap());
+        let watcher = ExpiryWatcher::new(&client);
+        let today = NaiveDate::from_ymd(2017, 5, 1);
+        let services = vec![service("far.com", NaiveDate::from_ymd(2017, 12, 1), "manual"),
+                            service("soon.com", NaiveDate::from_ymd(2017, 5, 6), "manual"),
+                            service("month.com", NaiveDate::from_ymd(2017, 5, 20), "manual"),
+                            service("auto.com", NaiveDate::from_ymd(2017, 5, 2), "automatic")];
+        let alerts = watcher.alerts_for(services, today);
+        assert_eq!(2, alerts.len());
+        assert_eq!("soon.com", alerts[0].service.name);
+        assert_eq!(5, alerts[0].days_left);
+        assert_eq!(7, alerts[0].threshold);
+        assert_eq!(30, alerts[1].threshold);
+    }
+}
@@ -0,0 +1,217 @@
+//! # Notifier
+//!
+//! Ready-made `Notifier` implementations, each behind its own feature:
+//! `notify-email` (local sendmail), `notify-webhook` (JSON POST),
+//! `notify-slack` (Slack incoming webhook) and `notify-matrix` (Matrix room).
+//!
+//! With `notify-webhook`, `webhook::WebhookEmitter` is a `Notifier` too,
+//! for signed deliveries.
+//!
+
+#[cfg(any(feature = "notify-email",
+          feature = "notify-webhook",
+          feature = "notify-slack",
+          feature = "notify-matrix"))]
+use super::{ExpiryAlert, Notifier};
+#[cfg(any(feature = "notify-email",
+          feature = "notify-webhook",
+          feature = "notify-slack",
+          feature = "notify-matrix"))]
+use std::io;
+
+#[cfg(feature = "notify-email")]
+use std::io::Write;
+#[cfg(feature = "notify-email")]
+use std::process::{Command, Stdio};
+
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack", feature = "notify-matrix"))]
+use client::{Network, RawResponse};
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack", feature = "notify-matrix"))]
+use http;
+#[cfg(feature = "notify-matrix")]
+use rest;
+#[cfg(feature = "notify-matrix")]
+use trace;
+
+/// Turn a non 2xx answer of a webhook into an error.
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack", feature = "notify-matrix"))]
+fn check(res: RawResponse) -> io::Result<()> {
+    if res.is_success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("webhook answered {}: {}", res.status, res.body)))
+    }
+}
+
+#[cfg(any(feature = "notify-webhook", feature = "notify-slack"))]
+fn post(network: &Network, url: &str, body: &str) -> io::Result<()> {
+    check(http::post_json(network, url, body)?)
+}
+
+/// Send alerts by email through the local `sendmail` binary.
+#[cfg(feature = "notify-email")]
+#[derive(Debug,Clone)]
+pub struct EmailNotifier {
+    pub from: String,
+    pub to: Vec<String>,
+    pub sendmail: String,
+}
+
+#[cfg(feature = "notify-email")]
+impl EmailNotifier {
+    pub fn new(from: &str, to: &[&str]) -> EmailNotifier {
+        EmailNotifier {
+            from: from.to_string(),
+            to: to.iter().map(|s| s.to_string()).collect(),
+            sendmail: "sendmail".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "notify-email")]
+impl Notifier for EmailNotifier {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        let mut child = Command::new(&self.sendmail).arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()?;
+        {
+            let stdin = child.stdin
+                .as_mut()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "no sendmail stdin"))?;
+            write!(stdin,
+                   "From: {}\r\nTo: {}\r\nSubject: [OVH] {} expires soon\r\n\r\n{}\r\n",
+                   self.from,
+                   self.to.join(", "),
+                   alert.service.display_name,
+                   alert.message())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("sendmail exited with {}", status)))
+        }
+    }
+}
+
+/// POST alerts as JSON documents to an HTTP endpoint.
+#[cfg(feature = "notify-webhook")]
+#[derive(Debug,Clone)]
+pub struct WebhookNotifier {
+    pub url: String,
+    /// Proxy of the calls, from the environment by default.
+    pub network: Network,
+}
+
+#[cfg(feature = "notify-webhook")]
+impl WebhookNotifier {
+    pub fn new(url: &str) -> WebhookNotifier {
+        WebhookNotifier {
+            url: url.to_string(),
+            network: Network::default(),
+        }
+    }
+}
+
+#[cfg(feature = "notify-webhook")]
+impl Notifier for WebhookNotifier {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        let body = json!({
+            "serviceId": alert.service.id,
+            "service": alert.service.name,
+            "product": alert.service.product,
+            "expiration": alert.expiration.to_string(),
+            "daysLeft": alert.days_left,
+            "threshold": alert.threshold,
+            "message": alert.message(),
+        });
+        post(&self.network, &self.url, &body.to_string())
+    }
+}
+
+/// Post alerts to a Slack channel through an incoming webhook.
+#[cfg(feature = "notify-slack")]
+#[derive(Debug,Clone)]
+pub struct SlackNotifier {
+    pub webhook_url: String,
+    /// Proxy of the calls, from the environment by default.
+    pub network: Network,
+}
+
+#[cfg(feature = "notify-slack")]
+impl SlackNotifier {
+    pub fn new(webhook_url: &str) -> SlackNotifier {
+        SlackNotifier {
+            webhook_url: webhook_url.to_string(),
+            network: Network::default(),
+        }
+    }
+}
+
+#[cfg(feature = "notify-slack")]
+impl Notifier for SlackNotifier {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        let body = json!({ "text": format!(":warning: {}", alert.message()) });
+        post(&self.network, &self.webhook_url, &body.to_string())
+    }
+}
+
+/// Send alerts as notices to a Matrix room, through the client-server API.
+#[cfg(feature = "notify-matrix")]
+#[derive(Debug,Clone)]
+pub struct MatrixNotifier {
+    /// Base URL of the homeserver, e.g. `https://matrix.org`.
+    pub homeserver: String,
+    /// Id of the room, e.g. `!abcdef:matrix.org`; the account must have joined it.
+    pub room_id: String,
+    pub access_token: String,
+    /// Proxy of the calls, from the environment by default.
+    pub network: Network,
+}
+
+#[cfg(feature = "notify-matrix")]
+impl MatrixNotifier {
+    pub fn new(homeserver: &str, room_id: &str, access_token: &str) -> MatrixNotifier {
+        MatrixNotifier {
+            homeserver: homeserver.trim_end_matches('/').to_string(),
+            room_id: room_id.to_string(),
+            access_token: access_token.to_string(),
+            network: Network::default(),
+        }
+    }
+
+    /// URL sending a message with the given transaction id.
+    fn send_url(&self, transaction_id: &str) -> String {
+        format!("{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                self.homeserver,
+                rest::escape(&self.room_id),
+                rest::escape(transaction_id))
+    }
+}
+
+#[cfg(feature = "notify-matrix")]
+impl Notifier for MatrixNotifier {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        let body = json!({
+            "msgtype": "m.notice",
+            "body": alert.message(),
+        });
+        let headers = [("Authorization", format!("Bearer {}", self.access_token))];
+        check(http::put_json_with_headers(&self.network,
+                                          &self.send_url(&trace::new_correlation_id()),
+                                          &body.to_string(),
+                                          &headers)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "notify-matrix")]
+    #[test]
+    fn test_matrix_send_url() {
+        let notifier = super::MatrixNotifier::new("https://matrix.org/", "!abc:matrix.org", "t");
+        assert_eq!(concat!("https://matrix.org/_matrix/client/v3/rooms/%21abc%3Amatrix.org",
+                           "/send/m.room.message/1"),
+                   notifier.send_url("1"));
+    }
+}
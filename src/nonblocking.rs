@@ -0,0 +1,438 @@
+//! # Nonblocking
+//!
+//! `AsyncClient`: the request methods of `OVHClient` as futures, for async
+//! services, behind the `async` feature.
+//!
+//! Requests are sent with the async client of reqwest, whatever the transport
+//! of the blocking client, and go through the same steps: throttle,
+//! middleware, confirmation guard, signature, retries and telemetry. The
+//! futures run on tokio: await them within a runtime with its time driver
+//! enabled (`#[tokio::main]` does). Only the request of an OAuth2 access token
+//! still runs on a blocking thread of the runtime.
+//!
+//! A panic during a call, e.g. in a middleware, fails the call with an error
+//! instead of unwinding through the executor.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::AsyncClient;
+//!
+//! let client = AsyncClient::new()?;
+//! let zones = client.get::<Vec<String>>("/domain/zone");
+//! // in an async fn: let zones = zones.await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use body::Body;
+use client::{self, json_body, Network, OVHClient, RawResponse, ResponseFormat, SignedRequest,
+             Timeouts, Transport};
+use config::Credential;
+use guard;
+use middleware::Request;
+use rest;
+use retry::{self, Transient};
+use trace;
+
+use chrono::Local;
+use futures_util::future::{self, BoxFuture, FutureExt, TryFutureExt};
+use reqwest;
+use serde::{Deserialize, Serialize};
+use tokio::{task, time};
+
+use std::any::Any;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The future of a call of `AsyncClient`.
+pub type Call<T> = BoxFuture<'static, io::Result<T>>;
+
+/// The async reqwest clients of the process, one per `Transport`.
+static HTTP_CLIENTS: Mutex<Vec<(Transport, reqwest::Client)>> = Mutex::new(Vec::new());
+
+fn http_client(transport: Transport) -> io::Result<reqwest::Client> {
+    let mut clients = HTTP_CLIENTS.lock().unwrap();
+    if let Some((_, client)) = clients.iter().find(|&(known, _)| *known == transport) {
+        return Ok(client.clone());
+    }
+    let client = transport.builder()?.build().map_err(client::reqwest_error)?;
+    clients.push((transport, client.clone()));
+    Ok(client)
+}
+
+fn failed<T: Send + 'static>(err: io::Error) -> Call<T> {
+    future::ready(Err(err)).boxed()
+}
+
+/// Wait for `delay`, without a timer when there is nothing to wait for.
+fn pause(delay: Duration) -> BoxFuture<'static, ()> {
+    if delay == Duration::from_secs(0) {
+        future::ready(()).boxed()
+    } else {
+        time::sleep(delay).boxed()
+    }
+}
+
+/// The error of a panicked call.
+fn panicked(payload: Box<dyn Any + Send>) -> io::Error {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => {
+            payload.downcast_ref::<&str>().map_or("unknown panic", |message| message).to_string()
+        }
+    };
+    io::Error::other(format!("call panicked: {}", message))
+}
+
+/// Run `f` on a blocking thread of the runtime.
+fn blocking<T, F>(f: F) -> Call<T>
+    where T: Send + 'static,
+          F: FnOnce() -> io::Result<T> + Send + 'static
+{
+    task::spawn_blocking(f)
+        .map(|joined| match joined {
+            Ok(result) => result,
+            Err(err) => {
+                match err.try_into_panic() {
+                    Ok(payload) => Err(panicked(payload)),
+                    Err(err) => Err(io::Error::other(err.to_string())),
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Send a request within `timeouts` and read its whole answer.
+fn perform(network: &Network,
+           method: &str,
+           url: &str,
+           headers: Vec<(String, String)>,
+           body: Option<String>,
+           timeouts: Timeouts)
+           -> Call<RawResponse> {
+    let method = match reqwest::Method::from_bytes(method.as_bytes()) {
+        Ok(method) => method,
+        Err(_) => return failed(io::Error::new(io::ErrorKind::InvalidInput, "bad method")),
+    };
+    let url = network.target(url);
+    let http = match Transport::of(network, &url, timeouts.connect).and_then(http_client) {
+        Ok(http) => http,
+        Err(err) => return failed(err),
+    };
+    let mut request = http.request(method, &url).timeout(timeouts.request);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = body {
+        request = request.body(body);
+    }
+    request.send()
+        .map_err(client::reqwest_error)
+        .and_then(|res| {
+            let status = res.status().as_u16();
+            let headers = client::header_pairs(res.headers());
+            res.bytes().map_err(client::reqwest_error).map_ok(move |body| {
+                RawResponse {
+                    status,
+                    headers,
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                }
+            })
+        })
+        .boxed()
+}
+
+/// Fetch the drift of the clock of `host`, as `OVHClient::sync_time`.
+fn sync_time(network: &Network, host: &str) -> Call<i64> {
+    let url = client::api_url(host, "/auth/time");
+    let host = host.to_string();
+    perform(network, "GET", &url, Vec::new(), None, Timeouts::default())
+        .map(move |res| {
+            let server_time = client::parse_time(&url, &res?.body)?;
+            Ok(OVHClient::remember_server_time(&host, server_time))
+        })
+        .boxed()
+}
+
+/// The drift of the clock of `host`, as `OVHClient::time_delta`.
+fn time_delta(network: &Network, host: &str) -> BoxFuture<'static, i64> {
+    if let Some(delta) = OVHClient::known_time_delta(host) {
+        return future::ready(delta).boxed();
+    }
+    sync_time(network, host)
+        .map(|delta| {
+            delta.unwrap_or_else(|err| {
+                info!("fail to fetch remote time: {}", err);
+                0
+            })
+        })
+        .boxed()
+}
+
+/// Sign a request, or authorize it with an access token, as `OVHClient::authorize`.
+fn authorize(client: &Arc<OVHClient>, request: &Request) -> Call<SignedRequest> {
+    let credential = &client.credential;
+    let method = request.method.clone();
+    let (query, body) = (request.query.clone(), request.body.clone());
+    let oauth2 = match credential.oauth2() {
+        Some(oauth2) => oauth2,
+        None => {
+            let credential: Credential = credential.clone();
+            return time_delta(&client.network, &credential.host)
+                .map(move |delta| {
+                    let url = client::api_url(&credential.host, &query);
+                    let timestamp = Local::now().timestamp() + delta;
+                    Ok(client::sign_request(&credential, &method, &url, &body, timestamp))
+                })
+                .boxed();
+        }
+    };
+    if let Some(token) = oauth2.cached_token() {
+        return future::ready(Ok(OVHClient::bearer(credential, &method, &query, &body, &token)))
+            .boxed();
+    }
+    let client = client.clone();
+    blocking(move || {
+        OVHClient::authorize_via(&client.network, &client.credential, &method, &query, &body)
+    })
+}
+
+/// Authorize and send a request, as `OVHClient::exchange`.
+fn exchange(client: &Arc<OVHClient>, request: &Request) -> Call<RawResponse> {
+    let network = client.network.clone();
+    let timeouts = client.timeouts;
+    let method = request.method.clone();
+    let extra = request.headers.clone();
+    authorize(client, request)
+        .and_then(move |signed| {
+            let mut headers: Vec<(String, String)> = signed.auth_headers()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            headers.push(("Accept".to_string(), ResponseFormat::Json.accept().to_string()));
+            headers.push(("Content-Type".to_string(),
+                          "application/json; charset=utf-8".to_string()));
+            headers.extend(extra);
+            let body = if method == "GET" || method == "HEAD" { None } else { Some(signed.body) };
+            perform(&network, &method, &signed.url, headers, body, timeouts)
+                .map_ok(move |res| client::answer(&method, res))
+        })
+        .boxed()
+}
+
+/// A call, with what it takes from where it was made.
+struct Pending {
+    client: Arc<OVHClient>,
+    method: &'static str,
+    query: String,
+    body: String,
+    confirmed: Vec<String>,
+    correlation_id: Option<String>,
+}
+
+/// One attempt of a call, as `rest::checked_send` does it.
+fn send_once(call: Arc<Pending>) -> Call<RawResponse> {
+    let wait = call.client
+        .throttle
+        .as_ref()
+        .map_or(Duration::from_secs(0), |throttle| throttle.reserve(Instant::now()));
+    if wait > Duration::from_secs(0) {
+        debug!("Throttled for {:?}", wait);
+    }
+    pause(wait)
+        .then(move |()| {
+            let client = call.client.clone();
+            let mut request = Request {
+                method: call.method.to_string(),
+                query: call.query.clone(),
+                body: call.body.clone(),
+                headers: Vec::new(),
+            };
+            let sent = match client.middleware.before(&mut request) {
+                Some(answer) => future::ready(answer).boxed(),
+                None if client.require_confirmation => {
+                    match guard::check_against(&request.method, &request.query, &call.confirmed) {
+                        Ok(()) => exchange(&client, &request),
+                        Err(err) => failed(err),
+                    }
+                }
+                None => exchange(&client, &request),
+            };
+            sent.map(move |answer| {
+                client.middleware.after(&request, &answer);
+                let correlation_id = call.correlation_id.clone();
+                let res = answer.map_err(|err| rest::with_correlation_id(err, &correlation_id))?;
+                rest::check_answer(&client, call.method, &call.query, res, correlation_id)
+            })
+        })
+        .boxed()
+}
+
+/// The attempts of a call from `attempt` on, retried as the blocking ones.
+fn send(call: Arc<Pending>, attempt: u32) -> Call<RawResponse> {
+    let started = Instant::now();
+    send_once(call.clone())
+        .then(move |sent| {
+            let client = call.client.clone();
+            client.telemetry.record(call.method,
+                                    &call.query,
+                                    attempt,
+                                    &sent,
+                                    started.elapsed(),
+                                    client.latency_budget);
+            let err = match sent {
+                Ok(res) => return future::ready(Ok(res)).boxed(),
+                Err(err) => err,
+            };
+            let (transient, delay) = match rest::retry_delay(&client,
+                                                             call.method,
+                                                             &call.query,
+                                                             attempt,
+                                                             &err) {
+                Some(retry) => retry,
+                None => return failed(err),
+            };
+            let resync = if transient == Transient::Timestamp {
+                sync_time(&client.network, &client.credential.host)
+                    .map(|synced| if let Err(err) = synced {
+                        info!("fail to fetch remote time: {}", err);
+                    })
+                    .boxed()
+            } else {
+                future::ready(()).boxed()
+            };
+            resync.then(move |()| pause(delay))
+                .then(move |()| send(call, attempt + 1))
+                .boxed()
+        })
+        .boxed()
+}
+
+/// A client whose request methods return futures.
+#[derive(Debug,Clone)]
+pub struct AsyncClient {
+    inner: Arc<OVHClient>,
+}
+
+impl AsyncClient {
+    /// Credentials from the environment or `Config.toml`, see `OVHClient::try_new`.
+    pub fn new() -> io::Result<AsyncClient> {
+        Ok(AsyncClient::from_client(OVHClient::try_new()?))
+    }
+
+    pub fn with_credential(credential: Credential) -> AsyncClient {
+        AsyncClient::from_client(OVHClient::with_credential(credential))
+    }
+
+    pub fn from_client(client: OVHClient) -> AsyncClient {
+        AsyncClient { inner: Arc::new(client) }
+    }
+
+    /// The blocking client, e.g. for the typed modules from a blocking thread.
+    pub fn blocking(&self) -> &OVHClient {
+        &self.inner
+    }
+
+    /// Start a call: the confirmations and the correlation id in effect here
+    /// apply to it, wherever it is awaited.
+    fn call<T>(&self, method: &'static str, path: &str, body: io::Result<Body>) -> Call<T>
+        where T: Deserialize + Send + 'static
+    {
+        let body = match body {
+            Ok(body) => body.to_bytes(),
+            Err(err) => return failed(err),
+        };
+        let call = Arc::new(Pending {
+            client: self.inner.clone(),
+            method,
+            query: path.to_string(),
+            body,
+            confirmed: guard::confirmations(),
+            correlation_id: trace::current(),
+        });
+        if let Some(ref id) = call.correlation_id {
+            debug!("[{}] {} {}", id, method, path);
+        }
+        retry::global().record_request();
+        let path = path.to_string();
+        AssertUnwindSafe(send(call, 1))
+            .catch_unwind()
+            .map(move |sent| match sent {
+                Ok(answer) => client::parse_answer(method, &path, &answer?.body),
+                Err(payload) => Err(panicked(payload)),
+            })
+            .boxed()
+    }
+
+    /// GET `path` (relative to `/1.0`), see `OVHClient::get`.
+    pub fn get<T>(&self, path: &str) -> Call<T>
+        where T: Deserialize + Send + 'static
+    {
+        self.call("GET", path, Ok(Body::Empty))
+    }
+
+    /// POST `body`, serialized before returning, see `OVHClient::post`.
+    pub fn post<B, T>(&self, path: &str, body: &B) -> Call<T>
+        where B: Serialize,
+              T: Deserialize + Send + 'static
+    {
+        self.call("POST", path, json_body(body))
+    }
+
+    pub fn put<B, T>(&self, path: &str, body: &B) -> Call<T>
+        where B: Serialize,
+              T: Deserialize + Send + 'static
+    {
+        self.call("PUT", path, json_body(body))
+    }
+
+    pub fn delete<T>(&self, path: &str) -> Call<T>
+        where T: Deserialize + Send + 'static
+    {
+        self.call("DELETE", path, Ok(Body::Empty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncClient;
+    use client::RawResponse;
+    use guard::{self, Confirmation};
+    use middleware::{Before, Request};
+    use serde_json::Value;
+    use testutil::{self, block_on, local_client};
+
+    use std::io;
+
+    fn broken(_: &mut Request) -> io::Result<Option<RawResponse>> {
+        panic!("broken middleware")
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut client = local_client("127.0.0.1:9");
+        client.middleware.push(Before(broken));
+        let err = block_on(AsyncClient::from_client(client).get::<Value>("/me")).unwrap_err();
+        assert_eq!("call panicked: broken middleware", err.to_string());
+    }
+
+    #[test]
+    fn test_confirmed() {
+        let (host, server) = testutil::serve(vec![(200, "")]);
+        let mut client = local_client(&host);
+        client.require_confirmation = true;
+        let client = AsyncClient::from_client(client);
+        let query = "/cloud/project/p1/instance/i-123";
+        let refused = block_on(client.delete::<Value>(query)).unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, refused.kind());
+        // confirmed where the call is made, awaited afterwards
+        let call = guard::confirmed(Confirmation::IAmSure("i-123".to_string()),
+                                    || client.delete::<Value>(query));
+        assert_eq!(Value::Null, block_on(call).unwrap());
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("DELETE /1.0/cloud/project/p1/instance/i-123 HTTP/1.1"));
+    }
+}
@@ -0,0 +1,190 @@
+//! # OAuth2
+//!
+//! Authentication of a service account with the OAuth2 client credentials
+//! grant, instead of signing requests with the application and consumer keys.
+//! An access token is requested on the first call and requested again shortly
+//! before it expires, or after the API refused it.
+//!
+//! A profile of the config file selects it with `auth = "oauth2"`:
+//!
+//! ```toml
+//! [ovh-eu]
+//! auth = "oauth2"
+//! client_id = "<CLIENT ID>"
+//! client_secret = "<CLIENT SECRET>"
+//! ```
+//!
+//! and so do `OVH_CLIENT_ID` and `OVH_CLIENT_SECRET` in the environment.
+//! `token_url` and `scope` override the defaults of the endpoint.
+//!
+
+use client::{Network, OVHClient};
+use error::{ApiError, Error};
+use rest;
+
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+/// Scope asked for when none is configured.
+pub const DEFAULT_SCOPE: &str = "all";
+
+/// A token is renewed this long before it expires, in seconds.
+const RENEW_MARGIN: u64 = 60;
+
+/// Token URL of the region of an API host, `https://<host>/auth/oauth2/token` for others.
+pub fn token_url(host: &str) -> String {
+    match host {
+        "eu.api.ovh.com" => "https://www.ovh.com/auth/oauth2/token".to_string(),
+        "ca.api.ovh.com" => "https://ca.ovh.com/auth/oauth2/token".to_string(),
+        "us.api.ovh.com" => "https://us.ovhcloud.com/auth/oauth2/token".to_string(),
+        _ => format!("https://{}/auth/oauth2/token", host),
+    }
+}
+
+/// Read the access token and its lifetime from the answer of the token URL.
+fn parse_token(body: &str) -> io::Result<(String, Duration)> {
+    let value: Value = serde_json::from_str(body)
+        .map_err(|err| rest::invalid_data(&format!("invalid token answer: {}", err)))?;
+    let token = rest::str_field(&value, "access_token")?;
+    let expires_in = value.get("expires_in").and_then(Value::as_u64).unwrap_or(3600);
+    Ok((token, Duration::from_secs(expires_in)))
+}
+
+/// The credentials of a service account, with its current access token.
+pub struct OAuth2 {
+    pub client_id: String,
+    pub(crate) client_secret: String,
+    pub token_url: String,
+    pub scope: String,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl OAuth2 {
+    pub fn new(client_id: &str, client_secret: &str, token_url: &str) -> OAuth2 {
+        OAuth2 {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            token_url: token_url.to_string(),
+            scope: DEFAULT_SCOPE.to_string(),
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scope = scope.to_string();
+        self
+    }
+
+    /// Body of the token request.
+    fn form(&self) -> String {
+        format!("grant_type=client_credentials&client_id={}&client_secret={}&scope={}",
+                rest::escape(&self.client_id),
+                rest::escape(&self.client_secret),
+                rest::escape(&self.scope))
+    }
+
+    /// A valid access token, requested from the token URL when needed.
+    pub fn access_token(&self) -> io::Result<String> {
+        self.access_token_via(&Network::default())
+    }
+
+    /// Like `access_token`, requesting it through `network`.
+    pub(crate) fn access_token_via(&self, network: &Network) -> io::Result<String> {
+        self.access_token_at(Instant::now(), |url, form| OVHClient::post_form(network, url, form))
+    }
+
+    /// The access token, while it does not need to be renewed.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn cached_token(&self) -> Option<String> {
+        let token = self.token.lock().unwrap_or_else(|err| err.into_inner());
+        match *token {
+            Some((ref access_token, renew_at)) if Instant::now() < renew_at => {
+                Some(access_token.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn access_token_at<F>(&self, now: Instant, fetch: F) -> io::Result<String>
+        where F: FnOnce(&str, &str) -> io::Result<(u16, String)>
+    {
+        let mut token = self.token.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((ref access_token, ref renew_at)) = *token {
+            if now < *renew_at {
+                return Ok(access_token.clone());
+            }
+        }
+        let (status, body) = fetch(&self.token_url, &self.form())?;
+        if status >= 400 {
+            let err = Error::Api(Box::new(ApiError::from_response("POST",
+                                                                  &self.token_url,
+                                                                  status,
+                                                                  &body)));
+            return Err(match status {
+                401 | 403 => io::Error::new(io::ErrorKind::PermissionDenied, err),
+                _ => err.into(),
+            });
+        }
+        let (access_token, lifetime) = parse_token(&body)?;
+        let margin = Duration::from_secs(RENEW_MARGIN).min(lifetime / 2);
+        *token = Some((access_token.clone(), now + lifetime - margin));
+        Ok(access_token)
+    }
+
+    /// Forget the access token, e.g. after the API refused it.
+    pub fn invalidate(&self) {
+        *self.token.lock().unwrap_or_else(|err| err.into_inner()) = None;
+    }
+}
+
+impl fmt::Debug for OAuth2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OAuth2")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"***")
+            .field("token_url", &self.token_url)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{token_url, OAuth2};
+    use rest;
+    use std::cell::Cell;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_access_token() {
+        let oauth2 = OAuth2::new("id", "s&cret", &token_url("eu.api.ovh.com"));
+        assert_eq!("https://www.ovh.com/auth/oauth2/token", oauth2.token_url);
+        let fetches = Cell::new(0);
+        let fetch = |url: &str, form: &str| -> io::Result<(u16, String)> {
+            fetches.set(fetches.get() + 1);
+            assert_eq!("https://www.ovh.com/auth/oauth2/token", url);
+            assert_eq!("grant_type=client_credentials&client_id=id&\
+                        client_secret=s%26cret&scope=all",
+                       form);
+            Ok((200, format!(r#"{{"access_token":"t{}","expires_in":3600}}"#, fetches.get())))
+        };
+        let now = Instant::now();
+        assert_eq!("t1", oauth2.access_token_at(now, fetch).unwrap());
+        assert_eq!("t1", oauth2.access_token_at(now + Duration::from_secs(3000), fetch).unwrap());
+        assert_eq!("t2", oauth2.access_token_at(now + Duration::from_secs(3540), fetch).unwrap());
+        oauth2.invalidate();
+        assert_eq!("t3", oauth2.access_token_at(now + Duration::from_secs(3540), fetch).unwrap());
+
+        oauth2.invalidate();
+        let refused = oauth2.access_token_at(now, |_: &str, _: &str| Ok((401, "{}".to_string())));
+        assert_eq!(io::ErrorKind::PermissionDenied, refused.unwrap_err().kind());
+        let throttled = oauth2.access_token_at(now, |_: &str, _: &str| Ok((429, "{}".to_string())));
+        assert!(rest::is_throttled(&throttled.unwrap_err()));
+        assert!(!format!("{:?}", oauth2).contains("s&cret"));
+    }
+}
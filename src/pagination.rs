@@ -0,0 +1,412 @@
+//! # Pagination
+//!
+//! Crawl listing routes which return ids (`/me/bill`, `/domain/zone/{zone}/record`, ...)
+//! and fetch the details of each id lazily.
+//!
+//! The progress of a crawl is a `PageCursor`, which can be saved
+//! and used to resume after a crash without starting over.
+//!
+//! Routes paginated by the API itself (v2 routes, and v1 routes answering objects
+//! with `X-Pagination-Mode: CachedObjectList-Pages`) are followed page by page
+//! with `Paginated`, using the `X-Pagination-*` headers.
+//!
+//! Typed modules expose lazy variants of their list functions (`dns::iter_records`,
+//! `services::iter_services`, ...) built on it: an item is only fetched when the
+//! consumer asks for it, instead of collecting the whole listing into a `Vec`.
+//! With the `async` feature, they also have `Stream` variants for `AsyncClient`
+//! (`dns::stream_records`, `services::stream_services`, ...): the next item is
+//! only fetched when the consumer polls for it.
+//!
+
+use client::{OVHClient, RawResponse, ResponseFormat};
+use instrument;
+#[cfg(feature = "async")]
+use nonblocking::AsyncClient;
+use rest::{self, Model};
+
+#[cfg(feature = "async")]
+use futures_util::future::{self, BoxFuture, FutureExt};
+#[cfg(feature = "async")]
+use futures_util::stream::{self, BoxStream, StreamExt};
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde_json::{self, Value};
+
+/// Progress of a crawl: the listing route and the ids still to fetch.
+#[derive(Debug,Clone,PartialEq)]
+pub struct PageCursor {
+    pub path: String,
+    pub remaining: VecDeque<String>,
+    /// Number of items already fetched.
+    pub fetched: u64,
+}
+
+impl PageCursor {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.path,
+            "remaining": self.remaining.iter().collect::<Vec<&String>>(),
+            "fetched": self.fetched,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> io::Result<PageCursor> {
+        let remaining = match value.get("remaining") {
+            Some(remaining) => rest::string_list(remaining)?,
+            None => Vec::new(),
+        };
+        Ok(PageCursor {
+            path: rest::str_field(value, "path")?,
+            remaining: remaining.into_iter().collect(),
+            fetched: rest::opt_u64_field(value, "fetched").unwrap_or(0),
+        })
+    }
+
+    /// Whether every item was fetched.
+    pub fn is_done(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    /// Write the cursor atomically, for checkpointing.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.to_path_buf().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut fd = File::create(&tmp)?;
+            fd.write_all(self.to_json().to_string().as_bytes())?;
+            fd.sync_all()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PageCursor> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| rest::invalid_data(&e.to_string()))?;
+        PageCursor::from_json(&value)
+    }
+}
+
+/// Iterator over the details of the ids listed by a route, yielding `(id, details)`.
+///
+/// The shared cursor is updated after every successful fetch, so another thread
+/// can checkpoint it while the crawl is running. After an error the failed id is
+/// kept in the cursor and the iteration stops, to be resumed later.
+pub struct Paginator<'a> {
+    client: &'a OVHClient,
+    cursor: Arc<Mutex<PageCursor>>,
+    failed: bool,
+}
+
+impl<'a> Paginator<'a> {
+    /// List the ids of `path` and start a crawl over them.
+    pub fn new(client: &'a OVHClient, path: &str) -> io::Result<Paginator<'a>> {
+        let ids = rest::string_list(&rest::get(client, path)?)?;
+        instrument::page(path, ids.len(), true);
+        Ok(Paginator::resume(client,
+                             PageCursor {
+                                 path: path.to_string(),
+                                 remaining: ids.into_iter().collect(),
+                                 fetched: 0,
+                             }))
+    }
+
+    /// Resume a crawl from a saved cursor, without listing the route again.
+    pub fn resume(client: &'a OVHClient, cursor: PageCursor) -> Paginator<'a> {
+        Paginator {
+            client,
+            cursor: Arc::new(Mutex::new(cursor)),
+            failed: false,
+        }
+    }
+
+    /// Snapshot of the progress.
+    pub fn cursor(&self) -> PageCursor {
+        self.cursor.lock().unwrap().clone()
+    }
+
+    /// Handle on the live cursor, to checkpoint it from another thread.
+    pub fn shared_cursor(&self) -> Arc<Mutex<PageCursor>> {
+        self.cursor.clone()
+    }
+}
+
+/// Decode the items of a crawl into a model, dropping the ids.
+pub(crate) fn decoded<'a, T: Model + 'a>(paginator: Paginator<'a>)
+                                        -> impl Iterator<Item = io::Result<T>> + 'a {
+    let client = paginator.client;
+    paginator.map(move |item| item.and_then(|(_, value)| rest::decode(client, &value)))
+}
+
+impl<'a> Iterator for Paginator<'a> {
+    type Item = io::Result<(String, Value)>;
+
+    fn next(&mut self) -> Option<io::Result<(String, Value)>> {
+        if self.failed {
+            return None;
+        }
+        let (path, id) = {
+            let cursor = self.cursor.lock().unwrap();
+            match cursor.remaining.front() {
+                Some(id) => (cursor.path.clone(), id.clone()),
+                None => return None,
+            }
+        };
+        match rest::get(self.client, &format!("{}/{}", path, rest::escape(&id))) {
+            Ok(value) => {
+                let mut cursor = self.cursor.lock().unwrap();
+                cursor.remaining.pop_front();
+                cursor.fetched += 1;
+                Some(Ok((id, value)))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// The items of a listing route for `AsyncClient`, see `listing`.
+#[cfg(feature = "async")]
+pub type Listing<T> = BoxStream<'static, io::Result<T>>;
+
+/// Progress of a `listing`: the ids still to fetch, once listed.
+#[cfg(feature = "async")]
+struct Crawl {
+    client: AsyncClient,
+    path: String,
+    remaining: Option<VecDeque<String>>,
+    failed: bool,
+}
+
+/// The details of the ids listed by `path`, decoded into a model, as
+/// `Paginator` fetches them: the ids are listed on the first poll, then each
+/// item is fetched when the consumer polls for it. After an error fetching an
+/// item the stream ends.
+#[cfg(feature = "async")]
+pub(crate) fn listing<T: Model + Send + 'static>(client: &AsyncClient, path: &str) -> Listing<T> {
+    let crawl = Crawl {
+        client: client.clone(),
+        path: path.to_string(),
+        remaining: None,
+        failed: false,
+    };
+    stream::unfold(crawl, next_item).boxed()
+}
+
+#[cfg(feature = "async")]
+fn next_item<T: Model + Send + 'static>(mut crawl: Crawl)
+                                        -> BoxFuture<'static, Option<(io::Result<T>, Crawl)>> {
+    if crawl.failed {
+        return future::ready(None).boxed();
+    }
+    let id = match crawl.remaining {
+        Some(ref mut remaining) => {
+            match remaining.pop_front() {
+                Some(id) => id,
+                None => return future::ready(None).boxed(),
+            }
+        }
+        None => {
+            return crawl.client
+                .get::<Value>(&crawl.path)
+                .then(move |listed| match listed.and_then(|ids| rest::string_list(&ids)) {
+                    Ok(ids) => {
+                        instrument::page(&crawl.path, ids.len(), true);
+                        crawl.remaining = Some(ids.into_iter().collect());
+                        next_item(crawl)
+                    }
+                    Err(err) => {
+                        crawl.failed = true;
+                        future::ready(Some((Err(err), crawl))).boxed()
+                    }
+                })
+                .boxed();
+        }
+    };
+    crawl.client
+        .get::<Value>(&format!("{}/{}", crawl.path, rest::escape(&id)))
+        .map(move |fetched| {
+            crawl.failed = fetched.is_err();
+            let item = fetched.and_then(|value| rest::decode(crawl.client.blocking(), &value));
+            Some((item, crawl))
+        })
+        .boxed()
+}
+
+/// Number of items asked per page by default.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// Iterator over the items of a route paginated by the API, fetching
+/// the next page when the current one is consumed.
+///
+/// The cursor of the next page is taken from the `X-Pagination-Cursor-Next`
+/// header; a route answering without it is a single page. After a failed page
+/// the iteration stops, and can be resumed with `from_cursor(next_cursor())`.
+pub struct Paginated<'a, T> {
+    client: &'a OVHClient,
+    path: String,
+    page_size: u32,
+    cursor: Option<String>,
+    page: VecDeque<Value>,
+    last_page: bool,
+    failed: bool,
+    model: PhantomData<T>,
+}
+
+impl<'a, T: Model> Paginated<'a, T> {
+    pub fn new(client: &'a OVHClient, path: &str) -> Paginated<'a, T> {
+        Paginated {
+            client,
+            path: path.to_string(),
+            page_size: DEFAULT_PAGE_SIZE,
+            cursor: None,
+            page: VecDeque::new(),
+            last_page: false,
+            failed: false,
+            model: PhantomData,
+        }
+    }
+
+    /// Resume from the cursor of a page.
+    pub fn from_cursor(client: &'a OVHClient, path: &str, cursor: &str) -> Paginated<'a, T> {
+        let mut paginated = Paginated::new(client, path);
+        paginated.cursor = Some(cursor.to_string());
+        paginated
+    }
+
+    pub fn page_size(mut self, size: u32) -> Paginated<'a, T> {
+        self.page_size = size;
+        self
+    }
+
+    /// Cursor of the page to fetch next, none on the first and after the last page.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn fetch_page(&mut self) -> io::Result<()> {
+        let mut headers = vec![("X-Pagination-Mode", "CachedObjectList-Pages".to_string()),
+                               ("X-Pagination-Size", self.page_size.to_string())];
+        if let Some(ref cursor) = self.cursor {
+            headers.push(("X-Pagination-Cursor", cursor.clone()));
+        }
+        let res = rest::checked_exchange(self.client,
+                                         "GET",
+                                         &self.path,
+                                         "",
+                                         ResponseFormat::Json,
+                                         &headers)?;
+        let (items, next) = page(&res)?;
+        instrument::page(&self.path, items.len(), next.is_none());
+        self.page.extend(items);
+        self.last_page = next.is_none();
+        self.cursor = next;
+        Ok(())
+    }
+}
+
+/// Items of a page and cursor of the next one.
+fn page(res: &RawResponse) -> io::Result<(Vec<Value>, Option<String>)> {
+    let value: Value = if res.body.trim().is_empty() {
+        Value::Array(Vec::new())
+    } else {
+        serde_json::from_str(&res.body).map_err(|e| rest::invalid_data(&e.to_string()))?
+    };
+    let items = rest::array(&value)?.clone();
+    let next = res.header("X-Pagination-Cursor-Next")
+        .filter(|cursor| !cursor.is_empty())
+        .map(|cursor| cursor.to_string());
+    Ok((items, next))
+}
+
+impl<'a, T: Model> Iterator for Paginated<'a, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<io::Result<T>> {
+        while self.page.is_empty() {
+            if self.failed || self.last_page {
+                return None;
+            }
+            if let Err(err) = self.fetch_page() {
+                self.failed = true;
+                return Some(Err(err));
+            }
+        }
+        self.page.pop_front().map(|value| rest::decode(self.client, &value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page, PageCursor};
+    use client::RawResponse;
+    use testutil;
+    use std::fs;
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_listing() {
+        use super::listing;
+        use futures_util::StreamExt;
+        use nonblocking::AsyncClient;
+        use services::Service;
+
+        let (host, server) = testutil::serve(vec![(200, "[1, 2, 3]"),
+                                                  (200,
+                                                   r#"{"serviceId": 1,
+                                                       "resource": {"name": "example.com"}}"#),
+                                                  (404, r#"{"message": "gone"}"#)]);
+        let client = AsyncClient::from_client(testutil::local_client(&host));
+        let services = testutil::block_on(listing::<Service>(&client, "/services")
+            .collect::<Vec<_>>());
+        // the stream ends at the failed item, without fetching the next one
+        assert_eq!(2, services.len());
+        assert_eq!(1, services[0].as_ref().unwrap().id);
+        assert!(services[1].is_err());
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /1.0/services HTTP/1.1"));
+        assert!(requests[2].starts_with("GET /1.0/services/2 HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = PageCursor {
+            path: "/me/bill".to_string(),
+            remaining: vec!["FR2".to_string(), "FR3".to_string()].into_iter().collect(),
+            fetched: 1,
+        };
+        let path = testutil::temp_path("ovh-rs-test-cursor.json");
+        cursor.save(&path).unwrap();
+        let loaded = PageCursor::load(&path).unwrap();
+        assert_eq!(cursor, loaded);
+        assert!(!loaded.is_done());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_page() {
+        let mut res = RawResponse {
+            status: 200,
+            headers: vec![("x-pagination-cursor-next".to_string(), "c2".to_string())],
+            body: r#"[{"id": 1}, {"id": 2}]"#.to_string(),
+        };
+        let (items, next) = page(&res).unwrap();
+        assert_eq!(2, items.len());
+        assert_eq!(Some("c2".to_string()), next);
+
+        res.headers.clear();
+        res.body = "[]".to_string();
+        assert_eq!((vec![], None), page(&res).unwrap());
+    }
+}
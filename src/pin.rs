@@ -0,0 +1,214 @@
+//! # Pin
+//!
+//! Public key pinning of the API hosts, for environments which don't want to
+//! trust every certificate authority.
+//!
+//! A pin is the SHA-256 of the DER `SubjectPublicKeyInfo` of a certificate of
+//! the chain, written `sha256//<base64>` (as curl and HPKP do). A host accepts
+//! any of its pins, so a rotation is handled by pinning the current and the
+//! next key before the switch, then dropping the old one.
+//!
+//! Pins are set per client with `ClientBuilder::pin` (or the `pins` of its
+//! `Network`); without any, `OVH_PINNED_KEYS` gives them:
+//! `eu.api.ovh.com=sha256//AAA...,sha256//BBB...;ca.api.ovh.com=...`.
+//!
+//! The pin of a key can be computed with:
+//!
+//! ```text
+//! openssl s_client -connect eu.api.ovh.com:443 </dev/null | openssl x509 -pubkey -noout \
+//!     | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64
+//! ```
+//!
+//! Both transports check them, on top of the certificate authorities, for
+//! every call to a pinned host: curl on the key of the server, reqwest on
+//! the keys of the whole chain.
+//!
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+
+use base64;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+const PREFIX: &str = "sha256//";
+
+/// Pins accepted for a host.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct PinSet {
+    pins: Vec<String>,
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+impl PinSet {
+    /// Pins given as `sha256//<base64>` or bare base64, separated by `,`.
+    pub fn parse(pins: &str) -> io::Result<PinSet> {
+        let mut set = PinSet::default();
+        for pin in pins.split(',').map(str::trim).filter(|pin| !pin.is_empty()) {
+            let encoded = pin.trim_start_matches(PREFIX);
+            match base64::decode(encoded) {
+                Ok(ref digest) if digest.len() == 32 => {
+                    set.pins.push(format!("{}{}", PREFIX, encoded))
+                }
+                _ => return Err(invalid_input(format!("bad SHA-256 pin {}", pin))),
+            }
+        }
+        if set.pins.is_empty() {
+            return Err(invalid_input("empty pin set".to_string()));
+        }
+        Ok(set)
+    }
+
+    /// Pins of the given DER encoded public keys (`SubjectPublicKeyInfo`).
+    pub fn from_public_keys(keys: &[&[u8]]) -> PinSet {
+        PinSet { pins: keys.iter().map(|key| spki_pin(key)).collect() }
+    }
+
+    pub fn pins(&self) -> &[String] {
+        &self.pins
+    }
+
+    /// Whether a DER encoded `SubjectPublicKeyInfo` has one of the pins.
+    pub fn matches(&self, der: &[u8]) -> bool {
+        self.pins.contains(&spki_pin(der))
+    }
+
+    /// Value of curl's `CURLOPT_PINNEDPUBLICKEY`.
+    pub fn curl_value(&self) -> String {
+        self.pins.join(";")
+    }
+}
+
+/// Pin of a DER encoded `SubjectPublicKeyInfo`: `sha256//<base64>`.
+pub fn spki_pin(der: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(der);
+    let mut digest = [0; 32];
+    hasher.result(&mut digest);
+    format!("{}{}", PREFIX, base64::encode(&digest))
+}
+
+/// Parse `host=pin[,pin...]` entries separated by `;`.
+pub fn parse_mapping(mapping: &str) -> io::Result<BTreeMap<String, PinSet>> {
+    let mut hosts = BTreeMap::new();
+    for entry in mapping.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let mut parts = entry.splitn(2, '=');
+        let host = parts.next().unwrap_or("").trim().to_lowercase();
+        let pins = parts.next()
+            .ok_or_else(|| invalid_input(format!("expected host=pins in {}", entry)))?;
+        hosts.insert(host, PinSet::parse(pins)?);
+    }
+    Ok(hosts)
+}
+
+/// Pins of `host` in `OVH_PINNED_KEYS`.
+pub fn from_env(host: &str) -> io::Result<Option<PinSet>> {
+    match env::var("OVH_PINNED_KEYS") {
+        Ok(mapping) => Ok(parse_mapping(&mapping)?.remove(&host.to_lowercase())),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+pub(crate) use self::verifier::pinned;
+
+/// Certificate verification of pinned hosts, with rustls.
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+mod verifier {
+    use super::PinSet;
+
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified,
+                                 ServerCertVerifier};
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use rustls::server::ParsedCertificate;
+    use rustls::{DigitallySignedStruct, SignatureScheme};
+
+    use std::convert::TryFrom;
+    use std::iter;
+    use std::sync::Arc;
+
+    /// Checks the certificate authorities, then the pins.
+    #[derive(Debug)]
+    struct PinnedVerifier {
+        roots: Arc<WebPkiServerVerifier>,
+        pins: PinSet,
+    }
+
+    impl ServerCertVerifier for PinnedVerifier {
+        fn verify_server_cert(&self,
+                              end_entity: &CertificateDer,
+                              intermediates: &[CertificateDer],
+                              server_name: &ServerName,
+                              ocsp_response: &[u8],
+                              now: UnixTime)
+                              -> Result<ServerCertVerified, rustls::Error> {
+            let verified = self.roots
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+            for certificate in iter::once(end_entity).chain(intermediates) {
+                let parsed = ParsedCertificate::try_from(certificate)?;
+                if self.pins.matches(parsed.subject_public_key_info().as_ref()) {
+                    return Ok(verified);
+                }
+            }
+            Err(rustls::Error::General("no pinned public key in the certificate chain"
+                .to_string()))
+        }
+
+        fn verify_tls12_signature(&self,
+                                  message: &[u8],
+                                  certificate: &CertificateDer,
+                                  signature: &DigitallySignedStruct)
+                                  -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.roots.verify_tls12_signature(message, certificate, signature)
+        }
+
+        fn verify_tls13_signature(&self,
+                                  message: &[u8],
+                                  certificate: &CertificateDer,
+                                  signature: &DigitallySignedStruct)
+                                  -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.roots.verify_tls13_signature(message, certificate, signature)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.roots.supported_verify_schemes()
+        }
+    }
+
+    /// Check the certificate authorities of `roots`, then `pins`.
+    pub(crate) fn pinned(roots: Arc<WebPkiServerVerifier>,
+                         pins: PinSet)
+                         -> Arc<dyn ServerCertVerifier> {
+        Arc::new(PinnedVerifier { roots, pins })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mapping, spki_pin, PinSet};
+
+    #[test]
+    fn test_pin_set() {
+        let pin = spki_pin(b"");
+        assert_eq!("sha256//47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=", pin);
+        let next = spki_pin(b"next key");
+        let set = PinSet::parse(&format!("{}, {}", pin, next.trim_start_matches("sha256//")))
+            .unwrap();
+        assert_eq!(&[pin.clone(), next.clone()], set.pins());
+        assert_eq!(format!("{};{}", pin, next), set.curl_value());
+        assert_eq!(set, PinSet::from_public_keys(&[b"", b"next key"]));
+        assert!(set.matches(b"next key") && !set.matches(b"other key"));
+        assert!(PinSet::parse("sha256//AAAA").is_err());
+        assert!(PinSet::parse("").is_err());
+
+        let hosts = parse_mapping(&format!("EU.api.ovh.com={};", pin)).unwrap();
+        assert_eq!(vec![pin], hosts["eu.api.ovh.com"].pins());
+        assert!(parse_mapping("eu.api.ovh.com").is_err());
+    }
+}
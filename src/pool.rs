@@ -0,0 +1,158 @@
+//! # Pool
+//!
+//! Clients of many accounts, for platforms managing OVH on behalf of their
+//! customers: a `ClientPool` builds the client of a tenant the first time it is
+//! asked for, with the credential given by a `CredentialProvider`, and drops it
+//! once it has been idle for a while.
+//!
+//! Tenants are isolated: each client has its own throttle, so a tenant running
+//! a bulk job does not slow the others down, and its own consumer key, which
+//! keeps the answers memoized by a `scope::RunScope` apart.
+//!
+
+use client::OVHClient;
+use config::Credential;
+use error::Error;
+use retry::RetryPolicy;
+use throttle::Throttle;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 15 * 60;
+
+/// Source of the credential of each tenant, e.g. a secret store.
+pub trait CredentialProvider {
+    fn credential(&self, tenant: &str) -> Result<Credential, Error>;
+}
+
+impl<F> CredentialProvider for F
+    where F: Fn(&str) -> Result<Credential, Error>
+{
+    fn credential(&self, tenant: &str) -> Result<Credential, Error> {
+        self(tenant)
+    }
+}
+
+struct Entry {
+    client: Arc<OVHClient>,
+    last_used: Instant,
+}
+
+/// Lazily built clients, one per tenant.
+pub struct ClientPool<P> {
+    provider: P,
+    rate_limit: Option<(f64, u32)>,
+    retry_policy: RetryPolicy,
+    idle_timeout: Duration,
+    clients: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl<P: CredentialProvider> ClientPool<P> {
+    /// Pool without rate limit nor retries, dropping clients idle for 15 minutes.
+    pub fn new(provider: P) -> ClientPool<P> {
+        ClientPool {
+            provider,
+            rate_limit: None,
+            retry_policy: RetryPolicy::default(),
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            clients: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Limit every tenant to `rate` requests per second, in bursts of at most `burst`.
+    pub fn rate_limit(mut self, rate: f64, burst: u32) -> ClientPool<P> {
+        self.rate_limit = Some((rate, burst));
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ClientPool<P> {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> ClientPool<P> {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// The client of `tenant`, built on first use.
+    pub fn client(&self, tenant: &str) -> Result<Arc<OVHClient>, Error> {
+        let now = Instant::now();
+        self.evict_idle_at(now);
+        if let Some(entry) = self.clients.lock().unwrap().get_mut(tenant) {
+            entry.last_used = now;
+            return Ok(entry.client.clone());
+        }
+        // built without the lock, so a slow provider does not block the other tenants
+        let mut client = OVHClient::with_credential(self.provider.credential(tenant)?);
+        client.retry_policy = self.retry_policy;
+        client.throttle = self.rate_limit.map(|(rate, burst)| Throttle::new(rate, burst));
+        let mut clients = self.clients.lock().unwrap();
+        let entry = clients.entry(tenant.to_string()).or_insert(Entry {
+            client: Arc::new(client),
+            last_used: now,
+        });
+        Ok(entry.client.clone())
+    }
+
+    /// Drop the client of a tenant, e.g. after its credential was revoked.
+    pub fn remove(&self, tenant: &str) -> bool {
+        self.clients.lock().unwrap().remove(tenant).is_some()
+    }
+
+    /// Drop the clients idle for longer than the idle timeout, returning how many.
+    /// Clients still held by callers keep working.
+    pub fn evict_idle(&self) -> usize {
+        self.evict_idle_at(Instant::now())
+    }
+
+    fn evict_idle_at(&self, now: Instant) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        let before = clients.len();
+        let timeout = self.idle_timeout;
+        clients.retain(|_, entry| now.saturating_duration_since(entry.last_used) <= timeout);
+        before - clients.len()
+    }
+
+    /// Tenants with a client.
+    pub fn tenants(&self) -> Vec<String> {
+        self.clients.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientPool;
+    use config::{Credential, Endpoint};
+    use error::Error;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_pool() {
+        let provider = |tenant: &str| {
+            if tenant == "unknown" {
+                return Err(Error::MissingKey(format!("{}.consumer_key", tenant)));
+            }
+            Credential::new_with_credential(Endpoint::OvhEu, "ak", "as", &format!("ck-{}", tenant))
+        };
+        let pool = ClientPool::new(provider)
+            .rate_limit(10.0, 5)
+            .idle_timeout(Duration::from_secs(60));
+        let acme = pool.client("acme").unwrap();
+        let globex = pool.client("globex").unwrap();
+        assert_eq!("ck-acme", acme.credential.consumer_key);
+        assert!(Arc::ptr_eq(&acme, &pool.client("acme").unwrap()));
+        assert!(!Arc::ptr_eq(acme.throttle.as_ref().unwrap(), globex.throttle.as_ref().unwrap()));
+        assert!(pool.client("unknown").is_err());
+        assert_eq!(vec!["acme", "globex"], pool.tenants());
+
+        assert_eq!(0, pool.evict_idle_at(Instant::now()));
+        assert_eq!(2, pool.evict_idle_at(Instant::now() + Duration::from_secs(61)));
+        assert!(pool.tenants().is_empty());
+        assert!(!Arc::ptr_eq(&acme, &pool.client("acme").unwrap()));
+        assert!(pool.remove("acme"));
+    }
+}
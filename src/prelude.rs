@@ -0,0 +1,75 @@
+//! # Prelude
+//!
+//! The types and modules most consumers need, in a single import:
+//!
+//! ```no_run
+//! use ovh::prelude::*;
+//!
+//! fn record_count(client: &OVHClient, zone: &str) -> Result<usize> {
+//!     Ok(dns::record_ids(client, zone)?.len())
+//! }
+//! ```
+//!
+
+pub use client::{FieldPolicy, OVHClient};
+pub use config::{Credential, Endpoint};
+pub use error::{ApiError, Error};
+
+pub use serde_json::Value;
+
+pub use {cloud, dedicated, dns, me, services};
+
+/// Result of the errors of the crate: `?` takes those of the calls too.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use testutil;
+
+    fn connect(endpoint: &str) -> Result<OVHClient> {
+        let endpoint: Endpoint = endpoint.parse()?;
+        let credential = Credential::new_with_credential(endpoint, "ak", "as", "ck")?;
+        Ok(OVHClient::with_credential(credential))
+    }
+
+    fn zone_count(client: &OVHClient) -> Result<usize> {
+        let zones: Vec<String> = client.get("/domain/zone")?;
+        Ok(zones.len())
+    }
+
+    #[test]
+    fn test_prelude() {
+        let client = connect("ovh-ca").unwrap();
+        assert_eq!(("ca.api.ovh.com", FieldPolicy::Lenient),
+                   (&client.credential.host[..], client.field_policy));
+        match connect("ovh-mars") {
+            Err(Error::UnknownEndpoint(name)) => assert_eq!("ovh-mars", name),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        // the crate root re-exports the same types
+        let endpoint: ::Endpoint = Endpoint::KimsufiEu;
+        assert_eq!("kimsufi-eu", endpoint.name());
+        let value: ::Value = Value::from(1);
+        assert_eq!(Some(1), value.as_u64());
+    }
+
+    #[test]
+    fn test_prelude_result() {
+        let (host, server) = testutil::serve(vec![(200, "[\"example.com\"]"),
+                                                  (403, "{\"message\":\"This call has not \
+                                                         been granted\"}")]);
+        let client = testutil::local_client(&host);
+        assert_eq!(1, zone_count(&client).unwrap());
+        // the API error of the call is not buried in an io::Error
+        match zone_count(&client) {
+            Err(Error::Api(api)) => assert_eq!(403, api.http_code),
+            other => panic!("{:?}", other),
+        }
+        server.join().unwrap();
+
+        let err = Error::from(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        assert_eq!(io::ErrorKind::TimedOut, io::Error::from(err).kind());
+    }
+}
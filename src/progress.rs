@@ -0,0 +1,134 @@
+//! # Progress
+//!
+//! Machine-readable progress of long-running helpers (zone sync, queue flush,
+//! task polling, ...). Helpers emit `ProgressEvent`s to a `Progress` given by
+//! the caller, who decides how to render them: progress bar, web UI, JSON lines.
+//!
+
+use std::fmt;
+use std::sync::mpsc::Sender;
+
+use serde_json::Value;
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum ProgressEvent {
+    /// An operation started, with its number of steps when known.
+    Started { operation: String, total: Option<usize> },
+    /// A step of the operation was done.
+    Step {
+        operation: String,
+        done: usize,
+        total: Option<usize>,
+        message: String,
+    },
+    /// The remote status of what the operation waits for changed.
+    Status { operation: String, status: String },
+    /// The operation sleeps before trying again or polling.
+    Waiting { operation: String, seconds: u64 },
+    Finished { operation: String },
+    Failed { operation: String, error: String },
+}
+
+impl ProgressEvent {
+    pub fn operation(&self) -> &str {
+        match *self {
+            ProgressEvent::Started { ref operation, .. } |
+            ProgressEvent::Step { ref operation, .. } |
+            ProgressEvent::Status { ref operation, .. } |
+            ProgressEvent::Waiting { ref operation, .. } |
+            ProgressEvent::Finished { ref operation } |
+            ProgressEvent::Failed { ref operation, .. } => operation,
+        }
+    }
+
+    /// The event as a JSON object with an `event` field, e.g. for JSON lines output.
+    pub fn to_json(&self) -> Value {
+        match *self {
+            ProgressEvent::Started { ref operation, total } => {
+                json!({ "event": "started", "operation": operation, "total": total })
+            }
+            ProgressEvent::Step { ref operation, done, total, ref message } => {
+                json!({
+                    "event": "step",
+                    "operation": operation,
+                    "done": done,
+                    "total": total,
+                    "message": message,
+                })
+            }
+            ProgressEvent::Status { ref operation, ref status } => {
+                json!({ "event": "status", "operation": operation, "status": status })
+            }
+            ProgressEvent::Waiting { ref operation, seconds } => {
+                json!({ "event": "waiting", "operation": operation, "seconds": seconds })
+            }
+            ProgressEvent::Finished { ref operation } => {
+                json!({ "event": "finished", "operation": operation })
+            }
+            ProgressEvent::Failed { ref operation, ref error } => {
+                json!({ "event": "failed", "operation": operation, "error": error })
+            }
+        }
+    }
+}
+
+type Sink = Box<dyn Fn(&ProgressEvent)>;
+
+/// Where progress events go. The default discards them.
+#[derive(Default)]
+pub struct Progress {
+    sink: Option<Sink>,
+}
+
+impl Progress {
+    pub fn none() -> Progress {
+        Progress { sink: None }
+    }
+
+    pub fn callback<F: Fn(&ProgressEvent) + 'static>(callback: F) -> Progress {
+        Progress { sink: Some(Box::new(callback)) }
+    }
+
+    /// Send the events to a channel, e.g. read by a UI thread.
+    /// Events are dropped once the receiver is gone.
+    pub fn channel(sender: Sender<ProgressEvent>) -> Progress {
+        Progress::callback(move |event| {
+            let _ = sender.send(event.clone());
+        })
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        if let Some(ref sink) = self.sink {
+            sink(&event);
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Progress({})", if self.sink.is_some() { "sink" } else { "none" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Progress, ProgressEvent};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_channel() {
+        let (sender, receiver) = channel();
+        let progress = Progress::channel(sender);
+        progress.emit(ProgressEvent::Started {
+            operation: "sync".to_string(),
+            total: Some(2),
+        });
+        progress.emit(ProgressEvent::Finished { operation: "sync".to_string() });
+        drop(progress);
+        let events: Vec<ProgressEvent> = receiver.iter().collect();
+        assert_eq!(2, events.len());
+        assert_eq!("sync", events[1].operation());
+        assert_eq!(json!({ "event": "started", "operation": "sync", "total": 2 }),
+                   events[0].to_json());
+    }
+}
@@ -0,0 +1,457 @@
+//! # Queue
+//!
+//! Write-ahead queue for mutation requests.
+//! Requests are persisted on disk as soon as they are enqueued
+//! and replayed against the API, with retries, once connectivity returns.
+//! Useful for edge agents (DynDNS updaters, ...) running on flaky networks.
+//!
+//! A request which can never be sent — refused by the API, or by the
+//! transport (e.g. a method it does not support) — is moved to the
+//! dead-letter file next to the queue, `<queue>.dead`, with its error, so
+//! that it does not hold back the requests queued after it.
+//!
+
+use client::{OVHClient, ResponseFormat};
+use error::{self, ApiError};
+use progress::{Progress, ProgressEvent};
+use rest;
+use retry;
+use trace::Operation;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{self, Value};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+
+/// A mutation request waiting to be sent to the API.
+#[derive(Debug,Clone,PartialEq)]
+pub struct QueuedRequest {
+    pub id: u64,
+    pub method: String,
+    pub query: String,
+    pub body: String,
+    pub attempts: u32,
+}
+
+impl QueuedRequest {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "method": self.method,
+            "query": self.query,
+            "body": self.body,
+            "attempts": self.attempts,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<QueuedRequest> {
+        Some(QueuedRequest {
+            id: value.get("id").and_then(Value::as_u64)?,
+            method: value.get("method").and_then(Value::as_str)?.to_string(),
+            query: value.get("query").and_then(Value::as_str)?.to_string(),
+            body: value.get("body").and_then(Value::as_str)?.to_string(),
+            attempts: value.get("attempts").and_then(Value::as_u64).unwrap_or(0) as u32,
+        })
+    }
+}
+
+/// Outcome of a `WriteQueue::flush`.
+#[derive(Debug,Clone,Default)]
+pub struct FlushReport {
+    /// Ids of the requests accepted by the API.
+    pub sent: Vec<u64>,
+    /// Requests refused by the API (4xx), moved to the dead-letter file, with
+    /// status and message.
+    pub rejected: Vec<(QueuedRequest, u16, String)>,
+    /// Requests the transport could not send, moved to the dead-letter file,
+    /// with the error.
+    pub failed: Vec<(QueuedRequest, String)>,
+    /// Number of requests still waiting, if the API could not be reached.
+    pub remaining: usize,
+}
+
+/// Why a queued request is moved to the dead-letter file.
+enum Undeliverable {
+    Rejected(Box<ApiError>),
+    Failed(io::Error),
+}
+
+/// Whether an error answer may be followed by a success: timeouts, throttling
+/// and server errors.
+fn is_transient(err: &ApiError) -> bool {
+    err.http_code == 408 || err.http_code == 429 || err.http_code >= 500
+}
+
+/// Persistent FIFO of mutation requests, stored as one JSON object per line.
+#[derive(Debug)]
+pub struct WriteQueue {
+    path: PathBuf,
+    dead_letters: PathBuf,
+    entries: Vec<QueuedRequest>,
+    next_id: u64,
+    max_attempts: u32,
+    retry_delay: Duration,
+    progress: Progress,
+}
+
+impl WriteQueue {
+    /// Open the queue stored at `path`, creating an empty one if the file does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<WriteQueue> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+        if path.exists() {
+            let fd = File::open(&path)?;
+            for line in BufReader::new(fd).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str::<Value>(&line)
+                    .ok()
+                    .and_then(|value| QueuedRequest::from_json(&value));
+                match entry {
+                    Some(entry) => entries.push(entry),
+                    None => warn!("Skipping corrupted queue entry: {}", line),
+                }
+            }
+        }
+        let next_id = entries.iter().map(|e| e.id + 1).max().unwrap_or(1);
+        let mut dead_letters = path.clone().into_os_string();
+        dead_letters.push(".dead");
+        Ok(WriteQueue {
+            path,
+            dead_letters: PathBuf::from(dead_letters),
+            entries,
+            next_id,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay: Duration::from_millis(DEFAULT_RETRY_DELAY_MS),
+            progress: Progress::none(),
+        })
+    }
+
+    /// Number of tries per request during a flush before giving up until the next flush.
+    pub fn set_max_attempts(&mut self, max_attempts: u32) {
+        self.max_attempts = if max_attempts == 0 { 1 } else { max_attempts };
+    }
+
+    /// Delay before the first retry, doubled after each failed attempt.
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay;
+    }
+
+    /// Report the requests sent, rejected and retried by `flush`.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    /// Where the requests which cannot be sent are moved, `<queue>.dead` by default.
+    pub fn set_dead_letter_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.dead_letters = path.as_ref().to_path_buf();
+    }
+
+    pub fn dead_letter_path(&self) -> &Path {
+        &self.dead_letters
+    }
+
+    /// Requests waiting to be sent, oldest first.
+    pub fn pending(&self) -> &[QueuedRequest] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append a mutation request (POST, PUT, PATCH, DELETE) and persist it
+    /// before returning its id.
+    pub fn enqueue(&mut self, method: &str, query: &str, body: &str) -> io::Result<u64> {
+        let method = method.to_uppercase();
+        match method.as_str() {
+            "POST" | "PUT" | "PATCH" | "DELETE" => {}
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("{} is not a mutation method", method)))
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(QueuedRequest {
+            id,
+            method,
+            query: query.to_string(),
+            body: body.to_string(),
+            attempts: 0,
+        });
+        self.persist()?;
+        Ok(id)
+    }
+
+    /// Send queued requests in order with `client`, and its network settings,
+    /// retry policy, throttle, middlewares and confirmation guard.
+    ///
+    /// Transient failures (network errors, `408`, `429` and 5xx answers) are
+    /// retried with exponential backoff, at least as late as the API asks,
+    /// within the limits of the global retry budget;
+    /// when a request still fails after `max_attempts`, flushing stops so that
+    /// ordering is preserved and the remaining requests wait for the next flush.
+    /// Requests refused with another 4xx status, or which the transport cannot
+    /// send, are moved to the dead-letter file and reported.
+    pub fn flush(&mut self, client: &OVHClient) -> io::Result<FlushReport> {
+        let operation = Operation::start("queue flush");
+        let mut report = FlushReport::default();
+        let total = self.entries.len();
+        self.progress.emit(ProgressEvent::Started {
+            operation: "queue flush".to_string(),
+            total: Some(total),
+        });
+        while !self.entries.is_empty() {
+            let mut delay = self.retry_delay;
+            let mut done = None;
+            for attempt in 0..self.max_attempts {
+                if attempt > 0 {
+                    if !retry::global().try_retry() {
+                        info!("[{}] Retry budget exhausted, stopping flush", operation.id());
+                        break;
+                    }
+                    self.progress.emit(ProgressEvent::Waiting {
+                        operation: "queue flush".to_string(),
+                        seconds: delay.as_secs(),
+                    });
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                let entry = self.entries[0].clone();
+                self.entries[0].attempts += 1;
+                let sent = rest::checked_exchange(client,
+                                                  &entry.method,
+                                                  &entry.query,
+                                                  &entry.body,
+                                                  ResponseFormat::Json,
+                                                  &[]);
+                let api = sent.as_ref().err().and_then(error::api_error).cloned();
+                match (sent, api) {
+                    (Ok(_), _) => done = Some(Ok(())),
+                    (Err(_), Some(api)) => {
+                        if !is_transient(&api) {
+                            done = Some(Err(Undeliverable::Rejected(Box::new(api))));
+                        } else {
+                            info!("[{}] Queued request {} failed with {}",
+                                  operation.id(),
+                                  entry.id,
+                                  api.http_code);
+                            let asked = api.rate_limit.as_ref().and_then(|info| info.wait());
+                            if let Some(wait) = asked {
+                                delay = delay.max(wait);
+                            }
+                        }
+                    }
+                    (Err(err), None) => {
+                        if retry::transient(&err).is_none() {
+                            done = Some(Err(Undeliverable::Failed(err)));
+                        } else {
+                            info!("[{}] Queued request {} failed: {}",
+                                  operation.id(),
+                                  entry.id,
+                                  err);
+                        }
+                    }
+                }
+                if done.is_some() {
+                    break;
+                }
+            }
+            let done = match done {
+                Some(done) => done,
+                None => {
+                    self.persist()?;
+                    break;
+                }
+            };
+            let entry = self.entries[0].clone();
+            match done {
+                Ok(()) => report.sent.push(entry.id),
+                Err(Undeliverable::Rejected(api)) => {
+                    let error = format!("refused with {}: {}", api.http_code, api.message);
+                    self.bury(&entry, &error)?;
+                    let api = *api;
+                    report.rejected.push((entry, api.http_code, api.message));
+                }
+                Err(Undeliverable::Failed(err)) => {
+                    warn!("[{}] Queued request {} cannot be sent, moving it to {}: {}",
+                          operation.id(),
+                          entry.id,
+                          self.dead_letters.display(),
+                          err);
+                    self.bury(&entry, &err.to_string())?;
+                    report.failed.push((entry, err.to_string()));
+                }
+            }
+            let entry = self.entries.remove(0);
+            self.persist()?;
+            self.progress.emit(ProgressEvent::Step {
+                operation: "queue flush".to_string(),
+                done: total - self.entries.len(),
+                total: Some(total),
+                message: format!("{} {}", entry.method, entry.query),
+            });
+        }
+        report.remaining = self.entries.len();
+        self.progress.emit(ProgressEvent::Finished { operation: "queue flush".to_string() });
+        Ok(report)
+    }
+
+    /// Append a request which cannot be sent to the dead-letter file, with its error.
+    fn bury(&self, entry: &QueuedRequest, error: &str) -> io::Result<()> {
+        let mut record = entry.to_json();
+        if let Value::Object(ref mut fields) = record {
+            fields.insert("error".to_string(), Value::String(error.to_string()));
+        }
+        let mut fd = OpenOptions::new().create(true).append(true).open(&self.dead_letters)?;
+        writeln!(fd, "{}", record)?;
+        fd.sync_all()
+    }
+
+    /// Rewrite the queue file atomically.
+    fn persist(&self) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut fd = File::create(&tmp)?;
+            for entry in &self.entries {
+                writeln!(fd, "{}", entry.to_json())?;
+            }
+            fd.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WriteQueue;
+    use middleware::AcceptLanguage;
+    use testutil;
+    use std::fs;
+    use std::time::Duration;
+
+    fn queue(name: &str) -> WriteQueue {
+        let path = testutil::temp_path(name);
+        let mut queue = WriteQueue::open(&path).unwrap();
+        queue.set_retry_delay(Duration::from_millis(1));
+        queue
+    }
+
+    fn remove(queue: WriteQueue) {
+        let _ = fs::remove_file(&queue.path);
+        let _ = fs::remove_file(queue.dead_letter_path());
+    }
+
+    #[test]
+    fn test_flush_retries_transient() {
+        let (host, server) = testutil::serve(vec![(429, ""), (408, ""), (503, ""), (200, "{}")]);
+        let client = testutil::local_client(&host);
+        let mut queue = queue("ovh-rs-test-queue-transient.jsonl");
+        queue.enqueue("PUT", "/me", "{\"city\": \"Roubaix\"}").unwrap();
+        let report = queue.flush(&client).unwrap();
+        assert_eq!((vec![1], 0), (report.sent, report.remaining));
+        assert_eq!(4, server.join().unwrap().len());
+        remove(queue);
+    }
+
+    #[test]
+    fn test_flush_stops_after_max_attempts() {
+        let (host, server) = testutil::serve(vec![(502, ""), (502, "")]);
+        let client = testutil::local_client(&host);
+        let mut queue = queue("ovh-rs-test-queue-stops.jsonl");
+        queue.set_max_attempts(2);
+        queue.enqueue("DELETE", "/domain/zone/example.com/record/1", "").unwrap();
+        queue.enqueue("POST", "/domain/zone/example.com/refresh", "").unwrap();
+        let report = queue.flush(&client).unwrap();
+        // the order is kept: the refresh waits for the deletion
+        assert_eq!(2, report.remaining);
+        assert_eq!(2, queue.pending()[0].attempts);
+        assert_eq!(2, server.join().unwrap().len());
+        remove(queue);
+    }
+
+    #[test]
+    fn test_flush_dead_letters() {
+        let (host, server) = testutil::serve(vec![(404, "{\"message\":\"gone\"}"),
+                                                  (200, "{}")]);
+        let client = testutil::local_client(&host);
+        let path = testutil::temp_path("ovh-rs-test-queue-dead.jsonl");
+        // written by hand, with a method no transport sends
+        fs::write(&path, "{\"id\":1,\"method\":\"PA TCH\",\"query\":\"/me\",\"body\":\"{}\"}\n")
+            .unwrap();
+        let mut queue = WriteQueue::open(&path).unwrap();
+        queue.enqueue("DELETE", "/domain/zone/example.com/record/1", "").unwrap();
+        queue.enqueue("POST", "/domain/zone/example.com/refresh", "").unwrap();
+        let report = queue.flush(&client).unwrap();
+        assert_eq!((vec![3], 0), (report.sent, report.remaining));
+        assert_eq!(1, report.failed[0].0.id);
+        assert!(report.failed[0].1.starts_with("bad method"), "{}", report.failed[0].1);
+        assert_eq!((2, 404), (report.rejected[0].0.id, report.rejected[0].1));
+        let dead = fs::read_to_string(queue.dead_letter_path()).unwrap();
+        let dead: Vec<&str> = dead.lines().collect();
+        assert_eq!(2, dead.len());
+        assert!(dead[0].contains("\"error\":\"bad method"), "{}", dead[0]);
+        assert!(dead[1].contains("/domain/zone/example.com/record/1") &&
+                dead[1].contains("refused with 404"),
+                "{}",
+                dead[1]);
+        assert_eq!(2, server.join().unwrap().len());
+        remove(queue);
+    }
+
+    #[test]
+    fn test_flush_with_client() {
+        let (host, server) = testutil::serve(vec![(200, "{}")]);
+        let mut client = testutil::local_client(&host);
+        client.middleware.push(AcceptLanguage("en".to_string()));
+        let mut queue = queue("ovh-rs-test-queue-client.jsonl");
+        queue.enqueue("patch", "/me", "{\"city\": \"Roubaix\"}").unwrap();
+        let report = queue.flush(&client).unwrap();
+        assert_eq!(vec![1], report.sent);
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("PATCH /1.0/me HTTP/1.1"), "{}", requests[0]);
+        assert!(requests[0].ends_with("{\"city\": \"Roubaix\"}"), "{}", requests[0]);
+        assert!(requests[0].to_lowercase().contains("accept-language: en"), "{}", requests[0]);
+        remove(queue);
+    }
+
+    #[test]
+    fn test_enqueue_persists() {
+        let path = testutil::temp_path("ovh-rs-test-queue-persist.jsonl");
+        let _ = fs::remove_file(&path);
+        {
+            let mut queue = WriteQueue::open(&path).unwrap();
+            assert_eq!(1, queue.enqueue("post", "/domain/zone/example.com/refresh", "").unwrap());
+            assert_eq!(2, queue.enqueue("PUT", "/me", "{\"city\": \"Roubaix\"}").unwrap());
+        }
+        let queue = WriteQueue::open(&path).unwrap();
+        assert_eq!(2, queue.len());
+        assert_eq!("POST", queue.pending()[0].method);
+        assert_eq!("{\"city\": \"Roubaix\"}", queue.pending()[1].body);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_enqueue_rejects_reads() {
+        let path = testutil::temp_path("ovh-rs-test-queue-reads.jsonl");
+        let _ = fs::remove_file(&path);
+        let mut queue = WriteQueue::open(&path).unwrap();
+        assert!(queue.enqueue("GET", "/me", "").is_err());
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+//! # Raw
+//!
+//! The untyped layer under the curated modules of `api`: JSON calls on any
+//! route, and endpoints defined by hand or with `#[derive(OvhEndpoint)]`.
+//! Use it when the curated layer lags behind the API (a new route, a new field).
+//!
+//! Both layers share the client, so they can be mixed: a raw answer is turned
+//! into a curated model with `decode` (any `Model` is also a `FromResponse`,
+//! so it can be the response of a derived endpoint), and the curated modules
+//! go through the same calls (correlation ids, audit log, retry budget).
+//!
+//! ```no_run
+//! use ovh::api::dns::Record;
+//! use ovh::raw;
+//! use ovh::OVHClient;
+//!
+//! let client = OVHClient::new();
+//! let value = raw::get(&client, "/domain/zone/example.com/record/1").unwrap();
+//! let record: Record = raw::decode(&client, &value).unwrap();
+//! ```
+//!
+
+pub use endpoint::{execute, paginate, render_path, ApiEndpoint, FromResponse};
+pub use pagination::{PageCursor, Paginator};
+pub use rest::{call, decode, decode_list, delete, escape, get, get_text, post, put, unknown_fields,
+               Model};
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, execute, get, ApiEndpoint, FromResponse};
+    use api::dns::{self, Record};
+    use serde_json::Value;
+    use std::io;
+    use testutil;
+
+    const RECORD: &str = "{\"id\":1,\"zone\":\"example.com\",\"fieldType\":\"A\",\
+                          \"subDomain\":\"www\",\"target\":\"192.0.2.1\",\"ttl\":60,\
+                          \"newField\":true}";
+
+    /// A route defined by hand, answering a curated model.
+    struct ZoneRecord {
+        zone: String,
+        id: u64,
+    }
+
+    impl ApiEndpoint for ZoneRecord {
+        type Request = ();
+        type Response = Record;
+        const METHOD: &'static str = "GET";
+        const PATH: &'static str = "/domain/zone/{zoneName}/record/{id}";
+
+        fn path_params(&self) -> Vec<(&'static str, String)> {
+            vec![("zoneName", self.zone.clone()), ("id", self.id.to_string())]
+        }
+
+        fn parse_response(value: &Value) -> io::Result<Record> {
+            Record::from_response(value)
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        let (host, server) = testutil::serve(vec![(200, RECORD), (200, RECORD), (200, RECORD)]);
+        let client = testutil::local_client(&host);
+        let value = get(&client, "/domain/zone/example.com/record/1").unwrap();
+        assert_eq!(Some(true), value["newField"].as_bool());
+        let record: Record = decode(&client, &value).unwrap();
+        let endpoint = ZoneRecord { zone: "example.com".to_string(), id: 1 };
+        assert_eq!(record, execute(&client, &endpoint).unwrap());
+        assert_eq!(record, dns::record(&client, "example.com", 1).unwrap());
+        let requests = server.join().unwrap();
+        assert!(requests.iter()
+            .all(|request| request.starts_with("GET /1.0/domain/zone/example.com/record/1 ")));
+    }
+}
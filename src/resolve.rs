@@ -0,0 +1,231 @@
+//! # Resolve
+//!
+//! Custom resolution of the API hosts, for networks without a usable DNS
+//! or to pin an address during an incident.
+//!
+//! A `Resolver` given to `ClientBuilder::resolver` (or the `resolver` of the
+//! `Network` of a client) answers for every host it knows; other hosts go
+//! through the system resolver. Without one, `OVH_RESOLVE` gives a static
+//! mapping: `eu.api.ovh.com=198.51.100.4,2001:db8::4;ca.api.ovh.com=...`.
+//! Any resolver (e.g. one built on a DNS client library) can be plugged in
+//! by implementing the trait.
+//!
+//! Both transports honour the resolution, for every call of the client.
+//!
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Resolution of API hosts.
+pub trait Resolver: Send + Sync {
+    /// Addresses of `host`, or an empty list to use the system resolver.
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>>;
+}
+
+/// A fixed host to addresses mapping.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct StaticResolver {
+    hosts: BTreeMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> StaticResolver {
+        StaticResolver::default()
+    }
+
+    pub fn insert(&mut self, host: &str, addresses: Vec<IpAddr>) {
+        self.hosts.insert(host.to_lowercase(), addresses);
+    }
+
+    /// Parse `host=addr[,addr...]` entries separated by `;`.
+    pub fn parse(mapping: &str) -> io::Result<StaticResolver> {
+        let mut resolver = StaticResolver::new();
+        for entry in mapping.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let mut parts = entry.splitn(2, '=');
+            let host = parts.next().unwrap_or("").trim();
+            let addresses = parts.next()
+                .ok_or_else(|| invalid_input(format!("expected host=address in {}", entry)))?;
+            let addresses = addresses.split(',')
+                .map(|address| {
+                    address.trim()
+                        .parse()
+                        .map_err(|_| invalid_input(format!("bad address {} for {}", address, host)))
+                })
+                .collect::<io::Result<Vec<IpAddr>>>()?;
+            resolver.insert(host, addresses);
+        }
+        Ok(resolver)
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        Ok(self.hosts.get(&host.to_lowercase()).cloned().unwrap_or_default())
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// A resolver shared by the calls of a client, see `Network::resolver`.
+/// Two are equal when they are the same resolver.
+#[derive(Clone)]
+pub struct SharedResolver(Arc<dyn Resolver>);
+
+impl SharedResolver {
+    pub fn new<R: Resolver + 'static>(resolver: R) -> SharedResolver {
+        SharedResolver(Arc::new(resolver))
+    }
+}
+
+impl Resolver for SharedResolver {
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        self.0.resolve(host)
+    }
+}
+
+impl fmt::Debug for SharedResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SharedResolver")
+    }
+}
+
+impl PartialEq for SharedResolver {
+    fn eq(&self, other: &SharedResolver) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Addresses of `host` in `OVH_RESOLVE`, empty when the system resolver
+/// should be used.
+pub fn from_env(host: &str) -> io::Result<Vec<IpAddr>> {
+    match env::var("OVH_RESOLVE") {
+        Ok(mapping) => StaticResolver::parse(&mapping)?.resolve(host),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Addresses of `host` from `resolver`, else from `OVH_RESOLVE`; empty when
+/// the system resolver should be used.
+pub(crate) fn custom(resolver: Option<&SharedResolver>, host: &str) -> io::Result<Vec<IpAddr>> {
+    match resolver {
+        Some(resolver) => resolver.resolve(host),
+        None => from_env(host),
+    }
+}
+
+/// Entry of curl's resolve list for `host` and its custom `addresses`.
+#[cfg(feature = "curl")]
+pub(crate) fn curl_entry(host: &str, port: u16, addresses: &[IpAddr]) -> String {
+    let addresses: Vec<String> = addresses.iter()
+        .map(|address| match *address {
+            IpAddr::V4(ref v4) => v4.to_string(),
+            IpAddr::V6(ref v6) => format!("[{}]", v6),
+        })
+        .collect();
+    format!("{}:{}:{}", host, port, addresses.join(","))
+}
+
+#[cfg(all(feature = "ldp", feature = "reqwest", not(feature = "curl")))]
+pub(crate) use self::lookup::addresses;
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+pub(crate) use self::lookup::Lookup;
+
+/// Resolution for the reqwest clients.
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+mod lookup {
+    use super::{custom, SharedResolver};
+    use client::IpFamily;
+
+    use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+    use tokio::task::{self, JoinHandle};
+
+    use std::error::Error;
+    use std::future::Future;
+    use std::io;
+    use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// The custom resolution, else the system one, restricted to the
+    /// addresses of a family.
+    pub(crate) struct Lookup {
+        family: IpFamily,
+        resolver: Option<SharedResolver>,
+    }
+
+    impl Lookup {
+        pub(crate) fn new(family: IpFamily, resolver: Option<SharedResolver>) -> Lookup {
+            Lookup { family, resolver }
+        }
+    }
+
+    /// The addresses of `host` in `family`: its custom ones, else those of
+    /// the system.
+    pub(crate) fn addresses(host: &str,
+                            family: IpFamily,
+                            resolver: Option<&SharedResolver>)
+                            -> io::Result<Vec<IpAddr>> {
+        let mut addresses = custom(resolver, host)?;
+        if addresses.is_empty() {
+            addresses = (host, 0).to_socket_addrs()?.map(|address| address.ip()).collect();
+        }
+        addresses.retain(|address| family.allows(address));
+        if addresses.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound,
+                                      format!("no {:?} address for {}", family, host)));
+        }
+        Ok(addresses)
+    }
+
+    impl Resolve for Lookup {
+        fn resolve(&self, name: Name) -> Resolving {
+            let (host, family) = (name.as_str().to_string(), self.family);
+            let resolver = self.resolver.clone();
+            Box::pin(Resolution(task::spawn_blocking(move || {
+                addresses(&host, family, resolver.as_ref())
+            })))
+        }
+    }
+
+    /// Addresses resolved on a blocking thread of the runtime.
+    struct Resolution(JoinHandle<io::Result<Vec<IpAddr>>>);
+
+    impl Future for Resolution {
+        type Output = Result<Addrs, Box<dyn Error + Send + Sync>>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            Pin::new(&mut self.0).poll(cx).map(|resolved| match resolved {
+                Ok(Ok(addresses)) => {
+                    let addresses = addresses.into_iter().map(|ip| SocketAddr::new(ip, 0));
+                    Ok(Box::new(addresses) as Addrs)
+                }
+                Ok(Err(err)) => Err(err.into()),
+                Err(err) => Err(err.into()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Resolver, StaticResolver};
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_static_resolver() {
+        let resolver = StaticResolver::parse("eu.api.ovh.com=198.51.100.4, 2001:db8::4; ").unwrap();
+        let addresses: Vec<IpAddr> = vec!["198.51.100.4".parse().unwrap(),
+                                          "2001:db8::4".parse().unwrap()];
+        assert_eq!(addresses,
+                   resolver.resolve("EU.api.ovh.com").unwrap());
+        assert!(resolver.resolve("ca.api.ovh.com").unwrap().is_empty());
+        assert!(StaticResolver::parse("eu.api.ovh.com").is_err());
+        assert!(StaticResolver::parse("eu.api.ovh.com=nope").is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! # Rest
+//!
+//! Internal helpers shared by the typed modules:
+//! JSON calls on top of `OVHClient::send` and field extraction.
+//!
+
+use client::{FieldPolicy, OVHClient, RawResponse, ResponseFormat, Timeouts};
+use error::{self, ApiError, Error};
+use guard;
+use instrument;
+use middleware::Request;
+use retry;
+use scope;
+use trace;
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+/// A typed model built from an API answer.
+pub trait Model: Sized {
+    /// Name of the model in error messages.
+    const NAME: &'static str;
+    /// Fields of the API schema known to the model, whether it maps them or not.
+    const FIELDS: &'static [&'static str];
+
+    fn from_json(value: &Value) -> io::Result<Self>;
+}
+
+/// Fields of an object answer which are not in `known`.
+pub fn unknown_fields<'a>(value: &'a Value, known: &[&str]) -> Vec<&'a str> {
+    match value.as_object() {
+        Some(map) => {
+            map.keys()
+                .map(|key| key.as_str())
+                .filter(|key| !known.contains(key))
+                .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Build a model, applying the field policy of the client to unknown fields.
+pub fn decode<T: Model>(client: &OVHClient, value: &Value) -> io::Result<T> {
+    let unknown = unknown_fields(value, T::FIELDS);
+    if !unknown.is_empty() {
+        match client.field_policy {
+            FieldPolicy::Strict => {
+                return Err(invalid_data(&format!("unknown fields in {}: {}",
+                                                 T::NAME,
+                                                 unknown.join(", "))))
+            }
+            FieldPolicy::Lenient => debug!("Ignoring unknown fields in {}: {}", T::NAME, unknown.join(", ")),
+        }
+    }
+    T::from_json(value)
+}
+
+/// Build models from an array answer.
+pub fn decode_list<T: Model>(client: &OVHClient, value: &Value) -> io::Result<Vec<T>> {
+    array(value)?.iter().map(|item| decode(client, item)).collect()
+}
+
+/// Send a call and check its status.
+/// Statuses >= 400 are turned into errors carrying the API message.
+/// Transient failures are retried according to the retry policy of the client.
+pub fn checked_send(client: &OVHClient,
+                method: &str,
+                query: &str,
+                body: &str,
+                format: ResponseFormat)
+                -> io::Result<String> {
+    checked_exchange(client, method, query, body, format, &[]).map(|res| res.body)
+}
+
+/// Like `checked_send`, with additional request headers and the whole answer.
+pub(crate) fn checked_exchange(client: &OVHClient,
+                               method: &str,
+                               query: &str,
+                               body: &str,
+                               format: ResponseFormat,
+                               extra: &[(&'static str, String)])
+                               -> io::Result<RawResponse> {
+    let request = Request {
+        method: method.to_string(),
+        query: query.to_string(),
+        body: body.to_string(),
+        headers: extra.iter().map(|&(name, ref value)| (name.to_string(), value.clone())).collect(),
+    };
+    exchange_within(client, request, format, client.timeouts)
+}
+
+/// Like `checked_exchange`, within `timeouts` rather than those of the client.
+pub(crate) fn exchange_within(client: &OVHClient,
+                              request: Request,
+                              format: ResponseFormat,
+                              timeouts: Timeouts)
+                              -> io::Result<RawResponse> {
+    let (method, query) = (request.method.as_str(), request.query.as_str());
+    let correlation_id = trace::current();
+    if let Some(ref id) = correlation_id {
+        debug!("[{}] {} {}", id, method, query);
+    }
+    retry::global().record_request();
+    let mut attempt = 1;
+    loop {
+        let started = Instant::now();
+        let sent = instrument::request(method, query, attempt, || {
+            send_once(client, request.clone(), format, timeouts, &correlation_id)
+        });
+        client.telemetry.record(method,
+                                query,
+                                attempt,
+                                &sent,
+                                started.elapsed(),
+                                client.latency_budget);
+        let err = match sent {
+            Ok(res) => return Ok(res),
+            Err(err) => err,
+        };
+        let (transient, delay) = match retry_delay(client, method, query, attempt, &err) {
+            Some(retry) => retry,
+            None => return Err(err),
+        };
+        if transient == retry::Transient::Timestamp {
+            if let Err(sync) = OVHClient::sync_time_via(&client.network, &client.credential.host) {
+                info!("fail to fetch remote time: {}", sync);
+            }
+        }
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Whether to retry a failed attempt, and after which delay, according to
+/// the retry policy of the client and the retry budget.
+pub(crate) fn retry_delay(client: &OVHClient,
+                          method: &str,
+                          query: &str,
+                          attempt: u32,
+                          err: &io::Error)
+                          -> Option<(retry::Transient, Duration)> {
+    let policy = client.retry_policy;
+    let transient = match policy.should_retry(method, err) {
+        Some(transient) if attempt < policy.max_attempts => transient,
+        _ => return None,
+    };
+    if !retry::global().try_retry() {
+        info!("Retry budget exhausted, not retrying {} {}", method, query);
+        return None;
+    }
+    let asked = error::api_error(err)
+        .and_then(|api| api.rate_limit.as_ref())
+        .and_then(|info| info.wait());
+    let delay = asked.map_or(policy.delay(attempt), |asked| asked.max(policy.delay(attempt)));
+    info!("{} {} failed ({}), retrying in {:?}", method, query, err, delay);
+    instrument::retry(method, query, attempt, delay, err);
+    Some((transient, delay))
+}
+
+fn send_once(client: &OVHClient,
+             request: Request,
+             format: ResponseFormat,
+             timeouts: Timeouts,
+             correlation_id: &Option<String>)
+             -> io::Result<RawResponse> {
+    let correlation_id = correlation_id.clone();
+    if let Some(ref throttle) = client.throttle {
+        throttle.acquire();
+    }
+    let (method, query) = (request.method.clone(), request.query.clone());
+    let res = client.middleware
+        .run(request, |request| {
+            if client.require_confirmation {
+                guard::check(&request.method, &request.query)?;
+            }
+            OVHClient::exchange(&client.credential,
+                                &client.network,
+                                request,
+                                format,
+                                timeouts)
+        })
+        .map_err(|err| with_correlation_id(err, &correlation_id))?;
+    check_answer(client, &method, &query, res, correlation_id)
+}
+
+/// Observe the answer of an attempt, and turn an error status into an error.
+pub(crate) fn check_answer(client: &OVHClient,
+                           method: &str,
+                           query: &str,
+                           res: RawResponse,
+                           correlation_id: Option<String>)
+                           -> io::Result<RawResponse> {
+    instrument::answer(&res);
+    if method != "GET" {
+        info!(target: "ovh::audit",
+              "[{}] {} {} -> {}",
+              correlation_id.as_ref().map_or("-", |id| id.as_str()),
+              method,
+              query,
+              res.status);
+    }
+    let rate_limit = res.rate_limit();
+    if let (Some(throttle), Some(info)) = (client.throttle.as_ref(), rate_limit.as_ref()) {
+        throttle.observe(info);
+    }
+    if res.status == 401 {
+        // A revoked or expired access token: ask for another one next time.
+        if let Some(oauth2) = client.credential.oauth2() {
+            oauth2.invalidate();
+        }
+    }
+    if res.status >= 400 {
+        let mut err = ApiError::from_response(method, query, res.status, &res.body);
+        err.correlation_id = correlation_id;
+        err.rate_limit = rate_limit;
+        err.query_id = res.header("X-Ovh-QueryId").map(String::from);
+        return Err(Error::Api(Box::new(err)).into());
+    }
+    Ok(res)
+}
+
+/// Call the API and parse the JSON answer.
+/// Within a `scope::RunScope`, GET answers are memoized and writes invalidate them.
+pub fn call(client: &OVHClient, method: &str, query: &str, body: Option<&Value>) -> io::Result<Value> {
+    let key = format!("{}@{}{}", client.credential.consumer_key, client.credential.host, query);
+    if method == "GET" {
+        if let Some(value) = scope::lookup(&key) {
+            return Ok(value);
+        }
+    } else {
+        scope::invalidate(&key);
+    }
+    let body = match body {
+        Some(value) => value.to_string(),
+        None => String::new(),
+    };
+    let body = checked_send(client, method, query, &body, ResponseFormat::Json)?;
+    let value = if body.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(&body).map_err(|e| invalid_data(&e.to_string()))?
+    };
+    if method == "GET" {
+        scope::store(&key, &value);
+    }
+    Ok(value)
+}
+
+/// GET a route with a plain text representation.
+/// Routes answering with a JSON string anyway are decoded.
+pub fn get_text(client: &OVHClient, query: &str) -> io::Result<String> {
+    let body = checked_send(client, "GET", query, "", ResponseFormat::Text)?;
+    Ok(text_body(body))
+}
+
+fn text_body(body: String) -> String {
+    if body.starts_with('"') {
+        if let Ok(Value::String(text)) = serde_json::from_str(&body) {
+            return text;
+        }
+    }
+    body
+}
+
+/// Append the correlation id of the current operation to an error message.
+pub(crate) fn with_correlation_id(err: io::Error, correlation_id: &Option<String>) -> io::Error {
+    match *correlation_id {
+        Some(ref id) => io::Error::new(err.kind(), format!("{} [correlation id {}]", err, id)),
+        None => err,
+    }
+}
+
+pub fn get(client: &OVHClient, query: &str) -> io::Result<Value> {
+    call(client, "GET", query, None)
+}
+
+pub fn post(client: &OVHClient, query: &str, body: &Value) -> io::Result<Value> {
+    call(client, "POST", query, Some(body))
+}
+
+pub fn put(client: &OVHClient, query: &str, body: &Value) -> io::Result<Value> {
+    call(client, "PUT", query, Some(body))
+}
+
+pub fn delete(client: &OVHClient, query: &str) -> io::Result<Value> {
+    call(client, "DELETE", query, None)
+}
+
+/// Error for an API answer which does not have the expected shape.
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Whether an error is a `429 Too Many Requests` answer of the API.
+pub fn is_throttled(err: &io::Error) -> bool {
+    error::api_error(err).is_some_and(|api| api.http_code == 429)
+}
+
+/// Error for an answer with a status >= 400 of a call outside `checked_exchange`,
+/// e.g. a download, carrying its `ApiError`.
+pub(crate) fn api_failure(method: &str, url: &str, status: u16, body: &str) -> io::Error {
+    Error::Api(Box::new(ApiError::from_response(method, url, status, body))).into()
+}
+
+/// Percent-encode a path segment (zone name, service name, ...).
+pub fn escape(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                escaped.push(byte as char)
+            }
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+pub fn str_field(value: &Value, key: &str) -> io::Result<String> {
+    opt_str_field(value, key).ok_or_else(|| invalid_data(&format!("missing string field {}", key)))
+}
+
+pub fn opt_str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(String::from)
+}
+
+pub fn u64_field(value: &Value, key: &str) -> io::Result<u64> {
+    value.get(key)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid_data(&format!("missing integer field {}", key)))
+}
+
+pub fn opt_u64_field(value: &Value, key: &str) -> Option<u64> {
+    value.get(key).and_then(Value::as_u64)
+}
+
+/// Items of a JSON array answer, an error if the answer is not an array.
+pub fn array(value: &Value) -> io::Result<&Vec<Value>> {
+    value.as_array().ok_or_else(|| invalid_data("expected an array"))
+}
+
+/// Listing endpoints return arrays of ids or names.
+pub fn string_list(value: &Value) -> io::Result<Vec<String>> {
+    Ok(array(value)?
+        .iter()
+        .filter_map(|item| match *item {
+            Value::String(ref s) => Some(s.clone()),
+            Value::Number(ref n) => Some(n.to_string()),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn u64_list(value: &Value) -> io::Result<Vec<u64>> {
+    Ok(array(value)?.iter().filter_map(Value::as_u64).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{api_failure, decode, escape, invalid_data, is_throttled, text_body, unknown_fields,
+                Model};
+    use client::{FieldPolicy, OVHClient};
+    use config::Credential;
+    use serde_json::Value;
+    use std::io;
+
+    struct Named(String);
+
+    impl Model for Named {
+        const NAME: &'static str = "Named";
+        const FIELDS: &'static [&'static str] = &["name"];
+
+        fn from_json(value: &Value) -> io::Result<Named> {
+            value.get("name")
+                .and_then(Value::as_str)
+                .map(|name| Named(name.to_string()))
+                .ok_or_else(|| invalid_data("missing name"))
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("example.com", escape("example.com"));
+        assert_eq!("ns1.example.com%2Fx%20y", escape("ns1.example.com/x y"));
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let value = json!({ "id": 1, "name": "a", "newField": true });
+        assert_eq!(vec!["newField"], unknown_fields(&value, &["id", "name"]));
+        assert!(unknown_fields(&json!([1, 2]), &[]).is_empty());
+    }
+
+    #[test]
+    fn test_decode_field_policy() {
+        let mut client = OVHClient::for_tests(Credential::new_from_file("Config.toml.dist").unwrap());
+        let value = json!({ "name": "a", "newField": true });
+        assert_eq!("a", decode::<Named>(&client, &value).unwrap().0);
+        client.field_policy = FieldPolicy::Strict;
+        let err = decode::<Named>(&client, &value).err().unwrap();
+        assert!(err.to_string().contains("newField"));
+        assert!(decode::<Named>(&client, &json!({ "name": "a" })).is_ok());
+    }
+
+    #[test]
+    fn test_throttled() {
+        assert!(is_throttled(&api_failure("GET", "/me/bill/1.pdf", 429, "")));
+        assert!(!is_throttled(&api_failure("GET", "/me/bill/1.pdf", 404, "")));
+        // only answers of the API count, whatever their message
+        assert!(!is_throttled(&io::Error::other("GET /me failed (429)")));
+    }
+
+    #[test]
+    fn test_text_body() {
+        assert_eq!("$TTL 3600\n", text_body("\"$TTL 3600\\n\"".to_string()));
+        assert_eq!("$TTL 3600\n", text_body("$TTL 3600\n".to_string()));
+    }
+}
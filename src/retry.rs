@@ -0,0 +1,239 @@
+//! # Retry
+//!
+//! Process-wide retry budget: every request earns a fraction of a retry,
+//! every retry spends one. When the budget is empty, helpers give up instead
+//! of retrying, so an OVH incident is not amplified by retry storms from
+//! every client of the process.
+//!
+//! `RetryPolicy` makes a client retry transient failures itself, with
+//! exponential backoff, within the same budget.
+//!
+
+use error;
+
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Retries are counted in thousandths.
+const UNIT: u64 = 1000;
+
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub struct BudgetStats {
+    pub requests: u64,
+    pub retries: u64,
+    /// Retries refused because the budget was empty.
+    pub denied: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: u64,
+    ratio: u64,
+    max_tokens: u64,
+    stats: BudgetStats,
+}
+
+/// A retry budget, see `global` for the one shared by the crate.
+#[derive(Debug)]
+pub struct RetryBudget {
+    state: Mutex<State>,
+}
+
+static GLOBAL: RetryBudget = RetryBudget::new(20, 10);
+
+/// The budget used by the helpers of the crate: 20% of retries,
+/// and bursts of at most 10 retries.
+pub fn global() -> &'static RetryBudget {
+    &GLOBAL
+}
+
+impl RetryBudget {
+    /// A budget allowing `percent` retries per 100 requests and at most
+    /// `burst` retries in a row. It starts full.
+    pub const fn new(percent: u64, burst: u64) -> RetryBudget {
+        RetryBudget {
+            state: Mutex::new(State {
+                tokens: burst * UNIT,
+                ratio: percent * UNIT / 100,
+                max_tokens: burst * UNIT,
+                stats: BudgetStats {
+                    requests: 0,
+                    retries: 0,
+                    denied: 0,
+                },
+            }),
+        }
+    }
+
+    /// Change the ratio and burst, e.g. of the global budget at startup.
+    pub fn configure(&self, percent: u64, burst: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.ratio = percent * UNIT / 100;
+        state.max_tokens = burst * UNIT;
+        state.tokens = state.tokens.min(state.max_tokens);
+    }
+
+    /// Account a first attempt of a request.
+    pub fn record_request(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (state.tokens + state.ratio).min(state.max_tokens);
+        state.stats.requests += 1;
+    }
+
+    /// Ask for a retry; `false` means the caller should give up.
+    pub fn try_retry(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.tokens >= UNIT {
+            state.tokens -= UNIT;
+            state.stats.retries += 1;
+            true
+        } else {
+            state.stats.denied += 1;
+            false
+        }
+    }
+
+    pub fn stats(&self) -> BudgetStats {
+        self.state.lock().unwrap().stats
+    }
+}
+
+/// Retries of transient failures by a client (`OVHClient::retry_policy`).
+///
+/// Disabled by default. Network errors and `500`, `502`, `503` answers are retried,
+/// as are requests rejected for their timestamp, once the clock drift is fetched
+/// again. `POST` is not idempotent: it is only retried when the API refused it
+/// before handling it (`503`, timestamp rejects).
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts of a request, the first included; 1 disables the retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each of the next ones.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::disabled()
+    }
+}
+
+/// Why a failed request may succeed if sent again.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Transient {
+    /// The request may not have reached the API.
+    Network,
+    /// `500`, `502` or `503`.
+    ServerError(u16),
+    /// `QUERY_TIME_OUT`: the timestamp is too far from the API clock.
+    Timestamp,
+    /// `429`: the request was refused, waiting for the time the API asks.
+    Throttled,
+}
+
+impl RetryPolicy {
+    pub fn disabled() -> RetryPolicy {
+        RetryPolicy::new(1, Duration::from_millis(500), Duration::from_secs(30))
+    }
+
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            max_delay,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    /// Delay before the retry number `retry`, starting at 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.base_delay.checked_mul(factor).unwrap_or(self.max_delay).min(self.max_delay)
+    }
+
+    /// Whether a request failing with `err` is sent again.
+    pub fn should_retry(&self, method: &str, err: &io::Error) -> Option<Transient> {
+        let transient = transient(err)?;
+        match (method, transient) {
+            ("POST", Transient::Timestamp) |
+            ("POST", Transient::Throttled) |
+            ("POST", Transient::ServerError(503)) => Some(transient),
+            ("POST", _) => None,
+            _ => Some(transient),
+        }
+    }
+}
+
+/// Whether a failure of a request is transient.
+pub fn transient(err: &io::Error) -> Option<Transient> {
+    match error::api_error(err) {
+        Some(api) if api.is("QUERY_TIME_OUT") => Some(Transient::Timestamp),
+        Some(api) => {
+            match api.http_code {
+                429 => Some(Transient::Throttled),
+                500 | 502 | 503 => Some(Transient::ServerError(api.http_code)),
+                _ => None,
+            }
+        }
+        None => {
+            match err.kind() {
+                io::ErrorKind::InvalidInput |
+                io::ErrorKind::InvalidData |
+                io::ErrorKind::PermissionDenied => None,
+                _ => Some(Transient::Network),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RetryBudget, RetryPolicy, Transient};
+    use error::{ApiError, Error};
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(200), Duration::from_secs(1));
+        let delays = (1..5).map(|retry| policy.delay(retry).as_millis()).collect::<Vec<_>>();
+        assert_eq!(vec![200, 400, 800, 1000], delays);
+        assert_eq!(Duration::from_secs(1), policy.delay(40));
+        assert!(!RetryPolicy::default().is_enabled());
+
+        let api = |status: u16, body: &str| {
+            let api = ApiError::from_response("GET", "/me", status, body);
+            io::Error::from(Error::Api(Box::new(api)))
+        };
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert_eq!(Some(Transient::Network), policy.should_retry("GET", &reset));
+        assert_eq!(None, policy.should_retry("POST", &reset));
+        assert_eq!(Some(Transient::ServerError(503)), policy.should_retry("POST", &api(503, "")));
+        assert_eq!(None, policy.should_retry("POST", &api(502, "")));
+        assert_eq!(None, policy.should_retry("GET", &api(404, "")));
+        let late = api(400, r#"{"errorCode":"QUERY_TIME_OUT","message":"Query out of time"}"#);
+        assert_eq!(Some(Transient::Timestamp), policy.should_retry("POST", &late));
+        assert_eq!(Some(Transient::Throttled), policy.should_retry("POST", &api(429, "")));
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(20, 2);
+        assert!(budget.try_retry());
+        assert!(budget.try_retry());
+        assert!(!budget.try_retry());
+        for _ in 0..4 {
+            budget.record_request();
+        }
+        assert!(!budget.try_retry());
+        budget.record_request();
+        assert!(budget.try_retry());
+        let stats = budget.stats();
+        assert_eq!((5, 3, 2), (stats.requests, stats.retries, stats.denied));
+    }
+}
@@ -0,0 +1,383 @@
+//! # Scheduler
+//!
+//! Recurring jobs run by the process itself — a nightly `backup::dump`, a
+//! weekly audit of the credentials, an hourly scan of the expiring services —
+//! for small agents which have no cron at hand.
+//!
+//! A job runs at the minutes of a cron spec (see `window::CronSpec`) or at a
+//! fixed interval, delayed by a random jitter so that agents started together
+//! do not call the API together. A job still running when it is due again is
+//! skipped rather than started twice.
+//!
+//! The scheduler runs on tokio, behind the `scheduler` feature: `run` on a
+//! runtime of its own, `spawn` on the runtime of the caller. Each run is a
+//! blocking task of the runtime (`spawn_blocking`), so a slow job does not
+//! delay the others; a failed or panicked run is logged.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::OVHClient;
+//! use ovh::backup;
+//! use ovh::scheduler::{Schedule, Scheduler};
+//! use std::time::Duration;
+//!
+//! let client = OVHClient::try_new()?;
+//! Scheduler::new(client)
+//!     .job("backup",
+//!          Schedule::cron("0 3 * * *")?.jitter(Duration::from_secs(600)),
+//!          |client| backup::dump(client, "ovh-config").map(|_| ()))
+//!     .job("expiry", Schedule::every(Duration::from_secs(3600)), |client| {
+//!         client.get::<ovh::Value>("/services").map(|_| ())
+//!     })
+//!     .run()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use client::OVHClient;
+use window::{CronSpec, Zone};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time;
+
+use tokio::runtime;
+use tokio::task::{self, JoinError, JoinHandle};
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+use chrono::{self, DateTime, Local};
+
+use rand::{self, Rng};
+
+/// Seconds between two checks of the due jobs.
+const TICK: u64 = 1;
+
+#[derive(Debug,Clone,PartialEq)]
+enum When {
+    Cron(CronSpec, Zone),
+    Every(time::Duration),
+}
+
+/// When a job runs.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Schedule {
+    when: When,
+    jitter: time::Duration,
+}
+
+impl Schedule {
+    /// At the minutes of a cron spec, in UTC: `0 3 * * *` every night at 3.
+    pub fn cron(spec: &str) -> io::Result<Schedule> {
+        Ok(Schedule {
+            when: When::Cron(spec.parse()?, Zone::Utc),
+            jitter: time::Duration::from_secs(0),
+        })
+    }
+
+    /// Every `interval`, the first run one interval after the start.
+    pub fn every(interval: time::Duration) -> Schedule {
+        Schedule {
+            when: When::Every(interval),
+            jitter: time::Duration::from_secs(0),
+        }
+    }
+
+    /// Read the cron spec in another timezone.
+    pub fn zone(mut self, zone: Zone) -> Self {
+        if let When::Cron(_, ref mut current) = self.when {
+            *current = zone;
+        }
+        self
+    }
+
+    /// Delay each run by a random time up to `jitter`.
+    pub fn jitter(mut self, jitter: time::Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The next run after `after`, before jitter.
+    fn next(&self, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+        match self.when {
+            When::Cron(ref spec, zone) => spec.next_after(zone, after),
+            When::Every(interval) => {
+                chrono::Duration::from_std(interval).ok().map(|interval| *after + interval)
+            }
+        }
+    }
+
+    /// The next run after `after`, delayed by `delay` seconds out of the jitter.
+    fn next_with(&self, after: &DateTime<Local>, delay: u64) -> Option<DateTime<Local>> {
+        let jitter = self.jitter.as_secs();
+        let delay = if jitter == 0 { 0 } else { delay % (jitter + 1) };
+        self.next(after).map(|next| next + chrono::Duration::seconds(delay as i64))
+    }
+}
+
+type Task = Arc<dyn Fn(&OVHClient) -> io::Result<()> + Send + Sync>;
+
+struct Job {
+    name: String,
+    schedule: Schedule,
+    task: Task,
+    running: Arc<AtomicBool>,
+    next: Option<DateTime<Local>>,
+}
+
+/// Clears the running flag of a job when its run ends, or panics.
+struct Running(Arc<AtomicBool>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Job {
+    fn plan(&mut self, after: &DateTime<Local>) {
+        self.next = self.schedule.next_with(after, rand::thread_rng().gen::<u64>());
+    }
+
+    /// Mark the job as running, `None` if its previous run is not over.
+    fn start(&self) -> Option<Running> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(Running(self.running.clone()))
+    }
+
+    /// Start a run on a blocking task, unless the previous one is not over.
+    /// The returned task ends with the run, giving its failure, as logged.
+    fn run(&self, client: &OVHClient) -> Option<JoinHandle<Option<String>>> {
+        let running = match self.start() {
+            Some(running) => running,
+            None => {
+                warn!("Job {} is still running, skipping this run", self.name);
+                return None;
+            }
+        };
+        let (name, task, client) = (self.name.clone(), self.task.clone(), client.clone());
+        info!("Running job {}", name);
+        let run = task::spawn_blocking(move || {
+            let _running = running;
+            task(&client)
+        });
+        Some(task::spawn(Report {
+            name: self.name.clone(),
+            run,
+        }))
+    }
+}
+
+/// Waits for a run and logs its error or its panic.
+struct Report {
+    name: String,
+    run: JoinHandle<io::Result<()>>,
+}
+
+impl Report {
+    fn failure(&self, ended: Result<io::Result<()>, JoinError>) -> Option<String> {
+        match ended {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(format!("Job {} failed: {}", self.name, err)),
+            Err(err) => Some(format!("Job {} did not finish: {}", self.name, err)),
+        }
+    }
+}
+
+impl Future for Report {
+    type Output = Option<String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<String>> {
+        let ended = match Pin::new(&mut self.run).poll(cx) {
+            Poll::Ready(ended) => ended,
+            Poll::Pending => return Poll::Pending,
+        };
+        let failure = self.failure(ended);
+        if let Some(ref failure) = failure {
+            warn!("{}", failure);
+        }
+        Poll::Ready(failure)
+    }
+}
+
+/// Recurring jobs sharing a client.
+pub struct Scheduler {
+    client: OVHClient,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(client: OVHClient) -> Scheduler {
+        Scheduler {
+            client,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Run `task` on `schedule`; its errors are logged.
+    pub fn job<F>(mut self, name: &str, schedule: Schedule, task: F) -> Self
+        where F: Fn(&OVHClient) -> io::Result<()> + Send + Sync + 'static
+    {
+        self.jobs.push(Job {
+            name: name.to_string(),
+            schedule,
+            task: Arc::new(task),
+            running: Arc::new(AtomicBool::new(false)),
+            next: None,
+        });
+        self
+    }
+
+    fn plan(&mut self, now: &DateTime<Local>) {
+        for job in &mut self.jobs {
+            job.plan(now);
+        }
+    }
+
+    /// Start the due jobs and plan their next runs.
+    fn tick(&mut self, now: &DateTime<Local>) {
+        for job in &mut self.jobs {
+            if job.next.is_some_and(|next| next <= *now) {
+                job.run(&self.client);
+                job.plan(now);
+            }
+        }
+    }
+
+    /// The task checking the due jobs every `TICK`; it must be polled by a
+    /// tokio runtime.
+    fn ticker(mut self) -> Ticker {
+        self.plan(&Local::now());
+        let mut ticks = interval(time::Duration::from_secs(TICK));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        Ticker {
+            scheduler: self,
+            ticks,
+        }
+    }
+
+    /// Run the jobs on a runtime of their own, on this thread, forever.
+    pub fn run(self) -> io::Result<()> {
+        runtime::Builder::new_current_thread().enable_time().build()?.block_on(self.ticker());
+        Ok(())
+    }
+
+    /// Run the jobs on the tokio runtime of the caller; panics outside of one,
+    /// as `tokio::spawn`.
+    pub fn spawn(self) -> SchedulerHandle {
+        SchedulerHandle { ticker: task::spawn(self.ticker()) }
+    }
+}
+
+/// Starts the due jobs at each tick.
+struct Ticker {
+    scheduler: Scheduler,
+    ticks: Interval,
+}
+
+impl Future for Ticker {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        while self.ticks.poll_tick(cx).is_ready() {
+            self.scheduler.tick(&Local::now());
+        }
+        Poll::Pending
+    }
+}
+
+/// A scheduler running on a tokio runtime.
+pub struct SchedulerHandle {
+    ticker: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Stop planning runs; the runs in progress go on.
+    pub fn stop(self) {
+        self.ticker.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Job, Schedule};
+    use client::OVHClient;
+    use testutil;
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use chrono::{DateTime, Local, TimeZone, UTC};
+    use tokio::runtime;
+
+    fn job<F>(task: F) -> Job
+        where F: Fn(&OVHClient) -> io::Result<()> + Send + Sync + 'static
+    {
+        Job {
+            name: "audit".to_string(),
+            schedule: Schedule::every(Duration::from_secs(60)),
+            task: Arc::new(task),
+            running: Arc::new(AtomicBool::new(false)),
+            next: None,
+        }
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        UTC.ymd(y, m, d).and_hms(h, min, 0).with_timezone(&Local)
+    }
+
+    #[test]
+    fn test_next() {
+        let nightly = Schedule::cron("0 3 * * *").unwrap();
+        assert_eq!(Some(utc(2017, 6, 4, 3, 0)), nightly.next_with(&utc(2017, 6, 3, 3, 0), 42));
+        assert_eq!(Some(utc(2017, 6, 3, 3, 0)), nightly.next_with(&utc(2017, 6, 3, 1, 0), 42));
+
+        let jittered = nightly.jitter(Duration::from_secs(600));
+        let next = jittered.next_with(&utc(2017, 6, 3, 1, 0), 42).unwrap();
+        assert_eq!(42, next.timestamp() - utc(2017, 6, 3, 3, 0).timestamp());
+        let next = jittered.next_with(&utc(2017, 6, 3, 1, 0), 1000).unwrap();
+        assert_eq!(399, next.timestamp() - utc(2017, 6, 3, 3, 0).timestamp());
+
+        let hourly = Schedule::every(Duration::from_secs(3600));
+        assert_eq!(Some(utc(2017, 6, 3, 2, 15)), hourly.next_with(&utc(2017, 6, 3, 1, 15), 7));
+        assert!(Schedule::cron("0 3 * *").is_err());
+    }
+
+    #[test]
+    fn test_overlap() {
+        let job = job(|_| Ok(()));
+        let running = job.start();
+        assert!(running.is_some());
+        assert!(job.start().is_none());
+        drop(running);
+        assert!(job.start().is_some());
+    }
+
+    #[test]
+    fn test_run_failures() {
+        let runtime = runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let _entered = runtime.enter();
+        let client = testutil::local_client("127.0.0.1:9");
+
+        let failing = job(|_| Err(io::Error::other("quota exceeded")));
+        let report = failing.run(&client).unwrap();
+        assert_eq!(Some("Job audit failed: quota exceeded".to_string()),
+                   runtime.block_on(report).unwrap());
+
+        let panicking = job(|_| panic!("no such service"));
+        let report = panicking.run(&client).unwrap();
+        let failure = runtime.block_on(report).unwrap().unwrap();
+        assert!(failure.starts_with("Job audit did not finish: ") &&
+                failure.contains("panicked") && failure.contains("no such service"),
+                "{}",
+                failure);
+        // the panicked run does not block the next ones
+        assert!(panicking.start().is_some());
+    }
+}
@@ -0,0 +1,160 @@
+//! # Scope
+//!
+//! Memoization of GET calls for the duration of one logical run (a reconcile,
+//! a report, ...): while a `RunScope` is alive, a route read twice from the same
+//! thread is fetched once, so the run sees consistent data and spends fewer calls.
+//!
+//! Writes made within the scope invalidate the cached reads of their route and of
+//! the routes below it, so a run sees its own changes. Everything is discarded
+//! when the scope is dropped. Worker threads join the scope with `ScopeHandle::enter`.
+//!
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+#[derive(Debug,Default)]
+struct Cache {
+    entries: BTreeMap<String, Value>,
+    hits: u64,
+    misses: u64,
+}
+
+type Shared = Arc<Mutex<Cache>>;
+
+thread_local!(static CURRENT: RefCell<Option<Shared>> = const { RefCell::new(None) });
+
+/// A memoization scope, current on its thread until dropped.
+///
+/// Scopes can be nested: the inner one shares the cache of the outer one.
+pub struct RunScope {
+    cache: Shared,
+    previous: Option<Shared>,
+}
+
+/// Handle on a scope, to join it from another thread.
+#[derive(Clone)]
+pub struct ScopeHandle {
+    cache: Shared,
+}
+
+impl RunScope {
+    /// Start a scope with an empty cache, or join the enclosing one.
+    pub fn start() -> RunScope {
+        let previous = CURRENT.with(|current| current.borrow().clone());
+        let cache = previous.clone().unwrap_or_else(|| Arc::new(Mutex::new(Cache::default())));
+        RunScope::with_cache(cache, previous)
+    }
+
+    fn with_cache(cache: Shared, previous: Option<Shared>) -> RunScope {
+        CURRENT.with(|current| *current.borrow_mut() = Some(cache.clone()));
+        RunScope {
+            cache,
+            previous,
+        }
+    }
+
+    pub fn handle(&self) -> ScopeHandle {
+        ScopeHandle { cache: self.cache.clone() }
+    }
+
+    /// Calls answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.cache.lock().unwrap().hits
+    }
+
+    /// Calls which went to the API.
+    pub fn misses(&self) -> u64 {
+        self.cache.lock().unwrap().misses
+    }
+}
+
+impl ScopeHandle {
+    /// Make the scope current on this thread, until the returned scope is dropped.
+    pub fn enter(&self) -> RunScope {
+        let previous = CURRENT.with(|current| current.borrow().clone());
+        RunScope::with_cache(self.cache.clone(), previous)
+    }
+}
+
+impl Drop for RunScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` within a new scope.
+pub fn in_run_scope<T, F: FnOnce() -> T>(f: F) -> T {
+    let _scope = RunScope::start();
+    f()
+}
+
+fn with_current<T, F: FnOnce(&mut Cache) -> T>(f: F) -> Option<T> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|cache| f(&mut cache.lock().unwrap())))
+}
+
+/// Cached answer of a GET on `key`, if a scope is current.
+pub(crate) fn lookup(key: &str) -> Option<Value> {
+    with_current(|cache| {
+            let value = cache.entries.get(key).cloned();
+            match value {
+                Some(_) => cache.hits += 1,
+                None => cache.misses += 1,
+            }
+            value
+        })
+        .and_then(|value| value)
+}
+
+pub(crate) fn store(key: &str, value: &Value) {
+    with_current(|cache| cache.entries.insert(key.to_string(), value.clone()));
+}
+
+/// Forget the answers of `key` and of the routes below it, after a write.
+pub(crate) fn invalidate(key: &str) {
+    let prefix = key.split('?').next().unwrap_or(key).to_string();
+    with_current(|cache| {
+        cache.entries.retain(|cached, _| {
+            !(cached.starts_with(&prefix) &&
+              cached[prefix.len()..].chars().next().is_none_or(|c| c == '/' || c == '?'))
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invalidate, lookup, store, RunScope};
+    use std::thread;
+
+    #[test]
+    fn test_run_scope() {
+        store("/me", &json!(1));
+        assert_eq!(None, lookup("/me"));
+        {
+            let scope = RunScope::start();
+            assert_eq!(None, lookup("/domain/zone/a"));
+            store("/domain/zone/a", &json!("a"));
+            store("/domain/zone/a/record/1", &json!(1));
+            store("/domain/zone/ab", &json!("ab"));
+            {
+                let _inner = RunScope::start();
+                assert_eq!(Some(json!("a")), lookup("/domain/zone/a"));
+            }
+            let handle = scope.handle();
+            thread::spawn(move || {
+                    let _scope = handle.enter();
+                    assert_eq!(Some(json!(1)), lookup("/domain/zone/a/record/1"));
+                })
+                .join()
+                .unwrap();
+            invalidate("/domain/zone/a");
+            assert_eq!(None, lookup("/domain/zone/a/record/1"));
+            assert_eq!(Some(json!("ab")), lookup("/domain/zone/ab"));
+            assert_eq!((3, 2), (scope.hits(), scope.misses()));
+        }
+        assert_eq!(None, lookup("/domain/zone/ab"));
+    }
+}
@@ -0,0 +1,378 @@
+//! # Seal
+//!
+//! Encryption at rest of credential files, for laptops and shared hosts.
+//!
+//! A sealed file is text: a header naming the cipher and how the key is derived,
+//! a blank line, then the base64 ciphertext.
+//!
+//! ```text
+//! ovh-rs-sealed v1
+//! cipher: aes-256-gcm
+//! kdf: pbkdf2-sha256
+//! iterations: 100000
+//! salt: ...
+//! nonce: ...
+//!
+//! ...
+//! ```
+//!
+//! The header is authenticated along with the content, line by line, so a file
+//! whose line endings were turned into CRLF (e.g. by git on Windows) still opens.
+//! The key comes from:
+//!
+//! - `pbkdf2-sha256`: a passphrase, from `OVH_CONFIG_PASSPHRASE` or asked on the terminal;
+//! - `raw`: a 32 bytes key, base64 encoded in `OVH_CONFIG_KEY`;
+//! - `kms`: a data key wrapped by a key management service, unwrapped by the `Kms`
+//!   given to `Credential::new_from_file_with_kms`, e.g. `kms::OvhKms` for the
+//!   OVHcloud KMS.
+//!
+//! `Credential::new` and `Credential::new_from_file` open sealed files transparently;
+//! `ovh seal-config` seals an existing file.
+//!
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use base64;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
+
+use rand::{OsRng, Rng};
+use rpassword;
+
+const MAGIC: &str = "ovh-rs-sealed v1";
+const CIPHER: &str = "aes-256-gcm";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+
+/// Iterations of PBKDF2 for new files.
+pub const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// A key management service, which wraps and unwraps data keys.
+pub trait Kms {
+    /// Id of the master key, stored in the header of sealed files.
+    fn key_id(&self) -> String;
+
+    fn wrap(&self, key: &[u8]) -> io::Result<Vec<u8>>;
+
+    fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Key of a sealed file.
+pub enum SealKey<'a> {
+    Passphrase(&'a str),
+    /// A raw 32 bytes key.
+    Key(&'a [u8]),
+    Kms(&'a dyn Kms),
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn random(len: usize) -> io::Result<Vec<u8>> {
+    let mut rng = OsRng::new().map_err(|e| io::Error::other(e.to_string()))?;
+    let mut bytes = vec![0; len];
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+fn derive(passphrase: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut key = vec![0; KEY_LEN];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn aes(key: &[u8]) -> io::Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(key).map_err(|_| invalid(&format!("key must be {} bytes", KEY_LEN)))
+}
+
+/// Whether `content` is a sealed file.
+pub fn is_sealed(content: &str) -> bool {
+    content.lines().next().map(str::trim_end) == Some(MAGIC)
+}
+
+/// Seal `plaintext`, with a fresh nonce (and salt or data key).
+pub fn seal(plaintext: &str, key: SealKey) -> io::Result<String> {
+    seal_with_iterations(plaintext, key, PBKDF2_ITERATIONS)
+}
+
+fn seal_with_iterations(plaintext: &str, key: SealKey, iterations: u32) -> io::Result<String> {
+    let nonce = random(NONCE_LEN)?;
+    let mut header = vec![MAGIC.to_string(), format!("cipher: {}", CIPHER)];
+    let key = match key {
+        SealKey::Passphrase(passphrase) => {
+            let salt = random(SALT_LEN)?;
+            header.push("kdf: pbkdf2-sha256".to_string());
+            header.push(format!("iterations: {}", iterations));
+            header.push(format!("salt: {}", base64::encode(&salt)));
+            derive(passphrase, &salt, iterations)
+        }
+        SealKey::Key(key) => {
+            if key.len() != KEY_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("key must be {} bytes", KEY_LEN)));
+            }
+            header.push("kdf: raw".to_string());
+            key.to_vec()
+        }
+        SealKey::Kms(kms) => {
+            let key = random(KEY_LEN)?;
+            header.push("kdf: kms".to_string());
+            header.push(format!("key-id: {}", kms.key_id()));
+            header.push(format!("wrapped-key: {}", base64::encode(&kms.wrap(&key)?)));
+            key
+        }
+    };
+    header.push(format!("nonce: {}", base64::encode(&nonce)));
+    let header = header.join("\n");
+
+    let payload = Payload { msg: plaintext.as_bytes(), aad: header.as_bytes() };
+    let sealed = aes(&key)?
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| io::Error::other("cannot encrypt"))?;
+    Ok(format!("{}\n\n{}\n", header, base64::encode(&sealed)))
+}
+
+/// Parsed sealed file: the header as authenticated (its lines joined by `\n`),
+/// its fields and the ciphertext with its tag.
+type Parsed<'a> = (String, Vec<(&'a str, &'a str)>, Vec<u8>);
+
+fn parse(content: &str) -> io::Result<Parsed<'_>> {
+    if !is_sealed(content) {
+        return Err(invalid("not a sealed file"));
+    }
+    // `lines` drops the `\r` of CRLF endings too
+    let mut lines = content.lines();
+    let header: Vec<&str> = lines.by_ref().take_while(|line| !line.trim().is_empty()).collect();
+    let mut fields = Vec::new();
+    for line in &header[1..] {
+        let mut parts = line.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => fields.push((name.trim(), value.trim())),
+            _ => return Err(invalid(&format!("invalid header line: {}", line))),
+        }
+    }
+    let body: String = lines.flat_map(str::split_whitespace).collect();
+    if body.is_empty() {
+        return Err(invalid("sealed file without content"));
+    }
+    let sealed = base64::decode(&body).map_err(|e| invalid(&format!("invalid base64: {}", e)))?;
+    if sealed.len() < TAG_LEN {
+        return Err(invalid("truncated sealed file"));
+    }
+    Ok((header.join("\n"), fields, sealed))
+}
+
+fn field<'a>(fields: &[(&str, &'a str)], name: &str) -> io::Result<&'a str> {
+    fields.iter()
+        .find(|field| field.0 == name)
+        .map(|field| field.1)
+        .ok_or_else(|| invalid(&format!("missing {} in the header", name)))
+}
+
+fn field_bytes(fields: &[(&str, &str)], name: &str) -> io::Result<Vec<u8>> {
+    base64::decode(field(fields, name)?).map_err(|e| invalid(&format!("invalid {}: {}", name, e)))
+}
+
+/// The kind of key a sealed file needs: `pbkdf2-sha256`, `raw` or `kms`.
+pub fn kdf(content: &str) -> io::Result<String> {
+    let (_, fields, _) = parse(content)?;
+    field(&fields, "kdf").map(str::to_string)
+}
+
+/// Open a sealed file with the given key.
+pub fn open_with(content: &str, key: SealKey) -> io::Result<String> {
+    let (header, fields, sealed) = parse(content)?;
+    let cipher = field(&fields, "cipher")?;
+    if cipher != CIPHER {
+        return Err(invalid(&format!("unsupported cipher {}", cipher)));
+    }
+    let kdf = field(&fields, "kdf")?;
+    let key = match (kdf, key) {
+        ("pbkdf2-sha256", SealKey::Passphrase(passphrase)) => {
+            let iterations = field(&fields, "iterations")?
+                .parse()
+                .ok()
+                .filter(|&iterations| iterations > 0)
+                .ok_or_else(|| invalid("invalid iterations"))?;
+            derive(passphrase, &field_bytes(&fields, "salt")?, iterations)
+        }
+        ("raw", SealKey::Key(key)) => key.to_vec(),
+        ("kms", SealKey::Kms(kms)) => {
+            kms.unwrap(field(&fields, "key-id")?, &field_bytes(&fields, "wrapped-key")?)?
+        }
+        _ => return Err(invalid(&format!("the file is sealed with a {} key", kdf))),
+    };
+    if key.len() != KEY_LEN {
+        return Err(invalid(&format!("key must be {} bytes", KEY_LEN)));
+    }
+    let nonce = field_bytes(&fields, "nonce")?;
+    if nonce.len() != NONCE_LEN {
+        return Err(invalid("invalid nonce"));
+    }
+
+    let payload = Payload { msg: &sealed, aad: header.as_bytes() };
+    let output = aes(&key)?
+        .decrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| invalid("cannot decrypt: wrong key or altered file"))?;
+    String::from_utf8(output).map_err(|_| invalid("decrypted content is not UTF-8"))
+}
+
+/// Open a sealed file, with the key of the environment, `kms`
+/// or a passphrase asked on the terminal.
+pub fn open(content: &str, kms: Option<&dyn Kms>) -> io::Result<String> {
+    match kdf(content)?.as_str() {
+        "pbkdf2-sha256" => {
+            let passphrase = passphrase("Passphrase of the OVH credentials: ")?;
+            open_with(content, SealKey::Passphrase(&passphrase))
+        }
+        "raw" => {
+            let key = env::var("OVH_CONFIG_KEY")
+                .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "OVH_CONFIG_KEY is not set"))?;
+            let key = base64::decode(key.trim())
+                .map_err(|e| invalid(&format!("invalid OVH_CONFIG_KEY: {}", e)))?;
+            open_with(content, SealKey::Key(&key))
+        }
+        "kms" => {
+            let kms = kms.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound,
+                               "sealed through a KMS, load it with new_from_file_with_kms")
+            })?;
+            open_with(content, SealKey::Kms(kms))
+        }
+        kdf => Err(invalid(&format!("unsupported kdf {}", kdf))),
+    }
+}
+
+/// The passphrase of `OVH_CONFIG_PASSPHRASE`, or asked on the terminal without echo.
+/// Fails without a terminal, e.g. in CI: set the variable there.
+pub fn passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = env::var("OVH_CONFIG_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(prompt)
+}
+
+/// Seal the file at `path` in place, atomically.
+pub fn seal_file<P: AsRef<Path>>(path: P, key: SealKey) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    if is_sealed(&content) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the file is already sealed"));
+    }
+    let sealed = seal(&content, key)?;
+    write_private(path, sealed.as_bytes())
+}
+
+/// Replace the file at `path` atomically, through a temporary file which is never
+/// readable by others: it is created 0600, then given the permissions of the file it
+/// replaces, if any.
+pub(crate) fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&tmp)
+        .and_then(|mut fd| fd.write_all(content))
+        .and_then(|_| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&tmp, metadata.permissions()),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sealed, kdf, open, open_with, seal, seal_with_iterations, Kms, SealKey};
+    use std::io;
+
+    const CONFIG: &str = "[default]\nendpoint = \"ovh-eu\"\n";
+
+    struct XorKms;
+
+    impl Kms for XorKms {
+        fn key_id(&self) -> String {
+            "test-key".to_string()
+        }
+
+        fn wrap(&self, key: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(key.iter().map(|byte| byte ^ 0x5a).collect())
+        }
+
+        fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> io::Result<Vec<u8>> {
+            assert_eq!("test-key", key_id);
+            self.wrap(wrapped)
+        }
+    }
+
+    #[test]
+    fn test_seal_passphrase() {
+        // few iterations, PBKDF2 is slow in debug builds
+        let sealed = seal_with_iterations(CONFIG, SealKey::Passphrase("correct horse"), 100)
+            .unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("endpoint"));
+        assert_eq!("pbkdf2-sha256", kdf(&sealed).unwrap());
+        assert_eq!(CONFIG, open_with(&sealed, SealKey::Passphrase("correct horse")).unwrap());
+        assert!(open_with(&sealed, SealKey::Passphrase("wrong")).is_err());
+        assert!(open_with(&sealed, SealKey::Key(&[0; 32])).is_err());
+    }
+
+    #[test]
+    fn test_seal_key_and_kms() {
+        let key = [7; 32];
+        let sealed = seal(CONFIG, SealKey::Key(&key)).unwrap();
+        assert_eq!(CONFIG, open_with(&sealed, SealKey::Key(&key)).unwrap());
+        assert!(seal(CONFIG, SealKey::Key(&key[..16])).is_err());
+
+        // the header is authenticated
+        let altered = sealed.replacen("kdf: raw", "kdf: raw ", 1);
+        assert!(open_with(&altered, SealKey::Key(&key)).is_err());
+
+        let sealed = seal(CONFIG, SealKey::Kms(&XorKms)).unwrap();
+        assert!(sealed.contains("key-id: test-key"));
+        assert_eq!(CONFIG, open_with(&sealed, SealKey::Kms(&XorKms)).unwrap());
+        assert_eq!(CONFIG, open(&sealed, Some(&XorKms)).unwrap());
+        assert!(open(&sealed, None).is_err());
+        assert!(!is_sealed(CONFIG));
+    }
+
+    #[test]
+    fn test_open_existing_files() {
+        // fixtures sealed once, so that changes of the format are noticed
+        let raw = "ovh-rs-sealed v1\ncipher: aes-256-gcm\nkdf: raw\nnonce: WoqIIR/n4T6d3ZQb\n\n\
+                   cj6jSM7CXAH8+ncofmitmOFtmksq/pZcFeDkPyzTDDNk/sLCiqyn+/oT1q9eIQ==\n";
+        assert_eq!(CONFIG, open_with(raw, SealKey::Key(&[7; 32])).unwrap());
+        let passphrase = "ovh-rs-sealed v1\ncipher: aes-256-gcm\nkdf: pbkdf2-sha256\n\
+                          iterations: 2\nsalt: PmSeKNxrInDEa9RPpaHkWg==\n\
+                          nonce: OZ0xbQceLox6WEiv\n\n\
+                          U3+sAILrPuMRkLiAuSt6WNMNqmxPJaxL38sPvW6QTXC12f07oNPG7LM4Qh4YmQ==\n";
+        assert_eq!(CONFIG, open_with(passphrase, SealKey::Passphrase("secret")).unwrap());
+
+        let crlf = raw.replace('\n', "\r\n");
+        assert!(is_sealed(&crlf));
+        assert_eq!(CONFIG, open_with(&crlf, SealKey::Key(&[7; 32])).unwrap());
+        let headless = "ovh-rs-sealed v1\r\ncipher: aes-256-gcm\r\n";
+        assert!(open_with(headless, SealKey::Key(&[7; 32])).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! # Select
+//!
+//! Target groups of services by name rather than one by one: a pattern such
+//! as `web-*`, `db-[0-9]` or `{www,api}.example.com` is expanded against the
+//! live inventory of a product, e.g. `/vps` or `/dedicated/server`.
+//!
+//! With the `select-regex` feature, `/^web-\d+$/` is a regular expression,
+//! matched anywhere in the name unless anchored.
+//!
+//! Inventories are cached for `DEFAULT_TTL` seconds, so a script or a command
+//! line selecting several patterns of one product lists it once.
+//!
+
+#[cfg(feature = "select-regex")]
+use regex::Regex;
+
+use client::OVHClient;
+use rest;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Lifetime of a cached inventory, in seconds.
+pub const DEFAULT_TTL: u64 = 60;
+
+#[derive(Debug,Clone,PartialEq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `[a-z0-9]`, or `[!...]` when negated.
+    Class(bool, Vec<(char, char)>),
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Token::Char(expected) => c == expected,
+            Token::Any | Token::Star => true,
+            Token::Class(negated, ref ranges) => {
+                ranges.iter().any(|&(low, high)| low <= c && c <= high) != negated
+            }
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+enum Kind {
+    Exact,
+    /// The alternatives of a glob, one per expansion of its braces.
+    Glob(Vec<Vec<Token>>),
+    #[cfg(feature = "select-regex")]
+    Regex(Regex),
+}
+
+/// A selection of names: an exact name, a shell glob (`*`, `?`, `[...]`,
+/// `{a,b}` and `\` to escape them) or a `/regex/`.
+#[derive(Debug,Clone)]
+pub struct Pattern {
+    source: String,
+    kind: Kind,
+}
+
+fn invalid(pattern: &str, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput,
+                   format!("invalid pattern {}: {}", pattern, message))
+}
+
+/// Expand the `{a,b}` alternatives of a glob, nested ones included.
+fn expand_braces(glob: &str) -> Result<Vec<String>, &'static str> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut depth = 0;
+    let mut open = None;
+    let mut commas = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => commas.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open.unwrap_or(0);
+                    let prefix: String = chars[..start].iter().collect();
+                    let suffix: String = chars[i + 1..].iter().collect();
+                    let mut bounds = vec![start];
+                    bounds.extend(commas.iter().cloned());
+                    bounds.push(i);
+                    let mut expanded = Vec::new();
+                    for pair in bounds.windows(2) {
+                        let choice: String = chars[pair[0] + 1..pair[1]].iter().collect();
+                        let alternative = format!("{}{}{}", prefix, choice, suffix);
+                        expanded.extend(expand_braces(&alternative)?);
+                    }
+                    return Ok(expanded);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if depth > 0 {
+        return Err("unclosed {");
+    }
+    Ok(vec![glob.to_string()])
+}
+
+fn tokenize(glob: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\\' => Token::Char(chars.next().ok_or("trailing \\")?),
+            '?' => Token::Any,
+            '*' if tokens.last() == Some(&Token::Star) => continue,
+            '*' => Token::Star,
+            '[' => {
+                let mut class: Vec<char> = Vec::new();
+                loop {
+                    match chars.next() {
+                        None => return Err("unclosed ["),
+                        Some(']') if !class.is_empty() && class != ['!'] && class != ['^'] => {
+                            break
+                        }
+                        Some(c) => class.push(c),
+                    }
+                }
+                let negated = class[0] == '!' || class[0] == '^';
+                let class = if negated { &class[1..] } else { &class[..] };
+                let mut ranges = Vec::new();
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == '-' {
+                        ranges.push((class[i], class[i + 2]));
+                        i += 3;
+                    } else {
+                        ranges.push((class[i], class[i]));
+                        i += 1;
+                    }
+                }
+                Token::Class(negated, ranges)
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn match_tokens(tokens: &[Token], name: &[char]) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((&Token::Star, rest)) => (0..name.len() + 1).any(|i| match_tokens(rest, &name[i..])),
+        Some((token, rest)) => {
+            !name.is_empty() && token.matches(name[0]) && match_tokens(rest, &name[1..])
+        }
+    }
+}
+
+impl Pattern {
+    /// Parse `/regex/`, a glob when it has any of `*?[{\`, or else an exact name.
+    pub fn parse(pattern: &str) -> io::Result<Pattern> {
+        let kind = if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            Pattern::regex(pattern, &pattern[1..pattern.len() - 1])?
+        } else if pattern.contains(|c| "*?[{\\".contains(c)) {
+            let alternatives = expand_braces(pattern)
+                .and_then(|globs| globs.iter().map(|glob| tokenize(glob)).collect())
+                .map_err(|message| invalid(pattern, message))?;
+            Kind::Glob(alternatives)
+        } else {
+            Kind::Exact
+        };
+        Ok(Pattern {
+            source: pattern.to_string(),
+            kind,
+        })
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    #[cfg(feature = "select-regex")]
+    fn regex(pattern: &str, expression: &str) -> io::Result<Kind> {
+        Regex::new(expression)
+            .map(Kind::Regex)
+            .map_err(|err| invalid(pattern, &err.to_string()))
+    }
+
+    #[cfg(not(feature = "select-regex"))]
+    fn regex(pattern: &str, _: &str) -> io::Result<Kind> {
+        Err(invalid(pattern, "regular expressions need the select-regex feature"))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self.kind {
+            Kind::Exact => self.source == name,
+            Kind::Glob(ref alternatives) => {
+                let name: Vec<char> = name.chars().collect();
+                alternatives.iter().any(|tokens| match_tokens(tokens, &name))
+            }
+            #[cfg(feature = "select-regex")]
+            Kind::Regex(ref regex) => regex.is_match(name),
+        }
+    }
+
+    /// The names matching, in their order.
+    pub fn filter<S: AsRef<str>>(&self, names: &[S]) -> Vec<String> {
+        names.iter()
+            .map(AsRef::as_ref)
+            .filter(|name| self.matches(name))
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// The names matching any of `patterns`, in their order and once each.
+pub fn filter<S: AsRef<str>>(patterns: &[Pattern], names: &[S]) -> Vec<String> {
+    names.iter()
+        .map(AsRef::as_ref)
+        .filter(|name| patterns.iter().any(|pattern| pattern.matches(name)))
+        .map(String::from)
+        .collect()
+}
+
+/// Cache of the names of the services of products, by account.
+#[derive(Debug)]
+pub struct Inventory {
+    ttl: Duration,
+    entries: Mutex<BTreeMap<String, (Instant, Vec<String>)>>,
+}
+
+static GLOBAL: Inventory = Inventory::new(Duration::from_secs(DEFAULT_TTL));
+
+/// The cache used by `match_services` and `select`.
+pub fn global() -> &'static Inventory {
+    &GLOBAL
+}
+
+/// API route listing the services of `product`: `vps` or `/vps`.
+fn route(product: &str) -> String {
+    format!("/{}", product.trim_matches('/'))
+}
+
+impl Inventory {
+    /// A cache keeping inventories for `ttl`; a zero `ttl` disables it.
+    pub const fn new(ttl: Duration) -> Inventory {
+        Inventory {
+            ttl,
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn lookup(&self, key: &str, now: Instant) -> Option<Vec<String>> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.get(key)
+            .filter(|&&(fetched_at, _)| now.duration_since(fetched_at) < self.ttl)
+            .map(|(_, names)| names.clone())
+    }
+
+    fn store(&self, key: String, names: Vec<String>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.retain(|_, &mut (fetched_at, _)| now.duration_since(fetched_at) < self.ttl);
+        entries.insert(key, (now, names));
+    }
+
+    /// The names of the services of `product` (`/vps`, `/dedicated/server`, ...),
+    /// listed by the API unless cached for the account of `client`.
+    pub fn names(&self, client: &OVHClient, product: &str) -> io::Result<Vec<String>> {
+        let route = route(product);
+        let credential = &client.credential;
+        let key = format!("{} {} {} {}",
+                          credential.host,
+                          credential.application_key,
+                          credential.consumer_key,
+                          route);
+        if let Some(names) = self.lookup(&key, Instant::now()) {
+            return Ok(names);
+        }
+        let names = rest::string_list(&rest::get(client, &route)?)?;
+        if self.ttl > Duration::from_secs(0) {
+            self.store(key, names.clone(), Instant::now());
+        }
+        Ok(names)
+    }
+
+    /// Forget every inventory, e.g. after ordering or terminating services.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+
+    /// The services of `product` matching `pattern`, see `Pattern::parse`.
+    pub fn match_services(&self,
+                          client: &OVHClient,
+                          pattern: &str,
+                          product: &str)
+                          -> io::Result<Vec<String>> {
+        self.select(client, &[pattern], product)
+    }
+
+    /// The services of `product` matching any of `patterns`.
+    pub fn select(&self,
+                  client: &OVHClient,
+                  patterns: &[&str],
+                  product: &str)
+                  -> io::Result<Vec<String>> {
+        let patterns = patterns.iter()
+            .map(|pattern| Pattern::parse(pattern))
+            .collect::<io::Result<Vec<Pattern>>>()?;
+        Ok(filter(&patterns, &self.names(client, product)?))
+    }
+}
+
+/// The services of `product` matching `pattern`, e.g.
+/// `match_services(&client, "web-*", "/vps")`.
+pub fn match_services(client: &OVHClient, pattern: &str, product: &str) -> io::Result<Vec<String>> {
+    global().match_services(client, pattern, product)
+}
+
+/// The services of `product` matching any of `patterns`.
+pub fn select(client: &OVHClient, patterns: &[&str], product: &str) -> io::Result<Vec<String>> {
+    global().select(client, patterns, product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{filter, route, Inventory, Pattern};
+    use std::time::{Duration, Instant};
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(name)
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(matches("web-*", "web-1"));
+        assert!(matches("web-*", "web-"));
+        assert!(!matches("web-*", "db-1"));
+        assert!(matches("*.example.com", "www.example.com"));
+        assert!(matches("db-?", "db-2"));
+        assert!(!matches("db-?", "db-12"));
+        assert!(matches("db-[0-9]", "db-7"));
+        assert!(!matches("db-[!0-9]", "db-7"));
+        assert!(matches("{www,api}.example.com", "api.example.com"));
+        assert!(matches("ns{1,2{a,b}}", "ns2b"));
+        assert!(!matches("{www,api}.example.com", "mail.example.com"));
+        assert!(matches("literal\\*", "literal*"));
+        assert!(!matches("literal\\*", "literally"));
+        assert!(matches("vps123.ovh.net", "vps123.ovh.net"));
+        assert!(Pattern::parse("web-[0-9").is_err());
+        assert!(Pattern::parse("{www,api").is_err());
+    }
+
+    #[cfg(feature = "select-regex")]
+    #[test]
+    fn test_regex() {
+        assert!(matches("/^web-\\d+$/", "web-12"));
+        assert!(!matches("/^web-\\d+$/", "web-a"));
+        assert!(Pattern::parse("/(/").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let names = ["web-1", "db-1", "web-2", "cache"];
+        let patterns = [Pattern::parse("web-*").unwrap(), Pattern::parse("web-1").unwrap()];
+        assert_eq!(vec!["web-1", "web-2"], filter(&patterns, &names));
+        assert_eq!(vec!["db-1"], Pattern::parse("db-*").unwrap().filter(&names));
+        assert_eq!("/dedicated/server", route("dedicated/server/"));
+    }
+
+    #[test]
+    fn test_inventory_cache() {
+        let inventory = Inventory::new(Duration::from_secs(60));
+        let now = Instant::now();
+        inventory.store("vps".to_string(), vec!["vps-1".to_string()], now);
+        assert_eq!(Some(vec!["vps-1".to_string()]), inventory.lookup("vps", now));
+        assert_eq!(None, inventory.lookup("vps", now + Duration::from_secs(60)));
+        inventory.clear();
+        assert_eq!(None, inventory.lookup("vps", now));
+    }
+}
@@ -0,0 +1,139 @@
+//! # Services
+//!
+//! Typed access to the account-wide service inventory (`/services`),
+//! covering every product with its billing dates.
+//!
+
+use client::OVHClient;
+#[cfg(feature = "async")]
+use nonblocking::AsyncClient;
+#[cfg(feature = "async")]
+use pagination::Listing;
+use pagination::{self, Paginator};
+use rest::{self, Model};
+
+use std::io;
+
+use chrono::NaiveDate;
+
+use serde_json::Value;
+
+/// A subscribed service with its renewal information.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Service {
+    pub id: u64,
+    /// Name used in the API routes of the product (domain name, server name, ...).
+    pub name: String,
+    pub display_name: String,
+    /// Product family, e.g. `domain`, `dedicated-server`.
+    pub product: String,
+    /// Date after which the service is suspended if not renewed.
+    pub expiration: Option<NaiveDate>,
+    pub next_billing: Option<NaiveDate>,
+    /// Renewal mode: `automatic`, `manual`, ...
+    pub renew_mode: Option<String>,
+}
+
+/// Parse the date part of an API date or datetime.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    if value.len() < 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(&value[..10], "%Y-%m-%d").ok()
+}
+
+impl Service {
+    /// Whether OVH will renew the service without user action.
+    pub fn is_automatic_renewal(&self) -> bool {
+        self.renew_mode.as_ref().is_some_and(|mode| mode == "automatic")
+    }
+}
+
+impl Model for Service {
+    const NAME: &'static str = "Service";
+    const FIELDS: &'static [&'static str] = &["serviceId", "route", "billing", "resource",
+                                              "parentServiceId", "customer", "tags"];
+
+    fn from_json(value: &Value) -> io::Result<Service> {
+        let resource = value.get("resource").unwrap_or(&Value::Null);
+        let billing = value.get("billing").unwrap_or(&Value::Null);
+        let date = |key: &str| {
+            billing.get(key)
+                .and_then(Value::as_str)
+                .and_then(parse_date)
+        };
+        let name = rest::str_field(resource, "name")?;
+        Ok(Service {
+            id: rest::u64_field(value, "serviceId")?,
+            display_name: rest::opt_str_field(resource, "displayName").unwrap_or_else(|| name.clone()),
+            name,
+            product: resource.pointer("/product/name")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string(),
+            expiration: date("expirationDate"),
+            next_billing: date("nextBillingDate"),
+            renew_mode: billing.pointer("/renew/current/mode")
+                .and_then(Value::as_str)
+                .map(String::from),
+        })
+    }
+}
+
+/// List the ids of all the services of the account.
+pub fn service_ids(client: &OVHClient) -> io::Result<Vec<u64>> {
+    rest::u64_list(&rest::get(client, "/services")?)
+}
+
+pub fn service(client: &OVHClient, id: u64) -> io::Result<Service> {
+    rest::decode(client, &rest::get(client, &format!("/services/{}", id))?)
+}
+
+/// Fetch every service of the account.
+pub fn services(client: &OVHClient) -> io::Result<Vec<Service>> {
+    service_ids(client)?
+        .into_iter()
+        .map(|id| service(client, id))
+        .collect()
+}
+
+/// Fetch the services lazily, one call per service consumed.
+pub fn iter_services<'a>(client: &'a OVHClient)
+                         -> io::Result<impl Iterator<Item = io::Result<Service>> + 'a> {
+    Ok(pagination::decoded(Paginator::new(client, "/services")?))
+}
+
+/// Stream the services, one call per service polled.
+#[cfg(feature = "async")]
+pub fn stream_services(client: &AsyncClient) -> Listing<Service> {
+    pagination::listing(client, "/services")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_date, Service};
+    use rest::Model;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_service_from_json() {
+        let value = json!({
+            "serviceId": 42,
+            "resource": {
+                "name": "example.com",
+                "displayName": "example.com",
+                "product": { "name": "domain" },
+            },
+            "billing": {
+                "expirationDate": "2017-06-01T00:00:00+02:00",
+                "nextBillingDate": "2017-05-01",
+                "renew": { "current": { "mode": "manual" } },
+            },
+        });
+        let service = Service::from_json(&value).unwrap();
+        assert_eq!("domain", service.product);
+        assert_eq!(Some(NaiveDate::from_ymd(2017, 6, 1)), service.expiration);
+        assert!(!service.is_automatic_renewal());
+        assert_eq!(None, parse_date("soon"));
+    }
+}
@@ -0,0 +1,249 @@
+//! # SMS
+//!
+//! SMS campaigns on an SMS service (`/sms/{serviceName}`): a message template
+//! rendered per recipient, batched into as few send jobs as possible,
+//! sent now or deferred, and followed by a status report.
+//!
+//! Every job of a campaign is sent with the campaign tag, which is what the
+//! status report uses to find its messages.
+//!
+
+use client::OVHClient;
+use rest;
+
+use std::collections::BTreeMap;
+use std::io;
+
+use chrono::{DateTime, Local};
+
+use serde_json::Value;
+
+/// Maximum receivers of a job.
+pub const MAX_RECEIVERS: usize = 500;
+
+/// Replace the `{{name}}` placeholders of `template` with `vars`.
+/// Unknown placeholders are an error, so a typo does not reach customers.
+pub fn render(template: &str, vars: &BTreeMap<String, String>) -> io::Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest_of = template;
+    while let Some(start) = rest_of.find("{{") {
+        let end = rest_of[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| invalid_input(format!("unclosed placeholder in {}", template)))?;
+        let name = rest_of[start + 2..end].trim();
+        let value = vars.get(name)
+            .ok_or_else(|| invalid_input(format!("no value for {{{{{}}}}}", name)))?;
+        rendered.push_str(&rest_of[..start]);
+        rendered.push_str(value);
+        rest_of = &rest_of[end + 2..];
+    }
+    rendered.push_str(rest_of);
+    Ok(rendered)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// A recipient and the values of its placeholders.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Recipient {
+    /// International format, e.g. `+33612345678`.
+    pub number: String,
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Recipient {
+    pub fn new(number: &str) -> Recipient {
+        Recipient {
+            number: number.to_string(),
+            vars: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), value.to_string());
+    }
+}
+
+/// A send job: one message to several receivers.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SmsJob {
+    pub message: String,
+    pub receivers: Vec<String>,
+}
+
+/// A templated message to a list of recipients.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SmsCampaign {
+    pub service: String,
+    /// Sender name or number, as allowed on the service.
+    pub sender: Option<String>,
+    pub template: String,
+    pub recipients: Vec<Recipient>,
+    /// Tag of the jobs, to follow the campaign.
+    pub tag: String,
+    /// Deferred sending, immediate when `None`.
+    pub send_at: Option<DateTime<Local>>,
+}
+
+impl SmsCampaign {
+    pub fn new(service: &str, tag: &str, template: &str) -> SmsCampaign {
+        SmsCampaign {
+            service: service.to_string(),
+            sender: None,
+            template: template.to_string(),
+            recipients: Vec::new(),
+            tag: tag.to_string(),
+            send_at: None,
+        }
+    }
+
+    pub fn set_sender(&mut self, sender: &str) {
+        self.sender = Some(sender.to_string());
+    }
+
+    pub fn set_send_at(&mut self, send_at: DateTime<Local>) {
+        self.send_at = Some(send_at);
+    }
+
+    pub fn add_recipient(&mut self, recipient: Recipient) {
+        self.recipients.push(recipient);
+    }
+
+    /// Render the message of every recipient, grouping identical messages
+    /// into jobs of at most `MAX_RECEIVERS`. Jobs keep the order of first appearance.
+    pub fn jobs(&self) -> io::Result<Vec<SmsJob>> {
+        let mut jobs: Vec<SmsJob> = Vec::new();
+        for recipient in &self.recipients {
+            let message = render(&self.template, &recipient.vars)?;
+            let open = jobs.iter_mut()
+                .find(|job| job.message == message && job.receivers.len() < MAX_RECEIVERS);
+            match open {
+                Some(job) => job.receivers.push(recipient.number.clone()),
+                None => {
+                    jobs.push(SmsJob {
+                        message,
+                        receivers: vec![recipient.number.clone()],
+                    })
+                }
+            }
+        }
+        Ok(jobs)
+    }
+
+    fn job_body(&self, job: &SmsJob) -> Value {
+        let differed = self.send_at
+            .map(|send_at| (send_at.timestamp() - Local::now().timestamp()).max(0) / 60)
+            .unwrap_or(0);
+        let mut body = json!({
+            "message": job.message,
+            "receivers": job.receivers,
+            "tag": self.tag,
+            "differedPeriod": differed,
+            "noStopClause": false,
+        });
+        if let Some(map) = body.as_object_mut() {
+            match self.sender {
+                Some(ref sender) => map.insert("sender".to_string(), Value::String(sender.clone())),
+                None => map.insert("senderForResponse".to_string(), Value::Bool(true)),
+            };
+        }
+        body
+    }
+
+    /// Send (or schedule) every job of the campaign.
+    pub fn send(&self, client: &OVHClient) -> io::Result<CampaignReceipt> {
+        let mut receipt = CampaignReceipt::default();
+        for job in self.jobs()? {
+            let value = rest::post(client,
+                                   &format!("/sms/{}/jobs", rest::escape(&self.service)),
+                                   &self.job_body(&job))?;
+            if let Some(ids) = value.get("ids") {
+                receipt.job_ids.extend(rest::u64_list(ids)?);
+            }
+            if let Some(invalid) = value.get("invalidReceivers") {
+                receipt.invalid_receivers.extend(rest::string_list(invalid)?);
+            }
+            receipt.credits += value.get("totalCreditsRemoved")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0);
+        }
+        Ok(receipt)
+    }
+}
+
+/// What the API accepted of a campaign.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct CampaignReceipt {
+    pub job_ids: Vec<u64>,
+    /// Receivers refused by the API (bad format, blacklisted, ...).
+    pub invalid_receivers: Vec<String>,
+    pub credits: f64,
+}
+
+/// Messages of a campaign by status.
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct CampaignStatus {
+    /// Jobs not sent yet (deferred or queued).
+    pub pending: usize,
+    /// Sent, without delivery receipt yet.
+    pub sent: usize,
+    pub delivered: usize,
+}
+
+impl CampaignStatus {
+    pub fn total(&self) -> usize {
+        self.pending + self.sent + self.delivered
+    }
+}
+
+/// Aggregate the status of the messages tagged with `tag`.
+pub fn campaign_status(client: &OVHClient,
+                       service: &str,
+                       tag: &str)
+                       -> io::Result<CampaignStatus> {
+    let service = rest::escape(service);
+    let tag = rest::escape(tag);
+    let mut status = CampaignStatus::default();
+    let pending = rest::get(client, &format!("/sms/{}/jobs?tag={}", service, tag))?;
+    status.pending = rest::u64_list(&pending)?.len();
+    let outgoing = rest::get(client, &format!("/sms/{}/outgoing?tag={}", service, tag))?;
+    for id in rest::u64_list(&outgoing)? {
+        let message = rest::get(client, &format!("/sms/{}/outgoing/{}", service, id))?;
+        if message.get("deliveredAt").is_some_and(|date| !date.is_null()) {
+            status.delivered += 1;
+        } else {
+            status.sent += 1;
+        }
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Recipient, SmsCampaign};
+
+    #[test]
+    fn test_campaign_jobs() {
+        let mut campaign = SmsCampaign::new("sms-ab12345-1",
+                                            "maintenance",
+                                            "Hello {{ name }}, {{site}} is down");
+        let recipients = [("+33600000001", "Ann"),
+                          ("+33600000002", "Bob"),
+                          ("+33600000003", "Ann")];
+        for &(number, name) in &recipients {
+            let mut recipient = Recipient::new(number);
+            recipient.set_var("name", name);
+            recipient.set_var("site", "gra");
+            campaign.add_recipient(recipient);
+        }
+        let jobs = campaign.jobs().unwrap();
+        assert_eq!(2, jobs.len());
+        assert_eq!("Hello Ann, gra is down", jobs[0].message);
+        assert_eq!(vec!["+33600000001".to_string(), "+33600000003".to_string()], jobs[0].receivers);
+        assert!(render("{{missing}}", &Default::default()).is_err());
+        assert!(render("{{name", &Default::default()).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! # Snapshot
+//!
+//! Schema snapshots: record live answers of typed endpoints, stripped of
+//! personal data, and check later that they still decode.
+//!
+//! Recording is done once against a real account; the snapshots are committed
+//! and the checks run offline, so an edit of a model which can no longer read
+//! a real answer fails the tests instead of failing at runtime.
+//! The crate's own models are checked against the files of `snapshots/`.
+//!
+//! ```no_run
+//! # use ovh::endpoint::ApiEndpoint;
+//! # use ovh::snapshot::SnapshotDir;
+//! # fn check<E: ApiEndpoint>(client: &ovh::OVHClient, endpoint: &E) -> std::io::Result<()> {
+//! let snapshots = SnapshotDir::new("snapshots");
+//! snapshots.record(client, "vrack", endpoint)?;
+//! snapshots.check::<E>("vrack")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+
+use client::OVHClient;
+use endpoint::ApiEndpoint;
+use rest;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Map, Value};
+
+/// Fields whose string values are replaced before a snapshot is written.
+pub const SENSITIVE_FIELDS: &[&str] = &[
+    "address", "applicationKey", "applicationSecret", "area", "birthCity", "birthDay",
+    "cellPhone", "city", "companyNationalIdentificationNumber", "consumerKey", "customerCode",
+    "email", "fax", "firstname", "ip", "ipv4", "ipv6", "key", "lastname", "nationalIdentificationNumber",
+    "password", "phone", "rescueMail", "secret", "spareEmail", "sshKey", "token", "vat", "zip",
+];
+
+/// Replacement of sensitive values.
+pub const REDACTED: &str = "<redacted>";
+
+/// Copy of an answer with the strings of `SENSITIVE_FIELDS` redacted, at any depth.
+/// Other values are kept so the snapshot still has the shape of the real answer.
+pub fn sanitize(value: &Value) -> Value {
+    match *value {
+        Value::Object(ref map) => {
+            let mut sanitized = Map::new();
+            for (key, item) in map {
+                let item = match *item {
+                    Value::String(ref text) if !text.is_empty() &&
+                                              SENSITIVE_FIELDS.contains(&key.as_str()) => {
+                        Value::String(REDACTED.to_string())
+                    }
+                    _ => sanitize(item),
+                };
+                sanitized.insert(key.clone(), item);
+            }
+            Value::Object(sanitized)
+        }
+        Value::Array(ref items) => Value::Array(items.iter().map(sanitize).collect()),
+        ref other => other.clone(),
+    }
+}
+
+/// A directory of snapshots, one `<name>.json` file each.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SnapshotDir {
+    dir: PathBuf,
+}
+
+impl SnapshotDir {
+    pub fn new<P: AsRef<Path>>(dir: P) -> SnapshotDir {
+        SnapshotDir { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Call a read-only endpoint and store its sanitized answer under `name`.
+    pub fn record<E: ApiEndpoint>(&self,
+                               client: &OVHClient,
+                               name: &str,
+                               endpoint: &E)
+                               -> io::Result<Value> {
+        if E::METHOD != "GET" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("cannot snapshot {} {}", E::METHOD, E::PATH)));
+        }
+        let value = sanitize(&rest::get(client, &endpoint.path()?)?);
+        self.save(name, &value)?;
+        Ok(value)
+    }
+
+    /// Write a snapshot atomically.
+    pub fn save(&self, name: &str, value: &Value) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(name);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let content = serde_json::to_string_pretty(value)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        File::create(&tmp)?.write_all(content.as_bytes())?;
+        fs::rename(&tmp, &path)
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Value> {
+        let mut content = String::new();
+        File::open(self.path(name))?.read_to_string(&mut content)?;
+        serde_json::from_str(&content).map_err(|e| rest::invalid_data(&format!("{}: {}", name, e)))
+    }
+
+    /// Names of the stored snapshots.
+    pub fn names(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Decode a snapshot with the endpoint, as its live answer would be.
+    pub fn check<E: ApiEndpoint>(&self, name: &str) -> io::Result<E::Response> {
+        E::parse_response(&self.load(name)?)
+            .map_err(|e| rest::invalid_data(&format!("snapshot {} no longer decodes: {}", name, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize, SnapshotDir, REDACTED};
+    use auth::ApiCredential;
+    use dedicated::Server;
+    use dns::Record;
+    use rest::{self, Model};
+    use serde_json::{self, Value};
+    use services::Service;
+    use testutil;
+    use std::fs;
+    use std::io;
+
+    /// Decode a snapshot into a model of the crate, failing on fields the model does not know.
+    fn check_model<T: Model>(content: &str) -> io::Result<T> {
+        let value: Value = serde_json::from_str(content).unwrap();
+        let unknown = rest::unknown_fields(&value, T::FIELDS);
+        if !unknown.is_empty() {
+            return Err(rest::invalid_data(&format!("unknown fields in {}: {}",
+                                                   T::NAME,
+                                                   unknown.join(", "))));
+        }
+        T::from_json(&value)
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let value = sanitize(&json!({
+            "name": "ns1",
+            "contacts": [{"email": "john@example.com", "phone": ""}],
+            "ip": null,
+        }));
+        assert_eq!(json!({"name": "ns1", "contacts": [{"email": REDACTED, "phone": ""}], "ip": null}),
+                   value);
+    }
+
+    #[test]
+    fn test_snapshot_dir() {
+        let dir = SnapshotDir::new(testutil::temp_path("ovh-rs-test-snapshots"));
+        dir.save("me", &json!({"nichandle": "xx1-ovh"})).unwrap();
+        assert_eq!(vec!["me".to_string()], dir.names().unwrap());
+        assert_eq!(json!({"nichandle": "xx1-ovh"}), dir.load("me").unwrap());
+        fs::remove_file(dir.path("me")).unwrap();
+    }
+
+    #[test]
+    fn test_model_snapshots() {
+        check_model::<Record>(include_str!("../snapshots/dns_record.json")).unwrap();
+        check_model::<Server>(include_str!("../snapshots/dedicated_server.json")).unwrap();
+        check_model::<Service>(include_str!("../snapshots/service.json")).unwrap();
+        check_model::<ApiCredential>(include_str!("../snapshots/api_credential.json")).unwrap();
+        assert!(check_model::<Record>(r#"{"id": 1, "extra": true}"#).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! # SSH
+//!
+//! Account-wide SSH key distribution: make sure a public key is present
+//! (or absent) in every Public Cloud project and in the rescue settings
+//! of every dedicated server, for engineers onboarding and offboarding.
+//!
+
+use client::OVHClient;
+use cloud::{self, sshkey};
+use dedicated;
+
+use std::fmt;
+use std::io;
+
+/// Place where a key is installed.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum KeyLocation {
+    /// Project-level key of a Public Cloud project, by project id.
+    CloudProject(String),
+    /// Rescue mode key of a dedicated server, by server name.
+    DedicatedRescue(String),
+}
+
+impl fmt::Display for KeyLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyLocation::CloudProject(ref id) => write!(f, "cloud project {}", id),
+            KeyLocation::DedicatedRescue(ref name) => write!(f, "rescue of dedicated server {}", name),
+        }
+    }
+}
+
+/// Where a key was changed, found already in place, or could not be handled.
+#[derive(Debug,Default)]
+pub struct DistributionReport {
+    pub changed: Vec<KeyLocation>,
+    pub unchanged: Vec<KeyLocation>,
+    /// Rescue settings already holding another key, left untouched.
+    pub skipped: Vec<KeyLocation>,
+    pub failed: Vec<(KeyLocation, io::Error)>,
+}
+
+/// Key type and material, without the trailing comment.
+pub fn normalize_key(public_key: &str) -> String {
+    public_key.split_whitespace().take(2).collect::<Vec<&str>>().join(" ")
+}
+
+fn same_key(a: &str, b: &str) -> bool {
+    normalize_key(a) == normalize_key(b)
+}
+
+/// Distribute or revoke a public key across the account.
+pub struct KeyDistributor<'a> {
+    client: &'a OVHClient,
+    cloud: bool,
+    dedicated: bool,
+    overwrite_rescue_key: bool,
+}
+
+impl<'a> KeyDistributor<'a> {
+    /// Distributor covering cloud projects and dedicated servers,
+    /// never replacing an existing rescue key.
+    pub fn new(client: &'a OVHClient) -> KeyDistributor<'a> {
+        KeyDistributor {
+            client,
+            cloud: true,
+            dedicated: true,
+            overwrite_rescue_key: false,
+        }
+    }
+
+    pub fn set_cloud(&mut self, enabled: bool) {
+        self.cloud = enabled;
+    }
+
+    pub fn set_dedicated(&mut self, enabled: bool) {
+        self.dedicated = enabled;
+    }
+
+    /// Servers hold a single rescue key: replace it even if it belongs to someone else.
+    pub fn set_overwrite_rescue_key(&mut self, overwrite: bool) {
+        self.overwrite_rescue_key = overwrite;
+    }
+
+    /// Ensure `public_key` is present everywhere, registered as `name` in cloud projects.
+    pub fn ensure_present(&self, name: &str, public_key: &str) -> io::Result<DistributionReport> {
+        let mut report = DistributionReport::default();
+        if self.cloud {
+            for project_id in cloud::project_ids(self.client)? {
+                let location = KeyLocation::CloudProject(project_id.clone());
+                let res = sshkey::ssh_keys(self.client, &project_id).and_then(|keys| {
+                    if keys.iter().any(|key| same_key(&key.public_key, public_key)) {
+                        Ok(false)
+                    } else {
+                        sshkey::create_ssh_key(self.client, &project_id, name, public_key)
+                            .map(|_| true)
+                    }
+                });
+                record(&mut report, location, res);
+            }
+        }
+        if self.dedicated {
+            for server_name in dedicated::server_names(self.client)? {
+                let location = KeyLocation::DedicatedRescue(server_name.clone());
+                let server = match dedicated::server(self.client, &server_name) {
+                    Ok(server) => server,
+                    Err(err) => {
+                        report.failed.push((location, err));
+                        continue;
+                    }
+                };
+                match server.rescue_ssh_key {
+                    Some(ref key) if same_key(key, public_key) => {
+                        report.unchanged.push(location);
+                        continue;
+                    }
+                    Some(_) if !self.overwrite_rescue_key => {
+                        report.skipped.push(location);
+                        continue;
+                    }
+                    _ => {}
+                }
+                let res = dedicated::set_rescue_ssh_key(self.client, &server_name, public_key)
+                    .map(|_| true);
+                record(&mut report, location, res);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Remove `public_key` from every project and rescue setting holding it.
+    pub fn ensure_absent(&self, public_key: &str) -> io::Result<DistributionReport> {
+        let mut report = DistributionReport::default();
+        if self.cloud {
+            for project_id in cloud::project_ids(self.client)? {
+                let location = KeyLocation::CloudProject(project_id.clone());
+                let res = sshkey::ssh_keys(self.client, &project_id).and_then(|keys| {
+                    let mut removed = false;
+                    for key in keys.iter().filter(|key| same_key(&key.public_key, public_key)) {
+                        sshkey::delete_ssh_key(self.client, &project_id, &key.id)?;
+                        removed = true;
+                    }
+                    Ok(removed)
+                });
+                record(&mut report, location, res);
+            }
+        }
+        if self.dedicated {
+            for server_name in dedicated::server_names(self.client)? {
+                let location = KeyLocation::DedicatedRescue(server_name.clone());
+                let res = dedicated::server(self.client, &server_name).and_then(|server| {
+                    match server.rescue_ssh_key {
+                        Some(ref key) if same_key(key, public_key) => {
+                            dedicated::set_rescue_ssh_key(self.client, &server_name, "")
+                                .map(|_| true)
+                        }
+                        _ => Ok(false),
+                    }
+                });
+                record(&mut report, location, res);
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn record(report: &mut DistributionReport, location: KeyLocation, res: io::Result<bool>) {
+    match res {
+        Ok(true) => {
+            info!("SSH key updated on {}", location);
+            report.changed.push(location)
+        }
+        Ok(false) => report.unchanged.push(location),
+        Err(err) => {
+            warn!("Cannot update SSH key on {}: {}", location, err);
+            report.failed.push((location, err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_key;
+
+    #[test]
+    fn test_normalize_key() {
+        assert_eq!("ssh-ed25519 AAAAC3Nza",
+                   normalize_key("ssh-ed25519   AAAAC3Nza jdoe@laptop\n"));
+        assert_eq!(normalize_key("ssh-rsa AAAAB3 alice"),
+                   normalize_key("ssh-rsa AAAAB3 alice@work"));
+    }
+}
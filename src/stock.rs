@@ -0,0 +1,179 @@
+//! # Stock
+//!
+//! Poll dedicated server availabilities and cloud flavor capacity
+//! for the wanted configurations, and fire a callback when stock appears.
+//!
+
+use client::OVHClient;
+use cloud::flavor;
+use dedicated::availability;
+
+use std::collections::HashSet;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+
+/// A configuration to watch.
+#[derive(Debug,Clone,PartialEq)]
+pub enum StockTarget {
+    /// A dedicated server plan, in any of `datacenters` (all if empty).
+    Server {
+        plan_code: String,
+        datacenters: Vec<String>,
+    },
+    /// A cloud flavor of a project, by name, in any of `regions` (all if empty).
+    Flavor {
+        project_id: String,
+        name: String,
+        regions: Vec<String>,
+    },
+}
+
+/// Stock which just became available.
+#[derive(Debug,Clone,PartialEq)]
+pub enum StockEvent {
+    Server {
+        plan_code: String,
+        fqn: String,
+        datacenter: String,
+        availability: String,
+    },
+    Flavor {
+        project_id: String,
+        flavor_id: String,
+        name: String,
+        region: String,
+    },
+}
+
+impl StockEvent {
+    fn key(&self) -> String {
+        match *self {
+            StockEvent::Server { ref fqn, ref datacenter, .. } => format!("server:{}@{}", fqn, datacenter),
+            StockEvent::Flavor { ref project_id, ref flavor_id, .. } => {
+                format!("flavor:{}/{}", project_id, flavor_id)
+            }
+        }
+    }
+}
+
+fn accepts(wanted: &[String], location: &str) -> bool {
+    wanted.is_empty() || wanted.iter().any(|w| w.eq_ignore_ascii_case(location))
+}
+
+/// Watch targets and report the stock appearing between two polls.
+pub struct StockWatcher<'a> {
+    client: &'a OVHClient,
+    targets: Vec<StockTarget>,
+    interval: Duration,
+    available: HashSet<String>,
+}
+
+impl<'a> StockWatcher<'a> {
+    pub fn new(client: &'a OVHClient, targets: Vec<StockTarget>) -> StockWatcher<'a> {
+        StockWatcher {
+            client,
+            targets,
+            interval: Duration::from_secs(DEFAULT_INTERVAL_SECS),
+            available: HashSet::new(),
+        }
+    }
+
+    /// Delay between two polls of `watch`.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Stock currently available for the targets.
+    pub fn current(&self) -> io::Result<Vec<StockEvent>> {
+        let mut events = Vec::new();
+        for target in &self.targets {
+            match *target {
+                StockTarget::Server { ref plan_code, ref datacenters } => {
+                    for server in availability::availabilities(self.client, Some(plan_code))? {
+                        for dc in server.datacenters.iter().filter(|dc| dc.is_available()) {
+                            if accepts(datacenters, &dc.datacenter) {
+                                events.push(StockEvent::Server {
+                                    plan_code: server.plan_code.clone(),
+                                    fqn: server.fqn.clone(),
+                                    datacenter: dc.datacenter.clone(),
+                                    availability: dc.availability.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                StockTarget::Flavor { ref project_id, ref name, ref regions } => {
+                    for flavor in flavor::flavors(self.client, project_id, None)? {
+                        if flavor.available && flavor.name == *name && accepts(regions, &flavor.region) {
+                            events.push(StockEvent::Flavor {
+                                project_id: project_id.clone(),
+                                flavor_id: flavor.id,
+                                name: flavor.name,
+                                region: flavor.region,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Poll once and return the stock which was not available at the previous poll.
+    /// Everything available is reported on the first poll.
+    pub fn poll(&mut self) -> io::Result<Vec<StockEvent>> {
+        let current = self.current()?;
+        let keys: HashSet<String> = current.iter().map(StockEvent::key).collect();
+        let appeared = current.into_iter()
+            .filter(|event| !self.available.contains(&event.key()))
+            .collect();
+        self.available = keys;
+        Ok(appeared)
+    }
+
+    /// Poll until `callback` returns `false`, calling it for every stock appearance.
+    /// Polling errors are logged and the watch goes on.
+    pub fn watch<F>(&mut self, mut callback: F)
+        where F: FnMut(&StockEvent) -> bool
+    {
+        loop {
+            match self.poll() {
+                Ok(events) => {
+                    for event in &events {
+                        if !callback(event) {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => warn!("Cannot poll stock: {}", err),
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts, StockEvent};
+
+    #[test]
+    fn test_accepts() {
+        assert!(accepts(&[], "gra"));
+        assert!(accepts(&["GRA".to_string(), "rbx".to_string()], "gra"));
+        assert!(!accepts(&["bhs".to_string()], "gra"));
+    }
+
+    #[test]
+    fn test_event_key() {
+        let event = StockEvent::Server {
+            plan_code: "24sk10".to_string(),
+            fqn: "24sk10.ram-16g".to_string(),
+            datacenter: "rbx".to_string(),
+            availability: "72H".to_string(),
+        };
+        assert_eq!("server:24sk10.ram-16g@rbx", event.key());
+    }
+}
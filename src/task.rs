@@ -0,0 +1,214 @@
+//! # Task
+//!
+//! Resumable polling of asynchronous OVH tasks.
+//!
+//! A `TaskHandle` names a task by product family, service and id. It can be
+//! saved as a token (`domain:example.com:42`) or a JSON document, so a process
+//! restarted while a task is running can keep waiting on it. Polling keeps no
+//! other state: a watcher that times out or is interrupted loses nothing.
+//!
+
+use client::OVHClient;
+use domain::{Task, TaskStatus};
+use progress::{Progress, ProgressEvent};
+use rest;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+/// Product family of a task, which gives its route.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum TaskFamily {
+    /// `/domain/{domain}/task/{id}`
+    Domain,
+    /// `/domain/zone/{zone}/task/{id}`
+    DnsZone,
+    /// `/dedicated/server/{server}/task/{id}`
+    DedicatedServer,
+    Other(String),
+}
+
+impl TaskFamily {
+    pub fn parse(family: &str) -> TaskFamily {
+        match family {
+            "domain" => TaskFamily::Domain,
+            "dnsZone" => TaskFamily::DnsZone,
+            "dedicatedServer" => TaskFamily::DedicatedServer,
+            other => TaskFamily::Other(other.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            TaskFamily::Domain => "domain",
+            TaskFamily::DnsZone => "dnsZone",
+            TaskFamily::DedicatedServer => "dedicatedServer",
+            TaskFamily::Other(ref other) => other,
+        }
+    }
+
+    fn prefix(&self) -> io::Result<&'static str> {
+        match *self {
+            TaskFamily::Domain => Ok("/domain"),
+            TaskFamily::DnsZone => Ok("/domain/zone"),
+            TaskFamily::DedicatedServer => Ok("/dedicated/server"),
+            TaskFamily::Other(ref other) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                   format!("unknown task family {}", other)))
+            }
+        }
+    }
+}
+
+/// A serializable reference to a running task.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TaskHandle {
+    pub family: TaskFamily,
+    /// Service the task runs on: domain name, zone name, server name.
+    pub service: String,
+    pub id: u64,
+}
+
+impl TaskHandle {
+    pub fn new(family: TaskFamily, service: &str, id: u64) -> TaskHandle {
+        TaskHandle {
+            family,
+            service: service.to_string(),
+            id,
+        }
+    }
+
+    /// Route of the task.
+    pub fn path(&self) -> io::Result<String> {
+        Ok(format!("{}/{}/task/{}", self.family.prefix()?, rest::escape(&self.service), self.id))
+    }
+
+    /// The handle as a `family:service:id` token.
+    pub fn to_token(&self) -> String {
+        format!("{}:{}:{}", self.family.name(), self.service, self.id)
+    }
+
+    pub fn from_token(token: &str) -> io::Result<TaskHandle> {
+        let invalid = || {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("bad task token {}", token))
+        };
+        let first = token.find(':').ok_or_else(invalid)?;
+        let last = token.rfind(':').ok_or_else(invalid)?;
+        if first == last {
+            return Err(invalid());
+        }
+        let id = token[last + 1..].parse().map_err(|_| invalid())?;
+        Ok(TaskHandle::new(TaskFamily::parse(&token[..first]), &token[first + 1..last], id))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "family": self.family.name(),
+            "service": self.service,
+            "id": self.id,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> io::Result<TaskHandle> {
+        Ok(TaskHandle::new(TaskFamily::parse(&rest::str_field(value, "family")?),
+                           &rest::str_field(value, "service")?,
+                           rest::u64_field(value, "id")?))
+    }
+
+    /// Write the handle atomically, to resume waiting after a restart.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.to_path_buf().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        File::create(&tmp)?.write_all(self.to_json().to_string().as_bytes())?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<TaskHandle> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| rest::invalid_data(&e.to_string()))?;
+        TaskHandle::from_json(&value)
+    }
+}
+
+/// Current state of a task.
+pub fn status(client: &OVHClient, handle: &TaskHandle) -> io::Result<Task> {
+    rest::decode(client, &rest::get(client, &handle.path()?)?)
+}
+
+/// Poll a task every `interval` until it is over, reporting its status changes.
+/// After `timeout` the error carries the token of the task, to resume later.
+pub fn watch(client: &OVHClient,
+             handle: &TaskHandle,
+             interval: Duration,
+             timeout: Duration,
+             progress: &Progress)
+             -> io::Result<Task> {
+    let operation = format!("task {}", handle.to_token());
+    progress.emit(ProgressEvent::Started {
+        operation: operation.clone(),
+        total: None,
+    });
+    let started = Instant::now();
+    let mut last: Option<TaskStatus> = None;
+    loop {
+        let current = status(client, handle)?;
+        if last.as_ref() != Some(&current.status) {
+            progress.emit(ProgressEvent::Status {
+                operation: operation.clone(),
+                status: format!("{:?}", current.status),
+            });
+        }
+        if current.status.is_final() {
+            progress.emit(ProgressEvent::Finished { operation });
+            return Ok(current);
+        }
+        if started.elapsed() >= timeout {
+            let err = format!("task {} still {:?}, resume with token {}",
+                              handle.id,
+                              current.status,
+                              handle.to_token());
+            progress.emit(ProgressEvent::Failed {
+                operation,
+                error: err.clone(),
+            });
+            return Err(io::Error::new(io::ErrorKind::TimedOut, err));
+        }
+        last = Some(current.status);
+        progress.emit(ProgressEvent::Waiting {
+            operation: operation.clone(),
+            seconds: interval.as_secs(),
+        });
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TaskFamily, TaskHandle};
+    use testutil;
+    use std::fs;
+
+    #[test]
+    fn test_handle_roundtrip() {
+        let handle = TaskHandle::new(TaskFamily::DedicatedServer, "ns1.ip-1-2-3.eu", 42);
+        assert_eq!("dedicatedServer:ns1.ip-1-2-3.eu:42", handle.to_token());
+        assert_eq!(handle, TaskHandle::from_token(&handle.to_token()).unwrap());
+        assert_eq!("/dedicated/server/ns1.ip-1-2-3.eu/task/42", handle.path().unwrap());
+        assert!(TaskHandle::from_token("domain:42").is_err());
+        assert!(TaskHandle::new(TaskFamily::parse("vps"), "vps1", 1).path().is_err());
+
+        let path = testutil::temp_path("ovh-rs-test-task.json");
+        handle.save(&path).unwrap();
+        assert_eq!(handle, TaskHandle::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
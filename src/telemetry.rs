@@ -0,0 +1,287 @@
+//! # Telemetry
+//!
+//! Request counts, latencies and error rates of a client: a `MetricsSink`
+//! sees every request sent to the API, retries included, with its method, its
+//! route template, its status and how long it took. Operators wire their
+//! Prometheus counters and histograms there instead of around every call.
+//!
+//! ```no_run
+//! # fn run() -> std::io::Result<()> {
+//! use ovh::OVHClient;
+//! use ovh::telemetry::Sample;
+//!
+//! let client = OVHClient::builder()
+//!     .metrics_sink(|sample: &Sample| {
+//!         println!("{} {} {} {}ms",
+//!                  sample.method,
+//!                  sample.route,
+//!                  sample.status_label(),
+//!                  sample.elapsed.as_millis());
+//!     })
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The route template replaces the segments of the path which look like
+//! identifiers (numbers, names with dots, dashes or digits, hashes, ...) with
+//! `{id}`, to keep the cardinality of the labels low:
+//! `/domain/zone/example.com/record/42` becomes `/domain/zone/{id}/record/{id}`.
+//!
+//! A request slower than the latency budget of its client
+//! (`OVHClient::with_latency_budget`) is also reported as a `SlowCall`: to the
+//! sinks, as a warning in the logs, and as a `tracing` event with the
+//! `tracing` feature. The budget applies to each attempt of a retried call.
+//!
+
+use client::RawResponse;
+use error;
+use instrument;
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One request sent to the API.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Sample {
+    pub method: String,
+    /// The path with its identifiers replaced, see `route_template`.
+    pub route: String,
+    /// Path and query string, as sent.
+    pub query: String,
+    /// Status of the answer, none when no answer came.
+    pub status: Option<u16>,
+    /// `X-Ovh-QueryId` of the answer, to quote to the OVH support.
+    pub query_id: Option<String>,
+    pub elapsed: Duration,
+    /// 1 for the first attempt, more for retries.
+    pub attempt: u32,
+}
+
+impl Sample {
+    /// Whether the request failed: an error status, or no answer.
+    pub fn is_error(&self) -> bool {
+        self.status.is_none_or(|status| status >= 400)
+    }
+
+    /// The status as a label, `none` when no answer came.
+    pub fn status_label(&self) -> String {
+        self.status.map_or_else(|| "none".to_string(), |status| status.to_string())
+    }
+}
+
+/// A request slower than its latency budget.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SlowCall {
+    pub sample: Sample,
+    pub budget: Duration,
+}
+
+impl fmt::Display for SlowCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "slow call {} {} ({}): {}ms for a budget of {}ms",
+               self.sample.method,
+               self.sample.query,
+               self.sample.route,
+               self.sample.elapsed.as_millis(),
+               self.budget.as_millis())?;
+        match self.sample.query_id {
+            Some(ref id) => write!(f, " [query id {}]", id),
+            None => Ok(()),
+        }
+    }
+}
+
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, sample: &Sample);
+
+    /// Observe a request slower than its latency budget, after its `record`.
+    fn slow_call(&self, _report: &SlowCall) {}
+}
+
+impl<F> MetricsSink for F
+    where F: Fn(&Sample) + Send + Sync
+{
+    fn record(&self, sample: &Sample) {
+        self(sample)
+    }
+}
+
+/// Whether a segment of a path is an identifier rather than a part of the route.
+fn is_identifier(segment: &str) -> bool {
+    let starts_lowercase = segment.starts_with(|c: char| c.is_ascii_lowercase());
+    let digits = segment.chars().filter(|c| c.is_ascii_digit()).count();
+    let alphanumeric = segment.chars().all(|c| c.is_ascii_alphanumeric());
+    // Route segments are camelCase words, at most followed by a version
+    // digit (`oauth2`, `ipv6`).
+    let trailing_digit = digits == 0 ||
+                         digits == 1 && segment.ends_with(|c: char| c.is_ascii_digit());
+    !(starts_lowercase && alphanumeric && trailing_digit)
+}
+
+/// The route of a query: its path, identifiers replaced with `{id}`.
+pub fn route_template(query: &str) -> String {
+    let path = query.split('?').next().unwrap_or(query);
+    path.split('/')
+        .map(|segment| if segment.is_empty() || !is_identifier(segment) {
+            segment
+        } else {
+            "{id}"
+        })
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+/// The metrics sinks of a client.
+#[derive(Clone,Default)]
+pub struct Telemetry {
+    sinks: Vec<Arc<dyn MetricsSink>>,
+}
+
+impl fmt::Debug for Telemetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Telemetry({} sinks)", self.sinks.len())
+    }
+}
+
+impl Telemetry {
+    pub fn push<S: MetricsSink + 'static>(&mut self, sink: S) {
+        self.sinks.push(Arc::new(sink));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Give a request to every sink, and report it when slower than `budget`.
+    pub(crate) fn record(&self,
+                         method: &str,
+                         query: &str,
+                         attempt: u32,
+                         answer: &io::Result<RawResponse>,
+                         elapsed: Duration,
+                         budget: Option<Duration>) {
+        let slow = budget.is_some_and(|budget| elapsed > budget);
+        if self.sinks.is_empty() && !slow {
+            return;
+        }
+        let (status, query_id) = match *answer {
+            Ok(ref res) => (Some(res.status), res.header("X-Ovh-QueryId").map(String::from)),
+            Err(ref err) => {
+                match error::api_error(err) {
+                    Some(api) => (Some(api.http_code), api.query_id.clone()),
+                    None => (None, None),
+                }
+            }
+        };
+        let sample = Sample {
+            method: method.to_string(),
+            route: route_template(query),
+            query: query.to_string(),
+            status,
+            query_id,
+            elapsed,
+            attempt,
+        };
+        for sink in &self.sinks {
+            sink.record(&sample);
+        }
+        if let (true, Some(budget)) = (slow, budget) {
+            let report = SlowCall {
+                sample,
+                budget,
+            };
+            warn!("{}", report);
+            instrument::slow_call(&report);
+            for sink in &self.sinks {
+                sink.slow_call(&report);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{route_template, MetricsSink, Sample, SlowCall, Telemetry};
+    use client::RawResponse;
+    use error::{ApiError, Error};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_route_template() {
+        assert_eq!("/domain/zone/{id}/record/{id}",
+                   route_template("/domain/zone/example.com/record/42"));
+        assert_eq!("/cloud/project/{id}/kube/{id}/node",
+                   route_template("/cloud/project/0123456789abcdef0123456789abcdef/kube/\
+                                   5e3c0fd6-1d0b-4b3a-9c1f-8f7e2a9b4c21/node"));
+        assert_eq!("/ip/{id}/mitigationProfiles",
+                   route_template("/ip/192.0.2.0%2F24/mitigationProfiles"));
+        assert_eq!("/me/bill/{id}", route_template("/me/bill/FR12345678?foo=bar"));
+        assert_eq!("/me/api/oauth2/client", route_template("/me/api/oauth2/client"));
+        assert_eq!("/telephony/{id}/service", route_template("/telephony/ab12345-ovh-1/service"));
+    }
+
+    #[test]
+    fn test_record() {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let mut telemetry = Telemetry::default();
+        let ok = Ok(RawResponse {
+            status: 200,
+            headers: vec![("X-Ovh-QueryId".to_string(), "EU.ext-1.5f2b".to_string())],
+            body: "{}".to_string(),
+        });
+        telemetry.record("GET", "/me", 1, &ok, Duration::from_millis(5), None);
+        let seen = samples.clone();
+        telemetry.push(move |sample: &Sample| seen.lock().unwrap().push(sample.clone()));
+        let timeout = Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        telemetry.record("DELETE",
+                         "/vps/vps-1234.vps.ovh.net",
+                         2,
+                         &timeout,
+                         Duration::from_millis(7),
+                         None);
+        let samples = samples.lock().unwrap();
+        assert_eq!(1, samples.len());
+        assert_eq!(("/vps/{id}", 2), (samples[0].route.as_str(), samples[0].attempt));
+        assert!(samples[0].is_error());
+        assert_eq!("none", samples[0].status_label());
+    }
+
+    struct SlowCalls(Arc<Mutex<Vec<SlowCall>>>);
+
+    impl MetricsSink for SlowCalls {
+        fn record(&self, _: &Sample) {}
+
+        fn slow_call(&self, report: &SlowCall) {
+            self.0.lock().unwrap().push(report.clone());
+        }
+    }
+
+    #[test]
+    fn test_slow_call() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut telemetry = Telemetry::default();
+        telemetry.push(SlowCalls(reports.clone()));
+        let budget = Some(Duration::from_millis(500));
+        let mut api = ApiError::from_response("GET", "/dedicated/server/ns1.example.net", 404, "");
+        api.query_id = Some("EU.ext-2.61a0".to_string());
+        let not_found = Err(io::Error::from(Error::Api(Box::new(api))));
+        let fast = Duration::from_millis(120);
+        telemetry.record("GET", "/dedicated/server/ns1.example.net", 1, &not_found, fast, budget);
+        assert!(reports.lock().unwrap().is_empty());
+
+        let slow = Duration::from_millis(1500);
+        telemetry.record("GET", "/dedicated/server/ns1.example.net", 1, &not_found, slow, budget);
+        let reports = reports.lock().unwrap();
+        assert_eq!(1, reports.len());
+        assert_eq!(Some(404), reports[0].sample.status);
+        assert_eq!("slow call GET /dedicated/server/ns1.example.net (/dedicated/server/{id}): \
+                    1500ms for a budget of 500ms [query id EU.ext-2.61a0]",
+                   reports[0].to_string());
+    }
+}
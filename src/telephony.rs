@@ -0,0 +1,290 @@
+//! # Telephony
+//!
+//! Call detail records of VoIP lines
+//! (`/telephony/{billingAccount}/service/{serviceName}/voiceConsumption`)
+//! over a date range, and their export as CSV for billing reconciliation.
+//!
+//! Fax campaigns (`/telephony/{billingAccount}/fax/{serviceName}/campaigns`)
+//! send an uploaded document to a list of numbers, now or at a given date.
+//! SMS campaigns are in the `sms` module.
+//!
+
+use client::OVHClient;
+use me::Price;
+use pagination::{self, PageCursor, Paginator};
+use rest::{self, Model};
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, Local, NaiveDate};
+
+use serde_json::Value;
+
+/// A call of a line.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CallRecord {
+    pub id: u64,
+    pub date: String,
+    pub calling: String,
+    pub called: String,
+    /// `landline`, `mobile`, `international`, `special`, ...
+    pub destination_type: Option<String>,
+    /// Duration in seconds.
+    pub duration: u64,
+    /// `incoming`, `outgoing`, `transfer`, ...
+    pub way_type: Option<String>,
+    pub plan_type: Option<String>,
+    pub designation: Option<String>,
+    pub price_without_tax: Option<Price>,
+}
+
+impl Model for CallRecord {
+    const NAME: &'static str = "CallRecord";
+    const FIELDS: &'static [&'static str] = &["consumptionId", "creationDatetime", "calling",
+                                              "called", "dialed", "destinationType", "duration",
+                                              "wayType", "planType", "designation",
+                                              "priceWithoutTax"];
+
+    fn from_json(value: &Value) -> io::Result<CallRecord> {
+        Ok(CallRecord {
+            id: rest::u64_field(value, "consumptionId")?,
+            date: rest::str_field(value, "creationDatetime")?,
+            calling: rest::opt_str_field(value, "calling").unwrap_or_default(),
+            called: rest::opt_str_field(value, "called").unwrap_or_default(),
+            destination_type: rest::opt_str_field(value, "destinationType"),
+            duration: rest::opt_u64_field(value, "duration").unwrap_or(0),
+            way_type: rest::opt_str_field(value, "wayType"),
+            plan_type: rest::opt_str_field(value, "planType"),
+            designation: rest::opt_str_field(value, "designation"),
+            price_without_tax: Price::field(value, "priceWithoutTax")?,
+        })
+    }
+}
+
+/// Billing accounts of the account (`ab12345-ovh-1`, ...).
+pub fn billing_accounts(client: &OVHClient) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client, "/telephony")?)
+}
+
+/// Services (lines, numbers) of a billing account.
+pub fn services(client: &OVHClient, billing_account: &str) -> io::Result<Vec<String>> {
+    rest::string_list(&rest::get(client,
+                                 &format!("/telephony/{}/service", rest::escape(billing_account)))?)
+}
+
+fn consumption_path(billing_account: &str, service: &str) -> String {
+    format!("/telephony/{}/service/{}/voiceConsumption",
+            rest::escape(billing_account),
+            rest::escape(service))
+}
+
+/// Ids of the calls of a line made from `from` (included) to `to` (excluded).
+pub fn call_ids(client: &OVHClient,
+                billing_account: &str,
+                service: &str,
+                from: NaiveDate,
+                to: NaiveDate)
+                -> io::Result<Vec<u64>> {
+    let path = format!("{}?creationDatetime.from={}&creationDatetime.to={}",
+                       consumption_path(billing_account, service),
+                       from.format("%Y-%m-%d"),
+                       to.format("%Y-%m-%d"));
+    rest::u64_list(&rest::get(client, &path)?)
+}
+
+pub fn call(client: &OVHClient,
+            billing_account: &str,
+            service: &str,
+            id: u64)
+            -> io::Result<CallRecord> {
+    let path = format!("{}/{}", consumption_path(billing_account, service), id);
+    rest::decode(client, &rest::get(client, &path)?)
+}
+
+/// Crawl of the calls of a range; its cursor can be saved to resume a long export.
+pub fn call_paginator<'a>(client: &'a OVHClient,
+                          billing_account: &str,
+                          service: &str,
+                          from: NaiveDate,
+                          to: NaiveDate)
+                          -> io::Result<Paginator<'a>> {
+    let ids = call_ids(client, billing_account, service, from, to)?;
+    Ok(Paginator::resume(client,
+                         PageCursor {
+                             path: consumption_path(billing_account, service),
+                             remaining: ids.into_iter().map(|id| id.to_string()).collect(),
+                             fetched: 0,
+                         }))
+}
+
+/// Fetch the calls of a range lazily.
+pub fn iter_calls<'a>(client: &'a OVHClient,
+                      billing_account: &str,
+                      service: &str,
+                      from: NaiveDate,
+                      to: NaiveDate)
+                      -> io::Result<impl Iterator<Item = io::Result<CallRecord>> + 'a> {
+    Ok(pagination::decoded(call_paginator(client, billing_account, service, from, to)?))
+}
+
+/// Columns of the CSV export.
+pub const CSV_HEADER: &str = "id,date,calling,called,destination_type,duration,way_type,\
+                                      plan_type,designation,price_without_tax,currency";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl CallRecord {
+    /// The record as a CSV line, without line ending, in the order of `CSV_HEADER`.
+    pub fn to_csv(&self) -> String {
+        let (price, currency) = match self.price_without_tax {
+            Some(ref price) => (price.value.to_string(), price.currency_code.clone()),
+            None => (String::new(), String::new()),
+        };
+        let fields = [self.id.to_string(),
+                      self.date.clone(),
+                      self.calling.clone(),
+                      self.called.clone(),
+                      self.destination_type.clone().unwrap_or_default(),
+                      self.duration.to_string(),
+                      self.way_type.clone().unwrap_or_default(),
+                      self.plan_type.clone().unwrap_or_default(),
+                      self.designation.clone().unwrap_or_default(),
+                      price,
+                      currency];
+        fields.iter().map(|field| csv_field(field)).collect::<Vec<String>>().join(",")
+    }
+}
+
+/// Write the calls of a range as CSV, streaming them as they are fetched.
+/// Returns the number of calls written.
+pub fn export_csv<W: Write>(client: &OVHClient,
+                            billing_account: &str,
+                            service: &str,
+                            from: NaiveDate,
+                            to: NaiveDate,
+                            out: &mut W)
+                            -> io::Result<usize> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    let mut count = 0;
+    for call in iter_calls(client, billing_account, service, from, to)? {
+        writeln!(out, "{}", call?.to_csv())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn campaigns_path(billing_account: &str, fax: &str) -> String {
+    format!("/telephony/{}/fax/{}/campaigns",
+            rest::escape(billing_account),
+            rest::escape(fax))
+}
+
+/// Create a fax campaign sending `document_id` (a `/me/document` id) to `recipients`,
+/// at `send_at` or as soon as started. Returns the campaign id.
+pub fn create_fax_campaign(client: &OVHClient,
+                           billing_account: &str,
+                           fax: &str,
+                           name: &str,
+                           document_id: &str,
+                           recipients: &[String],
+                           send_at: Option<DateTime<Local>>)
+                           -> io::Result<u64> {
+    let body = match send_at {
+        Some(send_at) => {
+            json!({
+                "name": name,
+                "documentId": document_id,
+                "recipientsType": "list",
+                "recipientsList": recipients,
+                "sendType": "scheduled",
+                "sendDate": send_at.to_rfc3339(),
+            })
+        }
+        None => {
+            json!({
+                "name": name,
+                "documentId": document_id,
+                "recipientsType": "list",
+                "recipientsList": recipients,
+                "sendType": "now",
+            })
+        }
+    };
+    let value = rest::post(client, &campaigns_path(billing_account, fax), &body)?;
+    rest::u64_field(&value, "id")
+}
+
+/// Start a campaign created without a send date.
+pub fn start_fax_campaign(client: &OVHClient,
+                          billing_account: &str,
+                          fax: &str,
+                          id: u64)
+                          -> io::Result<()> {
+    rest::call(client,
+               "POST",
+               &format!("{}/{}/start", campaigns_path(billing_account, fax), id),
+               None)?;
+    Ok(())
+}
+
+/// Recipients of a fax campaign by status.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct FaxCampaignStatus {
+    /// `todo`, `doing`, `done`, `error`, `stopped`, ...
+    pub status: String,
+    pub todo: Vec<String>,
+    pub success: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+pub fn fax_campaign_status(client: &OVHClient,
+                           billing_account: &str,
+                           fax: &str,
+                           id: u64)
+                           -> io::Result<FaxCampaignStatus> {
+    let path = format!("{}/{}", campaigns_path(billing_account, fax), id);
+    let campaign = rest::get(client, &path)?;
+    let detail = rest::get(client, &format!("{}/detail", path))?;
+    let list = |key: &str| match detail.get(key) {
+        Some(items) => rest::string_list(items),
+        None => Ok(Vec::new()),
+    };
+    Ok(FaxCampaignStatus {
+        status: rest::str_field(&campaign, "status")?,
+        todo: list("todo")?,
+        success: list("success")?,
+        failed: list("failed")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallRecord, CSV_HEADER};
+    use rest::Model;
+
+    #[test]
+    fn test_call_to_csv() {
+        let call = CallRecord::from_json(&json!({
+            "consumptionId": 7,
+            "creationDatetime": "2026-09-01T10:00:00+02:00",
+            "calling": "0033972101010",
+            "called": "0033612345678",
+            "destinationType": "mobile",
+            "duration": 95,
+            "wayType": "outgoing",
+            "designation": "Mobile, \"France\"",
+            "priceWithoutTax": { "value": 0.12, "currencyCode": "EUR", "text": "0.12 €" },
+        }))
+            .unwrap();
+        assert_eq!("7,2026-09-01T10:00:00+02:00,0033972101010,0033612345678,mobile,95,outgoing,,\
+                    \"Mobile, \"\"France\"\"\",0.12,EUR",
+                   call.to_csv());
+        assert_eq!(CSV_HEADER.split(',').count(), 11);
+    }
+}
@@ -0,0 +1,146 @@
+//! Helpers shared by the tests of the crate.
+
+use client::{remember_time_delta, OVHClient};
+use config::{Credential, Endpoint};
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use std::env;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A path in the temporary directory no other test, nor other test run,
+/// uses: `name` with the process id and a counter before its extension.
+pub fn temp_path(name: &str) -> PathBuf {
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let unique = match name.rfind('.') {
+        Some(dot) => format!("{}-{}-{}{}", &name[..dot], process::id(), n, &name[dot..]),
+        None => format!("{}-{}-{}", name, process::id(), n),
+    };
+    env::temp_dir().join(unique)
+}
+
+/// A client of the API at `host`, e.g. a `serve` address, with a known clock
+/// drift so that it does not ask `/auth/time`.
+pub fn local_client(host: &str) -> OVHClient {
+    remember_time_delta(host, 0);
+    let endpoint = Endpoint::Custom(host.to_string());
+    let mut client =
+        OVHClient::for_tests(Credential::new_with_credential(endpoint, "ak", "as", "ck").unwrap());
+    client.network.plain_http = true;
+    client
+}
+
+/// Run `future` on a tokio runtime, as the futures of `AsyncClient` need.
+#[cfg(feature = "async")]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    ::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// A local HTTP server answering `answers` (status and body) in order, one
+/// connection each. Returns its address, to use as the host of an endpoint,
+/// and the requests it read, once all are answered.
+pub fn serve(answers: Vec<(u16, &'static str)>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, body) in answers {
+            let (mut stream, _) = listener.accept().unwrap();
+            requests.push(read_request(&mut stream));
+            stream.write_all(answer(status, body).as_bytes()).unwrap();
+        }
+        requests
+    });
+    (host, server)
+}
+
+/// The certificate authority of `localhost` for `serve_tls`, as PEM.
+pub const TEST_CA: &str = include_str!("../testdata/ca.pem");
+
+/// The pin of the key of the `serve_tls` certificate, see `pin`.
+pub const LOCALHOST_PIN: &str = "sha256//h4pLJRIPdVZEaXaQh7hmfYqBB8hjoi8mzrwfY3oe9io=";
+
+/// A local HTTPS server for `localhost`, with a certificate signed by
+/// `TEST_CA`, answering `200 {}` to `connections` connections in order; a
+/// refused handshake counts as one, with an empty request.
+pub fn serve_tls(connections: usize) -> (String, thread::JoinHandle<Vec<String>>) {
+    let certificates = CertificateDer::pem_slice_iter(include_bytes!("../testdata/localhost.pem"))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_slice(include_bytes!("../testdata/localhost.key")).unwrap();
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certificates, key)
+        .unwrap();
+    let config = Arc::new(config);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = format!("localhost:{}", listener.local_addr().unwrap().port());
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for _ in 0..connections {
+            let (stream, _) = listener.accept().unwrap();
+            let connection = ServerConnection::new(config.clone()).unwrap();
+            let mut stream = StreamOwned::new(connection, stream);
+            let request = read_request(&mut stream);
+            if !request.is_empty() {
+                let _ = stream.write_all(answer(200, "{}").as_bytes());
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+            requests.push(request);
+        }
+        requests
+    });
+    (host, server)
+}
+
+fn answer(status: u16, body: &str) -> String {
+    format!("HTTP/1.1 {} Answer\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body)
+}
+
+/// Read a request, head and body.
+fn read_request<S: Read>(stream: &mut S) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let head = String::from_utf8_lossy(&request).into_owned();
+        if let Some(end) = head.find("\r\n\r\n") {
+            let length = head[..end]
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|&(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if request.len() >= end + 4 + length {
+                return head;
+            }
+        }
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return String::from_utf8_lossy(&request).into_owned(),
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! # Throttle
+//!
+//! Client-side rate limit: a token bucket refilled at `rate` requests per second,
+//! holding at most `burst` of them. Requests wait for a token before being sent,
+//! so bulk scripts (DNS syncs, inventory dumps) stay under the rate OVH tolerates
+//! instead of being answered `429`.
+//!
+//! Clones of a client share its throttle; `OVH_MAX_RPS` sets one for the clients
+//! made by `OVHClient::new`.
+//!
+//! The limits the API communicates in its answers (`X-RateLimit-*`, `Retry-After`)
+//! are parsed into a `RateLimitInfo`, attached to responses and errors; the throttle
+//! pauses every request until the time the API asked to wait for.
+//!
+
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+/// Rate limit communicated by the API in the headers of an answer.
+#[derive(Debug,Clone,PartialEq)]
+pub struct RateLimitInfo {
+    /// Requests allowed in the current window.
+    pub limit: Option<u64>,
+    /// Requests left in the current window.
+    pub remaining: Option<u64>,
+    /// Time until the window resets.
+    pub reset_after: Option<Duration>,
+    /// Time to wait before sending again, from `Retry-After`.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Parse the `X-RateLimit-*` (or `RateLimit-*`) and `Retry-After` headers,
+    /// `None` when the answer has none of them.
+    pub fn from_headers(headers: &[(String, String)]) -> Option<RateLimitInfo> {
+        let header = |names: &[&str]| {
+            headers.iter()
+                .find(|&(key, _)| names.iter().any(|name| key.eq_ignore_ascii_case(name)))
+                .map(|(_, value)| value.trim())
+        };
+        let number = |names: &[&str]| header(names).and_then(|value| value.parse::<u64>().ok());
+        let now = Local::now().timestamp() as u64;
+        let info = RateLimitInfo {
+            limit: number(&["X-RateLimit-Limit", "RateLimit-Limit"]),
+            remaining: number(&["X-RateLimit-Remaining", "RateLimit-Remaining"]),
+            // either seconds or, when larger than a year, an epoch timestamp
+            reset_after: number(&["X-RateLimit-Reset", "RateLimit-Reset"]).map(|reset| {
+                Duration::from_secs(if reset > 365 * 86400 {
+                    reset.saturating_sub(now)
+                } else {
+                    reset
+                })
+            }),
+            retry_after: header(&["Retry-After"]).and_then(|value| {
+                match value.parse::<u64>() {
+                    Ok(seconds) => Some(Duration::from_secs(seconds)),
+                    Err(_) => {
+                        DateTime::parse_from_rfc2822(value).ok().map(|date| {
+                            Duration::from_secs((date.timestamp() as u64).saturating_sub(now))
+                        })
+                    }
+                }
+            }),
+        };
+        if info.limit.is_none() && info.remaining.is_none() && info.reset_after.is_none() &&
+           info.retry_after.is_none() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// How long to wait before sending again: `Retry-After`, or the reset of
+    /// the window when no request is left.
+    pub fn wait(&self) -> Option<Duration> {
+        match (self.retry_after, self.remaining, self.reset_after) {
+            (Some(retry_after), _, _) => Some(retry_after),
+            (None, Some(0), Some(reset_after)) => Some(reset_after),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// No request is sent before, as asked by the API.
+    paused_until: Option<Instant>,
+    last_rate_limit: Option<RateLimitInfo>,
+}
+
+/// A token bucket shared by the clones of a client.
+#[derive(Debug)]
+pub struct Throttle {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Throttle {
+    /// At most `rate` requests per second on average, in bursts of at most `burst`.
+    pub fn new(rate: f64, burst: u32) -> Arc<Throttle> {
+        let burst = burst.max(1) as f64;
+        Arc::new(Throttle {
+            rate: rate.max(0.001),
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                paused_until: None,
+                last_rate_limit: None,
+            }),
+        })
+    }
+
+    /// At most `rate` requests per second, without bursts above it.
+    pub fn per_second(rate: u32) -> Arc<Throttle> {
+        Throttle::new(rate as f64, rate)
+    }
+
+    /// From `OVH_MAX_RPS`, if set to a positive number.
+    pub fn from_env() -> Option<Arc<Throttle>> {
+        env::var("OVH_MAX_RPS")
+            .ok()
+            .and_then(|rate| rate.parse::<u32>().ok())
+            .filter(|rate| *rate > 0)
+            .map(Throttle::per_second)
+    }
+
+    /// Take a token at `now`, returning how long to wait before sending.
+    /// The token is reserved, so concurrent callers queue up.
+    pub(crate) fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+        bucket.tokens -= 1.0;
+        let wait = if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        };
+        match bucket.paused_until {
+            Some(until) if until > now => wait.max(until - now),
+            _ => wait,
+        }
+    }
+
+    /// Take the limit of an answer into account from `now`.
+    fn observe_at(&self, info: &RateLimitInfo, now: Instant) {
+        let mut bucket = self.bucket.lock().unwrap();
+        if let Some(wait) = info.wait() {
+            let until = now + wait;
+            if bucket.paused_until.is_none_or(|paused_until| paused_until < until) {
+                info!("API asked to wait {:?}, pausing requests", wait);
+                bucket.paused_until = Some(until);
+            }
+        }
+        bucket.last_rate_limit = Some(info.clone());
+    }
+
+    /// Take the limit communicated in an answer into account.
+    pub fn observe(&self, info: &RateLimitInfo) {
+        self.observe_at(info, Instant::now())
+    }
+
+    /// The limit of the last answer which had one.
+    pub fn last_rate_limit(&self) -> Option<RateLimitInfo> {
+        self.bucket.lock().unwrap().last_rate_limit.clone()
+    }
+
+    /// Wait for a token.
+    pub fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if wait > Duration::from_secs(0) {
+            debug!("Throttled for {:?}", wait);
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimitInfo, Throttle};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_throttle() {
+        let throttle = Throttle::new(10.0, 2);
+        let start = Instant::now();
+        let waits = (0..4).map(|_| throttle.reserve(start).as_millis()).collect::<Vec<_>>();
+        assert_eq!(vec![0, 0, 100, 200], waits);
+        // the queued requests consumed the tokens refilled meanwhile
+        assert_eq!(100, throttle.reserve(start + Duration::from_millis(200)).as_millis());
+        assert_eq!(0, throttle.reserve(start + Duration::from_secs(5)).as_millis());
+    }
+
+    #[test]
+    fn test_rate_limit_info() {
+        let headers = |pairs: &[(&str, &str)]| {
+            pairs.iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(None, RateLimitInfo::from_headers(&headers(&[("Content-Type", "json")])));
+        let info = RateLimitInfo::from_headers(&headers(&[("x-ratelimit-limit", "60"),
+                                                          ("X-RateLimit-Remaining", "0"),
+                                                          ("X-RateLimit-Reset", "30")]))
+            .unwrap();
+        assert_eq!((Some(60), Some(0)), (info.limit, info.remaining));
+        assert_eq!(Some(Duration::from_secs(30)), info.wait());
+        let retry = RateLimitInfo::from_headers(&headers(&[("Retry-After", "2")])).unwrap();
+        assert_eq!(Some(Duration::from_secs(2)), retry.wait());
+
+        let throttle = Throttle::new(100.0, 10);
+        let start = Instant::now();
+        throttle.observe_at(&retry, start);
+        assert_eq!(2000, throttle.reserve(start).as_millis());
+        assert_eq!(0, throttle.reserve(start + Duration::from_secs(3)).as_millis());
+        assert_eq!(Some(retry), throttle.last_rate_limit());
+    }
+}
@@ -0,0 +1,165 @@
+//! # Time cache
+//!
+//! On-disk cache of the drift between the local clock and the API clock,
+//! so short-lived processes (CLI invocations, cron jobs) don't ask `/auth/time`
+//! before every signed request.
+//!
+//! The drift is kept per API host, as the endpoints of a process may not
+//! share a clock. The cache lives in the temporary directory by default;
+//! `OVH_TIME_CACHE` overrides its path (`off` disables it) and
+//! `OVH_TIME_CACHE_TTL` its lifetime in seconds.
+//!
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::Local;
+
+use serde_json::{self, Map, Value};
+
+/// Lifetime of a cached drift, in seconds.
+pub const DEFAULT_TTL: i64 = 600;
+
+/// Numbers the temporary files of the process.
+static STORES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TimeCache {
+    path: PathBuf,
+    ttl: i64,
+}
+
+impl TimeCache {
+    pub fn new(path: PathBuf, ttl: i64) -> TimeCache {
+        TimeCache {
+            path,
+            ttl,
+        }
+    }
+
+    /// The cache configured by the environment, if not disabled.
+    pub fn from_env() -> Option<TimeCache> {
+        let path = match env::var("OVH_TIME_CACHE") {
+            Ok(ref value) if value == "off" => return None,
+            Ok(value) => PathBuf::from(value),
+            Err(_) => env::temp_dir().join("ovh-rs-time-delta.json"),
+        };
+        let ttl = env::var("OVH_TIME_CACHE_TTL")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_TTL);
+        Some(TimeCache::new(path, ttl))
+    }
+
+    /// The drifts of the hosts in the cache, fresh or not.
+    fn hosts(&self) -> Map<String, Value> {
+        let mut content = String::new();
+        let read = File::open(&self.path).and_then(|mut fd| fd.read_to_string(&mut content));
+        match (read, serde_json::from_str::<Value>(&content)) {
+            (Ok(_), Ok(Value::Object(mut cache))) => {
+                match cache.remove("hosts") {
+                    Some(Value::Object(hosts)) => hosts,
+                    _ => Map::new(),
+                }
+            }
+            _ => Map::new(),
+        }
+    }
+
+    /// The cached drift of `host`, if still fresh.
+    pub fn load(&self, host: &str) -> Option<i64> {
+        let hosts = self.hosts();
+        let entry = hosts.get(host)?;
+        let fetched_at = entry.get("fetchedAt").and_then(Value::as_i64)?;
+        let age = Local::now().timestamp() - fetched_at;
+        if age < 0 || age >= self.ttl {
+            return None;
+        }
+        entry.get("delta").and_then(Value::as_i64)
+    }
+
+    /// Write the drift of `host` atomically, as several processes may share
+    /// the cache: each write goes through a temporary file of its own.
+    pub fn store(&self, host: &str, delta: i64) -> io::Result<()> {
+        let mut hosts = self.hosts();
+        hosts.insert(host.to_string(),
+                     json!({
+                         "delta": delta,
+                         "fetchedAt": Local::now().timestamp(),
+                     }));
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(".{}-{}.tmp", process::id(), STORES.fetch_add(1, Ordering::SeqCst)));
+        let tmp = PathBuf::from(tmp);
+        let value = json!({ "hosts": hosts });
+        let written = File::create(&tmp)
+            .and_then(|mut fd| fd.write_all(value.to_string().as_bytes()));
+        if let Err(err) = written.and_then(|_| fs::rename(&tmp, &self.path)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeCache;
+    use testutil;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_time_cache() {
+        let path = testutil::temp_path("ovh-rs-test-time-cache.json");
+        let cache = TimeCache::new(path.clone(), 60);
+        cache.store("eu.api.ovh.com", 3).unwrap();
+        assert_eq!(Some(3), cache.load("eu.api.ovh.com"));
+        cache.store("eu.api.ovh.com", -2).unwrap();
+        assert_eq!(Some(-2), cache.load("eu.api.ovh.com"));
+        assert_eq!(None, TimeCache::new(path.clone(), 0).load("eu.api.ovh.com"));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(None, cache.load("eu.api.ovh.com"));
+    }
+
+    #[test]
+    fn test_time_cache_hosts() {
+        let path = testutil::temp_path("ovh-rs-test-time-cache-hosts.json");
+        // a cache written before the drifts were kept per host
+        fs::write(&path, "{\"delta\": 5, \"fetchedAt\": 1496300000}").unwrap();
+        let cache = Arc::new(TimeCache::new(path.clone(), 60));
+        assert_eq!(None, cache.load("eu.api.ovh.com"));
+        cache.store("eu.api.ovh.com", 3).unwrap();
+        cache.store("ca.api.ovh.com", -40).unwrap();
+        assert_eq!(Some(3), cache.load("eu.api.ovh.com"));
+        assert_eq!(Some(-40), cache.load("ca.api.ovh.com"));
+        assert_eq!(None, cache.load("api.us.ovhcloud.com"));
+
+        // concurrent writes do not share a temporary file
+        let writers: Vec<_> = (0..8)
+            .map(|n| {
+                let cache = cache.clone();
+                thread::spawn(move || cache.store("eu.api.ovh.com", n))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert!(cache.load("eu.api.ovh.com").is_some());
+        let dir = path.parent().unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let leftovers = fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| {
+                let entry = entry.as_ref().unwrap().file_name();
+                let entry = entry.to_str().unwrap();
+                entry.starts_with(&name) && entry.ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(0, leftovers);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+//! # TLS
+//!
+//! The TLS library of the curl transport is chosen with one of two exclusive
+//! features:
+//!
+//! - `native-tls`: the TLS library and trust store of the system (OpenSSL on
+//!   Linux, Secure Transport on macOS, Schannel on Windows);
+//! - `rustls`: rustls, built into a static libcurl, for static musl builds
+//!   which cannot rely on a system library.
+//!
+//! ```toml
+//! [dependencies.ovh]
+//! version = "0.3"
+//! default-features = false
+//! features = ["rustls"]
+//! ```
+//!
+//! The default reqwest transport uses rustls with the certificate authorities
+//! of the system.
+//!
+//! Behind a TLS interception proxy, the certificate authority of the proxy is
+//! added to the trusted ones with `ClientBuilder::root_certificates`, the
+//! `root_certificates` of the `Network` of a client, or for every client the
+//! `OVH_EXTRA_CA_CERTS` file of PEM certificates. The system ones stay trusted
+//! (for curl, the bundle of `SSL_CERT_FILE` or of the usual locations).
+//!
+
+use error::Error;
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+use pin::{self, PinSet};
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// TLS library of the curl transport.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Backend {
+    NativeTls,
+    Rustls,
+}
+
+/// The backend selected at build time.
+pub fn backend() -> Backend {
+    if cfg!(feature = "rustls") {
+        Backend::Rustls
+    } else {
+        Backend::NativeTls
+    }
+}
+
+/// Usual locations of the system bundle, after `SSL_CERT_FILE`.
+#[cfg(feature = "curl")]
+const SYSTEM_BUNDLES: &[&str] = &["/etc/ssl/certs/ca-certificates.crt",
+                                                  "/etc/pki/tls/certs/ca-bundle.crt",
+                                                  "/etc/ssl/ca-bundle.pem",
+                                                  "/etc/ssl/cert.pem",
+                                                  "/usr/local/etc/openssl/cert.pem"];
+
+const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const END: &str = "-----END CERTIFICATE-----";
+
+/// The PEM certificates of a text, each one from its `BEGIN` to its `END` line.
+pub fn pem_certificates(text: &str) -> Vec<String> {
+    let mut certificates = Vec::new();
+    let mut rest = text;
+    while let Some(begin) = rest.find(BEGIN) {
+        let end = match rest[begin..].find(END) {
+            Some(end) => begin + end + END.len(),
+            None => break,
+        };
+        certificates.push(rest[begin..end].to_string());
+        rest = &rest[end..];
+    }
+    certificates
+}
+
+fn read_pem(path: &Path) -> Result<Vec<String>, Error> {
+    let name = path.display().to_string();
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|err| Error::Io(name.clone(), err))?;
+    let certificates = pem_certificates(&text);
+    if certificates.is_empty() {
+        return Err(Error::Parse(name, "no PEM certificate".to_string()));
+    }
+    Ok(certificates)
+}
+
+/// The certificates of a PEM file, e.g. for `Network::root_certificates`;
+/// an error if it has none.
+pub fn read_certificates<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    read_pem(path.as_ref())
+}
+
+/// The certificate authorities of a client, `added`, and those of `OVH_EXTRA_CA_CERTS`.
+pub(crate) fn extra_roots(added: &[String]) -> io::Result<Vec<String>> {
+    let mut roots = added.to_vec();
+    if let Some(path) = env::var_os("OVH_EXTRA_CA_CERTS").filter(|path| !path.is_empty()) {
+        roots.extend(read_pem(Path::new(&path))?);
+    }
+    Ok(roots)
+}
+
+/// The system bundle followed by the extra certificate authorities.
+#[cfg_attr(not(feature = "curl"), allow(dead_code))]
+fn combine(system: &str, extra: &[String]) -> String {
+    let mut bundle = system.trim_end().to_string();
+    for certificate in extra {
+        if !bundle.is_empty() {
+            bundle.push('\n');
+        }
+        bundle.push_str(certificate);
+    }
+    bundle.push('\n');
+    bundle
+}
+
+#[cfg(feature = "curl")]
+fn system_bundle() -> io::Result<String> {
+    let from_env = env::var_os("SSL_CERT_FILE").map(|path| Path::new(&path).to_path_buf());
+    let usual = SYSTEM_BUNDLES.iter().map(|path| Path::new(path).to_path_buf());
+    let candidates = from_env.into_iter().chain(usual);
+    for path in candidates {
+        let mut text = String::new();
+        if File::open(&path).and_then(|mut file| file.read_to_string(&mut text)).is_ok() {
+            return Ok(text);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound,
+                       "no system CA bundle to extend, set SSL_CERT_FILE"))
+}
+
+/// The bundle for curl to trust instead of the default one, none without
+/// extra certificate authorities.
+#[cfg(feature = "curl")]
+pub(crate) fn bundle(extra: &[String]) -> io::Result<Option<String>> {
+    if extra.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(combine(&system_bundle()?, extra)))
+}
+
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+pub(crate) use self::config::client_config;
+
+#[cfg(any(all(feature = "reqwest", not(feature = "curl")), feature = "async"))]
+mod config {
+    use super::{pin, PinSet};
+
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::crypto::ring;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::CertificateDer;
+    use rustls::{ClientConfig, RootCertStore};
+    use rustls_native_certs;
+
+    use std::io;
+    use std::sync::Arc;
+
+    /// The rustls configuration of a connection: the system certificate
+    /// authorities and the PEM `roots`, then the `pins` of the host, if any.
+    pub(crate) fn client_config(roots: &[String],
+                                pins: Option<PinSet>)
+                                -> io::Result<ClientConfig> {
+        let provider = Arc::new(ring::default_provider());
+        let mut store = RootCertStore::empty();
+        store.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+        for root in roots {
+            let certificate = CertificateDer::from_pem_slice(root.as_bytes())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            store.add(certificate).map_err(io::Error::other)?;
+        }
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(store),
+                                                                   provider.clone())
+            .build()
+            .map_err(io::Error::other)?;
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+        let config = match pins {
+            Some(pins) => {
+                builder.dangerous()
+                    .with_custom_certificate_verifier(pin::pinned(verifier, pins))
+                    .with_no_client_auth()
+            }
+            None => builder.with_webpki_verifier(verifier).with_no_client_auth(),
+        };
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combine, pem_certificates};
+
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n\
+                                       -----END CERTIFICATE-----";
+
+    #[test]
+    fn test_pem_certificates() {
+        let text = format!("proxy CA\n{}\n\n{}\n-----BEGIN CERTIFICATE-----\ntruncated",
+                           CERTIFICATE,
+                           CERTIFICATE.replace("MIIB", "MIIC"));
+        let certificates = pem_certificates(&text);
+        assert_eq!(2, certificates.len());
+        assert_eq!(CERTIFICATE, certificates[0]);
+        assert!(certificates[1].contains("MIIC"));
+        assert!(pem_certificates("not a certificate").is_empty());
+    }
+
+    #[test]
+    fn test_combine() {
+        let system = format!("{}\n\n", CERTIFICATE);
+        let extra = vec![CERTIFICATE.replace("MIIB", "MIIC")];
+        assert_eq!(format!("{}\n{}\n", CERTIFICATE, extra[0]), combine(&system, &extra));
+        assert_eq!(format!("{}\n", extra[0]), combine("", &extra));
+    }
+}
@@ -0,0 +1,113 @@
+//! # Trace
+//!
+//! Client-side correlation ids for logical operations (a zone reconcile,
+//! a queue flush, ...) spanning several API calls.
+//!
+//! While an `Operation` is alive, every API call made from the same thread
+//! is logged with its id, mutations are recorded on the `ovh::audit` log target,
+//! and API errors carry the id so a failure can be matched with its logs.
+//!
+
+use std::cell::RefCell;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use chrono::Local;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) });
+
+/// A new id, unique across processes of the host: `<time>-<pid>-<counter>`.
+pub fn new_correlation_id() -> String {
+    format!("{:x}-{:x}-{:x}",
+            Local::now().timestamp(),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+/// Correlation id of the operation running on this thread, if any.
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// A logical operation, current on its thread until dropped.
+///
+/// Operations can be nested: the inner one keeps the id of the outer one,
+/// so a whole workflow shares a single id.
+pub struct Operation {
+    name: String,
+    id: String,
+    previous: Option<String>,
+    started: Instant,
+}
+
+impl Operation {
+    /// Start an operation with a fresh id, or the id of the enclosing operation.
+    pub fn start(name: &str) -> Operation {
+        let previous = current();
+        let id = previous.clone().unwrap_or_else(new_correlation_id);
+        Operation::with_id(name, &id, previous)
+    }
+
+    /// Start an operation with an id coming from elsewhere (e.g. an incoming request).
+    pub fn resume(name: &str, id: &str) -> Operation {
+        Operation::with_id(name, id, current())
+    }
+
+    fn with_id(name: &str, id: &str, previous: Option<String>) -> Operation {
+        CURRENT.with(|current| *current.borrow_mut() = Some(id.to_string()));
+        debug!("[{}] {} started", id, name);
+        Operation {
+            name: name.to_string(),
+            id: id.to_string(),
+            previous,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        debug!("[{}] {} finished in {}ms",
+               self.id,
+               self.name,
+               elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis()));
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` within a new operation.
+pub fn in_operation<T, F: FnOnce() -> T>(name: &str, f: F) -> T {
+    let _operation = Operation::start(name);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current, new_correlation_id, Operation};
+
+    #[test]
+    fn test_operation_scope() {
+        assert_eq!(None, current());
+        assert!(new_correlation_id() != new_correlation_id());
+        {
+            let outer = Operation::start("outer");
+            let id = outer.id().to_string();
+            assert_eq!(Some(id.clone()), current());
+            {
+                let inner = Operation::start("inner");
+                assert_eq!(id, inner.id());
+            }
+            assert_eq!(Some(id), current());
+        }
+        assert_eq!(None, current());
+    }
+}
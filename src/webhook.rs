@@ -0,0 +1,253 @@
+//! # Webhook
+//!
+//! Forward the events of the watchers (`monitor` expiry alerts, `stock`
+//! appearances, ...) to HTTPS webhooks, for systems which only consume webhooks.
+//!
+//! Every delivery is a JSON POST of an `Event`, signed with HMAC-SHA256:
+//! the `X-Ovh-Rs-Signature` header is `sha256=<hex>` of `<timestamp>.<body>`,
+//! with the shared secret as key and the timestamp of the `X-Ovh-Rs-Timestamp`
+//! header. Receivers check it with `verify`, and should reject old timestamps
+//! to prevent replays. Deliveries are retried with exponential backoff, and the
+//! event id (`X-Ovh-Rs-Event`) is stable across retries for deduplication.
+//!
+//! Requires the `notify-webhook` feature.
+//!
+
+use client::Network;
+use http;
+use monitor::{ExpiryAlert, Notifier};
+use stock::StockEvent;
+use trace;
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+
+use serde_json::Value;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_DELAY_SECS: u64 = 1;
+
+/// An event to forward.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Event {
+    /// Unique id, for deduplication on the receiving side.
+    pub id: String,
+    /// E.g. `service.expiring`, `stock.available`.
+    pub kind: String,
+    /// RFC 3339 date of the event.
+    pub occurred_at: String,
+    pub data: Value,
+}
+
+impl Event {
+    pub fn new(kind: &str, data: Value) -> Event {
+        Event {
+            id: trace::new_correlation_id(),
+            kind: kind.to_string(),
+            occurred_at: Local::now().to_rfc3339(),
+            data,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": self.kind,
+            "occurredAt": self.occurred_at,
+            "data": self.data,
+        })
+    }
+}
+
+impl<'a> From<&'a ExpiryAlert> for Event {
+    fn from(alert: &'a ExpiryAlert) -> Event {
+        Event::new("service.expiring",
+                   json!({
+                       "serviceId": alert.service.id,
+                       "service": alert.service.name,
+                       "product": alert.service.product,
+                       "expiration": alert.expiration.to_string(),
+                       "daysLeft": alert.days_left,
+                       "threshold": alert.threshold,
+                       "message": alert.message(),
+                   }))
+    }
+}
+
+impl<'a> From<&'a StockEvent> for Event {
+    fn from(event: &'a StockEvent) -> Event {
+        let data = match *event {
+            StockEvent::Server { ref plan_code, ref fqn, ref datacenter, ref availability } => {
+                json!({
+                    "kind": "server",
+                    "planCode": plan_code,
+                    "fqn": fqn,
+                    "datacenter": datacenter,
+                    "availability": availability,
+                })
+            }
+            StockEvent::Flavor { ref project_id, ref flavor_id, ref name, ref region } => {
+                json!({
+                    "kind": "flavor",
+                    "projectId": project_id,
+                    "flavorId": flavor_id,
+                    "name": name,
+                    "region": region,
+                })
+            }
+        };
+        Event::new("stock.available", data)
+    }
+}
+
+/// Signature of a delivery: `sha256=` and the hex HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut hmac = Hmac::new(Sha256::new(), secret.as_bytes());
+    hmac.input(timestamp.to_string().as_bytes());
+    hmac.input(b".");
+    hmac.input(body.as_bytes());
+    let hex: Vec<String> = hmac.result()
+        .code()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex.concat())
+}
+
+/// Check the signature of a delivery, in constant time.
+pub fn verify(secret: &str, timestamp: i64, body: &str, signature: &str) -> bool {
+    let expected = sign(secret, timestamp, body);
+    expected.len() == signature.len() && fixed_time_eq(expected.as_bytes(), signature.as_bytes())
+}
+
+/// Pushes events to one webhook.
+#[derive(Debug,Clone)]
+pub struct WebhookEmitter {
+    url: String,
+    secret: String,
+    max_attempts: u32,
+    retry_delay: Duration,
+    network: Network,
+}
+
+impl WebhookEmitter {
+    /// Emitter to an `https://` URL; other schemes are refused, as the
+    /// events describe the account.
+    pub fn new(url: &str, secret: &str) -> io::Result<WebhookEmitter> {
+        if !url.starts_with("https://") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("webhook URL must use https: {}", url)));
+        }
+        Ok(WebhookEmitter {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay: Duration::from_secs(DEFAULT_RETRY_DELAY_SECS),
+            network: Network::default(),
+        })
+    }
+
+    /// Number of attempts of a delivery, 5 by default.
+    pub fn set_max_attempts(&mut self, max_attempts: u32) {
+        self.max_attempts = max_attempts.max(1);
+    }
+
+    /// Delay before the first retry, doubled at every retry; 1 second by default.
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay;
+    }
+
+    /// Deliver through `network`, e.g. the one of a client, instead of the
+    /// proxy of the environment.
+    pub fn set_network(&mut self, network: Network) {
+        self.network = network;
+    }
+
+    /// Deliver an event.
+    ///
+    /// Transport failures, 429 and 5xx answers are retried; other answers
+    /// outside of 2xx fail the delivery at once.
+    pub fn emit(&self, event: &Event) -> io::Result<()> {
+        let body = event.to_json().to_string();
+        let mut delay = self.retry_delay;
+        let mut last_error = None;
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            let timestamp = Local::now().timestamp();
+            let headers = [("X-Ovh-Rs-Event", event.id.clone()),
+                           ("X-Ovh-Rs-Timestamp", timestamp.to_string()),
+                           ("X-Ovh-Rs-Signature", sign(&self.secret, timestamp, &body))];
+            let err = match http::post_json_with_headers(&self.network, &self.url, &body, &headers) {
+                Ok(ref res) if res.is_success() => return Ok(()),
+                Ok(res) => {
+                    let err = io::Error::other(format!("webhook answered {}: {}",
+                                                     res.status,
+                                                     res.body));
+                    if res.status != 429 && !res.is_server_error() {
+                        return Err(err);
+                    }
+                    err
+                }
+                Err(err) => err,
+            };
+            info!("Delivery {} of event {} failed: {}", attempt + 1, event.id, err);
+            last_error = Some(err);
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("not delivered")))
+    }
+}
+
+impl Notifier for WebhookEmitter {
+    fn notify(&self, alert: &ExpiryAlert) -> io::Result<()> {
+        self.emit(&Event::from(alert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify, Event, WebhookEmitter};
+    use stock::StockEvent;
+
+    #[test]
+    fn test_sign() {
+        let body = r#"{"a":1}"#;
+        let signature = sign("secret", 1500000000, body);
+        assert_eq!("sha256=9b122666c0d5c14c39667bf533010c2de24e6f5853f2ec835100c80e98b00e2c",
+                   signature);
+        assert!(verify("secret", 1500000000, body, &signature));
+        assert!(!verify("secret", 1500000001, body, &signature));
+        assert!(!verify("other", 1500000000, body, &signature));
+        assert!(!verify("secret", 1500000000, body, "sha256=00"));
+    }
+
+    #[test]
+    fn test_event() {
+        let event = Event::from(&StockEvent::Flavor {
+            project_id: "p1".to_string(),
+            flavor_id: "f1".to_string(),
+            name: "b2-7".to_string(),
+            region: "GRA7".to_string(),
+        });
+        let value = event.to_json();
+        assert_eq!("stock.available", value["type"]);
+        assert_eq!("GRA7", value["data"]["region"]);
+        assert!(Event::new("a", json!({})).id != event.id);
+    }
+
+    #[test]
+    fn test_https_only() {
+        assert!(WebhookEmitter::new("http://example.com/hook", "secret").is_err());
+        assert!(WebhookEmitter::new("https://example.com/hook", "secret").is_ok());
+    }
+}
@@ -0,0 +1,576 @@
+//! # Change windows
+//!
+//! Defer mutation requests to a maintenance window, for teams whose change
+//! management only allows changes at given times.
+//!
+//! A `ChangeWindow` opens at the minutes matching a cron spec
+//! (`minute hour day-of-month month day-of-week`, e.g. `0 2 * * sat,sun`)
+//! and stays open for a given length, in UTC, the local timezone or a fixed
+//! offset such as `+01:00`.
+//!
+//! A `ChangeScheduler` persists the changes requested outside of the window,
+//! one JSON object per line, lists them and sends them in order once the
+//! window opens.
+//!
+
+use client::OVHClient;
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time;
+
+use chrono::{self, Datelike, DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone,
+             Timelike, UTC};
+
+use serde_json::{self, Value};
+
+/// How far ahead `ChangeWindow::next_opening` looks, in days.
+const SEARCH_DAYS: i64 = 5 * 366;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Timezone in which a window is written.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Zone {
+    Utc,
+    /// The timezone of the host, daylight saving included.
+    Local,
+    /// A fixed offset east of UTC, in seconds.
+    Fixed(i32),
+}
+
+impl FromStr for Zone {
+    type Err = io::Error;
+
+    /// `UTC`, `local`, or an offset: `+01:00`, `-0530`, `+02`.
+    fn from_str(zone: &str) -> io::Result<Zone> {
+        match zone {
+            "UTC" | "utc" | "Z" => return Ok(Zone::Utc),
+            "local" | "Local" => return Ok(Zone::Local),
+            _ => {}
+        }
+        let sign = match zone.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Err(invalid(format!("unknown timezone {}", zone))),
+        };
+        let digits: String = zone[1..].chars().filter(|&c| c != ':').collect();
+        let (hours, minutes) = match digits.len() {
+            2 => (digits.parse::<i32>().ok(), Some(0)),
+            4 => (digits[..2].parse::<i32>().ok(), digits[2..].parse::<i32>().ok()),
+            _ => (None, None),
+        };
+        match (hours, minutes) {
+            (Some(hours), Some(minutes)) if hours < 24 && minutes < 60 => {
+                Ok(Zone::Fixed(sign * (hours * 3600 + minutes * 60)))
+            }
+            _ => Err(invalid(format!("unknown timezone {}", zone))),
+        }
+    }
+}
+
+impl Zone {
+    /// The wall clock time of `instant` in the zone.
+    fn wall_clock(&self, instant: &DateTime<Local>) -> NaiveDateTime {
+        match *self {
+            Zone::Utc => instant.with_timezone(&UTC).naive_local(),
+            Zone::Local => instant.naive_local(),
+            Zone::Fixed(offset) => instant.with_timezone(&FixedOffset::east(offset)).naive_local(),
+        }
+    }
+
+    /// The instant of a wall clock time, `None` when skipped by a daylight saving change.
+    fn instant(&self, wall_clock: &NaiveDateTime) -> Option<DateTime<Local>> {
+        match *self {
+            Zone::Utc => Some(UTC.from_utc_datetime(wall_clock).with_timezone(&Local)),
+            Zone::Local => Local.from_local_datetime(wall_clock).earliest(),
+            Zone::Fixed(offset) => {
+                FixedOffset::east(offset)
+                    .from_local_datetime(wall_clock)
+                    .single()
+                    .map(|instant| instant.with_timezone(&Local))
+            }
+        }
+    }
+}
+
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul",
+                                          "aug", "sep", "oct", "nov", "dec"];
+
+/// Set of the values allowed by a cron field, one bit each.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_lowercase();
+        names.iter()
+            .position(|name| *name == lower)
+            .map(|index| index as u32 + min)
+            .or_else(|| text.parse().ok())
+            .filter(|value| *value >= min && *value <= max)
+            .ok_or_else(|| format!("{} is not within {}-{}", text, min, max))
+    };
+    let mut allowed = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(slash) => {
+                let step = part[slash + 1..]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in {}", part))?;
+                (&part[..slash], step)
+            }
+            None => (part, 1),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some(dash) = range.find('-') {
+            (value(&range[..dash])?, value(&range[dash + 1..])?)
+        } else if step > 1 {
+            (value(range)?, max)
+        } else {
+            let single = value(range)?;
+            (single, single)
+        };
+        if first > last {
+            return Err(format!("empty range {}", range));
+        }
+        let mut current = first;
+        while current <= last {
+            allowed |= 1 << current;
+            current += step;
+        }
+    }
+    Ok(allowed)
+}
+
+/// A cron spec: `minute hour day-of-month month day-of-week`, with `*`, lists,
+/// ranges, steps and the names of months and days (`jan`, `mon`, ...).
+/// As in cron, a day matches either of the day fields when both are restricted.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CronSpec {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for CronSpec {
+    type Err = io::Error;
+
+    fn from_str(spec: &str) -> io::Result<CronSpec> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(format!("cron spec {} has not 5 fields", spec)));
+        }
+        let parse = |field: &str, min, max, names| {
+            parse_field(field, min, max, names)
+                .map_err(|err| invalid(format!("invalid cron spec {}: {}", spec, err)))
+        };
+        let mut weekdays = parse(fields[4], 0, 7, WEEKDAYS)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(CronSpec {
+            source: spec.to_string(),
+            minutes: parse(fields[0], 0, 59, &[])?,
+            hours: parse(fields[1], 0, 23, &[])?,
+            days: parse(fields[2], 1, 31, &[])?,
+            months: parse(fields[3], 1, 12, MONTHS)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+impl CronSpec {
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    fn matches_day(&self, date: &NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        self.months & (1 << date.month()) != 0 &&
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    /// Whether the spec matches the minute of `wall_clock`.
+    pub fn matches(&self, wall_clock: &NaiveDateTime) -> bool {
+        self.matches_day(&wall_clock.date()) && self.hours & (1 << wall_clock.hour()) != 0 &&
+        self.minutes & (1 << wall_clock.minute()) != 0
+    }
+
+    /// First minute matching the spec from `from` included, within `SEARCH_DAYS`.
+    fn next_match(&self, from: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut current = from.date().and_hms(from.hour(), from.minute(), 0);
+        let end = current + chrono::Duration::days(SEARCH_DAYS);
+        while current < end {
+            if !self.matches_day(&current.date()) {
+                current = current.date().succ().and_hms(0, 0, 0);
+            } else if self.hours & (1 << current.hour()) == 0 {
+                current = current.date().and_hms(current.hour(), 0, 0) + chrono::Duration::hours(1);
+            } else if self.minutes & (1 << current.minute()) == 0 {
+                current = current + chrono::Duration::minutes(1);
+            } else {
+                return Some(current);
+            }
+        }
+        None
+    }
+
+    /// First minute matching the spec in `zone` strictly after `after`, skipping
+    /// the times lost to daylight saving. `None` if no date of the next years matches.
+    pub fn next_after(&self, zone: Zone, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut from = zone.wall_clock(after) + chrono::Duration::minutes(1);
+        loop {
+            let next = self.next_match(from)?;
+            match zone.instant(&next) {
+                Some(instant) => return Some(instant),
+                None => from = next + chrono::Duration::minutes(1),
+            }
+        }
+    }
+}
+
+/// The times at which changes are allowed.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ChangeWindow {
+    spec: CronSpec,
+    length: time::Duration,
+    zone: Zone,
+}
+
+impl ChangeWindow {
+    /// A window opening at the minutes of the cron `spec`, in UTC, for `length`.
+    pub fn new(spec: &str, length: time::Duration) -> io::Result<ChangeWindow> {
+        if length < time::Duration::from_secs(60) {
+            return Err(invalid("a change window lasts at least a minute".to_string()));
+        }
+        Ok(ChangeWindow {
+            spec: spec.parse()?,
+            length,
+            zone: Zone::Utc,
+        })
+    }
+
+    /// Read the spec in another timezone.
+    pub fn zone(mut self, zone: Zone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    pub fn spec(&self) -> &CronSpec {
+        &self.spec
+    }
+
+    fn length_minutes(&self) -> i64 {
+        (self.length.as_secs() / 60) as i64
+    }
+
+    pub fn is_open_at(&self, instant: &DateTime<Local>) -> bool {
+        let wall_clock = self.zone.wall_clock(instant);
+        let minute = wall_clock.date().and_hms(wall_clock.hour(), wall_clock.minute(), 0);
+        (0..self.length_minutes())
+            .any(|ago| self.spec.matches(&(minute - chrono::Duration::minutes(ago))))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(&Local::now())
+    }
+
+    /// When changes may next be sent: `after` itself if the window is open,
+    /// else its next opening. `None` if the spec matches no date of the next years.
+    pub fn next_opening(&self, after: &DateTime<Local>) -> Option<DateTime<Local>> {
+        if self.is_open_at(after) {
+            return Some(*after);
+        }
+        self.spec.next_after(self.zone, after)
+    }
+}
+
+/// A change waiting for the window.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ScheduledChange {
+    pub id: u64,
+    pub method: String,
+    pub query: String,
+    pub body: String,
+    /// Why the change is made, e.g. a ticket reference.
+    pub note: String,
+    /// Unix timestamp of the request.
+    pub requested_at: i64,
+}
+
+impl ScheduledChange {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "method": self.method,
+            "query": self.query,
+            "body": self.body,
+            "note": self.note,
+            "requestedAt": self.requested_at,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<ScheduledChange> {
+        Some(ScheduledChange {
+            id: value.get("id").and_then(Value::as_u64)?,
+            method: value.get("method").and_then(Value::as_str)?.to_string(),
+            query: value.get("query").and_then(Value::as_str)?.to_string(),
+            body: value.get("body").and_then(Value::as_str)?.to_string(),
+            note: value.get("note").and_then(Value::as_str).unwrap_or("").to_string(),
+            requested_at: value.get("requestedAt").and_then(Value::as_i64).unwrap_or(0),
+        })
+    }
+}
+
+/// Outcome of a `ChangeScheduler::apply`.
+#[derive(Debug,Clone,Default)]
+pub struct ApplyReport {
+    /// Ids of the changes accepted by the API.
+    pub sent: Vec<u64>,
+    /// The change that failed, with the error; it stays pending and the
+    /// following ones wait behind it.
+    pub failed: Option<(u64, String)>,
+    /// Number of changes still pending.
+    pub remaining: usize,
+}
+
+/// Persistent list of changes deferred to a `ChangeWindow`.
+#[derive(Debug)]
+pub struct ChangeScheduler {
+    path: PathBuf,
+    window: ChangeWindow,
+    entries: Vec<ScheduledChange>,
+    next_id: u64,
+}
+
+impl ChangeScheduler {
+    /// Open the changes stored at `path`, creating an empty list if the file does not exist.
+    pub fn open<P: AsRef<Path>>(path: P, window: ChangeWindow) -> io::Result<ChangeScheduler> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str::<Value>(&line)
+                    .ok()
+                    .and_then(|value| ScheduledChange::from_json(&value));
+                match entry {
+                    Some(entry) => entries.push(entry),
+                    None => warn!("Skipping corrupted scheduled change: {}", line),
+                }
+            }
+        }
+        let next_id = entries.iter().map(|entry| entry.id + 1).max().unwrap_or(1);
+        Ok(ChangeScheduler {
+            path,
+            window,
+            entries,
+            next_id,
+        })
+    }
+
+    pub fn window(&self) -> &ChangeWindow {
+        &self.window
+    }
+
+    /// Changes waiting for the window, oldest first.
+    pub fn pending(&self) -> &[ScheduledChange] {
+        &self.entries
+    }
+
+    /// When the pending changes will be sent, if `apply` runs then.
+    pub fn planned_at(&self) -> Option<DateTime<Local>> {
+        self.window.next_opening(&Local::now())
+    }
+
+    /// Defer a mutation request (POST, PUT, PATCH, DELETE) to the window,
+    /// persisting it before returning its id.
+    pub fn schedule(&mut self,
+                    method: &str,
+                    query: &str,
+                    body: &str,
+                    note: &str)
+                    -> io::Result<u64> {
+        let method = method.to_uppercase();
+        match method.as_str() {
+            "POST" | "PUT" | "PATCH" | "DELETE" => {}
+            _ => return Err(invalid(format!("{} is not a mutation method", method))),
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(ScheduledChange {
+            id,
+            method,
+            query: query.to_string(),
+            body: body.to_string(),
+            note: note.to_string(),
+            requested_at: Local::now().timestamp(),
+        });
+        self.persist()?;
+        Ok(id)
+    }
+
+    /// Drop a pending change; `false` if there is none with this id.
+    pub fn cancel(&mut self, id: u64) -> io::Result<bool> {
+        let count = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.len() == count {
+            return Ok(false);
+        }
+        self.persist()?;
+        Ok(true)
+    }
+
+    /// Send the pending changes in order while the window is open.
+    /// Outside of the window nothing is sent.
+    pub fn apply(&mut self, client: &OVHClient) -> io::Result<ApplyReport> {
+        let mut report = ApplyReport::default();
+        while !self.entries.is_empty() && self.window.is_open() {
+            let entry = self.entries[0].clone();
+            match client.send_raw(&entry.method, &entry.query, &entry.body) {
+                Ok(_) => {
+                    info!(target: "ovh::audit",
+                          "change {} applied: {} {} ({})",
+                          entry.id,
+                          entry.method,
+                          entry.query,
+                          entry.note);
+                    self.entries.remove(0);
+                    self.persist()?;
+                    report.sent.push(entry.id);
+                }
+                Err(err) => {
+                    warn!("Change {} failed: {}", entry.id, err);
+                    report.failed = Some((entry.id, err.to_string()));
+                    break;
+                }
+            }
+        }
+        report.remaining = self.entries.len();
+        Ok(report)
+    }
+
+    /// Sleep until the window opens, then `apply`.
+    pub fn wait_and_apply(&mut self, client: &OVHClient) -> io::Result<ApplyReport> {
+        let now = Local::now();
+        let opening = self.window
+            .next_opening(&now)
+            .ok_or_else(|| invalid(format!("{} never opens", self.window.spec.as_str())))?;
+        let wait = opening.timestamp() - now.timestamp();
+        if wait > 0 {
+            thread::sleep(time::Duration::from_secs(wait as u64));
+        }
+        self.apply(client)
+    }
+
+    /// Rewrite the file atomically.
+    fn persist(&self) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut fd = File::create(&tmp)?;
+            for entry in &self.entries {
+                writeln!(fd, "{}", entry.to_json())?;
+            }
+            fd.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeScheduler, ChangeWindow, CronSpec, Zone};
+    use testutil;
+    use std::fs;
+    use std::time::Duration;
+    use chrono::{DateTime, Local, NaiveDate, TimeZone, UTC};
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        UTC.ymd(y, m, d).and_hms(h, min, 0).with_timezone(&Local)
+    }
+
+    #[test]
+    fn test_cron_spec() {
+        let spec: CronSpec = "*/15 2-4 * * sat,sun".parse().unwrap();
+        // 2017-06-03 is a Saturday.
+        assert!(spec.matches(&NaiveDate::from_ymd(2017, 6, 3).and_hms(2, 30, 0)));
+        assert!(!spec.matches(&NaiveDate::from_ymd(2017, 6, 3).and_hms(2, 31, 0)));
+        assert!(!spec.matches(&NaiveDate::from_ymd(2017, 6, 5).and_hms(2, 30, 0)));
+        let spec: CronSpec = "0 0 1 * 1".parse().unwrap();
+        // Either day field matches when both are restricted.
+        assert!(spec.matches(&NaiveDate::from_ymd(2017, 6, 1).and_hms(0, 0, 0)));
+        assert!(spec.matches(&NaiveDate::from_ymd(2017, 6, 5).and_hms(0, 0, 0)));
+        assert!(!spec.matches(&NaiveDate::from_ymd(2017, 6, 6).and_hms(0, 0, 0)));
+        assert!("0 2 * *".parse::<CronSpec>().is_err());
+        assert!("61 2 * * *".parse::<CronSpec>().is_err());
+        assert!("0 2 * foo *".parse::<CronSpec>().is_err());
+    }
+
+    #[test]
+    fn test_zone() {
+        assert_eq!(Zone::Fixed(3600), "+01:00".parse().unwrap());
+        assert_eq!(Zone::Fixed(-19800), "-0530".parse().unwrap());
+        assert_eq!(Zone::Utc, "UTC".parse().unwrap());
+        assert!("Europe/Paris".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn test_window() {
+        let window = ChangeWindow::new("0 2 * * sat", Duration::from_secs(2 * 3600)).unwrap();
+        assert!(window.is_open_at(&utc(2017, 6, 3, 2, 0)));
+        assert!(window.is_open_at(&utc(2017, 6, 3, 3, 59)));
+        assert!(!window.is_open_at(&utc(2017, 6, 3, 4, 0)));
+        assert_eq!(Some(utc(2017, 6, 10, 2, 0)), window.next_opening(&utc(2017, 6, 3, 4, 0)));
+        assert_eq!(Some(utc(2017, 6, 3, 3, 0)), window.next_opening(&utc(2017, 6, 3, 3, 0)));
+
+        let paris = window.zone(Zone::Fixed(7200));
+        assert!(paris.is_open_at(&utc(2017, 6, 3, 0, 30)));
+        assert_eq!(Some(utc(2017, 6, 10, 0, 0)), paris.next_opening(&utc(2017, 6, 3, 2, 0)));
+
+        let never = ChangeWindow::new("0 0 31 2 *", Duration::from_secs(60)).unwrap();
+        assert_eq!(None, never.next_opening(&utc(2017, 6, 3, 0, 0)));
+    }
+
+    #[test]
+    fn test_scheduler_persists() {
+        let path = testutil::temp_path("ovh-rs-test-window.jsonl");
+        let _ = fs::remove_file(&path);
+        let window = ChangeWindow::new("0 2 * * sat", Duration::from_secs(3600)).unwrap();
+        {
+            let mut scheduler = ChangeScheduler::open(&path, window.clone()).unwrap();
+            assert!(scheduler.schedule("GET", "/me", "", "").is_err());
+            assert_eq!(1, scheduler.schedule("delete", "/ip/1.2.3.4", "", "CHG-1").unwrap());
+            assert_eq!(2, scheduler.schedule("PUT", "/me", "{}", "CHG-2").unwrap());
+            assert!(scheduler.cancel(1).unwrap());
+            assert!(!scheduler.cancel(1).unwrap());
+        }
+        let scheduler = ChangeScheduler::open(&path, window).unwrap();
+        assert_eq!(1, scheduler.pending().len());
+        assert_eq!("CHG-2", scheduler.pending()[0].note);
+        assert!(scheduler.planned_at().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+}